@@ -0,0 +1,101 @@
+//! Bytecodes embedded into executables.
+//!
+//! An executable with a program is a copy of this command followed by
+//! bytecodes, their length and a magic number.
+
+use std::{
+    fs::File,
+    io::{self, Read, Seek, SeekFrom},
+    path::Path,
+};
+
+const MAGIC: &[u8; 8] = b"arachne\0";
+const TRAILER_SIZE: usize = 16;
+
+/// Appends bytecodes to an executable.
+pub fn embed(executable: &[u8], codes: &[u8]) -> Vec<u8> {
+    let mut bundle = Vec::with_capacity(executable.len() + codes.len() + TRAILER_SIZE);
+
+    bundle.extend(executable);
+    bundle.extend(codes);
+    bundle.extend((codes.len() as u64).to_le_bytes());
+    bundle.extend(MAGIC);
+
+    bundle
+}
+
+/// Reads bytecodes embedded into an executable file if any.
+pub fn read(path: &Path) -> io::Result<Option<Vec<u8>>> {
+    let mut file = File::open(path)?;
+    let size = file.seek(SeekFrom::End(0))?;
+
+    if size < TRAILER_SIZE as u64 {
+        return Ok(None);
+    }
+
+    let mut trailer = [0; TRAILER_SIZE];
+    file.seek(SeekFrom::End(-(TRAILER_SIZE as i64)))?;
+    file.read_exact(&mut trailer)?;
+
+    let Some(len) = decode_trailer(&trailer).filter(|&len| len <= size - TRAILER_SIZE as u64)
+    else {
+        return Ok(None);
+    };
+
+    let mut codes = vec![0; len as usize];
+    file.seek(SeekFrom::Start(size - TRAILER_SIZE as u64 - len))?;
+    file.read_exact(&mut codes)?;
+
+    Ok(Some(codes))
+}
+
+// Decodes a length of bytecodes from a trailer.
+fn decode_trailer(trailer: &[u8; TRAILER_SIZE]) -> Option<u64> {
+    let (len, magic) = trailer.split_at(8);
+
+    (magic == MAGIC).then(|| u64::from_le_bytes(len.try_into().expect("length")))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+    use std::io::Write;
+    use tempfile::NamedTempFile;
+
+    fn read_bundle(bundle: &[u8]) -> Option<Vec<u8>> {
+        let mut file = NamedTempFile::new().unwrap();
+
+        file.write_all(bundle).unwrap();
+
+        read(file.path()).unwrap()
+    }
+
+    #[test]
+    fn read_embedded_codes() {
+        assert_eq!(
+            read_bundle(&embed(b"executable", &[1, 2, 3])),
+            Some(vec![1, 2, 3])
+        );
+    }
+
+    #[test]
+    fn read_empty_codes() {
+        assert_eq!(read_bundle(&embed(b"executable", &[])), Some(vec![]));
+    }
+
+    #[test]
+    fn read_executable_without_codes() {
+        assert_eq!(read_bundle(b"executable"), None);
+        assert_eq!(read_bundle(b"exe"), None);
+    }
+
+    #[test]
+    fn read_invalid_length() {
+        let mut bundle = b"exe".to_vec();
+        bundle.extend(u64::MAX.to_le_bytes());
+        bundle.extend(MAGIC);
+
+        assert_eq!(read_bundle(&bundle), None);
+    }
+}
@@ -1,12 +1,22 @@
-use futures::{pin_mut, StreamExt};
+mod bundle;
+
+use compiler::WasmCompiler;
+use futures::{pin_mut, stream::iter, StreamExt};
 use interpreter::Interpreter;
-use parse::parse;
-use std::{error::Error, process::exit};
+use parse::{parse_concrete, parse_with_source_map, SourceMap};
+use std::{
+    convert::Infallible,
+    env::current_exe,
+    error::Error,
+    path::{Path, PathBuf},
+    process::exit,
+};
 use tokio::{
-    fs::File,
-    io::{stdin, AsyncBufReadExt, AsyncRead, BufReader},
+    fs::{self, File},
+    io::{stdin, stdout, AsyncBufReadExt, AsyncRead, AsyncReadExt, AsyncWriteExt, BufReader},
 };
 use tokio_stream::wrappers::LinesStream;
+use vm::{Coverage, TraceFormat, Tracer, Vm};
 
 #[tokio::main]
 async fn main() {
@@ -17,29 +27,386 @@ async fn main() {
 }
 
 async fn run() -> Result<(), Box<dyn Error>> {
+    // An executable built by the `build` subcommand runs its own program.
+    if let Some(codes) = current_exe()
+        .ok()
+        .and_then(|path| bundle::read(&path).ok().flatten())
+    {
+        return Ok(Vm::new().run(&codes)?);
+    }
+
     let matches = clap::Command::new(clap::crate_name!())
         .version(clap::crate_version!())
+        .args_conflicts_with_subcommands(true)
         .arg(clap::Arg::new("source file").required(false))
+        .arg(
+            clap::Arg::new("register")
+                .long("register")
+                .help("Runs a source file on a register machine")
+                .action(clap::ArgAction::SetTrue),
+        )
+        .subcommand(
+            clap::Command::new("build")
+                .about("Compiles a source file into a standalone executable")
+                .arg(
+                    clap::Arg::new("output")
+                        .short('o')
+                        .long("output")
+                        .value_name("FILE")
+                        .help("Sets a path of the executable"),
+                )
+                .arg(
+                    clap::Arg::new("wasm")
+                        .long("wasm")
+                        .help("Compiles a source file into a WebAssembly module instead")
+                        .action(clap::ArgAction::SetTrue),
+                )
+                .arg(clap::Arg::new("source file").required(true)),
+        )
+        .subcommand(
+            clap::Command::new("fmt")
+                .about("Formats source files")
+                .arg(
+                    clap::Arg::new("check")
+                        .long("check")
+                        .help("Checks if source files are formatted")
+                        .action(clap::ArgAction::SetTrue),
+                )
+                .arg(clap::Arg::new("source files").num_args(0..)),
+        )
+        .subcommand(
+            clap::Command::new("debug")
+                .about("Debugs a source file")
+                .arg(
+                    clap::Arg::new("dap")
+                        .long("dap")
+                        .help("Runs a debug adapter over standard input and output")
+                        .action(clap::ArgAction::SetTrue),
+                )
+                .arg(clap::Arg::new("source file").required_unless_present("dap")),
+        )
+        .subcommand(
+            clap::Command::new("profile")
+                .about("Profiles a source file")
+                .arg(
+                    clap::Arg::new("period")
+                        .long("period")
+                        .help("Sets a number of instructions between samples")
+                        .value_parser(clap::value_parser!(u64).range(1..))
+                        .default_value("1000"),
+                )
+                .arg(
+                    clap::Arg::new("folded")
+                        .long("folded")
+                        .value_name("FILE")
+                        .help("Writes folded stacks for flame graph tools"),
+                )
+                .arg(clap::Arg::new("source file").required(true)),
+        )
+        .subcommand(
+            clap::Command::new("trace")
+                .about("Traces executed instructions of a source file")
+                .arg(
+                    clap::Arg::new("json")
+                        .long("json")
+                        .help("Writes traces as JSON lines")
+                        .action(clap::ArgAction::SetTrue),
+                )
+                .arg(
+                    clap::Arg::new("output")
+                        .long("output")
+                        .value_name("FILE")
+                        .help("Writes traces to a file instead of standard error"),
+                )
+                .arg(clap::Arg::new("source file").required(true)),
+        )
+        .subcommand(
+            clap::Command::new("coverage")
+                .about("Measures line coverage of a source file")
+                .arg(
+                    clap::Arg::new("lcov")
+                        .long("lcov")
+                        .value_name("FILE")
+                        .help("Writes coverage in the lcov format"),
+                )
+                .arg(clap::Arg::new("source file").required(true)),
+        )
+        .subcommand(
+            clap::Command::new("lsp")
+                .about("Runs a language server over standard input and output"),
+        )
         .get_matches();
 
-    if let Some(path) = matches.get_one::<String>("source file") {
-        interpret(File::open(&path).await?).await
+    match matches.subcommand() {
+        Some(("build", matches)) => {
+            build_file(
+                matches
+                    .get_one::<String>("source file")
+                    .expect("source file"),
+                matches.get_one::<String>("output"),
+                matches.get_flag("wasm"),
+            )
+            .await
+        }
+        Some(("fmt", matches)) => {
+            let paths = matches
+                .get_many::<String>("source files")
+                .unwrap_or_default()
+                .collect::<Vec<_>>();
+
+            if paths.is_empty() {
+                format_stdin(matches.get_flag("check")).await
+            } else {
+                format_files(&paths, matches.get_flag("check")).await
+            }
+        }
+        Some(("debug", matches)) => {
+            if matches.get_flag("dap") {
+                debug::Adapter::new(std::io::stdin().lock(), std::io::stdout().lock()).run()?;
+            } else if let Some(path) = matches.get_one::<String>("source file") {
+                debug_file(path).await?;
+            }
+
+            Ok(())
+        }
+        Some(("profile", matches)) => {
+            profile_file(
+                matches
+                    .get_one::<String>("source file")
+                    .expect("source file"),
+                *matches.get_one("period").expect("period"),
+                matches.get_one::<String>("folded"),
+            )
+            .await
+        }
+        Some(("trace", matches)) => {
+            trace_file(
+                matches
+                    .get_one::<String>("source file")
+                    .expect("source file"),
+                if matches.get_flag("json") {
+                    TraceFormat::Json
+                } else {
+                    TraceFormat::Text
+                },
+                matches.get_one::<String>("output"),
+            )
+            .await
+        }
+        Some(("coverage", matches)) => {
+            cover_file(
+                matches
+                    .get_one::<String>("source file")
+                    .expect("source file"),
+                matches.get_one::<String>("lcov"),
+            )
+            .await
+        }
+        Some(("lsp", _)) => Ok(tokio::task::spawn_blocking(|| {
+            lsp::Server::new(std::io::stdin().lock(), std::io::stdout().lock()).run()
+        })
+        .await??),
+        _ => {
+            let interpreter = if matches.get_flag("register") {
+                Interpreter::with_register_vm()
+            } else {
+                Interpreter::new()
+            };
+
+            if let Some(path) = matches.get_one::<String>("source file") {
+                interpret(&interpreter, File::open(&path).await?).await
+            } else {
+                interpret(&interpreter, stdin()).await
+            }
+        }
+    }
+}
+
+async fn build_file(
+    path: &str,
+    output_path: Option<&String>,
+    wasm: bool,
+) -> Result<(), Box<dyn Error>> {
+    let output_path = output_path
+        .map(PathBuf::from)
+        .unwrap_or_else(|| Path::new(path).with_extension(if wasm { "wasm" } else { "" }));
+
+    if output_path == Path::new(path) {
+        return Err(format!("output path same as source file: {path}").into());
+    } else if wasm {
+        let module = compile_wasm(&fs::read_to_string(path).await?).await?;
+
+        return Ok(fs::write(&output_path, module).await?);
+    }
+
+    let program = debug::Program::new(fs::read_to_string(path).await?)?;
+    let executable = fs::read(current_exe()?).await?;
+
+    fs::write(&output_path, bundle::embed(&executable, program.codes())).await?;
+
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+
+        fs::set_permissions(&output_path, std::fs::Permissions::from_mode(0o755)).await?;
+    }
+
+    Ok(())
+}
+
+async fn compile_wasm(source: &str) -> Result<Vec<u8>, Box<dyn Error>> {
+    let (values, _) = SourceMap::new(source, &parse_concrete(source)?);
+    let mut compiler = WasmCompiler::new();
+
+    {
+        let mut values = iter(values.into_iter().map(Ok::<_, Infallible>));
+        let results = compiler.compile(&mut values);
+
+        pin_mut!(results);
+
+        while let Some(result) = results.next().await {
+            result?;
+        }
+    }
+
+    Ok(compiler.module())
+}
+
+async fn format_stdin(check: bool) -> Result<(), Box<dyn Error>> {
+    let mut source = String::new();
+
+    stdin().read_to_string(&mut source).await?;
+    let formatted = format::format(&parse_concrete(&source)?);
+
+    if !check {
+        stdout().write_all(formatted.as_bytes()).await?;
+    } else if formatted != source {
+        return Err("unformatted input".into());
+    }
+
+    Ok(())
+}
+
+async fn format_files(paths: &[&String], check: bool) -> Result<(), Box<dyn Error>> {
+    let mut unformatted = vec![];
+
+    for &path in paths {
+        let source = fs::read_to_string(path).await?;
+        let formatted = format::format(&parse_concrete(&source)?);
+
+        if formatted == source {
+            continue;
+        } else if check {
+            unformatted.push(path.as_str());
+        } else {
+            fs::write(path, formatted).await?;
+        }
+    }
+
+    if unformatted.is_empty() {
+        Ok(())
+    } else {
+        Err(format!("unformatted files: {}", unformatted.join(", ")).into())
+    }
+}
+
+async fn debug_file(path: &str) -> Result<(), Box<dyn Error>> {
+    let program = debug::Program::new(fs::read_to_string(path).await?)?;
+    let mut debugger = debug::Debugger::new(&program, true);
+
+    program.debug(
+        &mut debugger,
+        &mut debug::Console::new(std::io::stdin().lock(), std::io::stdout().lock()),
+    )?;
+
+    Ok(())
+}
+
+async fn profile_file(
+    path: &str,
+    period: u64,
+    folded_path: Option<&String>,
+) -> Result<(), Box<dyn Error>> {
+    let program = debug::Program::new(fs::read_to_string(path).await?)?;
+    let profile = debug::Profiler::new(&program, period).run()?;
+
+    eprintln!("{profile}");
+
+    if let Some(path) = folded_path {
+        fs::write(path, profile.folded()).await?;
+    }
+
+    Ok(())
+}
+
+async fn trace_file(
+    path: &str,
+    format: TraceFormat,
+    output_path: Option<&String>,
+) -> Result<(), Box<dyn Error>> {
+    let program = debug::Program::new(fs::read_to_string(path).await?)?;
+
+    if let Some(path) = output_path {
+        let mut tracer = Tracer::with_output(
+            std::io::BufWriter::new(std::fs::File::create(path)?),
+            format,
+        );
+
+        program.run(&mut tracer)?;
+        tracer.finish()?;
     } else {
-        interpret(stdin()).await
+        let mut tracer = Tracer::with_output(std::io::stderr().lock(), format);
+
+        program.run(&mut tracer)?;
+        tracer.finish()?;
+    }
+
+    Ok(())
+}
+
+async fn cover_file(path: &str, lcov_path: Option<&String>) -> Result<(), Box<dyn Error>> {
+    let program = debug::Program::new(fs::read_to_string(path).await?)?;
+    let mut tracer = Tracer::new();
+
+    program.run(&mut tracer)?;
+
+    let coverage = Coverage::new(&tracer.finish()?, program.debug_info());
+
+    for (&line, count) in coverage.lines() {
+        eprintln!(
+            "{count:>8} | {:>4} | {}",
+            line,
+            program.line(line).unwrap_or_default()
+        );
     }
+
+    eprintln!(
+        "{}/{} lines covered",
+        coverage.hit_count(),
+        coverage.lines().len()
+    );
+
+    if let Some(lcov_path) = lcov_path {
+        fs::write(lcov_path, coverage.to_lcov(path)).await?;
+    }
+
+    Ok(())
 }
 
-async fn interpret<T: AsyncRead>(input: T) -> Result<(), Box<dyn Error>> {
+async fn interpret<T: AsyncRead>(
+    interpreter: &Interpreter,
+    input: T,
+) -> Result<(), Box<dyn Error>> {
     let lines = LinesStream::new(BufReader::new(input).lines());
 
     pin_mut!(lines);
 
-    let values = parse(&mut lines);
+    let source_map = SourceMap::default();
+    let values = parse_with_source_map(&mut lines, &source_map);
 
     pin_mut!(values);
 
-    let interpreter = Interpreter::new();
-    let outputs = interpreter.interpret(&mut values);
+    let outputs = interpreter.interpret_with_source_map(&mut values, &source_map);
 
     pin_mut!(outputs);
 
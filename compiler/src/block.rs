@@ -8,6 +8,8 @@ pub struct Block<'a> {
     parent: Option<&'a Block<'a>>,
     variables: HashMap<Symbol, usize>,
     temporary_count: usize,
+    // Whether variables in a block are ones of a loop.
+    r#loop: bool,
 }
 
 impl<'a> Block<'a> {
@@ -21,6 +23,7 @@ impl<'a> Block<'a> {
             parent: None,
             variables: HashMap::with_capacity(capacity),
             temporary_count: 0,
+            r#loop: false,
         }
     }
 
@@ -30,9 +33,28 @@ impl<'a> Block<'a> {
             parent: Some(self),
             variables: Default::default(),
             temporary_count: 0,
+            r#loop: false,
         }
     }
 
+    /// Forks a block for variables of a loop.
+    pub fn fork_loop(&'a self) -> Self {
+        Self {
+            r#loop: true,
+            ..self.fork()
+        }
+    }
+
+    /// Returns a number of values on a stack above variables of the innermost
+    /// loop.
+    pub fn loop_depth(&self) -> usize {
+        if self.r#loop {
+            return 0;
+        }
+
+        self.variables.len() + self.temporary_count + self.parent.expect("loop block").loop_depth()
+    }
+
     pub fn get_variable(&self, name: Symbol) -> Variable {
         let offset = self.variables.len() + self.temporary_count;
 
@@ -41,8 +63,15 @@ impl<'a> Block<'a> {
         } else if let Some(parent) = &self.parent {
             return match parent.get_variable(name) {
                 Variable::Bound(index) => Variable::Bound(index + offset),
-                variable @ Variable::Free(_) => variable,
+                variable @ (Variable::Free(_) | Variable::Sibling(_)) => variable,
             };
+        } else if let Some(index) = self
+            .function
+            .siblings()
+            .iter()
+            .position(|other| other == &name)
+        {
+            return Variable::Sibling(index);
         } else if let Some(index) = self
             .function
             .free_variables()
@@ -133,6 +162,38 @@ mod tests {
         assert_eq!(block.get_variable("z".into()), Variable::Bound(0));
     }
 
+    #[test]
+    fn get_loop_depth() {
+        let function = Function::new();
+        let mut block = Block::new(&function);
+
+        block.insert_variable("x".into());
+
+        let mut block = block.fork_loop();
+
+        block.insert_variable("y".into());
+
+        let mut block = block.fork();
+
+        block.insert_variable("z".into());
+        *block.temporary_count_mut() += 1;
+
+        assert_eq!(block.loop_depth(), 2);
+        assert_eq!(block.get_variable("y".into()), Variable::Bound(2));
+    }
+
+    #[test]
+    fn get_sibling() {
+        let function = Function::with_siblings(vec!["f".into(), "g".into()]);
+        let mut block = Block::new(&function);
+
+        block.insert_variable("f".into());
+
+        assert_eq!(block.get_variable("f".into()), Variable::Bound(0));
+        assert_eq!(block.fork().get_variable("g".into()), Variable::Sibling(1));
+        assert!(function.free_variables().is_empty());
+    }
+
     #[test]
     fn get_variable_in_parent() {
         let function = Function::new();
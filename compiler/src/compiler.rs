@@ -1,19 +1,53 @@
-use crate::{block::Block, function::Function, variable::Variable, CompileError};
+use crate::{
+    block::Block, convert::convert_statement, emit::Emitter, function::Function,
+    liveness::move_last_uses, lower::Lowerer, CompileError,
+};
 use async_stream::try_stream;
 use futures::{Stream, StreamExt};
-use runtime::{Array, Symbol, TypedValueRef, Value};
-use std::{cell::RefCell, error::Error, mem::size_of};
-use vm::Instruction;
+use parse::SourceMap;
+use runtime::Value;
+use std::{cell::RefCell, error::Error};
+use vm::DebugInfo;
+
+/// Names of special forms.
+pub const SPECIAL_FORMS: &[&str] = &[
+    "do", "fn", "if", "let", "let-rec", "loop", "match", "recur", "while",
+];
+
+/// Names of primitive functions.
+pub const PRIMITIVES: &[&str] = &[
+    "get", "set", "len", "display", "repr", "+", "-", "*", "/", "=", "!=", "<", "<=", ">", ">=",
+    "not", "and", "or", "ref", "deref", "set!", "slice", "concat", "push", "pop", "reverse",
+    "sort", "map", "filter", "fold", "range",
+];
 
 pub struct Compiler<'a> {
     codes: &'a RefCell<Vec<u8>>,
+    debug: Option<(&'a SourceMap, &'a RefCell<DebugInfo>)>,
 }
 
 impl<'a> Compiler<'a> {
     pub fn new(codes: &'a RefCell<Vec<u8>>) -> Self {
-        Self { codes }
+        Self { codes, debug: None }
     }
 
+    /// Creates a compiler recording debug information of values in a source
+    /// map.
+    pub fn with_debug_info(
+        codes: &'a RefCell<Vec<u8>>,
+        source_map: &'a SourceMap,
+        debug_info: &'a RefCell<DebugInfo>,
+    ) -> Self {
+        Self {
+            codes,
+            debug: Some((source_map, debug_info)),
+        }
+    }
+
+    /// Compiles values into bytecodes statement by statement.
+    ///
+    /// Each statement is lowered into an intermediate representation, analyzed
+    /// for last uses of variables, converted into closures and then emitted.
     pub fn compile<E: Error + 'static>(
         &'a mut self,
         values: &'a mut (impl Stream<Item = Result<Value, E>> + Unpin),
@@ -21,339 +55,24 @@ impl<'a> Compiler<'a> {
         try_stream! {
             let function = Function::new();
             let mut block = Block::new(&function);
+            let lowerer = Lowerer::new(self.debug.map(|(source_map, _)| source_map));
+            let mut emitter = Emitter::new(self.codes, self.debug.map(|(_, debug_info)| debug_info));
 
             while let Some(value) = values.next().await {
-                self.compile_statement(&value.map_err(|error| CompileError::Other(error.into()))?, &mut block, true)?;
-
+                let mut statement = lowerer.lower(&value.map_err(|error| CompileError::Other(error.into()))?)?;
+                move_last_uses(&mut statement);
+                let statement = convert_statement(statement, &mut block);
                 let name = function.free_variables().iter().next().map(ToString::to_string);
 
                 if let Some(name) = name {
                     Err(CompileError::VariableNotDefined(name))?;
                 }
 
-                yield ();
-            }
-        }
-    }
-
-    fn compile_statement(
-        &mut self,
-        value: &Value,
-        block: &mut Block,
-        dump: bool,
-    ) -> Result<(), CompileError> {
-        if let Some(array) = value.as_array() {
-            if let Some(symbol) = array.get_usize(0).to_symbol() {
-                match symbol.as_str() {
-                    "let" => {
-                        if array.len_usize() != 3 {
-                            return Err(CompileError::Syntax(array.to_string()));
-                        } else if let Some(symbol) = array.get_usize(1).to_symbol() {
-                            self.compile_expression(array.get_usize(2), block, false)?;
-                            block.insert_variable(symbol);
-                            *block.temporary_count_mut() -= 1;
-                        } else {
-                            return Err(CompileError::Syntax(array.to_string()));
-                        }
-                    }
-                    "let-rec" => {
-                        if array.len_usize() != 3 {
-                            return Err(CompileError::Syntax(array.to_string()));
-                        } else if let (Some(symbol), Some(array)) = (
-                            array.get_usize(1).to_symbol(),
-                            array.get_usize(2).as_array(),
-                        ) {
-                            self.compile_function(Some(symbol), array, block)?;
-                            block.insert_variable(symbol);
-                            *block.temporary_count_mut() -= 1;
-                        } else {
-                            return Err(CompileError::Syntax(array.to_string()));
-                        }
-                    }
-                    _ => self.compile_expression_statement(value, block, dump)?,
-                }
-            } else {
-                self.compile_expression_statement(value, block, dump)?
-            }
-        } else {
-            self.compile_expression_statement(value, block, dump)?
-        }
-
-        Ok(())
-    }
-
-    fn compile_expression_statement(
-        &mut self,
-        value: &Value,
-        block: &mut Block,
-        dump: bool,
-    ) -> Result<(), CompileError> {
-        self.compile_expression(value, block, false)?;
-        let mut codes = self.codes.borrow_mut();
-
-        if dump {
-            codes.push(Instruction::Dump as u8);
-        }
-
-        codes.push(Instruction::Drop as u8);
-        *block.temporary_count_mut() -= 1;
-
-        Ok(())
-    }
-
-    fn compile_expression(
-        &mut self,
-        value: &Value,
-        block: &mut Block,
-        tail: bool,
-    ) -> Result<(), CompileError> {
-        if let Some(value) = value.as_typed() {
-            match value {
-                TypedValueRef::Array(array) => {
-                    if let Some(symbol) = array.get_usize(0).to_symbol() {
-                        let symbol = symbol.as_str();
-
-                        if symbol == "fn" {
-                            self.compile_function(None, array, block)?;
-                        } else if symbol == "if" {
-                            return self.compile_if(array, 1, block, tail);
-                        } else if let Some(instruction) = match symbol {
-                            "get" => Some(Instruction::Get),
-                            "set" => Some(Instruction::Set),
-                            "len" => Some(Instruction::Length),
-                            "+" => Some(Instruction::Add),
-                            "-" => Some(Instruction::Subtract),
-                            "*" => Some(Instruction::Multiply),
-                            "/" => Some(Instruction::Divide),
-                            "=" => Some(Instruction::Equal),
-                            "!=" => Some(Instruction::NotEqual),
-                            "<" => Some(Instruction::LessThan),
-                            "<=" => Some(Instruction::LessThanOrEqual),
-                            ">" => Some(Instruction::GreaterThan),
-                            ">=" => Some(Instruction::GreaterThanOrEqual),
-                            "not" => Some(Instruction::Not),
-                            "and" => Some(Instruction::And),
-                            "or" => Some(Instruction::Or),
-                            _ => None,
-                        } {
-                            self.compile_arguments(array, block)?;
-                            self.codes.borrow_mut().push(instruction as u8);
-
-                            *block.temporary_count_mut() -= match instruction {
-                                Instruction::Length => 0,
-                                Instruction::Not => 0,
-                                Instruction::Set => 2,
-                                _ => 1,
-                            };
-                        } else {
-                            return self.compile_call(array, block, tail);
-                        }
-                    } else {
-                        return self.compile_call(array, block, tail);
-                    }
-                }
-                TypedValueRef::Closure(_) => return Err(CompileError::Closure),
-                TypedValueRef::Float64(number) => {
-                    let mut codes = self.codes.borrow_mut();
-
-                    codes.push(Instruction::Float64 as u8);
-                    codes.extend(number.to_f64().to_le_bytes());
-                    *block.temporary_count_mut() += 1;
-                }
-                TypedValueRef::Integer32(number) => {
-                    let mut codes = self.codes.borrow_mut();
-
-                    codes.push(Instruction::Integer32 as u8);
-                    codes.extend(number.to_i32().to_le_bytes());
-                    *block.temporary_count_mut() += 1;
-                }
-                TypedValueRef::Symbol(symbol) => self.compile_variable(symbol, block),
-            }
-        } else {
-            self.codes.borrow_mut().push(Instruction::Nil as u8);
-            *block.temporary_count_mut() += 1;
-        }
-
-        if tail {
-            self.codes.borrow_mut().push(Instruction::Return as u8);
-            *block.temporary_count_mut() -= 1;
-        }
-
-        Ok(())
-    }
-
-    fn compile_variable(&mut self, symbol: Symbol, block: &mut Block) {
-        let mut codes = self.codes.borrow_mut();
-
-        match block.get_variable(symbol) {
-            Variable::Bound(index) => {
-                codes.push(Instruction::Peek as u8);
-                codes.push(index as u8);
-            }
-            Variable::Free(index) => {
-                codes.push(Instruction::Environment as u8);
-                codes.push(index as u8);
-            }
-        }
-
-        *block.temporary_count_mut() += 1;
-    }
-
-    fn compile_function(
-        &mut self,
-        name: Option<Symbol>,
-        array: &Array,
-        block: &mut Block,
-    ) -> Result<(), CompileError> {
-        let mut codes = self.codes.borrow_mut();
-
-        codes.push(Instruction::Jump as u8);
-        codes.extend(0u16.to_le_bytes()); // stub address
-        let jump_index = codes.len();
-
-        let function_index = codes.len();
-        drop(codes);
-
-        let arguments = array.get_usize(1);
-        let Some(arguments) = arguments.as_array() else {
-            return Err(CompileError::Syntax(array.to_string()));
-        };
-        let arity = u8::try_from(arguments.len_usize())?;
-
-        let function = {
-            let function = Function::new();
-            let mut block = Block::with_capacity(&function, arguments.len_usize() + 1);
-
-            if let Some(name) = name {
-                block.insert_variable(name);
-            }
-
-            for index in 0..arguments.len_usize() {
-                if let Some(argument) = arguments.get_usize(index).to_symbol() {
-                    block.insert_variable(argument);
-                }
-            }
-
-            for index in 2..array.len_usize() - 1 {
-                self.compile_statement(array.get_usize(index), &mut block, false)?;
-            }
-
-            self.compile_expression(array.get_usize(array.len_usize() - 1), &mut block, true)?;
-
-            function
-        };
-
-        let mut codes = self.codes.borrow_mut();
-        let current_index = codes.len();
-
-        codes[jump_index - size_of::<u16>()..jump_index]
-            .copy_from_slice(&((current_index - jump_index) as u16).to_le_bytes());
-        drop(codes);
-
-        for &symbol in &*function.free_variables() {
-            self.compile_variable(symbol, block);
-        }
-
-        let mut codes = self.codes.borrow_mut();
-        codes.push(Instruction::Close as u8);
-        codes.extend((function_index as u32).to_le_bytes());
-        codes.push(arity); // arity
-        codes.push(function.free_variables().len() as u8);
-
-        *block.temporary_count_mut() -= function.free_variables().len();
-        *block.temporary_count_mut() += 1;
-
-        Ok(())
-    }
-
-    fn compile_arguments(&mut self, array: &Array, block: &mut Block) -> Result<(), CompileError> {
-        for index in 1..array.len_usize() {
-            self.compile_expression(array.get_usize(index), block, false)?;
-        }
-
-        Ok(())
-    }
-
-    fn compile_call(
-        &mut self,
-        array: &Array,
-        block: &mut Block,
-        tail: bool,
-    ) -> Result<(), CompileError> {
-        self.compile_expression(array.get_usize(0), block, false)?;
-        self.compile_arguments(array, block)?;
-
-        let mut codes = self.codes.borrow_mut();
-        codes.push(if tail {
-            Instruction::TailCall
-        } else {
-            Instruction::Call
-        } as u8);
-        codes.push((array.len_usize() - 1) as u8);
-        *block.temporary_count_mut() -= array.len_usize() - 1;
-
-        Ok(())
-    }
-
-    fn compile_if(
-        &mut self,
-        array: &Array,
-        condition_index: usize,
-        block: &mut Block,
-        tail: bool,
-    ) -> Result<(), CompileError> {
-        self.compile_expression(array.get_usize(condition_index), block, false)?;
-
-        let mut codes = self.codes.borrow_mut();
-        codes.push(Instruction::Branch as u8);
-        codes.extend(0u16.to_le_bytes());
-        let branch_index = codes.len();
-        drop(codes);
-        *block.temporary_count_mut() -= 1;
-
-        self.compile_expression(
-            array.get_usize(condition_index + 1),
-            &mut block.fork(),
-            tail,
-        )?;
-
-        let then_index = if tail {
-            None
-        } else {
-            let mut codes = self.codes.borrow_mut();
-            codes.push(Instruction::Jump as u8);
-            codes.extend(0u16.to_le_bytes());
-            Some(codes.len())
-        };
-
-        {
-            let mut codes = self.codes.borrow_mut();
-            let current_index = codes.len();
-            codes[branch_index - size_of::<u16>()..branch_index]
-                .copy_from_slice(&((current_index - branch_index) as i16).to_le_bytes());
-        }
-
-        {
-            let mut block = block.fork();
+                emitter.emit(&statement)?;
 
-            if condition_index + 3 < array.len_usize() {
-                self.compile_if(array, condition_index + 2, &mut block, tail)?;
-            } else {
-                self.compile_expression(array.get_usize(condition_index + 2), &mut block, tail)?;
+                yield ();
             }
         }
-
-        if let Some(then_index) = then_index {
-            let mut codes = self.codes.borrow_mut();
-            let current_index = codes.len();
-
-            codes[then_index - size_of::<u16>()..then_index]
-                .copy_from_slice(&((current_index - then_index) as i16).to_le_bytes());
-
-            *block.temporary_count_mut() += 1;
-        }
-
-        Ok(())
     }
 }
 
@@ -363,7 +82,7 @@ mod tests {
     use futures::{pin_mut, stream::iter};
     use runtime::NIL;
     use std::io;
-    use vm::format_instructions;
+    use vm::{format_instructions, FunctionInfo};
 
     type Error = io::Error;
 
@@ -398,6 +117,100 @@ mod tests {
         compile_instructions(values).await.unwrap_err()
     }
 
+    mod debug_info {
+        use super::*;
+        use parse::parse_concrete;
+        use pretty_assertions::assert_eq;
+
+        async fn compile_debug_info(source: &str) -> DebugInfo {
+            let (values, source_map) = SourceMap::new(source, &parse_concrete(source).unwrap());
+            let codes = vec![].into();
+            let debug_info = Default::default();
+
+            {
+                let mut compiler = Compiler::with_debug_info(&codes, &source_map, &debug_info);
+                let values = iter(values).map(Ok);
+
+                pin_mut!(values);
+
+                let results = compiler.compile::<Error>(&mut values);
+
+                pin_mut!(results);
+
+                while let Some(result) = results.next().await {
+                    result.unwrap();
+                }
+            }
+
+            debug_info.into_inner()
+        }
+
+        #[tokio::test]
+        async fn record_lines() {
+            let debug_info =
+                compile_debug_info("(let y 2)\n(let x\n  (+ 1\n    (* y 3)))\n(- x 1)").await;
+
+            assert_eq!(
+                debug_info
+                    .lines()
+                    .iter()
+                    .map(|&(_, line)| line)
+                    .collect::<Vec<_>>(),
+                [1, 3, 4, 3, 5]
+            );
+        }
+
+        #[tokio::test]
+        async fn record_functions() {
+            let debug_info = compile_debug_info(
+                "(let y 1)\n(let-rec f (fn (x) (f y)))\n(let g (fn () 42))\n(fn () ())",
+            )
+            .await;
+
+            assert_eq!(
+                debug_info
+                    .functions()
+                    .iter()
+                    .map(|function| (
+                        function.name(),
+                        function.arguments(),
+                        function.environment()
+                    ))
+                    .collect::<Vec<_>>(),
+                [
+                    (Some("f"), &["x".to_string()][..], &["y".to_string()][..]),
+                    (Some("g"), &[], &[]),
+                    (None, &[], &[]),
+                ]
+            );
+        }
+
+        #[tokio::test]
+        async fn record_function_ranges() {
+            let debug_info = compile_debug_info("(let f\n  (fn ()\n    (fn () 42)))").await;
+            let [inner, outer] = debug_info.functions() else {
+                panic!("two functions expected");
+            };
+
+            assert_eq!(inner.span(), Some(20..30));
+            assert_eq!(outer.span(), Some(9..31));
+            assert!(outer.codes().start < inner.codes().start);
+            assert!(inner.codes().end < outer.codes().end);
+            assert_eq!(
+                debug_info
+                    .function_at(inner.codes().start)
+                    .and_then(FunctionInfo::name),
+                None
+            );
+            assert_eq!(
+                debug_info
+                    .function_at(outer.codes().start)
+                    .and_then(FunctionInfo::name),
+                Some("f")
+            );
+        }
+    }
+
     mod call {
         use super::*;
 
@@ -511,6 +324,25 @@ mod tests {
             );
         }
 
+        #[tokio::test]
+        async fn compile_function_with_set() {
+            insta::assert_snapshot!(
+                compile([[
+                    "fn".into(),
+                    ["xs".into(), "x".into()].into(),
+                    [
+                        "set".into(),
+                        "xs".into(),
+                        ["len".into(), "xs".into()].into(),
+                        "x".into()
+                    ]
+                    .into()
+                ]
+                .into()])
+                .await
+            );
+        }
+
         mod closure {
             use super::*;
 
@@ -754,6 +586,21 @@ mod tests {
             );
         }
 
+        #[tokio::test]
+        async fn compile_mutual() {
+            insta::assert_snapshot!(
+                compile([[
+                    "let-rec".into(),
+                    "f".into(),
+                    ["fn".into(), [].into(), ["g".into()].into()].into(),
+                    "g".into(),
+                    ["fn".into(), [].into(), ["f".into()].into()].into()
+                ]
+                .into()])
+                .await
+            );
+        }
+
         #[tokio::test]
         async fn compile_invalid() {
             insta::assert_snapshot!(compile_error([["let-rec".into(), "x".into()].into()]).await);
@@ -763,4 +610,382 @@ mod tests {
             );
         }
     }
+
+    mod r#loop {
+        use super::*;
+
+        #[tokio::test]
+        async fn compile_loop() {
+            insta::assert_snapshot!(
+                compile([[
+                    "loop".into(),
+                    ["i".into(), 0.0.into(), "x".into(), 1.0.into()].into(),
+                    [
+                        "if".into(),
+                        ["<".into(), "i".into(), 3.0.into()].into(),
+                        [
+                            "recur".into(),
+                            ["+".into(), "i".into(), 1.0.into()].into(),
+                            ["*".into(), "x".into(), 2.0.into()].into()
+                        ]
+                        .into(),
+                        "x".into()
+                    ]
+                    .into()
+                ]
+                .into()])
+                .await
+            );
+        }
+
+        #[tokio::test]
+        async fn compile_loop_in_tail() {
+            insta::assert_snapshot!(
+                compile([[
+                    "fn".into(),
+                    ["n".into()].into(),
+                    [
+                        "loop".into(),
+                        ["i".into(), "n".into()].into(),
+                        [
+                            "let".into(),
+                            "x".into(),
+                            ["-".into(), "i".into(), 1.0.into()].into()
+                        ]
+                        .into(),
+                        [
+                            "if".into(),
+                            "x".into(),
+                            ["recur".into(), "x".into()].into(),
+                            "i".into()
+                        ]
+                        .into()
+                    ]
+                    .into()
+                ]
+                .into()])
+                .await
+            );
+        }
+
+        #[tokio::test]
+        async fn compile_while() {
+            insta::assert_snapshot!(
+                compile([
+                    ["let".into(), "x".into(), 1.0.into()].into(),
+                    [
+                        "while".into(),
+                        "x".into(),
+                        ["display".into(), "x".into()].into()
+                    ]
+                    .into()
+                ])
+                .await
+            );
+        }
+
+        #[tokio::test]
+        async fn compile_recur_outside_loop() {
+            insta::assert_snapshot!(compile_error([["recur".into(), 1.0.into()].into()]).await);
+        }
+
+        #[tokio::test]
+        async fn compile_recur_in_non_tail_position() {
+            insta::assert_snapshot!(
+                compile_error([[
+                    "loop".into(),
+                    ["i".into(), 0.0.into()].into(),
+                    ["+".into(), 1.0.into(), ["recur".into(), "i".into()].into()].into()
+                ]
+                .into()])
+                .await
+            );
+        }
+
+        #[tokio::test]
+        async fn compile_recur_with_wrong_arity() {
+            insta::assert_snapshot!(
+                compile_error([[
+                    "loop".into(),
+                    ["i".into(), 0.0.into()].into(),
+                    ["recur".into()].into()
+                ]
+                .into()])
+                .await
+            );
+        }
+    }
+
+    mod r#match {
+        use super::*;
+
+        #[tokio::test]
+        async fn compile_match() {
+            insta::assert_snapshot!(
+                compile([[
+                    "match".into(),
+                    1.0.into(),
+                    [0.0.into(), 1.0.into()].into(),
+                    [["x".into(), "_".into()].into(), "x".into()].into(),
+                    ["_".into(), 2.0.into()].into()
+                ]
+                .into()])
+                .await
+            );
+        }
+
+        #[tokio::test]
+        async fn compile_match_with_guard() {
+            insta::assert_snapshot!(
+                compile([[
+                    "match".into(),
+                    1.0.into(),
+                    [
+                        "x".into(),
+                        "when".into(),
+                        ["<".into(), "x".into(), 0.0.into()].into(),
+                        ["-".into(), 0.0.into(), "x".into()].into()
+                    ]
+                    .into(),
+                    ["x".into(), "x".into()].into()
+                ]
+                .into()])
+                .await
+            );
+        }
+
+        #[tokio::test]
+        async fn compile_match_with_rest() {
+            insta::assert_snapshot!(
+                compile([[
+                    "match".into(),
+                    NIL,
+                    [["x".into(), "&".into(), "xs".into()].into(), "xs".into()].into()
+                ]
+                .into()])
+                .await
+            );
+        }
+
+        #[tokio::test]
+        async fn compile_unreachable_pattern() {
+            insta::assert_snapshot!(
+                compile_error([[
+                    "match".into(),
+                    1.0.into(),
+                    ["x".into(), 1.0.into()].into(),
+                    [2.0.into(), 2.0.into()].into()
+                ]
+                .into()])
+                .await
+            );
+        }
+
+        #[tokio::test]
+        async fn compile_invalid_pattern() {
+            insta::assert_snapshot!(
+                compile_error([[
+                    "match".into(),
+                    1.0.into(),
+                    [["x".into(), "x".into()].into(), 1.0.into()].into()
+                ]
+                .into()])
+                .await
+            );
+        }
+    }
+
+    mod primitive {
+        use super::*;
+
+        #[tokio::test]
+        async fn compile_and() {
+            insta::assert_snapshot!(
+                compile([["and".into(), 1.0.into(), 2.0.into(), 3.0.into()].into()]).await
+            );
+        }
+
+        #[tokio::test]
+        async fn compile_or() {
+            insta::assert_snapshot!(
+                compile([["or".into(), 1.0.into(), 2.0.into(), 3.0.into()].into()]).await
+            );
+        }
+
+        #[tokio::test]
+        async fn compile_or_in_tail() {
+            insta::assert_snapshot!(
+                compile([[
+                    "fn".into(),
+                    ["x".into()].into(),
+                    ["or".into(), "x".into(), ["x".into()].into()].into()
+                ]
+                .into()])
+                .await
+            );
+        }
+
+        #[tokio::test]
+        async fn compile_variadic_arithmetic() {
+            insta::assert_snapshot!(
+                compile([["+".into(), 1.0.into(), 2.0.into(), 3.0.into()].into()]).await
+            );
+        }
+
+        #[tokio::test]
+        async fn compile_chained_comparison() {
+            insta::assert_snapshot!(
+                compile([["<".into(), 1.0.into(), 2.0.into(), 3.0.into()].into()]).await
+            );
+        }
+
+        #[tokio::test]
+        async fn compile_reference() {
+            insta::assert_snapshot!(
+                compile([
+                    ["let".into(), "x".into(), ["ref".into(), 1.0.into()].into()].into(),
+                    [
+                        "set!".into(),
+                        "x".into(),
+                        ["+".into(), ["deref".into(), "x".into()].into(), 1.0.into()].into()
+                    ]
+                    .into()
+                ])
+                .await
+            );
+        }
+
+        #[tokio::test]
+        async fn compile_array_primitives() {
+            insta::assert_snapshot!(
+                compile([
+                    [
+                        "let".into(),
+                        "xs".into(),
+                        ["range".into(), 3.0.into()].into()
+                    ]
+                    .into(),
+                    [
+                        "slice".into(),
+                        ["concat".into(), "xs".into(), "xs".into(), "xs".into()].into(),
+                        1.0.into(),
+                        2.0.into()
+                    ]
+                    .into(),
+                    [
+                        "reverse".into(),
+                        ["push".into(), "xs".into(), 1.0.into()].into()
+                    ]
+                    .into(),
+                    ["sort".into(), ["pop".into(), "xs".into()].into()].into(),
+                ])
+                .await
+            );
+        }
+
+        #[tokio::test]
+        async fn compile_higher_order_array_primitives() {
+            let identity = || ["fn".into(), ["x".into()].into(), "x".into()].into();
+
+            insta::assert_snapshot!(
+                compile([
+                    [
+                        "let".into(),
+                        "xs".into(),
+                        ["range".into(), 1.0.into(), 3.0.into()].into()
+                    ]
+                    .into(),
+                    ["map".into(), identity(), "xs".into()].into(),
+                    ["filter".into(), identity(), "xs".into()].into(),
+                    [
+                        "fold".into(),
+                        [
+                            "fn".into(),
+                            ["x".into(), "y".into()].into(),
+                            ["+".into(), "x".into(), "y".into()].into()
+                        ]
+                        .into(),
+                        0.0.into(),
+                        "xs".into()
+                    ]
+                    .into(),
+                    [
+                        "sort".into(),
+                        "xs".into(),
+                        [
+                            "fn".into(),
+                            ["x".into(), "y".into()].into(),
+                            [">".into(), "x".into(), "y".into()].into()
+                        ]
+                        .into()
+                    ]
+                    .into(),
+                ])
+                .await
+            );
+        }
+
+        #[tokio::test]
+        async fn compile_invalid_arity() {
+            insta::assert_snapshot!(compile_error([["+".into(), 1.0.into()].into()]).await);
+        }
+    }
+
+    mod sequence {
+        use super::*;
+
+        #[tokio::test]
+        async fn compile_do() {
+            insta::assert_snapshot!(
+                compile([[
+                    "do".into(),
+                    ["let".into(), "x".into(), 1.0.into()].into(),
+                    ["display".into(), "x".into()].into(),
+                    "x".into()
+                ]
+                .into()])
+                .await
+            );
+        }
+
+        #[tokio::test]
+        async fn compile_let() {
+            insta::assert_snapshot!(
+                compile([[
+                    "+".into(),
+                    1.0.into(),
+                    [
+                        "let".into(),
+                        [
+                            ["x".into(), 2.0.into()].into(),
+                            ["y".into(), "x".into()].into()
+                        ]
+                        .into(),
+                        ["*".into(), "x".into(), "y".into()].into()
+                    ]
+                    .into()
+                ]
+                .into()])
+                .await
+            );
+        }
+
+        #[tokio::test]
+        async fn compile_let_in_tail() {
+            insta::assert_snapshot!(
+                compile([[
+                    "fn".into(),
+                    ["x".into()].into(),
+                    [
+                        "let".into(),
+                        [["y".into(), "x".into()].into()].into(),
+                        ["y".into()].into()
+                    ]
+                    .into()
+                ]
+                .into()])
+                .await
+            );
+        }
+    }
 }
@@ -0,0 +1,410 @@
+use crate::{
+    block::Block,
+    function::Function,
+    ir::{Closure, Expression, Lambda, Statement},
+    variable::Variable,
+};
+use runtime::Symbol;
+use vm::Instruction;
+
+/// Resolves variables into stack slots and closure environments, and converts
+/// lambdas into closures.
+///
+/// Stack slots are counted in the same order as bytecodes are emitted.
+pub fn convert_statement(statement: Statement, block: &mut Block) -> Statement {
+    match statement {
+        Statement::Expression(expression) => {
+            let expression = convert_expression(expression, block, false);
+            *block.temporary_count_mut() -= 1;
+
+            Statement::Expression(expression)
+        }
+        Statement::Let { name, value, line } => {
+            let value = convert_expression(value, block, false);
+
+            block.insert_variable(name);
+            *block.temporary_count_mut() -= 1;
+
+            Statement::Let { name, value, line }
+        }
+        Statement::LetRec { functions, line } => {
+            let lambdas = functions
+                .into_iter()
+                .map(|function| match function {
+                    Expression::Lambda(lambda) => lambda,
+                    function => unreachable!("unlowered function: {function:?}"),
+                })
+                .collect::<Vec<_>>();
+            // Functions share their environment to create each other.
+            let function =
+                Function::with_siblings(lambdas.iter().filter_map(|lambda| lambda.name).collect());
+            let lambdas = lambdas
+                .into_iter()
+                .map(|lambda| convert_function(lambda, &function))
+                .collect::<Vec<_>>();
+            let free_variables = function.free_variables().clone();
+
+            Statement::LetRec {
+                functions: lambdas
+                    .into_iter()
+                    .map(|lambda| {
+                        let name = lambda.name;
+                        let closure = close(lambda, free_variables.clone(), block);
+
+                        if let Some(name) = name {
+                            block.insert_variable(name);
+                        }
+
+                        *block.temporary_count_mut() -= 1;
+
+                        Expression::Closure(closure)
+                    })
+                    .collect(),
+                line,
+            }
+        }
+    }
+}
+
+fn convert_expression(expression: Expression, block: &mut Block, tail: bool) -> Expression {
+    let expression = match expression {
+        Expression::Call {
+            function,
+            arguments,
+        } => {
+            let function = convert_expression(*function, block, false);
+            let arguments = convert_arguments(arguments, block);
+            *block.temporary_count_mut() -= arguments.len();
+
+            return Expression::Call {
+                function: function.into(),
+                arguments,
+            };
+        }
+        Expression::If {
+            condition,
+            then,
+            r#else,
+        } => {
+            let condition = convert_expression(*condition, block, false);
+            *block.temporary_count_mut() -= 1;
+            let then = convert_expression(*then, &mut block.fork(), tail);
+            let r#else = convert_expression(*r#else, &mut block.fork(), tail);
+
+            if !tail {
+                *block.temporary_count_mut() += 1;
+            }
+
+            return Expression::If {
+                condition: condition.into(),
+                then: then.into(),
+                r#else: r#else.into(),
+            };
+        }
+        Expression::Or { left, right } => {
+            let left = convert_expression(*left, block, false);
+            // A left value is dropped before a right expression is evaluated.
+            *block.temporary_count_mut() -= 1;
+            let right = convert_expression(*right, &mut block.fork(), tail);
+
+            if !tail {
+                *block.temporary_count_mut() += 1;
+            }
+
+            return Expression::Or {
+                left: left.into(),
+                right: right.into(),
+            };
+        }
+        Expression::Loop { bindings, body } => {
+            let (bindings, body) = {
+                let mut block = block.fork_loop();
+                let bindings = bindings
+                    .into_iter()
+                    .map(|(name, value)| {
+                        let value = convert_expression(value, &mut block, false);
+
+                        block.insert_variable(name);
+                        *block.temporary_count_mut() -= 1;
+
+                        (name, value)
+                    })
+                    .collect();
+
+                (bindings, convert_expression(*body, &mut block.fork(), tail))
+            };
+
+            // Loop variables are dropped under its value.
+            if !tail {
+                *block.temporary_count_mut() += 1;
+            }
+
+            return Expression::Loop {
+                bindings,
+                body: body.into(),
+            };
+        }
+        Expression::Recur { arguments, .. } => {
+            let depth = block.loop_depth();
+            let arguments = convert_arguments(arguments, block);
+
+            // `recur` never returns but is counted as a value like other
+            // branches.
+            *block.temporary_count_mut() -= arguments.len();
+            *block.temporary_count_mut() += 1;
+
+            Expression::Recur { arguments, depth }
+        }
+        Expression::Sequence { body, result } => {
+            let (body, result) = {
+                let mut block = block.fork();
+                let body = body
+                    .into_iter()
+                    .map(|statement| convert_statement(statement, &mut block))
+                    .collect();
+
+                (body, convert_expression(*result, &mut block, tail))
+            };
+
+            // Variables in a sequence are dropped under its value.
+            if !tail {
+                *block.temporary_count_mut() += 1;
+            }
+
+            return Expression::Sequence {
+                body,
+                result: result.into(),
+            };
+        }
+        Expression::Lambda(lambda) => Expression::Closure(convert_lambda(lambda, block)),
+        Expression::Located { line, expression } => {
+            return Expression::Located {
+                line,
+                expression: convert_expression(*expression, block, tail).into(),
+            }
+        }
+        Expression::Primitive {
+            instruction,
+            arguments,
+        } => {
+            let arguments = convert_arguments(arguments, block);
+
+            *block.temporary_count_mut() -= match instruction {
+                Instruction::Deref => 0,
+                Instruction::Display => 0,
+                Instruction::Fold => 2,
+                Instruction::Length => 0,
+                Instruction::Not => 0,
+                Instruction::Pop => 0,
+                Instruction::Ref => 0,
+                Instruction::Repr => 0,
+                Instruction::Reverse => 0,
+                Instruction::Set => 2,
+                Instruction::Slice => 2,
+                _ => 1,
+            };
+
+            Expression::Primitive {
+                instruction,
+                arguments,
+            }
+        }
+        Expression::Move(symbol) => match convert_variable(symbol, block) {
+            Expression::Local(index) => Expression::Take(index),
+            expression => expression,
+        },
+        Expression::Variable(symbol) => convert_variable(symbol, block),
+        expression @ (Expression::Closure(_)
+        | Expression::Environment(_)
+        | Expression::Local(_)
+        | Expression::Sibling(_)
+        | Expression::Take(_)
+        | Expression::Float64(_)
+        | Expression::Integer32(_)
+        | Expression::Nil) => {
+            *block.temporary_count_mut() += 1;
+            expression
+        }
+    };
+
+    // A value is popped on return.
+    if tail {
+        *block.temporary_count_mut() -= 1;
+    }
+
+    expression
+}
+
+fn convert_arguments(arguments: Vec<Expression>, block: &mut Block) -> Vec<Expression> {
+    arguments
+        .into_iter()
+        .map(|argument| convert_expression(argument, block, false))
+        .collect()
+}
+
+fn convert_variable(symbol: Symbol, block: &mut Block) -> Expression {
+    let expression = match block.get_variable(symbol) {
+        Variable::Bound(index) => Expression::Local(index),
+        Variable::Free(index) => Expression::Environment(index),
+        Variable::Sibling(index) => Expression::Sibling(index),
+    };
+
+    *block.temporary_count_mut() += 1;
+
+    expression
+}
+
+fn convert_lambda(lambda: Lambda, block: &mut Block) -> Closure {
+    let function = Function::new();
+    let lambda = convert_function(lambda, &function);
+    let free_variables = function.free_variables().clone();
+
+    close(lambda, free_variables, block)
+}
+
+// Converts a body of a function collecting its free variables.
+fn convert_function(lambda: Lambda, function: &Function) -> Lambda {
+    let (body, result) = {
+        let mut block = Block::with_capacity(function, lambda.arguments.len() + 1);
+
+        if let Some(name) = lambda.name.filter(|_| lambda.recursive) {
+            block.insert_variable(name);
+        }
+
+        for &argument in &lambda.arguments {
+            block.insert_variable(argument);
+        }
+
+        let body = lambda
+            .body
+            .into_iter()
+            .map(|statement| convert_statement(statement, &mut block))
+            .collect();
+
+        (body, convert_expression(*lambda.result, &mut block, true))
+    };
+
+    Lambda {
+        body,
+        result: result.into(),
+        ..lambda
+    }
+}
+
+// Creates a closure of a converted function capturing free variables.
+fn close(lambda: Lambda, free_variables: Vec<Symbol>, block: &mut Block) -> Closure {
+    let environment = free_variables
+        .iter()
+        .map(|&symbol| convert_variable(symbol, block))
+        .collect::<Vec<_>>();
+
+    *block.temporary_count_mut() -= environment.len();
+    *block.temporary_count_mut() += 1;
+
+    Closure {
+        lambda,
+        free_variables,
+        environment,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn convert_local_variables() {
+        let function = Function::new();
+        let mut block = Block::new(&function);
+
+        block.insert_variable("x".into());
+        block.insert_variable("y".into());
+
+        assert_eq!(
+            convert_statement(
+                Statement::Expression(Expression::Primitive {
+                    instruction: Instruction::Add,
+                    arguments: vec![
+                        Expression::Variable("x".into()),
+                        Expression::Variable("y".into())
+                    ],
+                }),
+                &mut block
+            ),
+            Statement::Expression(Expression::Primitive {
+                instruction: Instruction::Add,
+                arguments: vec![Expression::Local(1), Expression::Local(1)],
+            })
+        );
+    }
+
+    #[test]
+    fn convert_free_variables() {
+        let function = Function::new();
+        let mut block = Block::new(&function);
+
+        assert_eq!(
+            convert_statement(
+                Statement::Expression(Expression::Variable("x".into())),
+                &mut block
+            ),
+            Statement::Expression(Expression::Environment(0))
+        );
+        assert_eq!(function.free_variables().as_slice(), [Symbol::from("x")]);
+    }
+
+    #[test]
+    fn convert_closure() {
+        let function = Function::new();
+        let mut block = Block::new(&function);
+
+        block.insert_variable("x".into());
+
+        assert_eq!(
+            convert_statement(
+                Statement::Let {
+                    name: "f".into(),
+                    value: Expression::Lambda(Lambda {
+                        name: Some("f".into()),
+                        recursive: true,
+                        arguments: vec!["y".into()],
+                        body: vec![],
+                        result: Expression::Call {
+                            function: Expression::Variable("f".into()).into(),
+                            arguments: vec![
+                                Expression::Variable("x".into()),
+                                Expression::Variable("y".into())
+                            ],
+                        }
+                        .into(),
+                        span: None,
+                    }),
+                    line: None,
+                },
+                &mut block
+            ),
+            Statement::Let {
+                name: "f".into(),
+                value: Expression::Closure(Closure {
+                    lambda: Lambda {
+                        name: Some("f".into()),
+                        recursive: true,
+                        arguments: vec!["y".into()],
+                        body: vec![],
+                        result: Expression::Call {
+                            function: Expression::Local(1).into(),
+                            arguments: vec![Expression::Environment(0), Expression::Local(2)],
+                        }
+                        .into(),
+                        span: None,
+                    },
+                    free_variables: vec!["x".into()],
+                    environment: vec![Expression::Local(0)],
+                }),
+                line: None,
+            }
+        );
+        assert!(function.free_variables().is_empty());
+    }
+}
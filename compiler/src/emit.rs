@@ -0,0 +1,487 @@
+use crate::{
+    ir::{Closure, Expression, Statement},
+    optimizer::optimize,
+    CompileError,
+};
+use runtime::Symbol;
+use std::{cell::RefCell, mem::size_of, ops::Range};
+use vm::{DebugInfo, FunctionInfo, Instruction};
+
+/// Emits bytecodes from an intermediate representation after closure
+/// conversion.
+pub struct Emitter<'a> {
+    codes: &'a RefCell<Vec<u8>>,
+    debug_info: Option<&'a RefCell<DebugInfo>>,
+    line: Option<usize>,
+    groups: Vec<Group>,
+    // Start addresses of bodies of loops being emitted.
+    loops: Vec<usize>,
+}
+
+// Mutually recursive functions being emitted.
+struct Group {
+    arities: Vec<u8>,
+    environment_size: usize,
+    addresses: Vec<usize>,
+    // Indices of function addresses in closure instructions with indices of
+    // functions in a group.
+    references: Vec<(usize, usize)>,
+}
+
+impl<'a> Emitter<'a> {
+    pub fn new(codes: &'a RefCell<Vec<u8>>, debug_info: Option<&'a RefCell<DebugInfo>>) -> Self {
+        Self {
+            codes,
+            debug_info,
+            line: None,
+            groups: vec![],
+            loops: vec![],
+        }
+    }
+
+    /// Emits and optimizes a top-level statement dumping its value.
+    pub fn emit(&mut self, statement: &Statement) -> Result<(), CompileError> {
+        let start = self.codes.borrow().len();
+
+        self.emit_statement(statement, true)?;
+        self.optimize(start);
+
+        Ok(())
+    }
+
+    fn emit_statement(&mut self, statement: &Statement, dump: bool) -> Result<(), CompileError> {
+        match statement {
+            Statement::Expression(expression) => {
+                self.emit_expression(expression, false)?;
+
+                if dump {
+                    self.push(Instruction::Dump);
+                }
+
+                self.push(Instruction::Drop);
+            }
+            Statement::Let { value, line, .. } => {
+                let line = self.enter_location(*line);
+                self.emit_expression(value, false)?;
+                self.leave_location(line);
+            }
+            Statement::LetRec { functions, line } => {
+                let line = self.enter_location(*line);
+                self.emit_functions(functions)?;
+                self.leave_location(line);
+            }
+        }
+
+        Ok(())
+    }
+
+    fn emit_expression(&mut self, expression: &Expression, tail: bool) -> Result<(), CompileError> {
+        match expression {
+            Expression::Call {
+                function,
+                arguments,
+            } => {
+                self.emit_expression(function, false)?;
+                self.emit_arguments(arguments)?;
+
+                let mut codes = self.codes.borrow_mut();
+                codes.push(if tail {
+                    Instruction::TailCall
+                } else {
+                    Instruction::Call
+                } as u8);
+                codes.push(arguments.len() as u8);
+
+                return Ok(());
+            }
+            Expression::Closure(closure) => self.emit_closure(closure)?,
+            Expression::Environment(index) => {
+                self.codes
+                    .borrow_mut()
+                    .extend([Instruction::Environment as u8, *index as u8]);
+            }
+            Expression::Float64(number) => {
+                let mut codes = self.codes.borrow_mut();
+
+                codes.push(Instruction::Float64 as u8);
+                codes.extend(number.to_le_bytes());
+            }
+            Expression::If {
+                condition,
+                then,
+                r#else,
+            } => return self.emit_if(condition, then, r#else, tail),
+            Expression::Integer32(number) => {
+                let mut codes = self.codes.borrow_mut();
+
+                codes.push(Instruction::Integer32 as u8);
+                codes.extend(number.to_le_bytes());
+            }
+            Expression::Local(index) => {
+                self.codes
+                    .borrow_mut()
+                    .extend([Instruction::Peek as u8, *index as u8]);
+            }
+            Expression::Located { line, expression } => {
+                let line = self.enter_location(Some(*line));
+                self.emit_expression(expression, tail)?;
+                self.leave_location(line);
+
+                return Ok(());
+            }
+            Expression::Loop { bindings, body } => return self.emit_loop(bindings, body, tail),
+            Expression::Nil => self.push(Instruction::Nil),
+            Expression::Or { left, right } => return self.emit_or(left, right, tail),
+            Expression::Take(index) => {
+                self.codes
+                    .borrow_mut()
+                    .extend([Instruction::Take as u8, *index as u8]);
+            }
+            Expression::Primitive {
+                instruction,
+                arguments,
+            } => {
+                self.emit_arguments(arguments)?;
+                self.push(*instruction);
+            }
+            Expression::Recur { arguments, depth } => return self.emit_recur(arguments, *depth),
+            Expression::Sequence { body, result } => return self.emit_sequence(body, result, tail),
+            Expression::Sibling(index) => self.emit_sibling(*index),
+            Expression::Lambda(_) | Expression::Move(_) | Expression::Variable(_) => {
+                unreachable!("unconverted expression: {expression:?}")
+            }
+        }
+
+        if tail {
+            self.push(Instruction::Return);
+        }
+
+        Ok(())
+    }
+
+    fn emit_arguments(&mut self, arguments: &[Expression]) -> Result<(), CompileError> {
+        for argument in arguments {
+            self.emit_expression(argument, false)?;
+        }
+
+        Ok(())
+    }
+
+    fn emit_closure(&mut self, closure: &Closure) -> Result<(), CompileError> {
+        let lambda = &closure.lambda;
+        let arity = u8::try_from(lambda.arguments.len())?;
+        let jump_index = self.push_jump(Instruction::Jump);
+        let function_index = jump_index;
+
+        for statement in &lambda.body {
+            self.emit_statement(statement, false)?;
+        }
+
+        self.emit_expression(&lambda.result, true)?;
+        self.optimize(function_index);
+
+        let current_index = self.patch_jump(jump_index);
+
+        self.insert_function(closure, function_index..current_index);
+
+        self.emit_arguments(&closure.environment)?;
+
+        let mut codes = self.codes.borrow_mut();
+        codes.push(Instruction::Close as u8);
+        codes.extend((function_index as u32).to_le_bytes());
+        codes.push(arity);
+        codes.push(closure.environment.len() as u8);
+
+        Ok(())
+    }
+
+    // Emits mutually recursive functions followed by their closures.
+    //
+    // Functions are optimized together with ones enclosing them as they refer
+    // to each other.
+    fn emit_functions(&mut self, functions: &[Expression]) -> Result<(), CompileError> {
+        let closures = functions
+            .iter()
+            .map(|function| match function {
+                Expression::Closure(closure) => closure,
+                function => unreachable!("unconverted function: {function:?}"),
+            })
+            .collect::<Vec<_>>();
+
+        self.groups.push(Group {
+            arities: closures
+                .iter()
+                .map(|closure| u8::try_from(closure.lambda.arguments.len()))
+                .collect::<Result<_, _>>()?,
+            environment_size: closures
+                .first()
+                .map_or(0, |closure| closure.environment.len()),
+            addresses: Vec::with_capacity(closures.len()),
+            references: vec![],
+        });
+
+        let jump_index = self.push_jump(Instruction::Jump);
+        let mut ranges = Vec::with_capacity(closures.len());
+
+        for closure in &closures {
+            let start = self.codes.borrow().len();
+
+            if let Some(group) = self.groups.last_mut() {
+                group.addresses.push(start);
+            }
+
+            for statement in &closure.lambda.body {
+                self.emit_statement(statement, false)?;
+            }
+
+            self.emit_expression(&closure.lambda.result, true)?;
+            ranges.push(start..self.codes.borrow().len());
+        }
+
+        let group = self.groups.pop().expect("group");
+
+        {
+            let mut codes = self.codes.borrow_mut();
+
+            for &(index, function) in &group.references {
+                codes[index..index + size_of::<u32>()]
+                    .copy_from_slice(&(group.addresses[function] as u32).to_le_bytes());
+            }
+        }
+
+        self.patch_jump(jump_index);
+
+        for ((closure, range), arity) in closures.iter().zip(ranges).zip(group.arities) {
+            self.insert_function(closure, range.clone());
+            self.emit_arguments(&closure.environment)?;
+
+            let mut codes = self.codes.borrow_mut();
+            codes.push(Instruction::Close as u8);
+            codes.extend((range.start as u32).to_le_bytes());
+            codes.push(arity);
+            codes.push(closure.environment.len() as u8);
+        }
+
+        Ok(())
+    }
+
+    // Emits a function in a current group with an environment of a current
+    // closure.
+    fn emit_sibling(&mut self, index: usize) {
+        let group = self.groups.last_mut().expect("group");
+        let mut codes = self.codes.borrow_mut();
+
+        for environment_index in 0..group.environment_size {
+            codes.extend([Instruction::Environment as u8, environment_index as u8]);
+        }
+
+        codes.push(Instruction::Close as u8);
+        group.references.push((codes.len(), index));
+        codes.extend(0u32.to_le_bytes());
+        codes.push(group.arities[index]);
+        codes.push(group.environment_size as u8);
+    }
+
+    fn emit_if(
+        &mut self,
+        condition: &Expression,
+        then: &Expression,
+        r#else: &Expression,
+        tail: bool,
+    ) -> Result<(), CompileError> {
+        self.emit_expression(condition, false)?;
+        let branch_index = self.push_jump(Instruction::Branch);
+        self.emit_expression(then, tail)?;
+        let then_index = (!tail).then(|| self.push_jump(Instruction::Jump));
+        self.patch_jump(branch_index);
+        self.emit_expression(r#else, tail)?;
+
+        if let Some(then_index) = then_index {
+            self.patch_jump(then_index);
+        }
+
+        Ok(())
+    }
+
+    fn emit_or(
+        &mut self,
+        left: &Expression,
+        right: &Expression,
+        tail: bool,
+    ) -> Result<(), CompileError> {
+        self.emit_expression(left, false)?;
+        self.codes.borrow_mut().extend([Instruction::Peek as u8, 0]);
+        let branch_index = self.push_jump(Instruction::Branch);
+        let left_index = if tail {
+            self.push(Instruction::Return);
+            None
+        } else {
+            Some(self.push_jump(Instruction::Jump))
+        };
+        self.patch_jump(branch_index);
+        self.push(Instruction::Drop);
+        self.emit_expression(right, tail)?;
+
+        if let Some(left_index) = left_index {
+            self.patch_jump(left_index);
+        }
+
+        Ok(())
+    }
+
+    fn emit_sequence(
+        &mut self,
+        body: &[Statement],
+        result: &Expression,
+        tail: bool,
+    ) -> Result<(), CompileError> {
+        for statement in body {
+            self.emit_statement(statement, false)?;
+        }
+
+        self.emit_expression(result, tail)?;
+
+        if !tail {
+            let count = body
+                .iter()
+                .map(|statement| match statement {
+                    Statement::Expression(_) => 0,
+                    Statement::Let { .. } => 1,
+                    Statement::LetRec { functions, .. } => functions.len(),
+                })
+                .sum::<usize>();
+
+            if count > 0 {
+                self.codes
+                    .borrow_mut()
+                    .extend([Instruction::Nip as u8, u8::try_from(count)?]);
+            }
+        }
+
+        Ok(())
+    }
+
+    fn emit_loop(
+        &mut self,
+        bindings: &[(Symbol, Expression)],
+        body: &Expression,
+        tail: bool,
+    ) -> Result<(), CompileError> {
+        for (_, value) in bindings {
+            self.emit_expression(value, false)?;
+        }
+
+        self.loops.push(self.codes.borrow().len());
+        self.emit_expression(body, tail)?;
+        self.loops.pop();
+
+        if !tail && !bindings.is_empty() {
+            self.codes
+                .borrow_mut()
+                .extend([Instruction::Nip as u8, u8::try_from(bindings.len())?]);
+        }
+
+        Ok(())
+    }
+
+    // Stores new values into loop variables, drops values above them and
+    // jumps back to the start of a loop body.
+    fn emit_recur(&mut self, arguments: &[Expression], depth: usize) -> Result<(), CompileError> {
+        self.emit_arguments(arguments)?;
+
+        let start = *self.loops.last().expect("loop");
+        let index = u8::try_from(arguments.len() + depth)?;
+        let mut codes = self.codes.borrow_mut();
+
+        for _ in arguments {
+            codes.extend([Instruction::Store as u8, index - 1]);
+        }
+
+        for _ in 0..depth {
+            codes.push(Instruction::Drop as u8);
+        }
+
+        codes.push(Instruction::Jump as u8);
+        let pointer = start as isize - (codes.len() + size_of::<u16>()) as isize;
+        codes.extend(i16::try_from(pointer)?.to_le_bytes());
+
+        Ok(())
+    }
+
+    fn insert_function(&self, closure: &Closure, codes: Range<usize>) {
+        let Some(debug_info) = self.debug_info else {
+            return;
+        };
+        let lambda = &closure.lambda;
+
+        debug_info.borrow_mut().insert_function(FunctionInfo::new(
+            lambda.name.map(|name| name.as_str().into()),
+            codes,
+            lambda.span.clone(),
+            lambda.arguments.iter().map(ToString::to_string).collect(),
+            closure
+                .free_variables
+                .iter()
+                .map(ToString::to_string)
+                .collect(),
+        ));
+    }
+
+    fn push(&mut self, instruction: Instruction) {
+        self.codes.borrow_mut().push(instruction as u8);
+    }
+
+    // Pushes a jump or branch instruction with a stub address and returns an
+    // index after it.
+    fn push_jump(&mut self, instruction: Instruction) -> usize {
+        let mut codes = self.codes.borrow_mut();
+
+        codes.push(instruction as u8);
+        codes.extend(0u16.to_le_bytes());
+        codes.len()
+    }
+
+    // Patches a jump or branch instruction to jump to the current index and
+    // returns the index.
+    fn patch_jump(&mut self, index: usize) -> usize {
+        let mut codes = self.codes.borrow_mut();
+        let current_index = codes.len();
+
+        codes[index - size_of::<u16>()..index]
+            .copy_from_slice(&((current_index - index) as i16).to_le_bytes());
+
+        current_index
+    }
+
+    // Optimizes bytecodes from a start address and relocates debug information.
+    fn optimize(&mut self, start: usize) {
+        let addresses = optimize(&mut self.codes.borrow_mut(), start);
+
+        if let Some(debug_info) = self.debug_info {
+            debug_info.borrow_mut().relocate(start, &addresses);
+        }
+    }
+
+    fn enter_location(&mut self, line: Option<usize>) -> Option<usize> {
+        let previous = self.line;
+
+        if let (Some(line), Some(debug_info)) = (line, self.debug_info) {
+            debug_info
+                .borrow_mut()
+                .insert_line(self.codes.borrow().len(), line);
+            self.line = Some(line);
+        }
+
+        previous
+    }
+
+    fn leave_location(&mut self, line: Option<usize>) {
+        if let (Some(line), Some(debug_info)) = (line, self.debug_info) {
+            debug_info
+                .borrow_mut()
+                .continue_line(self.codes.borrow().len(), line);
+        }
+
+        self.line = line;
+    }
+}
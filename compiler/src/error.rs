@@ -7,10 +7,16 @@ use std::{
 
 #[derive(Debug)]
 pub enum CompileError {
+    Arity(String),
     Closure,
     Other(Box<dyn Error>),
+    Pattern(String),
+    Recur(String),
+    Reference,
     Syntax(String),
     SymbolLength(String),
+    UnreachablePattern(String),
+    UnsupportedPrimitive(String),
     VariableNotDefined(String),
 }
 
@@ -19,18 +25,36 @@ impl Error for CompileError {}
 impl Display for CompileError {
     fn fmt(&self, formatter: &mut Formatter) -> fmt::Result {
         match self {
+            Self::Arity(literal) => {
+                write!(formatter, "wrong number of arguments: {literal}")
+            }
             Self::Closure => {
                 write!(formatter, "closure cannot be compiled")
             }
             Self::Other(error) => {
                 write!(formatter, "{error}")
             }
+            Self::Pattern(literal) => {
+                write!(formatter, "invalid pattern: {literal}")
+            }
+            Self::Recur(literal) => {
+                write!(formatter, "recur not in tail position of loop: {literal}")
+            }
+            Self::Reference => {
+                write!(formatter, "reference cannot be compiled")
+            }
             Self::Syntax(literal) => {
                 write!(formatter, "invalid syntax: {literal}")
             }
             Self::SymbolLength(symbol) => {
                 write!(formatter, "symbol too long: {symbol}")
             }
+            Self::UnreachablePattern(literal) => {
+                write!(formatter, "unreachable pattern: {literal}")
+            }
+            Self::UnsupportedPrimitive(name) => {
+                write!(formatter, "primitive not supported: {name}")
+            }
             Self::VariableNotDefined(symbol) => {
                 write!(formatter, "variable not found: {symbol}")
             }
@@ -0,0 +1,200 @@
+use crate::ir::{Expression, Lambda, Statement};
+use runtime::Symbol;
+
+/// Collects free variables of mutually recursive functions in an order of
+/// their first uses.
+///
+/// Names of the functions themselves are not free in them.
+pub fn collect_free_variables(functions: &[&Lambda]) -> Vec<Symbol> {
+    let mut collector = Collector {
+        bound: functions.iter().filter_map(|lambda| lambda.name).collect(),
+        free: vec![],
+    };
+
+    for lambda in functions {
+        collector.visit_lambda(lambda);
+    }
+
+    collector.free
+}
+
+/// Collects free variables of an expression in which some variables are
+/// bound.
+pub fn collect_expression_free_variables(
+    expression: &Expression,
+    bound: Vec<Symbol>,
+) -> Vec<Symbol> {
+    let mut collector = Collector {
+        bound,
+        free: vec![],
+    };
+
+    collector.visit_expression(expression);
+
+    collector.free
+}
+
+struct Collector {
+    bound: Vec<Symbol>,
+    free: Vec<Symbol>,
+}
+
+impl Collector {
+    fn visit_lambda(&mut self, lambda: &Lambda) {
+        let length = self.bound.len();
+
+        self.bound.extend(lambda.name.filter(|_| lambda.recursive));
+        self.bound.extend(&lambda.arguments);
+
+        for statement in &lambda.body {
+            self.visit_statement(statement);
+        }
+
+        self.visit_expression(&lambda.result);
+        self.bound.truncate(length);
+    }
+
+    fn visit_statement(&mut self, statement: &Statement) {
+        match statement {
+            Statement::Expression(expression) => self.visit_expression(expression),
+            Statement::Let { name, value, .. } => {
+                self.visit_expression(value);
+                self.bound.push(*name);
+            }
+            Statement::LetRec { functions, .. } => {
+                for function in functions {
+                    if let Expression::Lambda(lambda) = function {
+                        self.bound.extend(lambda.name);
+                    }
+                }
+
+                for function in functions {
+                    self.visit_expression(function);
+                }
+            }
+        }
+    }
+
+    fn visit_expression(&mut self, expression: &Expression) {
+        match expression {
+            Expression::Call {
+                function,
+                arguments,
+            } => {
+                self.visit_expression(function);
+                arguments
+                    .iter()
+                    .for_each(|argument| self.visit_expression(argument));
+            }
+            Expression::If {
+                condition,
+                then,
+                r#else,
+            } => {
+                self.visit_expression(condition);
+                self.visit_expression(then);
+                self.visit_expression(r#else);
+            }
+            Expression::Or { left, right } => {
+                self.visit_expression(left);
+                self.visit_expression(right);
+            }
+            Expression::Lambda(lambda) => self.visit_lambda(lambda),
+            Expression::Located { expression, .. } => self.visit_expression(expression),
+            Expression::Loop { bindings, body } => {
+                let length = self.bound.len();
+
+                for (name, value) in bindings {
+                    self.visit_expression(value);
+                    self.bound.push(*name);
+                }
+
+                self.visit_expression(body);
+                self.bound.truncate(length);
+            }
+            Expression::Primitive { arguments, .. } | Expression::Recur { arguments, .. } => {
+                arguments
+                    .iter()
+                    .for_each(|argument| self.visit_expression(argument))
+            }
+            Expression::Sequence { body, result } => {
+                let length = self.bound.len();
+
+                for statement in body {
+                    self.visit_statement(statement);
+                }
+
+                self.visit_expression(result);
+                self.bound.truncate(length);
+            }
+            &Expression::Move(name) | &Expression::Variable(name) => {
+                if !self.bound.contains(&name) && !self.free.contains(&name) {
+                    self.free.push(name);
+                }
+            }
+            Expression::Closure(_)
+            | Expression::Environment(_)
+            | Expression::Local(_)
+            | Expression::Sibling(_)
+            | Expression::Take(_) => {
+                unreachable!("converted expression: {expression:?}")
+            }
+            Expression::Float64(_) | Expression::Integer32(_) | Expression::Nil => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    fn call(function: &str, arguments: &[&str]) -> Expression {
+        Expression::Call {
+            function: Expression::Variable(function.into()).into(),
+            arguments: arguments
+                .iter()
+                .map(|&name| Expression::Variable(name.into()))
+                .collect(),
+        }
+    }
+
+    fn lambda(name: &str, arguments: &[&str], body: Vec<Statement>, result: Expression) -> Lambda {
+        Lambda {
+            name: Some(name.into()),
+            recursive: true,
+            arguments: arguments.iter().map(|&name| name.into()).collect(),
+            body,
+            result: result.into(),
+            span: None,
+        }
+    }
+
+    #[test]
+    fn collect_in_order() {
+        assert_eq!(
+            collect_free_variables(&[
+                &lambda("f", &["x"], vec![], call("g", &["y", "x"])),
+                &lambda("g", &["x", "y"], vec![], call("f", &["z", "y"])),
+            ]),
+            ["y".into(), "z".into()] as [Symbol; 2]
+        );
+    }
+
+    #[test]
+    fn skip_bound_variables() {
+        assert_eq!(
+            collect_free_variables(&[&lambda(
+                "f",
+                &[],
+                vec![Statement::Let {
+                    name: "x".into(),
+                    value: call("y", &[]),
+                    line: None,
+                }],
+                Expression::Lambda(lambda("g", &["z"], vec![], call("x", &["z", "g", "w"])))
+            )]),
+            ["y".into(), "w".into()] as [Symbol; 2]
+        );
+    }
+}
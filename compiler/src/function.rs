@@ -4,6 +4,7 @@ use std::cell::{Ref, RefCell, RefMut};
 #[derive(Debug, Default)]
 pub struct Function {
     free_variables: RefCell<Vec<Symbol>>,
+    siblings: Vec<Symbol>,
 }
 
 impl Function {
@@ -11,6 +12,18 @@ impl Function {
         Self::default()
     }
 
+    /// Creates a function shared by a group of mutually recursive functions.
+    pub fn with_siblings(siblings: Vec<Symbol>) -> Self {
+        Self {
+            free_variables: Default::default(),
+            siblings,
+        }
+    }
+
+    pub fn siblings(&self) -> &[Symbol] {
+        &self.siblings
+    }
+
     pub fn free_variables(&self) -> Ref<'_, Vec<Symbol>> {
         self.free_variables.borrow()
     }
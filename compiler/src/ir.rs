@@ -0,0 +1,119 @@
+use core::ops::Range;
+use runtime::Symbol;
+use vm::Instruction;
+
+/// A statement in an intermediate representation.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Statement {
+    /// An expression whose value is discarded.
+    Expression(Expression),
+    Let {
+        name: Symbol,
+        value: Expression,
+        line: Option<usize>,
+    },
+    /// Mutually recursive functions.
+    ///
+    /// Functions are lambdas before closure conversion and closures after it.
+    LetRec {
+        functions: Vec<Expression>,
+        line: Option<usize>,
+    },
+}
+
+/// An expression in an intermediate representation.
+///
+/// Lowering produces variables and lambdas by names. Closure conversion
+/// resolves them into stack slots, environments and closures.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Expression {
+    Call {
+        function: Box<Expression>,
+        arguments: Vec<Expression>,
+    },
+    Closure(Closure),
+    /// A free variable in a closure environment.
+    Environment(usize),
+    Float64(f64),
+    If {
+        condition: Box<Expression>,
+        then: Box<Expression>,
+        r#else: Box<Expression>,
+    },
+    Integer32(i32),
+    Lambda(Lambda),
+    /// A body evaluated repeatedly with loop variables.
+    ///
+    /// Bindings are evaluated in order like `let` statements and `Recur` in
+    /// tail positions of the body starts its next iteration.
+    Loop {
+        bindings: Vec<(Symbol, Expression)>,
+        body: Box<Expression>,
+    },
+    /// A bound variable at an index from a stack top.
+    Local(usize),
+    /// An expression at a source line.
+    Located {
+        line: usize,
+        expression: Box<Expression>,
+    },
+    /// A last use of a bound variable whose value can be moved.
+    Move(Symbol),
+    Nil,
+    /// A value of a left expression if it is not `nil`, or a value of a right
+    /// one otherwise.
+    ///
+    /// The right expression is evaluated only if the left one is `nil`.
+    Or {
+        left: Box<Expression>,
+        right: Box<Expression>,
+    },
+    Primitive {
+        instruction: Instruction,
+        arguments: Vec<Expression>,
+    },
+    /// New values of variables of the innermost loop for its next iteration.
+    ///
+    /// A depth is a number of values on a stack above the loop variables,
+    /// which is resolved by closure conversion.
+    Recur {
+        arguments: Vec<Expression>,
+        depth: usize,
+    },
+    /// Statements followed by a result expression in a nested scope.
+    ///
+    /// Variables bound by the statements are dropped after the result is
+    /// evaluated.
+    Sequence {
+        body: Vec<Statement>,
+        result: Box<Expression>,
+    },
+    /// A function at an index in a group of mutually recursive functions.
+    ///
+    /// It is created with the same environment as a closure being called.
+    Sibling(usize),
+    /// A last use of a bound variable at an index from a stack top.
+    Take(usize),
+    Variable(Symbol),
+}
+
+/// A function definition.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Lambda {
+    pub name: Option<Symbol>,
+    pub recursive: bool,
+    pub arguments: Vec<Symbol>,
+    pub body: Vec<Statement>,
+    pub result: Box<Expression>,
+    /// A byte range in a source code.
+    pub span: Option<Range<usize>>,
+}
+
+/// A function definition with its environment.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Closure {
+    pub lambda: Lambda,
+    pub free_variables: Vec<Symbol>,
+    /// Expressions of captured values in an order of free variables.
+    pub environment: Vec<Expression>,
+}
@@ -1,8 +1,22 @@
 mod block;
 mod compiler;
+mod convert;
+mod emit;
 mod error;
+mod free_variable;
 mod function;
+mod ir;
+mod liveness;
+mod lower;
+mod optimizer;
+mod pattern;
+mod register;
+mod scope;
 mod variable;
+mod wasm;
 
-pub use compiler::Compiler;
+pub use compiler::{Compiler, PRIMITIVES, SPECIAL_FORMS};
 pub use error::CompileError;
+pub use register::RegisterCompiler;
+pub use scope::{Binding, BindingKind, Reference, Scopes};
+pub use wasm::WasmCompiler;
@@ -0,0 +1,491 @@
+use crate::{
+    free_variable::collect_expression_free_variables,
+    ir::{Expression, Lambda, Statement},
+};
+use runtime::Symbol;
+use std::collections::HashSet;
+
+/// Marks last uses of local variables in functions as moves.
+///
+/// Moved values are not cloned, so that arrays referenced only by them can be
+/// updated in place. Variables at a top level are never moved because later
+/// statements can refer to them.
+pub fn move_last_uses(statement: &mut Statement) {
+    match statement {
+        Statement::Expression(expression)
+        | Statement::Let {
+            value: expression, ..
+        } => visit_lambdas(expression),
+        Statement::LetRec { functions, .. } => functions.iter_mut().for_each(visit_lambdas),
+    }
+}
+
+fn visit_lambdas(expression: &mut Expression) {
+    match expression {
+        Expression::Call {
+            function,
+            arguments,
+        } => {
+            visit_lambdas(function);
+            arguments.iter_mut().for_each(visit_lambdas);
+        }
+        Expression::If {
+            condition,
+            then,
+            r#else,
+        } => {
+            visit_lambdas(condition);
+            visit_lambdas(then);
+            visit_lambdas(r#else);
+        }
+        Expression::Or { left, right } => {
+            visit_lambdas(left);
+            visit_lambdas(right);
+        }
+        Expression::Lambda(lambda) => {
+            analyze_lambda(lambda);
+        }
+        Expression::Located { expression, .. } => visit_lambdas(expression),
+        Expression::Loop { bindings, body } => {
+            for (_, value) in bindings {
+                visit_lambdas(value);
+            }
+
+            visit_lambdas(body);
+        }
+        Expression::Primitive { arguments, .. } | Expression::Recur { arguments, .. } => {
+            arguments.iter_mut().for_each(visit_lambdas)
+        }
+        Expression::Sequence { body, result } => {
+            body.iter_mut().for_each(move_last_uses);
+            visit_lambdas(result);
+        }
+        _ => {}
+    }
+}
+
+// Analyzes liveness of variables in a function backward and returns its free
+// variables.
+fn analyze_lambda(lambda: &mut Lambda) -> HashSet<Symbol> {
+    // A recursive function itself is never moved as call frames refer to it.
+    let mut live = HashSet::new();
+
+    analyze_body(
+        &mut lambda.body,
+        &mut lambda.result,
+        lambda.arguments.iter().copied().collect(),
+        &mut live,
+        &HashSet::new(),
+        &HashSet::new(),
+    );
+
+    for argument in &lambda.arguments {
+        live.remove(argument);
+    }
+
+    if let Some(name) = lambda.name.filter(|_| lambda.recursive) {
+        live.remove(&name);
+    }
+
+    live
+}
+
+// Analyzes liveness of variables in statements and their result backward.
+//
+// Kept variables stay live even if they are shadowed by the statements.
+// Variables live at the end of an iteration of the innermost loop are live at
+// `recur`.
+fn analyze_body(
+    body: &mut [Statement],
+    result: &mut Expression,
+    mut bound: HashSet<Symbol>,
+    live: &mut HashSet<Symbol>,
+    kept: &HashSet<Symbol>,
+    recur_live: &HashSet<Symbol>,
+) {
+    let mut scopes = Vec::with_capacity(body.len());
+
+    for statement in body.iter() {
+        scopes.push(bound.clone());
+
+        match statement {
+            Statement::Expression(_) => {}
+            Statement::Let { name, .. } => {
+                bound.insert(*name);
+            }
+            Statement::LetRec { functions, .. } => bound.extend(function_names(functions)),
+        }
+    }
+
+    analyze_expression(result, &bound, live, recur_live);
+
+    for (statement, bound) in body.iter_mut().zip(&scopes).rev() {
+        match statement {
+            Statement::Expression(expression) => {
+                analyze_expression(expression, bound, live, recur_live)
+            }
+            Statement::Let { name, value, .. } => {
+                if !kept.contains(name) {
+                    live.remove(name);
+                }
+
+                analyze_expression(value, bound, live, recur_live);
+            }
+            Statement::LetRec { functions, .. } => {
+                let names = function_names(functions).collect::<HashSet<_>>();
+
+                live.retain(|name| !names.contains(name) || kept.contains(name));
+
+                for function in functions {
+                    let mut function_live = HashSet::new();
+
+                    analyze_expression(function, bound, &mut function_live, recur_live);
+                    live.extend(function_live.difference(&names));
+                }
+            }
+        }
+    }
+}
+
+fn function_names(functions: &[Expression]) -> impl Iterator<Item = Symbol> + '_ {
+    functions.iter().filter_map(|function| match function {
+        Expression::Lambda(lambda) => lambda.name,
+        _ => None,
+    })
+}
+
+fn analyze_expression(
+    expression: &mut Expression,
+    bound: &HashSet<Symbol>,
+    live: &mut HashSet<Symbol>,
+    recur_live: &HashSet<Symbol>,
+) {
+    match expression {
+        Expression::Call {
+            function,
+            arguments,
+        } => {
+            analyze_arguments(arguments, bound, live, recur_live);
+            analyze_expression(function, bound, live, recur_live);
+        }
+        Expression::If {
+            condition,
+            then,
+            r#else,
+        } => {
+            let mut else_live = live.clone();
+
+            analyze_expression(r#else, bound, &mut else_live, recur_live);
+            analyze_expression(then, bound, live, recur_live);
+            live.extend(else_live);
+            analyze_expression(condition, bound, live, recur_live);
+        }
+        Expression::Or { left, right } => {
+            let mut right_live = live.clone();
+
+            analyze_expression(right, bound, &mut right_live, recur_live);
+            live.extend(right_live);
+            analyze_expression(left, bound, live, recur_live);
+        }
+        Expression::Lambda(lambda) => live.extend(analyze_lambda(lambda)),
+        Expression::Located { expression, .. } => {
+            analyze_expression(expression, bound, live, recur_live)
+        }
+        Expression::Loop { bindings, body } => {
+            analyze_loop(bindings, body, bound, live, recur_live)
+        }
+        Expression::Primitive { arguments, .. } => {
+            analyze_arguments(arguments, bound, live, recur_live)
+        }
+        Expression::Recur { arguments, .. } => {
+            // Values live after `recur` are ones used in a next iteration.
+            live.clone_from(recur_live);
+            analyze_arguments(arguments, bound, live, recur_live);
+        }
+        Expression::Sequence { body, result } => {
+            // Variables live after a sequence are still live in it even if
+            // they are shadowed.
+            let kept = live.clone();
+
+            analyze_body(body, result, bound.clone(), live, &kept, recur_live);
+        }
+        &mut Expression::Variable(name) => {
+            if bound.contains(&name) && !live.contains(&name) {
+                *expression = Expression::Move(name);
+            }
+
+            live.insert(name);
+        }
+        Expression::Closure(_)
+        | Expression::Environment(_)
+        | Expression::Local(_)
+        | Expression::Move(_)
+        | Expression::Sibling(_)
+        | Expression::Take(_) => {
+            unreachable!("analyzed expression: {expression:?}")
+        }
+        Expression::Float64(_) | Expression::Integer32(_) | Expression::Nil => {}
+    }
+}
+
+fn analyze_loop(
+    bindings: &mut [(Symbol, Expression)],
+    body: &mut Expression,
+    bound: &HashSet<Symbol>,
+    live: &mut HashSet<Symbol>,
+    recur_live: &HashSet<Symbol>,
+) {
+    let kept = live.clone();
+    let mut scopes = Vec::with_capacity(bindings.len());
+    let mut body_bound = bound.clone();
+
+    for (name, _) in bindings.iter() {
+        scopes.push(body_bound.clone());
+        body_bound.insert(*name);
+    }
+
+    // Variables used in a loop body are live until its last iteration ends.
+    let mut body_recur_live = live.clone();
+    body_recur_live.extend(collect_expression_free_variables(
+        body,
+        bindings.iter().map(|(name, _)| *name).collect(),
+    ));
+
+    analyze_expression(body, &body_bound, live, &body_recur_live);
+
+    for ((name, value), bound) in bindings.iter_mut().zip(&scopes).rev() {
+        if !kept.contains(name) {
+            live.remove(name);
+        }
+
+        analyze_expression(value, bound, live, recur_live);
+    }
+}
+
+fn analyze_arguments(
+    arguments: &mut [Expression],
+    bound: &HashSet<Symbol>,
+    live: &mut HashSet<Symbol>,
+    recur_live: &HashSet<Symbol>,
+) {
+    for argument in arguments.iter_mut().rev() {
+        analyze_expression(argument, bound, live, recur_live);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+    use vm::Instruction;
+
+    fn lambda(arguments: &[&str], body: Vec<Statement>, result: Expression) -> Expression {
+        Expression::Lambda(Lambda {
+            name: None,
+            recursive: false,
+            arguments: arguments.iter().map(|&name| name.into()).collect(),
+            body,
+            result: result.into(),
+            span: None,
+        })
+    }
+
+    fn variable(name: &str) -> Expression {
+        Expression::Variable(name.into())
+    }
+
+    fn r#move(name: &str) -> Expression {
+        Expression::Move(name.into())
+    }
+
+    fn add(lhs: Expression, rhs: Expression) -> Expression {
+        Expression::Primitive {
+            instruction: Instruction::Add,
+            arguments: vec![lhs, rhs],
+        }
+    }
+
+    fn analyze(expression: Expression) -> Expression {
+        let mut statement = Statement::Expression(expression);
+
+        move_last_uses(&mut statement);
+
+        let Statement::Expression(expression) = statement else {
+            unreachable!()
+        };
+
+        expression
+    }
+
+    #[test]
+    fn move_last_use() {
+        assert_eq!(
+            analyze(lambda(&["x"], vec![], add(variable("x"), variable("x")))),
+            lambda(&["x"], vec![], add(variable("x"), r#move("x")))
+        );
+    }
+
+    #[test]
+    fn move_in_branches() {
+        assert_eq!(
+            analyze(lambda(
+                &["x", "y"],
+                vec![],
+                Expression::If {
+                    condition: variable("y").into(),
+                    then: variable("x").into(),
+                    r#else: Expression::Nil.into(),
+                }
+            )),
+            lambda(
+                &["x", "y"],
+                vec![],
+                Expression::If {
+                    condition: r#move("y").into(),
+                    then: r#move("x").into(),
+                    r#else: Expression::Nil.into(),
+                }
+            )
+        );
+    }
+
+    #[test]
+    fn move_shadowed_variable() {
+        assert_eq!(
+            analyze(lambda(
+                &["x"],
+                vec![
+                    Statement::Let {
+                        name: "y".into(),
+                        value: variable("x"),
+                        line: None,
+                    },
+                    Statement::Let {
+                        name: "x".into(),
+                        value: variable("y"),
+                        line: None,
+                    },
+                ],
+                add(variable("x"), variable("y"))
+            )),
+            lambda(
+                &["x"],
+                vec![
+                    Statement::Let {
+                        name: "y".into(),
+                        value: r#move("x"),
+                        line: None,
+                    },
+                    Statement::Let {
+                        name: "x".into(),
+                        value: variable("y"),
+                        line: None,
+                    },
+                ],
+                add(r#move("x"), r#move("y"))
+            )
+        );
+    }
+
+    #[test]
+    fn keep_variable_shadowed_in_sequence() {
+        assert_eq!(
+            analyze(lambda(
+                &["x"],
+                vec![],
+                add(
+                    Expression::Sequence {
+                        body: vec![Statement::Let {
+                            name: "x".into(),
+                            value: variable("x"),
+                            line: None,
+                        }],
+                        result: variable("x").into(),
+                    },
+                    variable("x")
+                )
+            )),
+            lambda(
+                &["x"],
+                vec![],
+                add(
+                    Expression::Sequence {
+                        body: vec![Statement::Let {
+                            name: "x".into(),
+                            value: variable("x"),
+                            line: None,
+                        }],
+                        result: variable("x").into(),
+                    },
+                    r#move("x")
+                )
+            )
+        );
+    }
+
+    #[test]
+    fn keep_variable_used_in_loop() {
+        let recur = |argument| Expression::Recur {
+            arguments: vec![argument],
+            depth: 0,
+        };
+
+        assert_eq!(
+            analyze(lambda(
+                &["x", "y"],
+                vec![],
+                Expression::Loop {
+                    bindings: vec![("i".into(), variable("x"))],
+                    body: Expression::If {
+                        condition: variable("i").into(),
+                        then: recur(add(variable("i"), variable("y"))).into(),
+                        r#else: variable("y").into(),
+                    }
+                    .into(),
+                }
+            )),
+            lambda(
+                &["x", "y"],
+                vec![],
+                Expression::Loop {
+                    bindings: vec![("i".into(), r#move("x"))],
+                    body: Expression::If {
+                        condition: variable("i").into(),
+                        then: recur(add(r#move("i"), variable("y"))).into(),
+                        r#else: r#move("y").into(),
+                    }
+                    .into(),
+                }
+            )
+        );
+    }
+
+    #[test]
+    fn keep_captured_variable() {
+        assert_eq!(
+            analyze(lambda(
+                &["x"],
+                vec![],
+                add(lambda(&[], vec![], variable("x")), variable("x"))
+            )),
+            lambda(
+                &["x"],
+                vec![],
+                add(lambda(&[], vec![], variable("x")), r#move("x"))
+            )
+        );
+    }
+
+    #[test]
+    fn keep_free_variable() {
+        assert_eq!(
+            analyze(lambda(&[], vec![], variable("x"))),
+            lambda(&[], vec![], variable("x"))
+        );
+    }
+
+    #[test]
+    fn keep_top_level_variable() {
+        assert_eq!(analyze(variable("x")), variable("x"));
+    }
+}
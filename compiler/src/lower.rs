@@ -0,0 +1,1445 @@
+use crate::{
+    ir::{Expression, Lambda, Statement},
+    pattern::Pattern,
+    scope::{BindingKind, Resolver, Scope},
+    CompileError,
+};
+use parse::{SourceMap, Span, Spans};
+use runtime::{Array, Symbol, TypedValueRef, Value};
+use std::{
+    cell::{Cell, RefCell},
+    ops::RangeInclusive,
+};
+use vm::Instruction;
+
+/// Lowers values of source forms into an intermediate representation.
+pub struct Lowerer<'a> {
+    source_map: Option<&'a SourceMap>,
+    resolver: Option<&'a Resolver>,
+    // Spans of values in a top-level statement being lowered.
+    spans: RefCell<Spans>,
+    // A number of variables of a loop whose tail position is being lowered.
+    recur_arity: Cell<Option<usize>>,
+}
+
+impl<'a> Lowerer<'a> {
+    pub fn new(source_map: Option<&'a SourceMap>) -> Self {
+        Self {
+            source_map,
+            resolver: None,
+            spans: Default::default(),
+            recur_arity: Cell::new(None),
+        }
+    }
+
+    /// Creates a lowerer which resolves variables while lowering values.
+    pub fn with_resolver(source_map: &'a SourceMap, resolver: &'a Resolver) -> Self {
+        Self {
+            resolver: Some(resolver),
+            ..Self::new(Some(source_map))
+        }
+    }
+
+    /// Lowers a top-level statement.
+    ///
+    /// Top-level statements must be lowered in the order of their values in a
+    /// source map.
+    pub fn lower(&self, value: &Value) -> Result<Statement, CompileError> {
+        if let Some(source_map) = self.source_map {
+            *self.spans.borrow_mut() = source_map.take_spans(value);
+        }
+
+        let statement = self.lower_statement(value);
+        *self.spans.borrow_mut() = Default::default();
+        statement
+    }
+
+    fn lower_statement(&self, value: &Value) -> Result<Statement, CompileError> {
+        let Some(array) = value.as_array() else {
+            return Ok(Statement::Expression(self.lower_expression(value)?));
+        };
+        let recursive = match array.get_usize(0).to_symbol() {
+            // A `let` form with a list of bindings is an expression.
+            Some(symbol) if symbol.as_str() == "let" && array.get_usize(1).is_array() => {
+                return Ok(Statement::Expression(self.lower_expression(value)?));
+            }
+            Some(symbol) if symbol.as_str() == "let" => false,
+            Some(symbol) if symbol.as_str() == "let-rec" => true,
+            _ => return Ok(Statement::Expression(self.lower_expression(value)?)),
+        };
+
+        if recursive && array.len_usize() > 3 {
+            return self.lower_let_rec(array);
+        }
+
+        let Some(name) = array
+            .get_usize(1)
+            .to_symbol()
+            .filter(|_| array.len_usize() == 3)
+        else {
+            return Err(CompileError::Syntax(array.to_string()));
+        };
+
+        if recursive {
+            self.bind(
+                array.get_usize(1),
+                BindingKind::LetRec,
+                self.span(array.get_usize(2)).map(|span| span.start()),
+            );
+        }
+
+        let value = self.lower_binding(name, array.get_usize(2), recursive, array)?;
+
+        if !recursive {
+            self.bind(
+                array.get_usize(1),
+                BindingKind::Let,
+                self.array_span(array).map(|span| span.end()),
+            );
+        }
+
+        Ok(Statement::Let {
+            name,
+            value,
+            line: self.line(array),
+        })
+    }
+
+    fn lower_binding(
+        &self,
+        name: Symbol,
+        value: &Value,
+        recursive: bool,
+        array: &Array,
+    ) -> Result<Expression, CompileError> {
+        Ok(match value.as_array().filter(|array| is_lambda(array)) {
+            Some(function) => {
+                Expression::Lambda(self.lower_lambda(Some(name), recursive, function)?)
+            }
+            None if recursive => return Err(CompileError::Syntax(array.to_string())),
+            None => self.lower_expression(value)?,
+        })
+    }
+
+    // Lowers `let-rec` with multiple bindings into mutually recursive functions.
+    fn lower_let_rec(&self, array: &Array) -> Result<Statement, CompileError> {
+        let syntax_error = || CompileError::Syntax(array.to_string());
+
+        if array.len_usize().is_multiple_of(2) {
+            return Err(syntax_error());
+        }
+
+        let mut names = Vec::with_capacity(array.len_usize() / 2);
+
+        for index in (1..array.len_usize()).step_by(2) {
+            names.push(
+                array
+                    .get_usize(index)
+                    .to_symbol()
+                    .filter(|name| !names.contains(name))
+                    .ok_or_else(syntax_error)?,
+            );
+        }
+
+        // Functions are visible from the first one so that they can refer to
+        // each other.
+        let start = self.span(array.get_usize(2)).map(|span| span.start());
+
+        for index in (1..array.len_usize()).step_by(2) {
+            self.bind(array.get_usize(index), BindingKind::LetRec, start);
+        }
+
+        let functions = names
+            .into_iter()
+            .zip((2..array.len_usize()).step_by(2))
+            .map(|(name, index)| {
+                let function = array
+                    .get_usize(index)
+                    .as_array()
+                    .filter(|array| is_lambda(array))
+                    .ok_or_else(syntax_error)?;
+
+                Ok(Expression::Lambda(self.lower_lambda(
+                    Some(name),
+                    true,
+                    function,
+                )?))
+            })
+            .collect::<Result<_, CompileError>>()?;
+
+        Ok(Statement::LetRec {
+            functions,
+            line: self.line(array),
+        })
+    }
+
+    fn lower_expression(&self, value: &Value) -> Result<Expression, CompileError> {
+        // Only expressions in tail positions of loops can start their next
+        // iterations.
+        let arity = self.recur_arity.take();
+        let expression = self.lower_tail_expression(value);
+        self.recur_arity.set(arity);
+
+        expression
+    }
+
+    // Lowers an expression in a tail position of an enclosing expression.
+    fn lower_tail_expression(&self, value: &Value) -> Result<Expression, CompileError> {
+        Ok(match value.as_typed() {
+            Some(TypedValueRef::Array(array)) => {
+                let expression = self.lower_array(array)?;
+
+                match self.line(array) {
+                    Some(line) => Expression::Located {
+                        line,
+                        expression: expression.into(),
+                    },
+                    None => expression,
+                }
+            }
+            Some(TypedValueRef::Closure(_)) => return Err(CompileError::Closure),
+            Some(TypedValueRef::Float64(number)) => Expression::Float64(number.to_f64()),
+            Some(TypedValueRef::Integer32(number)) => Expression::Integer32(number.to_i32()),
+            Some(TypedValueRef::Reference(_)) => return Err(CompileError::Reference),
+            Some(TypedValueRef::Symbol(symbol)) => {
+                self.refer(value, symbol);
+                Expression::Variable(symbol)
+            }
+            None => Expression::Nil,
+        })
+    }
+
+    fn lower_array(&self, array: &Array) -> Result<Expression, CompileError> {
+        if let Some(symbol) = array.get_usize(0).to_symbol() {
+            let symbol = symbol.as_str();
+
+            if symbol == "do" {
+                return self.lower_do(array);
+            } else if symbol == "fn" {
+                return Ok(Expression::Lambda(self.lower_lambda(None, false, array)?));
+            } else if symbol == "let" {
+                return self.lower_let(array);
+            } else if symbol == "if" {
+                return self.lower_if(array, 1);
+            } else if symbol == "loop" {
+                return self.lower_loop(array);
+            } else if symbol == "match" {
+                return self.lower_match(array);
+            } else if symbol == "recur" {
+                return self.lower_recur(array);
+            } else if symbol == "while" {
+                return self.lower_while(array);
+            } else if let Some(instruction) = primitive_instruction(symbol) {
+                return self.lower_primitive(instruction, array);
+            }
+        }
+
+        Ok(Expression::Call {
+            function: self.lower_expression(array.get_usize(0))?.into(),
+            arguments: self.lower_arguments(array)?,
+        })
+    }
+
+    fn lower_do(&self, array: &Array) -> Result<Expression, CompileError> {
+        if array.len_usize() < 2 {
+            return Ok(Expression::Nil);
+        }
+
+        let scope = self.enter(array);
+        let expression = self.lower_sequence(vec![], array, 1)?;
+        self.exit(scope);
+
+        Ok(expression)
+    }
+
+    // Lowers `let` with a list of bindings into a sequence of bindings
+    // followed by its body.
+    fn lower_let(&self, array: &Array) -> Result<Expression, CompileError> {
+        let syntax_error = || CompileError::Syntax(array.to_string());
+        let bindings = array.get_usize(1);
+        let bindings = bindings.as_array().ok_or_else(syntax_error)?;
+
+        if array.len_usize() < 3 {
+            return Err(syntax_error());
+        }
+
+        let scope = self.enter(array);
+        let body = (0..bindings.len_usize())
+            .map(|index| {
+                let binding = bindings.get_usize(index);
+                let binding = binding
+                    .as_array()
+                    .filter(|binding| binding.len_usize() == 2)
+                    .ok_or_else(syntax_error)?;
+                let name = binding.get_usize(0).to_symbol().ok_or_else(syntax_error)?;
+                let value = self.lower_binding(name, binding.get_usize(1), false, array)?;
+
+                self.bind(
+                    binding.get_usize(0),
+                    BindingKind::Let,
+                    self.array_span(binding).map(|span| span.end()),
+                );
+
+                Ok(Statement::Let {
+                    name,
+                    value,
+                    line: self.line(binding),
+                })
+            })
+            .collect::<Result<_, CompileError>>()?;
+        let expression = self.lower_sequence(body, array, 2)?;
+        self.exit(scope);
+
+        Ok(expression)
+    }
+
+    // Lowers forms from an index in an array into statements followed by a
+    // result expression.
+    fn lower_sequence(
+        &self,
+        mut body: Vec<Statement>,
+        array: &Array,
+        start: usize,
+    ) -> Result<Expression, CompileError> {
+        for index in start..array.len_usize() - 1 {
+            body.push(self.lower_statement(array.get_usize(index))?);
+        }
+
+        let result = self.lower_tail_expression(array.get_usize(array.len_usize() - 1))?;
+
+        Ok(if body.is_empty() {
+            result
+        } else {
+            Expression::Sequence {
+                body,
+                result: result.into(),
+            }
+        })
+    }
+
+    fn lower_loop(&self, array: &Array) -> Result<Expression, CompileError> {
+        let syntax_error = || CompileError::Syntax(array.to_string());
+        let bindings = array.get_usize(1);
+        let bindings = bindings
+            .as_array()
+            .filter(|bindings| bindings.len_usize().is_multiple_of(2))
+            .ok_or_else(syntax_error)?;
+
+        if array.len_usize() < 3 {
+            return Err(syntax_error());
+        }
+
+        let scope = self.enter(array);
+        let bindings = (0..bindings.len_usize())
+            .step_by(2)
+            .map(|index| {
+                let name = bindings
+                    .get_usize(index)
+                    .to_symbol()
+                    .ok_or_else(syntax_error)?;
+                let value = bindings.get_usize(index + 1);
+                let expression = self.lower_expression(value)?;
+
+                self.bind(
+                    bindings.get_usize(index),
+                    BindingKind::Let,
+                    self.span(value).map(|span| span.end()),
+                );
+
+                Ok((name, expression))
+            })
+            .collect::<Result<Vec<_>, CompileError>>()?;
+
+        let arity = self.recur_arity.replace(Some(bindings.len()));
+        let body = self.lower_sequence(vec![], array, 2);
+        self.recur_arity.set(arity);
+        self.exit(scope);
+
+        Ok(Expression::Loop {
+            bindings,
+            body: body?.into(),
+        })
+    }
+
+    // Lowers `match` into branches on conditions of patterns of clauses.
+    //
+    // A clause is a pattern followed by an optional guard after `when` and
+    // its body.
+    fn lower_match(&self, array: &Array) -> Result<Expression, CompileError> {
+        let syntax_error = || CompileError::Syntax(array.to_string());
+
+        if array.len_usize() < 2 {
+            return Err(syntax_error());
+        }
+
+        // A value is bound to a symbol of a number, which source code can
+        // refer to only with escapes.
+        let value = Symbol::from("0");
+        let argument = self.lower_expression(array.get_usize(1))?;
+        let mut clauses = Vec::<(Pattern, Option<Expression>, Expression)>::new();
+
+        for index in 2..array.len_usize() {
+            let clause = array.get_usize(index);
+            let clause = clause
+                .as_array()
+                .filter(|clause| clause.len_usize() >= 2)
+                .ok_or_else(syntax_error)?;
+            let pattern = Pattern::parse(clause.get_usize(0))?;
+            let guarded = clause
+                .get_usize(1)
+                .to_symbol()
+                .is_some_and(|symbol| symbol.as_str() == "when");
+
+            if guarded && clause.len_usize() < 4 {
+                return Err(syntax_error());
+            } else if clauses
+                .iter()
+                .any(|(other, guard, _)| guard.is_none() && other.covers(&pattern))
+            {
+                return Err(CompileError::UnreachablePattern(
+                    clause.get_usize(0).to_string(),
+                ));
+            }
+
+            let scope = self.enter(clause);
+            let start = self.span(clause.get_usize(0)).map(|span| span.end());
+
+            Pattern::visit_variables(clause.get_usize(0), &mut |variable| {
+                self.bind(variable, BindingKind::Let, start)
+            });
+
+            let guard = guarded
+                .then(|| self.lower_expression(clause.get_usize(2)))
+                .transpose()?;
+            let body = self.lower_sequence(vec![], clause, if guarded { 3 } else { 1 })?;
+            self.exit(scope);
+
+            clauses.push((pattern, guard, body));
+        }
+
+        let result =
+            clauses
+                .into_iter()
+                .rev()
+                .fold(Expression::Nil, |r#else, (pattern, guard, body)| {
+                    let mut conditions = vec![];
+                    let mut bindings = vec![];
+
+                    pattern.lower(Expression::Variable(value), &mut conditions, &mut bindings);
+
+                    // Variables are bound once for a guard and again for a body.
+                    if let Some(guard) = guard {
+                        conditions.push(sequence(bindings.clone(), guard));
+                    }
+
+                    let then = sequence(bindings, body);
+
+                    if conditions.is_empty() {
+                        then
+                    } else {
+                        Expression::If {
+                            condition: fold_arguments(conditions, |left, right| Expression::If {
+                                condition: left.into(),
+                                then: right.into(),
+                                r#else: Expression::Nil.into(),
+                            })
+                            .into(),
+                            then: then.into(),
+                            r#else: r#else.into(),
+                        }
+                    }
+                });
+
+        Ok(Expression::Sequence {
+            body: vec![Statement::Let {
+                name: value,
+                value: argument,
+                line: None,
+            }],
+            result: result.into(),
+        })
+    }
+
+    fn lower_recur(&self, array: &Array) -> Result<Expression, CompileError> {
+        let Some(arity) = self.recur_arity.get() else {
+            return Err(CompileError::Recur(array.to_string()));
+        };
+        let arguments = self.lower_arguments(array)?;
+
+        if arguments.len() != arity {
+            return Err(CompileError::Arity(array.to_string()));
+        }
+
+        Ok(Expression::Recur {
+            arguments,
+            depth: 0,
+        })
+    }
+
+    // Lowers `while` into a loop without variables which repeats its body
+    // while its condition is not `nil`.
+    fn lower_while(&self, array: &Array) -> Result<Expression, CompileError> {
+        if array.len_usize() < 2 {
+            return Err(CompileError::Syntax(array.to_string()));
+        }
+
+        let condition = self.lower_expression(array.get_usize(1))?;
+        let scope = self.enter(array);
+        let body = (2..array.len_usize())
+            .map(|index| self.lower_statement(array.get_usize(index)))
+            .collect::<Result<Vec<_>, _>>()?;
+        self.exit(scope);
+        let recur = Expression::Recur {
+            arguments: vec![],
+            depth: 0,
+        };
+
+        Ok(Expression::Loop {
+            bindings: vec![],
+            body: Expression::If {
+                condition: condition.into(),
+                then: if body.is_empty() {
+                    recur
+                } else {
+                    Expression::Sequence {
+                        body,
+                        result: recur.into(),
+                    }
+                }
+                .into(),
+                r#else: Expression::Nil.into(),
+            }
+            .into(),
+        })
+    }
+
+    fn lower_primitive(
+        &self,
+        instruction: Instruction,
+        array: &Array,
+    ) -> Result<Expression, CompileError> {
+        let arguments = self.lower_arguments(array)?;
+
+        if !primitive_arity(instruction).contains(&arguments.len()) {
+            return Err(CompileError::Arity(array.to_string()));
+        }
+
+        Ok(match instruction {
+            // Empty logical operators are their identities.
+            Instruction::And if arguments.is_empty() => Expression::Float64(1.0),
+            Instruction::Or if arguments.is_empty() => Expression::Nil,
+            // Logical operators are lowered into branches to short-circuit.
+            Instruction::And => fold_arguments(arguments, |left, right| Expression::If {
+                condition: left.into(),
+                then: right.into(),
+                r#else: Expression::Nil.into(),
+            }),
+            Instruction::Or => fold_arguments(arguments, |left, right| Expression::Or {
+                left: left.into(),
+                right: right.into(),
+            }),
+            // Optional operands are filled so that primitives have fixed arities.
+            // A unary minus subtracts its operand from zero.
+            Instruction::Range | Instruction::Subtract if arguments.len() == 1 => {
+                Expression::Primitive {
+                    instruction,
+                    arguments: [Expression::Float64(0.0)]
+                        .into_iter()
+                        .chain(arguments)
+                        .collect(),
+                }
+            }
+            Instruction::Sort if arguments.len() == 1 => Expression::Primitive {
+                instruction,
+                arguments: arguments.into_iter().chain([Expression::Nil]).collect(),
+            },
+            Instruction::Add
+            | Instruction::Subtract
+            | Instruction::Multiply
+            | Instruction::Divide
+            | Instruction::Concat => arguments
+                .into_iter()
+                .reduce(|left, right| Expression::Primitive {
+                    instruction,
+                    arguments: vec![left, right],
+                })
+                .expect("arguments"),
+            Instruction::Equal
+            | Instruction::NotEqual
+            | Instruction::LessThan
+            | Instruction::LessThanOrEqual
+            | Instruction::GreaterThan
+            | Instruction::GreaterThanOrEqual
+                if arguments.len() > 2 =>
+            {
+                lower_comparisons(instruction, arguments)
+            }
+            _ => Expression::Primitive {
+                instruction,
+                arguments,
+            },
+        })
+    }
+
+    fn lower_arguments(&self, array: &Array) -> Result<Vec<Expression>, CompileError> {
+        (1..array.len_usize())
+            .map(|index| self.lower_expression(array.get_usize(index)))
+            .collect()
+    }
+
+    fn lower_lambda(
+        &self,
+        name: Option<Symbol>,
+        recursive: bool,
+        array: &Array,
+    ) -> Result<Lambda, CompileError> {
+        let syntax_error = || CompileError::Syntax(array.to_string());
+        let arguments = array.get_usize(1);
+        let arguments = arguments.as_array().ok_or_else(syntax_error)?;
+
+        if array.len_usize() < 3 {
+            return Err(syntax_error());
+        }
+
+        let span = self.array_span(array);
+        let arguments = (0..arguments.len_usize())
+            .map(|index| {
+                let argument = arguments.get_usize(index);
+                let name = argument.to_symbol().ok_or_else(syntax_error)?;
+
+                Ok((argument, name))
+            })
+            .collect::<Result<Vec<_>, CompileError>>()?;
+        let scope = self.enter(array);
+
+        for &(argument, _) in &arguments {
+            self.bind(
+                argument,
+                BindingKind::Argument,
+                span.map(|span| span.start()),
+            );
+        }
+
+        let lambda = Lambda {
+            name,
+            recursive,
+            arguments: arguments.into_iter().map(|(_, name)| name).collect(),
+            body: (2..array.len_usize() - 1)
+                .map(|index| self.lower_statement(array.get_usize(index)))
+                .collect::<Result<_, _>>()?,
+            result: self
+                .lower_expression(array.get_usize(array.len_usize() - 1))?
+                .into(),
+            span: span.map(|span| span.start()..span.end()),
+        };
+        self.exit(scope);
+
+        Ok(lambda)
+    }
+
+    fn lower_if(&self, array: &Array, condition_index: usize) -> Result<Expression, CompileError> {
+        Ok(Expression::If {
+            condition: self
+                .lower_expression(array.get_usize(condition_index))?
+                .into(),
+            then: self
+                .lower_tail_expression(array.get_usize(condition_index + 1))?
+                .into(),
+            r#else: if condition_index + 3 < array.len_usize() {
+                self.lower_if(array, condition_index + 2)?
+            } else {
+                self.lower_tail_expression(array.get_usize(condition_index + 2))?
+            }
+            .into(),
+        })
+    }
+
+    fn line(&self, array: &Array) -> Option<usize> {
+        Some(self.source_map?.line(self.array_span(array)?.start()))
+    }
+
+    fn array_span(&self, array: &Array) -> Option<Span> {
+        self.spans.borrow().array(array)
+    }
+
+    fn span(&self, value: &Value) -> Option<Span> {
+        self.spans.borrow().value(value)
+    }
+
+    // Enters a scope of variables bound in an array.
+    fn enter(&self, array: &Array) -> Option<Scope> {
+        let resolver = self.resolver?;
+
+        Some(resolver.enter(self.array_span(array).map(|span| span.end())))
+    }
+
+    // Exits a scope. Scopes left by errors are exited on the errors of whole
+    // statements.
+    fn exit(&self, scope: Option<Scope>) {
+        if let Some((resolver, scope)) = self.resolver.zip(scope) {
+            resolver.exit(scope);
+        }
+    }
+
+    // Binds a variable of a symbol visible from an offset.
+    fn bind(&self, value: &Value, kind: BindingKind, start: Option<usize>) {
+        if let (Some(resolver), Some(name), Some(span), Some(start)) =
+            (self.resolver, value.to_symbol(), self.span(value), start)
+        {
+            resolver.bind(name, kind, span, start);
+        }
+    }
+
+    fn refer(&self, value: &Value, name: Symbol) {
+        if let Some((resolver, span)) = self.resolver.zip(self.span(value)) {
+            resolver.refer(name, span);
+        }
+    }
+}
+
+fn is_lambda(array: &Array) -> bool {
+    array
+        .get_usize(0)
+        .to_symbol()
+        .is_some_and(|symbol| symbol.as_str() == "fn")
+}
+
+// Lowers chained comparisons into conjunctions of comparisons of adjacent
+// operands.
+fn lower_comparisons(instruction: Instruction, arguments: Vec<Expression>) -> Expression {
+    let compare = |operands: &[Expression]| {
+        fold_arguments(
+            operands
+                .windows(2)
+                .map(|operands| Expression::Primitive {
+                    instruction,
+                    arguments: operands.to_vec(),
+                })
+                .collect(),
+            |left, right| Expression::If {
+                condition: left.into(),
+                then: right.into(),
+                r#else: Expression::Nil.into(),
+            },
+        )
+    };
+
+    if arguments[1..].iter().all(is_atom) {
+        return compare(&arguments);
+    }
+
+    // Operands are bound to arguments of a function applied immediately, so
+    // that all of them are evaluated exactly once.
+    let names = (0..arguments.len())
+        .map(|index| Symbol::from(index.to_string()))
+        .collect::<Vec<_>>();
+
+    Expression::Call {
+        function: Expression::Lambda(Lambda {
+            name: None,
+            recursive: false,
+            result: compare(
+                &names
+                    .iter()
+                    .map(|&name| Expression::Variable(name))
+                    .collect::<Vec<_>>(),
+            )
+            .into(),
+            arguments: names,
+            body: vec![],
+            span: None,
+        })
+        .into(),
+        arguments,
+    }
+}
+
+// Folds arguments from right to left.
+fn fold_arguments(
+    arguments: Vec<Expression>,
+    fold: impl Fn(Expression, Expression) -> Expression,
+) -> Expression {
+    arguments
+        .into_iter()
+        .rev()
+        .reduce(|right, left| fold(left, right))
+        .expect("arguments")
+}
+
+fn sequence(body: Vec<Statement>, result: Expression) -> Expression {
+    if body.is_empty() {
+        result
+    } else {
+        Expression::Sequence {
+            body,
+            result: result.into(),
+        }
+    }
+}
+
+fn is_atom(expression: &Expression) -> bool {
+    matches!(
+        expression,
+        Expression::Float64(_)
+            | Expression::Integer32(_)
+            | Expression::Nil
+            | Expression::Variable(_)
+    )
+}
+
+fn primitive_arity(instruction: Instruction) -> RangeInclusive<usize> {
+    match instruction {
+        Instruction::Deref
+        | Instruction::Display
+        | Instruction::Length
+        | Instruction::Not
+        | Instruction::Pop
+        | Instruction::Ref
+        | Instruction::Repr
+        | Instruction::Reverse => 1..=1,
+        Instruction::Filter
+        | Instruction::Get
+        | Instruction::Map
+        | Instruction::Push
+        | Instruction::SetRef => 2..=2,
+        Instruction::Fold | Instruction::Set | Instruction::Slice => 3..=3,
+        Instruction::Range | Instruction::Sort => 1..=2,
+        Instruction::And | Instruction::Or => 0..=usize::MAX,
+        Instruction::Subtract => 1..=usize::MAX,
+        _ => 2..=usize::MAX,
+    }
+}
+
+fn primitive_instruction(name: &str) -> Option<Instruction> {
+    Some(match name {
+        "get" => Instruction::Get,
+        "set" => Instruction::Set,
+        "len" => Instruction::Length,
+        "display" => Instruction::Display,
+        "repr" => Instruction::Repr,
+        "+" => Instruction::Add,
+        "-" => Instruction::Subtract,
+        "*" => Instruction::Multiply,
+        "/" => Instruction::Divide,
+        "=" => Instruction::Equal,
+        "!=" => Instruction::NotEqual,
+        "<" => Instruction::LessThan,
+        "<=" => Instruction::LessThanOrEqual,
+        ">" => Instruction::GreaterThan,
+        ">=" => Instruction::GreaterThanOrEqual,
+        "not" => Instruction::Not,
+        "and" => Instruction::And,
+        "or" => Instruction::Or,
+        "ref" => Instruction::Ref,
+        "deref" => Instruction::Deref,
+        "set!" => Instruction::SetRef,
+        "slice" => Instruction::Slice,
+        "concat" => Instruction::Concat,
+        "push" => Instruction::Push,
+        "pop" => Instruction::Pop,
+        "reverse" => Instruction::Reverse,
+        "sort" => Instruction::Sort,
+        "map" => Instruction::Map,
+        "filter" => Instruction::Filter,
+        "fold" => Instruction::Fold,
+        "range" => Instruction::Range,
+        _ => return None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::PRIMITIVES;
+    use pretty_assertions::assert_eq;
+
+    fn lower(value: Value) -> Result<Statement, CompileError> {
+        Lowerer::new(None).lower_statement(&value)
+    }
+
+    #[test]
+    fn lower_primitives() {
+        for name in PRIMITIVES {
+            assert!(primitive_instruction(name).is_some());
+        }
+    }
+
+    #[test]
+    fn lower_let() {
+        assert_eq!(
+            lower(
+                [
+                    "let".into(),
+                    "x".into(),
+                    ["+".into(), "y".into(), 1.0.into()].into()
+                ]
+                .into()
+            )
+            .unwrap(),
+            Statement::Let {
+                name: "x".into(),
+                value: Expression::Primitive {
+                    instruction: Instruction::Add,
+                    arguments: vec![Expression::Variable("y".into()), Expression::Float64(1.0)],
+                },
+                line: None,
+            }
+        );
+    }
+
+    #[test]
+    fn lower_let_rec() {
+        assert_eq!(
+            lower(
+                [
+                    "let-rec".into(),
+                    "f".into(),
+                    [
+                        "fn".into(),
+                        ["x".into()].into(),
+                        ["f".into(), "x".into()].into()
+                    ]
+                    .into()
+                ]
+                .into()
+            )
+            .unwrap(),
+            Statement::Let {
+                name: "f".into(),
+                value: Expression::Lambda(Lambda {
+                    name: Some("f".into()),
+                    recursive: true,
+                    arguments: vec!["x".into()],
+                    body: vec![],
+                    result: Expression::Call {
+                        function: Expression::Variable("f".into()).into(),
+                        arguments: vec![Expression::Variable("x".into())],
+                    }
+                    .into(),
+                    span: None,
+                }),
+                line: None,
+            }
+        );
+    }
+
+    #[test]
+    fn lower_mutual_let_rec() {
+        let lambda = |name: &str, other: &str| Lambda {
+            name: Some(name.into()),
+            recursive: true,
+            arguments: vec![],
+            body: vec![],
+            result: Expression::Call {
+                function: Expression::Variable(other.into()).into(),
+                arguments: vec![],
+            }
+            .into(),
+            span: None,
+        };
+
+        assert_eq!(
+            lower(
+                [
+                    "let-rec".into(),
+                    "f".into(),
+                    ["fn".into(), [].into(), ["g".into()].into()].into(),
+                    "g".into(),
+                    ["fn".into(), [].into(), ["f".into()].into()].into(),
+                ]
+                .into()
+            )
+            .unwrap(),
+            Statement::LetRec {
+                functions: vec![
+                    Expression::Lambda(lambda("f", "g")),
+                    Expression::Lambda(lambda("g", "f")),
+                ],
+                line: None,
+            }
+        );
+    }
+
+    #[test]
+    fn lower_invalid_mutual_let_rec() {
+        let function = || Value::from(["fn".into(), [].into(), 42.0.into()]);
+
+        for value in [
+            ["let-rec".into(), "f".into(), function(), "g".into()].into(),
+            [
+                "let-rec".into(),
+                "f".into(),
+                function(),
+                "g".into(),
+                42.0.into(),
+            ]
+            .into(),
+            [
+                "let-rec".into(),
+                "f".into(),
+                function(),
+                "f".into(),
+                function(),
+            ]
+            .into(),
+        ] {
+            assert!(matches!(lower(value), Err(CompileError::Syntax(_))));
+        }
+    }
+
+    #[test]
+    fn lower_if_chain() {
+        assert_eq!(
+            lower(["if".into(), "x".into(), 1.0.into(), "y".into(), 2.0.into()].into()).unwrap(),
+            Statement::Expression(Expression::If {
+                condition: Expression::Variable("x".into()).into(),
+                then: Expression::Float64(1.0).into(),
+                r#else: Expression::If {
+                    condition: Expression::Variable("y".into()).into(),
+                    then: Expression::Float64(2.0).into(),
+                    r#else: Expression::Nil.into(),
+                }
+                .into(),
+            })
+        );
+    }
+
+    #[test]
+    fn lower_invalid_let_rec() {
+        assert!(matches!(
+            lower(["let-rec".into(), "x".into(), 42.0.into()].into()),
+            Err(CompileError::Syntax(_))
+        ));
+    }
+
+    #[test]
+    fn lower_invalid_arguments() {
+        assert!(matches!(
+            lower(["fn".into(), [42.0.into()].into(), 42.0.into()].into()),
+            Err(CompileError::Syntax(_))
+        ));
+    }
+
+    #[test]
+    fn lower_and() {
+        assert_eq!(
+            lower(["and".into(), "x".into(), "y".into(), "z".into()].into()).unwrap(),
+            Statement::Expression(Expression::If {
+                condition: Expression::Variable("x".into()).into(),
+                then: Expression::If {
+                    condition: Expression::Variable("y".into()).into(),
+                    then: Expression::Variable("z".into()).into(),
+                    r#else: Expression::Nil.into(),
+                }
+                .into(),
+                r#else: Expression::Nil.into(),
+            })
+        );
+    }
+
+    #[test]
+    fn lower_or() {
+        assert_eq!(
+            lower(["or".into(), "x".into(), "y".into(), "z".into()].into()).unwrap(),
+            Statement::Expression(Expression::Or {
+                left: Expression::Variable("x".into()).into(),
+                right: Expression::Or {
+                    left: Expression::Variable("y".into()).into(),
+                    right: Expression::Variable("z".into()).into(),
+                }
+                .into(),
+            })
+        );
+    }
+
+    #[test]
+    fn lower_empty_and_or() {
+        assert_eq!(
+            lower(["and".into()].into()).unwrap(),
+            Statement::Expression(Expression::Float64(1.0))
+        );
+        assert_eq!(
+            lower(["or".into()].into()).unwrap(),
+            Statement::Expression(Expression::Nil)
+        );
+        assert_eq!(
+            lower(["and".into(), "x".into()].into()).unwrap(),
+            Statement::Expression(Expression::Variable("x".into()))
+        );
+    }
+
+    #[test]
+    fn lower_negation() {
+        assert_eq!(
+            lower(["-".into(), "x".into()].into()).unwrap(),
+            Statement::Expression(Expression::Primitive {
+                instruction: Instruction::Subtract,
+                arguments: vec![Expression::Float64(0.0), Expression::Variable("x".into())],
+            })
+        );
+    }
+
+    #[test]
+    fn lower_variadic_arithmetic() {
+        assert_eq!(
+            lower(["-".into(), "x".into(), "y".into(), "z".into()].into()).unwrap(),
+            Statement::Expression(Expression::Primitive {
+                instruction: Instruction::Subtract,
+                arguments: vec![
+                    Expression::Primitive {
+                        instruction: Instruction::Subtract,
+                        arguments: vec![
+                            Expression::Variable("x".into()),
+                            Expression::Variable("y".into())
+                        ],
+                    },
+                    Expression::Variable("z".into())
+                ],
+            })
+        );
+    }
+
+    #[test]
+    fn lower_optional_operands() {
+        assert_eq!(
+            lower(["range".into(), "x".into()].into()).unwrap(),
+            Statement::Expression(Expression::Primitive {
+                instruction: Instruction::Range,
+                arguments: vec![Expression::Float64(0.0), Expression::Variable("x".into())],
+            })
+        );
+        assert_eq!(
+            lower(["sort".into(), "x".into()].into()).unwrap(),
+            Statement::Expression(Expression::Primitive {
+                instruction: Instruction::Sort,
+                arguments: vec![Expression::Variable("x".into()), Expression::Nil],
+            })
+        );
+        assert!(matches!(
+            lower(["fold".into(), "f".into(), "x".into()].into()),
+            Err(CompileError::Arity(_))
+        ));
+    }
+
+    #[test]
+    fn lower_chained_comparison() {
+        let less_than = |lhs: &str, rhs: &str| Expression::Primitive {
+            instruction: Instruction::LessThan,
+            arguments: vec![
+                Expression::Variable(lhs.into()),
+                Expression::Variable(rhs.into()),
+            ],
+        };
+
+        assert_eq!(
+            lower(["<".into(), "x".into(), "y".into(), "z".into()].into()).unwrap(),
+            Statement::Expression(Expression::If {
+                condition: less_than("x", "y").into(),
+                then: less_than("y", "z").into(),
+                r#else: Expression::Nil.into(),
+            })
+        );
+        assert_eq!(
+            lower(["<".into(), "x".into(), ["f".into()].into(), "z".into()].into()).unwrap(),
+            Statement::Expression(Expression::Call {
+                function: Expression::Lambda(Lambda {
+                    name: None,
+                    recursive: false,
+                    arguments: vec!["0".into(), "1".into(), "2".into()],
+                    body: vec![],
+                    result: Expression::If {
+                        condition: less_than("0", "1").into(),
+                        then: less_than("1", "2").into(),
+                        r#else: Expression::Nil.into(),
+                    }
+                    .into(),
+                    span: None,
+                })
+                .into(),
+                arguments: vec![
+                    Expression::Variable("x".into()),
+                    Expression::Call {
+                        function: Expression::Variable("f".into()).into(),
+                        arguments: vec![],
+                    },
+                    Expression::Variable("z".into()),
+                ],
+            })
+        );
+    }
+
+    #[test]
+    fn lower_invalid_arity() {
+        for value in [
+            ["+".into(), 1.0.into()].into(),
+            ["<".into()].into(),
+            ["-".into()].into(),
+            ["not".into(), "x".into(), "y".into()].into(),
+            ["get".into(), "x".into()].into(),
+            ["set".into(), "x".into(), 0.0.into()].into(),
+        ] {
+            assert!(matches!(lower(value), Err(CompileError::Arity(_))));
+        }
+    }
+
+    #[test]
+    fn lower_do() {
+        assert_eq!(
+            lower(["do".into(), ["f".into()].into(), "x".into()].into()).unwrap(),
+            Statement::Expression(Expression::Sequence {
+                body: vec![Statement::Expression(Expression::Call {
+                    function: Expression::Variable("f".into()).into(),
+                    arguments: vec![],
+                })],
+                result: Expression::Variable("x".into()).into(),
+            })
+        );
+        assert_eq!(
+            lower(["do".into(), "x".into()].into()).unwrap(),
+            Statement::Expression(Expression::Variable("x".into()))
+        );
+        assert_eq!(
+            lower(["do".into()].into()).unwrap(),
+            Statement::Expression(Expression::Nil)
+        );
+    }
+
+    #[test]
+    fn lower_let_expression() {
+        assert_eq!(
+            lower(
+                [
+                    "let".into(),
+                    [
+                        ["x".into(), 1.0.into()].into(),
+                        ["y".into(), "x".into()].into()
+                    ]
+                    .into(),
+                    "y".into()
+                ]
+                .into()
+            )
+            .unwrap(),
+            Statement::Expression(Expression::Sequence {
+                body: vec![
+                    Statement::Let {
+                        name: "x".into(),
+                        value: Expression::Float64(1.0),
+                        line: None,
+                    },
+                    Statement::Let {
+                        name: "y".into(),
+                        value: Expression::Variable("x".into()),
+                        line: None,
+                    },
+                ],
+                result: Expression::Variable("y".into()).into(),
+            })
+        );
+    }
+
+    #[test]
+    fn lower_invalid_let_expression() {
+        for value in [
+            ["let".into(), [["x".into(), 1.0.into()].into()].into()].into(),
+            ["let".into(), [["x".into()].into()].into(), "x".into()].into(),
+            [
+                "let".into(),
+                [[1.0.into(), 1.0.into()].into()].into(),
+                "x".into(),
+            ]
+            .into(),
+            ["f".into(), ["let".into(), "x".into(), 1.0.into()].into()].into(),
+        ] {
+            assert!(matches!(lower(value), Err(CompileError::Syntax(_))));
+        }
+    }
+
+    #[test]
+    fn lower_while() {
+        assert_eq!(
+            lower(["while".into(), "x".into(), ["f".into()].into()].into()).unwrap(),
+            Statement::Expression(Expression::Loop {
+                bindings: vec![],
+                body: Expression::If {
+                    condition: Expression::Variable("x".into()).into(),
+                    then: Expression::Sequence {
+                        body: vec![Statement::Expression(Expression::Call {
+                            function: Expression::Variable("f".into()).into(),
+                            arguments: vec![],
+                        })],
+                        result: Expression::Recur {
+                            arguments: vec![],
+                            depth: 0,
+                        }
+                        .into(),
+                    }
+                    .into(),
+                    r#else: Expression::Nil.into(),
+                }
+                .into(),
+            })
+        );
+    }
+
+    #[test]
+    fn lower_match() {
+        let value = || Expression::Variable("0".into());
+
+        assert_eq!(
+            lower(
+                [
+                    "match".into(),
+                    "x".into(),
+                    [1.0.into(), 2.0.into()].into(),
+                    ["y".into(), "when".into(), "y".into(), "y".into()].into()
+                ]
+                .into()
+            )
+            .unwrap(),
+            Statement::Expression(Expression::Sequence {
+                body: vec![Statement::Let {
+                    name: "0".into(),
+                    value: Expression::Variable("x".into()),
+                    line: None,
+                }],
+                result: Expression::If {
+                    condition: Expression::Primitive {
+                        instruction: Instruction::Equal,
+                        arguments: vec![value(), Expression::Float64(1.0)],
+                    }
+                    .into(),
+                    then: Expression::Float64(2.0).into(),
+                    r#else: Expression::If {
+                        condition: Expression::Sequence {
+                            body: vec![Statement::Let {
+                                name: "y".into(),
+                                value: value(),
+                                line: None,
+                            }],
+                            result: Expression::Variable("y".into()).into(),
+                        }
+                        .into(),
+                        then: Expression::Sequence {
+                            body: vec![Statement::Let {
+                                name: "y".into(),
+                                value: value(),
+                                line: None,
+                            }],
+                            result: Expression::Variable("y".into()).into(),
+                        }
+                        .into(),
+                        r#else: Expression::Nil.into(),
+                    }
+                    .into(),
+                }
+                .into(),
+            })
+        );
+    }
+
+    #[test]
+    fn lower_invalid_match() {
+        for value in [
+            ["match".into()].into(),
+            ["match".into(), "x".into(), "y".into()].into(),
+            ["match".into(), "x".into(), ["y".into()].into()].into(),
+            [
+                "match".into(),
+                "x".into(),
+                ["y".into(), "when".into(), "y".into()].into(),
+            ]
+            .into(),
+        ] {
+            assert!(matches!(lower(value), Err(CompileError::Syntax(_))));
+        }
+
+        assert!(matches!(
+            lower(
+                [
+                    "match".into(),
+                    "x".into(),
+                    ["_".into(), 1.0.into()].into(),
+                    ["y".into(), 2.0.into()].into()
+                ]
+                .into()
+            ),
+            Err(CompileError::UnreachablePattern(_))
+        ));
+    }
+
+    #[test]
+    fn lower_recur_in_tail_positions() {
+        let recur = || Expression::Recur {
+            arguments: vec![Expression::Variable("x".into())],
+            depth: 0,
+        };
+
+        assert_eq!(
+            lower(
+                [
+                    "loop".into(),
+                    ["x".into(), 1.0.into()].into(),
+                    [
+                        "if".into(),
+                        "x".into(),
+                        ["do".into(), ["recur".into(), "x".into()].into()].into(),
+                        ["recur".into(), "x".into()].into(),
+                    ]
+                    .into()
+                ]
+                .into()
+            )
+            .unwrap(),
+            Statement::Expression(Expression::Loop {
+                bindings: vec![("x".into(), Expression::Float64(1.0))],
+                body: Expression::If {
+                    condition: Expression::Variable("x".into()).into(),
+                    then: recur().into(),
+                    r#else: recur().into(),
+                }
+                .into(),
+            })
+        );
+    }
+
+    #[test]
+    fn lower_invalid_recur() {
+        for value in [
+            ["recur".into()].into(),
+            [
+                "loop".into(),
+                [].into(),
+                ["do".into(), ["recur".into()].into(), 1.0.into()].into(),
+            ]
+            .into(),
+            [
+                "loop".into(),
+                [].into(),
+                ["if".into(), ["recur".into()].into(), 1.0.into()].into(),
+            ]
+            .into(),
+            [
+                "loop".into(),
+                [].into(),
+                ["fn".into(), [].into(), ["recur".into()].into()].into(),
+            ]
+            .into(),
+            ["while".into(), 1.0.into(), ["recur".into()].into()].into(),
+        ] {
+            assert!(matches!(lower(value), Err(CompileError::Recur(_))));
+        }
+    }
+
+    #[test]
+    fn lower_invalid_loop() {
+        for value in [
+            ["loop".into(), ["x".into()].into(), "x".into()].into(),
+            ["loop".into(), [1.0.into(), 1.0.into()].into(), 1.0.into()].into(),
+            ["loop".into(), [].into()].into(),
+            ["while".into()].into(),
+        ] {
+            assert!(matches!(lower(value), Err(CompileError::Syntax(_))));
+        }
+    }
+}
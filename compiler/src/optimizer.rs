@@ -0,0 +1,422 @@
+use std::collections::{HashMap, HashSet};
+use vm::{decode_instruction, InstructionIr};
+
+#[derive(Clone, Debug)]
+struct Node {
+    address: usize,
+    instruction: InstructionIr,
+    // An absolute target address of a jump, branch or closure.
+    target: Option<usize>,
+}
+
+/// Optimizes bytecodes from a start address to the end with peephole
+/// rewrites.
+///
+/// It folds constants, removes dead pushes, threads jumps and fuses common
+/// sequences into superinstructions. Bytecodes must not be referenced from
+/// outside of the range except at the start.
+///
+/// It returns new addresses of old offsets from the start including the end.
+pub fn optimize(codes: &mut Vec<u8>, start: usize) -> Vec<usize> {
+    let end = codes.len();
+    let mut nodes = decode(codes, start);
+    let mut aliases = HashMap::new();
+
+    while rewrite(&mut nodes, &mut aliases, end) {}
+
+    let addresses = nodes
+        .iter()
+        .scan(start, |address, node| {
+            let current = *address;
+            *address += size(&node.instruction);
+            Some((node.address, current))
+        })
+        .collect::<HashMap<_, _>>();
+    let new_end = start
+        + nodes
+            .iter()
+            .map(|node| size(&node.instruction))
+            .sum::<usize>();
+    let relocate = |address: usize| {
+        let address = resolve(&aliases, address);
+
+        if address < start {
+            address
+        } else if address >= end {
+            new_end
+        } else {
+            addresses[&address]
+        }
+    };
+
+    codes.truncate(start);
+
+    for node in &nodes {
+        let instruction = match (&node.instruction, node.target) {
+            (InstructionIr::Jump { .. }, Some(target)) => InstructionIr::Jump {
+                pointer: relative_pointer(relocate(target), codes.len(), &node.instruction),
+            },
+            (InstructionIr::Branch { .. }, Some(target)) => InstructionIr::Branch {
+                pointer: relative_pointer(relocate(target), codes.len(), &node.instruction),
+            },
+            (InstructionIr::BranchNotEqual { .. }, Some(target)) => InstructionIr::BranchNotEqual {
+                pointer: relative_pointer(relocate(target), codes.len(), &node.instruction),
+            },
+            (
+                &InstructionIr::Close {
+                    arity,
+                    environment_size,
+                    ..
+                },
+                Some(target),
+            ) => InstructionIr::Close {
+                pointer: relocate(target) as u32,
+                arity,
+                environment_size,
+            },
+            (instruction, _) => instruction.clone(),
+        };
+
+        instruction.encode(codes);
+    }
+
+    let mut last = start;
+
+    (start..=end)
+        .map(|address| {
+            // Addresses inside instructions map to their starts.
+            if address == end || addresses.contains_key(&address) || aliases.contains_key(&address)
+            {
+                last = relocate(address);
+            }
+
+            last
+        })
+        .collect()
+}
+
+fn decode(codes: &[u8], start: usize) -> Vec<Node> {
+    let mut nodes = vec![];
+    let mut index = start;
+
+    while index < codes.len() {
+        let address = index;
+        let instruction = decode_instruction(codes, &mut index).expect("valid instruction");
+        let target = match instruction {
+            InstructionIr::Jump { pointer }
+            | InstructionIr::Branch { pointer }
+            | InstructionIr::BranchNotEqual { pointer } => {
+                Some(index.wrapping_add(pointer as isize as usize))
+            }
+            InstructionIr::Close { pointer, .. } => Some(pointer as usize),
+            _ => None,
+        };
+
+        nodes.push(Node {
+            address,
+            instruction,
+            target,
+        });
+    }
+
+    nodes
+}
+
+// Applies rewrites once and returns `true` if any of them is applied.
+fn rewrite(nodes: &mut Vec<Node>, aliases: &mut HashMap<usize, usize>, end: usize) -> bool {
+    let mut changed = thread_jumps(nodes, aliases);
+    let targets = nodes
+        .iter()
+        .filter_map(|node| node.target)
+        .map(|target| resolve(aliases, target))
+        .collect::<HashSet<_>>();
+    let old = std::mem::take(nodes);
+    let mut index = 0;
+
+    while index < old.len() {
+        let node = &old[index];
+        // Instructions other than the first one in a sequence must not be jump
+        // targets.
+        let next = |offset: usize| {
+            old.get(index + offset)
+                .filter(|node| !targets.contains(&node.address))
+                .map(|node| &node.instruction)
+        };
+
+        let (instruction, count) = match (&node.instruction, next(1), next(2)) {
+            (InstructionIr::Jump { .. }, _, _)
+                if node.target.map(|target| resolve(aliases, target))
+                    == Some(old.get(index + 1).map_or(end, |node| node.address)) =>
+            {
+                (None, 1)
+            }
+            (
+                InstructionIr::Nil
+                | InstructionIr::Float64(_)
+                | InstructionIr::Integer32(_)
+                | InstructionIr::Symbol { .. }
+                | InstructionIr::Peek(_)
+                | InstructionIr::Take(_)
+                | InstructionIr::Environment(_),
+                Some(InstructionIr::Drop),
+                _,
+            ) => (None, 2),
+            (&InstructionIr::Float64(lhs), Some(&InstructionIr::Float64(rhs)), Some(operation))
+                if fold(lhs, rhs, operation).is_some() =>
+            {
+                (fold(lhs, rhs, operation).map(InstructionIr::Float64), 3)
+            }
+            (InstructionIr::Dump, Some(InstructionIr::Drop), _) => {
+                (Some(InstructionIr::DumpDrop), 2)
+            }
+            (&InstructionIr::Float64(number), Some(InstructionIr::Add), _) => {
+                (Some(InstructionIr::AddFloat64(number)), 2)
+            }
+            (&InstructionIr::Float64(number), Some(InstructionIr::Subtract), _) => {
+                (Some(InstructionIr::SubtractFloat64(number)), 2)
+            }
+            (InstructionIr::Equal, Some(&InstructionIr::Branch { pointer }), _) => {
+                (Some(InstructionIr::BranchNotEqual { pointer }), 2)
+            }
+            _ => {
+                nodes.push(node.clone());
+                index += 1;
+                continue;
+            }
+        };
+
+        match instruction {
+            Some(instruction) => {
+                for other in &old[index + 1..index + count] {
+                    aliases.insert(other.address, node.address);
+                }
+
+                nodes.push(Node {
+                    address: node.address,
+                    instruction,
+                    target: old[index + count - 1].target,
+                });
+            }
+            None => {
+                let following = old.get(index + count).map_or(end, |node| node.address);
+
+                for node in &old[index..index + count] {
+                    aliases.insert(node.address, following);
+                }
+            }
+        }
+
+        changed = true;
+        index += count;
+    }
+
+    changed
+}
+
+// Redirects jumps and branches to jumps to their final targets.
+fn thread_jumps(nodes: &mut [Node], aliases: &HashMap<usize, usize>) -> bool {
+    let jumps = nodes
+        .iter()
+        .filter(|node| matches!(node.instruction, InstructionIr::Jump { .. }))
+        .filter_map(|node| Some((node.address, node.target?)))
+        .collect::<HashMap<_, _>>();
+    let mut changed = false;
+
+    for node in nodes {
+        let (
+            InstructionIr::Jump { .. }
+            | InstructionIr::Branch { .. }
+            | InstructionIr::BranchNotEqual { .. },
+            Some(target),
+        ) = (&node.instruction, node.target)
+        else {
+            continue;
+        };
+
+        let mut target = resolve(aliases, target);
+        let mut visited = HashSet::from([node.address]);
+
+        while let Some(&next) = jumps.get(&target) {
+            if !visited.insert(target) {
+                break;
+            }
+
+            target = resolve(aliases, next);
+            changed = true;
+        }
+
+        node.target = Some(target);
+    }
+
+    changed
+}
+
+fn fold(lhs: f64, rhs: f64, operation: &InstructionIr) -> Option<f64> {
+    Some(match operation {
+        InstructionIr::Add => lhs + rhs,
+        InstructionIr::Subtract => lhs - rhs,
+        InstructionIr::Multiply => lhs * rhs,
+        InstructionIr::Divide => lhs / rhs,
+        _ => return None,
+    })
+}
+
+fn resolve(aliases: &HashMap<usize, usize>, mut address: usize) -> usize {
+    while let Some(&alias) = aliases.get(&address) {
+        address = alias;
+    }
+
+    address
+}
+
+fn size(instruction: &InstructionIr) -> usize {
+    let mut codes = vec![];
+    instruction.encode(&mut codes);
+    codes.len()
+}
+
+fn relative_pointer(target: usize, address: usize, instruction: &InstructionIr) -> i16 {
+    (target as isize - (address + size(instruction)) as isize) as i16
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+    use vm::format_instructions;
+
+    fn encode(instructions: &[InstructionIr]) -> Vec<u8> {
+        let mut codes = vec![];
+
+        for instruction in instructions {
+            instruction.encode(&mut codes);
+        }
+
+        codes
+    }
+
+    fn optimize_instructions(instructions: &[InstructionIr]) -> String {
+        let mut codes = encode(instructions);
+
+        optimize(&mut codes, 0);
+
+        format_instructions(&codes).unwrap()
+    }
+
+    #[test]
+    fn fold_constants() {
+        insta::assert_snapshot!(optimize_instructions(&[
+            InstructionIr::Float64(1.0),
+            InstructionIr::Float64(2.0),
+            InstructionIr::Add,
+            InstructionIr::Float64(3.0),
+            InstructionIr::Multiply,
+            InstructionIr::Float64(4.0),
+            InstructionIr::Divide,
+            InstructionIr::Dump,
+            InstructionIr::Drop,
+        ]));
+    }
+
+    #[test]
+    fn remove_dead_pushes() {
+        insta::assert_snapshot!(optimize_instructions(&[
+            InstructionIr::Peek(0),
+            InstructionIr::Drop,
+            InstructionIr::Nil,
+            InstructionIr::Drop,
+            InstructionIr::Symbol {
+                len: 3,
+                string: "foo".into(),
+            },
+            InstructionIr::Drop,
+            InstructionIr::Float64(1.0),
+            InstructionIr::Dump,
+            InstructionIr::Drop,
+        ]));
+    }
+
+    #[test]
+    fn thread_jumps() {
+        insta::assert_snapshot!(optimize_instructions(&[
+            InstructionIr::Nil,
+            InstructionIr::Branch { pointer: 5 },
+            InstructionIr::Nil,
+            InstructionIr::Jump { pointer: 1 },
+            InstructionIr::Nil,
+            InstructionIr::Jump { pointer: 1 },
+            InstructionIr::Nil,
+            InstructionIr::Dump,
+            InstructionIr::Drop,
+        ]));
+    }
+
+    #[test]
+    fn remove_jump_to_next() {
+        insta::assert_snapshot!(optimize_instructions(&[
+            InstructionIr::Jump { pointer: 0 },
+            InstructionIr::Nil,
+            InstructionIr::Dump,
+            InstructionIr::Drop,
+            InstructionIr::Jump { pointer: 0 },
+        ]));
+    }
+
+    #[test]
+    fn fuse_superinstructions() {
+        insta::assert_snapshot!(optimize_instructions(&[
+            InstructionIr::Peek(0),
+            InstructionIr::Float64(1.0),
+            InstructionIr::Add,
+            InstructionIr::Float64(2.0),
+            InstructionIr::Subtract,
+            InstructionIr::Peek(0),
+            InstructionIr::Peek(1),
+            InstructionIr::Equal,
+            InstructionIr::Branch { pointer: 1 },
+            InstructionIr::Nil,
+            InstructionIr::Dump,
+            InstructionIr::Drop,
+        ]));
+    }
+
+    #[test]
+    fn keep_jump_targets() {
+        insta::assert_snapshot!(optimize_instructions(&[
+            InstructionIr::Peek(0),
+            InstructionIr::Branch { pointer: 9 },
+            InstructionIr::Float64(1.0),
+            InstructionIr::Add,
+            InstructionIr::Dump,
+        ]));
+    }
+
+    #[test]
+    fn relocate_functions() {
+        let mut codes = encode(&[
+            InstructionIr::Jump { pointer: 20 },
+            InstructionIr::Float64(1.0),
+            InstructionIr::Float64(2.0),
+            InstructionIr::Add,
+            InstructionIr::Return,
+            InstructionIr::Close {
+                pointer: 3,
+                arity: 0,
+                environment_size: 0,
+            },
+            InstructionIr::Dump,
+            InstructionIr::Drop,
+        ]);
+
+        let addresses = optimize(&mut codes, 0);
+
+        insta::assert_snapshot!(format_instructions(&codes).unwrap());
+        assert_eq!(addresses[0], 0);
+        assert_eq!(addresses[3], 3);
+        assert_eq!(addresses[12], 3);
+        assert_eq!(addresses[22], 12);
+        assert_eq!(addresses[23], 13);
+        assert_eq!(addresses[31], 20);
+        assert_eq!(addresses[32], 21);
+    }
+}
@@ -0,0 +1,330 @@
+use crate::{
+    ir::{Expression, Statement},
+    CompileError,
+};
+use runtime::{Symbol, TypedValueRef, Value};
+use vm::Instruction;
+
+const WILDCARD: &str = "_";
+const REST: &str = "&";
+
+// Names of variables of loops collecting rest elements. Source code can refer
+// to symbols of numbers only with escapes.
+const REST_INDEX: &str = "1";
+const REST_ARRAY: &str = "2";
+
+/// A pattern in a `match` clause.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Pattern {
+    /// Element patterns followed by an optional pattern of the rest elements.
+    Array {
+        elements: Vec<Pattern>,
+        rest: Option<Box<Pattern>>,
+    },
+    Bind(Symbol),
+    /// A number or `nil` equal to a value.
+    Literal(Expression),
+    Wildcard,
+}
+
+impl Pattern {
+    pub fn parse(value: &Value) -> Result<Self, CompileError> {
+        let pattern = Self::parse_value(value)?;
+        let mut names = vec![];
+
+        if !pattern.collect_names(&mut names) {
+            return Err(CompileError::Pattern(value.to_string()));
+        }
+
+        Ok(pattern)
+    }
+
+    /// Visits symbols of variables in a value of a valid pattern.
+    pub fn visit_variables<'a>(value: &'a Value, visit: &mut impl FnMut(&'a Value)) {
+        match value.as_typed() {
+            Some(TypedValueRef::Array(array)) => {
+                for index in 0..array.len_usize() {
+                    Self::visit_variables(array.get_usize(index), visit);
+                }
+            }
+            Some(TypedValueRef::Symbol(symbol)) if !matches!(symbol.as_str(), WILDCARD | REST) => {
+                visit(value)
+            }
+            _ => {}
+        }
+    }
+
+    fn parse_value(value: &Value) -> Result<Self, CompileError> {
+        let error = || CompileError::Pattern(value.to_string());
+
+        Ok(match value.as_typed() {
+            Some(TypedValueRef::Array(array)) => {
+                let length = array.len_usize();
+                let mut elements = Vec::with_capacity(length);
+                let mut rest = None;
+
+                for index in 0..length {
+                    let element = array.get_usize(index);
+
+                    if !is_symbol(element, REST) {
+                        elements.push(Self::parse_value(element)?);
+                        continue;
+                    }
+
+                    let pattern = Self::parse_value(array.get_usize(index + 1))?;
+
+                    if index + 2 != length || !matches!(pattern, Self::Bind(_) | Self::Wildcard) {
+                        return Err(error());
+                    }
+
+                    rest = Some(pattern.into());
+                    break;
+                }
+
+                Self::Array { elements, rest }
+            }
+            Some(TypedValueRef::Closure(_) | TypedValueRef::Reference(_)) => return Err(error()),
+            Some(TypedValueRef::Float64(number)) => {
+                Self::Literal(Expression::Float64(number.to_f64()))
+            }
+            Some(TypedValueRef::Integer32(number)) => {
+                Self::Literal(Expression::Integer32(number.to_i32()))
+            }
+            Some(TypedValueRef::Symbol(symbol)) => match symbol.as_str() {
+                WILDCARD => Self::Wildcard,
+                REST => return Err(error()),
+                _ => Self::Bind(symbol),
+            },
+            None => Self::Literal(Expression::Nil),
+        })
+    }
+
+    // Collects names bound by a pattern and returns `false` if any of them
+    // are duplicate.
+    fn collect_names(&self, names: &mut Vec<Symbol>) -> bool {
+        match self {
+            Self::Array { elements, rest } => elements
+                .iter()
+                .chain(rest.as_deref())
+                .all(|pattern| pattern.collect_names(names)),
+            Self::Bind(name) if names.contains(name) => false,
+            Self::Bind(name) => {
+                names.push(*name);
+                true
+            }
+            Self::Literal(_) | Self::Wildcard => true,
+        }
+    }
+
+    /// Returns `true` if a pattern matches every value another pattern
+    /// matches.
+    pub fn covers(&self, other: &Self) -> bool {
+        match (self, other) {
+            (Self::Bind(_) | Self::Wildcard, _) => true,
+            (Self::Literal(literal), Self::Literal(other)) => literal == other,
+            (
+                Self::Array { elements, rest },
+                Self::Array {
+                    elements: others,
+                    rest: other_rest,
+                },
+            ) => {
+                (if rest.is_some() {
+                    others.len() >= elements.len()
+                } else {
+                    other_rest.is_none() && others.len() == elements.len()
+                }) && elements
+                    .iter()
+                    .zip(others)
+                    .all(|(element, other)| element.covers(other))
+            }
+            _ => false,
+        }
+    }
+
+    /// Lowers a pattern matched against a value into conditions all of
+    /// which hold on a match and bindings of its variables.
+    pub fn lower(
+        &self,
+        value: Expression,
+        conditions: &mut Vec<Expression>,
+        bindings: &mut Vec<Statement>,
+    ) {
+        match self {
+            Self::Array { elements, rest } => {
+                let length = Expression::Float64(elements.len() as f64);
+
+                // Elements are checked only after the length is.
+                if rest.is_none() {
+                    conditions.push(primitive(
+                        Instruction::Equal,
+                        [primitive(Instruction::Length, [value.clone()]), length],
+                    ));
+                } else if !elements.is_empty() {
+                    conditions.push(primitive(
+                        Instruction::GreaterThanOrEqual,
+                        [primitive(Instruction::Length, [value.clone()]), length],
+                    ));
+                }
+
+                for (index, element) in elements.iter().enumerate() {
+                    element.lower(
+                        primitive(
+                            Instruction::Get,
+                            [value.clone(), Expression::Float64(index as f64)],
+                        ),
+                        conditions,
+                        bindings,
+                    );
+                }
+
+                if let Some(Self::Bind(name)) = rest.as_deref() {
+                    bindings.push(Statement::Let {
+                        name: *name,
+                        value: lower_rest(value, elements.len()),
+                        line: None,
+                    });
+                }
+            }
+            &Self::Bind(name) => bindings.push(Statement::Let {
+                name,
+                value,
+                line: None,
+            }),
+            Self::Literal(literal) => {
+                conditions.push(primitive(Instruction::Equal, [value, literal.clone()]))
+            }
+            Self::Wildcard => {}
+        }
+    }
+}
+
+// Lowers a loop collecting elements of an array from an index into a new
+// array.
+fn lower_rest(value: Expression, start: usize) -> Expression {
+    let index = Symbol::from(REST_INDEX);
+    let array = Symbol::from(REST_ARRAY);
+    let start = Expression::Float64(start as f64);
+
+    Expression::Loop {
+        bindings: vec![(index, start.clone()), (array, Expression::Nil)],
+        body: Expression::If {
+            condition: primitive(
+                Instruction::LessThan,
+                [
+                    Expression::Variable(index),
+                    primitive(Instruction::Length, [value.clone()]),
+                ],
+            )
+            .into(),
+            then: Expression::Recur {
+                arguments: vec![
+                    primitive(
+                        Instruction::Add,
+                        [Expression::Variable(index), Expression::Float64(1.0)],
+                    ),
+                    primitive(
+                        Instruction::Set,
+                        [
+                            Expression::Variable(array),
+                            primitive(Instruction::Subtract, [Expression::Variable(index), start]),
+                            primitive(Instruction::Get, [value, Expression::Variable(index)]),
+                        ],
+                    ),
+                ],
+                depth: 0,
+            }
+            .into(),
+            r#else: Expression::Variable(array).into(),
+        }
+        .into(),
+    }
+}
+
+fn primitive<const N: usize>(instruction: Instruction, arguments: [Expression; N]) -> Expression {
+    Expression::Primitive {
+        instruction,
+        arguments: arguments.into(),
+    }
+}
+
+fn is_symbol(value: &Value, name: &str) -> bool {
+    value
+        .to_symbol()
+        .is_some_and(|symbol| symbol.as_str() == name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    fn parse(value: Value) -> Result<Pattern, CompileError> {
+        Pattern::parse(&value)
+    }
+
+    #[test]
+    fn parse_patterns() {
+        assert_eq!(
+            parse(
+                [
+                    "x".into(),
+                    "_".into(),
+                    1.0.into(),
+                    ["y".into()].into(),
+                    "&".into(),
+                    "z".into()
+                ]
+                .into()
+            )
+            .unwrap(),
+            Pattern::Array {
+                elements: vec![
+                    Pattern::Bind("x".into()),
+                    Pattern::Wildcard,
+                    Pattern::Literal(Expression::Float64(1.0)),
+                    Pattern::Array {
+                        elements: vec![Pattern::Bind("y".into())],
+                        rest: None,
+                    },
+                ],
+                rest: Some(Pattern::Bind("z".into()).into()),
+            }
+        );
+    }
+
+    #[test]
+    fn parse_invalid_patterns() {
+        for value in [
+            "&".into(),
+            ["x".into(), "&".into()].into(),
+            ["&".into(), "x".into(), "y".into()].into(),
+            ["&".into(), ["x".into()].into()].into(),
+            ["x".into(), "x".into()].into(),
+            ["x".into(), "&".into(), "x".into()].into(),
+        ] {
+            assert!(matches!(parse(value), Err(CompileError::Pattern(_))));
+        }
+    }
+
+    #[test]
+    fn cover_patterns() {
+        let parse = |value: Value| parse(value).unwrap();
+
+        assert!(parse("x".into()).covers(&parse(["y".into()].into())));
+        assert!(parse(1.0.into()).covers(&parse(1.0.into())));
+        assert!(!parse(1.0.into()).covers(&parse(2.0.into())));
+        assert!(
+            parse(["x".into(), "_".into()].into()).covers(&parse(["y".into(), 1.0.into()].into()))
+        );
+        assert!(!parse(["x".into(), "_".into()].into()).covers(&parse(["y".into()].into())));
+        assert!(
+            parse(["x".into(), "&".into(), "_".into()].into()).covers(&parse(
+                ["y".into(), "z".into(), "&".into(), "w".into()].into()
+            ))
+        );
+        assert!(!parse(["x".into(), "&".into(), "_".into()].into())
+            .covers(&parse(["&".into(), "w".into()].into())));
+        assert!(!parse(["x".into()].into()).covers(&parse(["&".into(), "w".into()].into())));
+    }
+}
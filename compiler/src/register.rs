@@ -0,0 +1,932 @@
+use crate::{
+    free_variable::collect_free_variables,
+    ir::{Expression, Lambda, Statement},
+    liveness::move_last_uses,
+    lower::Lowerer,
+    CompileError,
+};
+use async_stream::try_stream;
+use futures::{Stream, StreamExt};
+use runtime::{Symbol, Value};
+use std::{cell::RefCell, error::Error, mem::replace};
+use vm::{
+    BinaryOperator, Instruction, Register, RegisterInstruction, TernaryOperator, UnaryOperator,
+};
+
+// A register of a closure being called in each frame.
+const CLOSURE_REGISTER: Register = 0;
+
+/// A compiler targeting a register machine.
+///
+/// Variables are allocated to registers by names. Unlike the stack machine,
+/// reads of local variables do not copy their values.
+pub struct RegisterCompiler<'a> {
+    instructions: &'a RefCell<Vec<RegisterInstruction>>,
+}
+
+impl<'a> RegisterCompiler<'a> {
+    pub fn new(instructions: &'a RefCell<Vec<RegisterInstruction>>) -> Self {
+        Self { instructions }
+    }
+
+    pub fn compile<E: Error + 'static>(
+        &'a mut self,
+        values: &'a mut (impl Stream<Item = Result<Value, E>> + Unpin),
+    ) -> impl Stream<Item = Result<(), CompileError>> + 'a {
+        try_stream! {
+            let lowerer = Lowerer::new(None);
+            let mut scope = Scope::new(None, &[]);
+
+            while let Some(value) = values.next().await {
+                let mut statement = lowerer.lower(&value.map_err(|error| CompileError::Other(error.into()))?)?;
+                move_last_uses(&mut statement);
+                let start = self.instructions.borrow().len();
+                let variables = scope.variables.clone();
+                let result = self.compile_statement(&statement, &mut scope, true);
+                let name = scope.free_variables.first().map(ToString::to_string);
+
+                // Roll back a failed statement so that later ones can be compiled.
+                if result.is_err() || name.is_some() {
+                    self.instructions.borrow_mut().truncate(start);
+                    scope.variables = variables;
+                    scope.free_variables.clear();
+                }
+
+                result?;
+
+                if let Some(name) = name {
+                    Err(CompileError::VariableNotDefined(name))?;
+                }
+
+                yield ();
+            }
+        }
+    }
+
+    fn compile_statement(
+        &self,
+        statement: &Statement,
+        scope: &mut Scope,
+        dump: bool,
+    ) -> Result<(), CompileError> {
+        match statement {
+            Statement::Expression(expression) => {
+                let register = self.compile_expression(expression, scope, scope.next()?)?;
+
+                if dump {
+                    self.push(RegisterInstruction::Dump(register));
+                }
+            }
+            Statement::Let { name, value, .. } => {
+                let register = scope.next()?;
+
+                self.compile_into(value, scope, register)?;
+                scope.variables.push((*name, register));
+            }
+            Statement::LetRec { functions, .. } => self.compile_functions(functions, scope)?,
+        }
+
+        Ok(())
+    }
+
+    // Compiles an expression using registers from a target one as temporaries
+    // and returns a register holding its value.
+    fn compile_expression(
+        &self,
+        expression: &Expression,
+        scope: &mut Scope,
+        target: Register,
+    ) -> Result<Register, CompileError> {
+        match expression {
+            Expression::Call {
+                function,
+                arguments,
+            } => {
+                self.compile_call(function, arguments, scope, target)?;
+                self.push(RegisterInstruction::Call {
+                    function: target,
+                    arity: arguments.len() as u8,
+                });
+            }
+            Expression::Float64(value) => self.push(RegisterInstruction::Float64 {
+                destination: target,
+                value: *value,
+            }),
+            Expression::If {
+                condition,
+                then,
+                r#else,
+            } => {
+                let branch = self.compile_condition(condition, scope, target)?;
+                self.compile_into(then, scope, target)?;
+                let jump = self.push_jump(RegisterInstruction::Jump(0));
+                self.patch(branch);
+                self.compile_into(r#else, scope, target)?;
+                self.patch(jump);
+            }
+            Expression::Integer32(value) => self.push(RegisterInstruction::Integer32 {
+                destination: target,
+                value: *value,
+            }),
+            Expression::Lambda(lambda) => self.compile_lambda(lambda, scope, target)?,
+            Expression::Located { expression, .. } => {
+                return self.compile_expression(expression, scope, target)
+            }
+            Expression::Loop { bindings, body } => {
+                let register = self.compile_loop(bindings, scope, target, |scope, register| {
+                    self.compile_expression(body, scope, register)
+                })?;
+
+                if register != target {
+                    self.push(RegisterInstruction::Move {
+                        destination: target,
+                        source: register,
+                    });
+                }
+            }
+            Expression::Nil => self.push(RegisterInstruction::Nil(target)),
+            Expression::Or { left, right } => {
+                self.compile_into(left, scope, target)?;
+                let branch = self.push_jump(RegisterInstruction::Branch {
+                    condition: target,
+                    pointer: 0,
+                });
+                let jump = self.push_jump(RegisterInstruction::Jump(0));
+                self.patch(branch);
+                self.compile_into(right, scope, target)?;
+                self.patch(jump);
+            }
+            Expression::Primitive {
+                instruction,
+                arguments,
+            } => self.compile_primitive(*instruction, arguments, scope, target)?,
+            Expression::Recur { arguments, .. } => self.compile_recur(arguments, scope, target)?,
+            Expression::Sequence { body, result } => {
+                let register = self.compile_sequence(body, scope, target, |scope, register| {
+                    self.compile_expression(result, scope, register)
+                })?;
+
+                if register != target {
+                    self.push(RegisterInstruction::Move {
+                        destination: target,
+                        source: register,
+                    });
+                }
+            }
+            Expression::Move(name) => match scope.resolve(*name) {
+                // A variable on its last use is moved so that its value is not
+                // shared.
+                Variable::Register(register) => self.push(RegisterInstruction::Take {
+                    destination: target,
+                    source: register,
+                }),
+                Variable::Environment(index) => self.push(RegisterInstruction::Environment {
+                    destination: target,
+                    index,
+                }),
+                Variable::Sibling(index) => self.compile_sibling(index, scope, target)?,
+            },
+            Expression::Variable(name) => match scope.resolve(*name) {
+                Variable::Register(register) => return Ok(register),
+                Variable::Environment(index) => self.push(RegisterInstruction::Environment {
+                    destination: target,
+                    index,
+                }),
+                Variable::Sibling(index) => self.compile_sibling(index, scope, target)?,
+            },
+            Expression::Closure(_)
+            | Expression::Environment(_)
+            | Expression::Local(_)
+            | Expression::Sibling(_)
+            | Expression::Take(_) => {
+                unreachable!("converted expression: {expression:?}")
+            }
+        };
+
+        Ok(target)
+    }
+
+    // Compiles an expression whose value is only read, so that a variable on
+    // its last use is read in its register without moving it.
+    fn compile_read(
+        &self,
+        expression: &Expression,
+        scope: &mut Scope,
+        target: Register,
+    ) -> Result<Register, CompileError> {
+        match expression {
+            Expression::Move(name) => {
+                self.compile_expression(&Expression::Variable(*name), scope, target)
+            }
+            expression => self.compile_expression(expression, scope, target),
+        }
+    }
+
+    fn compile_into(
+        &self,
+        expression: &Expression,
+        scope: &mut Scope,
+        target: Register,
+    ) -> Result<(), CompileError> {
+        let register = self.compile_expression(expression, scope, target)?;
+
+        if register != target {
+            self.push(RegisterInstruction::Move {
+                destination: target,
+                source: register,
+            });
+        }
+
+        Ok(())
+    }
+
+    fn compile_tail(
+        &self,
+        expression: &Expression,
+        scope: &mut Scope,
+        target: Register,
+    ) -> Result<(), CompileError> {
+        match expression {
+            Expression::Call {
+                function,
+                arguments,
+            } => {
+                self.compile_call(function, arguments, scope, target)?;
+                self.push(RegisterInstruction::TailCall {
+                    function: target,
+                    arity: arguments.len() as u8,
+                });
+            }
+            Expression::If {
+                condition,
+                then,
+                r#else,
+            } => {
+                let branch = self.compile_condition(condition, scope, target)?;
+                self.compile_tail(then, scope, target)?;
+                self.patch(branch);
+                self.compile_tail(r#else, scope, target)?;
+            }
+            Expression::Or { left, right } => {
+                self.compile_into(left, scope, target)?;
+                let branch = self.push_jump(RegisterInstruction::Branch {
+                    condition: target,
+                    pointer: 0,
+                });
+                self.push(RegisterInstruction::Return(target));
+                self.patch(branch);
+                self.compile_tail(right, scope, target)?;
+            }
+            Expression::Located { expression, .. } => {
+                self.compile_tail(expression, scope, target)?
+            }
+            Expression::Loop { bindings, body } => {
+                self.compile_loop(bindings, scope, target, |scope, register| {
+                    self.compile_tail(body, scope, register)
+                })?
+            }
+            Expression::Recur { arguments, .. } => self.compile_recur(arguments, scope, target)?,
+            Expression::Sequence { body, result } => {
+                self.compile_sequence(body, scope, target, |scope, register| {
+                    self.compile_tail(result, scope, register)
+                })?
+            }
+            _ => {
+                // A returned value is moved out of its register.
+                let register = self.compile_read(expression, scope, target)?;
+                self.push(RegisterInstruction::Return(register));
+            }
+        }
+
+        Ok(())
+    }
+
+    // Compiles statements with variables above a target register followed by
+    // a result.
+    //
+    // The result is compiled above the variables too, so that its temporaries
+    // do not overwrite them.
+    fn compile_sequence<T>(
+        &self,
+        body: &[Statement],
+        scope: &mut Scope,
+        target: Register,
+        compile_result: impl FnOnce(&mut Scope, Register) -> Result<T, CompileError>,
+    ) -> Result<T, CompileError> {
+        let length = scope.variables.len();
+        let base = replace(&mut scope.base, offset(target, 1)?);
+
+        for statement in body {
+            self.compile_statement(statement, scope, false)?;
+        }
+
+        let register = scope.next()?;
+        let result = compile_result(scope, register)?;
+
+        scope.variables.truncate(length);
+        scope.base = base;
+
+        Ok(result)
+    }
+
+    // Compiles loop variables above a target register followed by a loop body.
+    fn compile_loop<T>(
+        &self,
+        bindings: &[(Symbol, Expression)],
+        scope: &mut Scope,
+        target: Register,
+        compile_body: impl FnOnce(&mut Scope, Register) -> Result<T, CompileError>,
+    ) -> Result<T, CompileError> {
+        let length = scope.variables.len();
+        let base = replace(&mut scope.base, offset(target, 1)?);
+        let mut registers = Vec::with_capacity(bindings.len());
+
+        for (name, value) in bindings {
+            let register = scope.next()?;
+
+            self.compile_into(value, scope, register)?;
+            scope.variables.push((*name, register));
+            registers.push(register);
+        }
+
+        scope
+            .loops
+            .push((self.instructions.borrow().len() as u32, registers));
+        let register = scope.next()?;
+        let result = compile_body(scope, register)?;
+
+        scope.loops.pop();
+        scope.variables.truncate(length);
+        scope.base = base;
+
+        Ok(result)
+    }
+
+    // Evaluates new values of loop variables into temporaries before moving
+    // them, so that they can refer to old values of each other.
+    fn compile_recur(
+        &self,
+        arguments: &[Expression],
+        scope: &mut Scope,
+        target: Register,
+    ) -> Result<(), CompileError> {
+        for (index, argument) in arguments.iter().enumerate() {
+            self.compile_into(argument, scope, offset(target, index)?)?;
+        }
+
+        let (start, registers) = scope.loops.last().expect("loop");
+
+        for (index, &register) in registers.iter().enumerate() {
+            self.push(RegisterInstruction::Take {
+                destination: register,
+                source: offset(target, index)?,
+            });
+        }
+
+        self.push(RegisterInstruction::Jump(*start));
+
+        Ok(())
+    }
+
+    // Compiles a condition and returns an index of a branch instruction.
+    fn compile_condition(
+        &self,
+        condition: &Expression,
+        scope: &mut Scope,
+        target: Register,
+    ) -> Result<usize, CompileError> {
+        let condition = self.compile_expression(condition, scope, target)?;
+
+        Ok(self.push_jump(RegisterInstruction::Branch {
+            condition,
+            pointer: 0,
+        }))
+    }
+
+    fn compile_call(
+        &self,
+        function: &Expression,
+        arguments: &[Expression],
+        scope: &mut Scope,
+        target: Register,
+    ) -> Result<(), CompileError> {
+        self.compile_into(function, scope, target)?;
+
+        for (index, argument) in arguments.iter().enumerate() {
+            self.compile_into(argument, scope, offset(target, index + 1)?)?;
+        }
+
+        Ok(())
+    }
+
+    fn compile_primitive(
+        &self,
+        instruction: Instruction,
+        arguments: &[Expression],
+        scope: &mut Scope,
+        target: Register,
+    ) -> Result<(), CompileError> {
+        let arity = match instruction {
+            Instruction::Deref
+            | Instruction::Display
+            | Instruction::Length
+            | Instruction::Not
+            | Instruction::Pop
+            | Instruction::Ref
+            | Instruction::Repr
+            | Instruction::Reverse => 1,
+            Instruction::Fold | Instruction::Set | Instruction::Slice => 3,
+            _ => 2,
+        };
+        let mut operands = Vec::with_capacity(arity);
+        let mut next = target;
+
+        // Missing operands are `nil` while extra ones are evaluated and ignored.
+        for index in 0..arity.max(arguments.len()) {
+            let register = match arguments.get(index) {
+                Some(argument) if keeps_operands(instruction) => {
+                    self.compile_expression(argument, scope, next)?
+                }
+                Some(argument) => self.compile_read(argument, scope, next)?,
+                None => self.compile_expression(&Expression::Nil, scope, next)?,
+            };
+
+            if register == next {
+                next = offset(next, 1)?;
+            }
+
+            operands.push(register);
+        }
+
+        let destination = target;
+
+        self.push(match (instruction, operands.as_slice()) {
+            (Instruction::Set, &[array, index, value, ..]) => RegisterInstruction::Set {
+                destination,
+                array,
+                index,
+                value,
+            },
+            (_, &[first, second, third, ..]) if arity == 3 => RegisterInstruction::Ternary {
+                operator: ternary_operator(instruction),
+                destination,
+                first,
+                second,
+                third,
+            },
+            (_, &[source, ..]) if arity == 1 => RegisterInstruction::Unary {
+                operator: unary_operator(instruction),
+                destination,
+                source,
+            },
+            (_, &[lhs, rhs, ..]) => RegisterInstruction::Binary {
+                operator: binary_operator(instruction),
+                destination,
+                lhs,
+                rhs,
+            },
+            _ => unreachable!(),
+        });
+
+        Ok(())
+    }
+
+    fn compile_lambda(
+        &self,
+        lambda: &Lambda,
+        scope: &mut Scope,
+        target: Register,
+    ) -> Result<(), CompileError> {
+        let jump = self.push_jump(RegisterInstruction::Jump(0));
+        let pointer = self.instructions.borrow().len() as u32;
+        let free_variables = self
+            .compile_function(
+                lambda,
+                Scope::new(lambda.name.filter(|_| lambda.recursive), &lambda.arguments),
+            )?
+            .free_variables;
+
+        self.patch(jump);
+        self.compile_closure(lambda, pointer, &free_variables, scope, target)
+    }
+
+    // Compiles mutually recursive functions followed by their closures.
+    //
+    // The functions share their environment to create each other.
+    fn compile_functions(
+        &self,
+        functions: &[Expression],
+        scope: &mut Scope,
+    ) -> Result<(), CompileError> {
+        let lambdas = functions
+            .iter()
+            .map(|function| match function {
+                Expression::Lambda(lambda) => lambda,
+                function => unreachable!("unlowered function: {function:?}"),
+            })
+            .collect::<Vec<_>>();
+        let free_variables = collect_free_variables(&lambdas);
+        let siblings = lambdas
+            .iter()
+            .map(|lambda| Ok((lambda.name, u8::try_from(lambda.arguments.len())?)))
+            .collect::<Result<Vec<_>, CompileError>>()?;
+        let jump = self.push_jump(RegisterInstruction::Jump(0));
+        let mut pointers = Vec::with_capacity(lambdas.len());
+        let mut references = vec![];
+
+        for lambda in &lambdas {
+            pointers.push(self.instructions.borrow().len() as u32);
+
+            let mut function_scope = Scope::new(lambda.name, &lambda.arguments);
+            function_scope.free_variables = free_variables.clone();
+            function_scope.siblings = siblings.clone();
+
+            references.extend(self.compile_function(lambda, function_scope)?.references);
+        }
+
+        for (index, sibling) in references {
+            match &mut self.instructions.borrow_mut()[index] {
+                RegisterInstruction::Close { pointer, .. } => *pointer = pointers[sibling],
+                _ => unreachable!(),
+            }
+        }
+
+        self.patch(jump);
+
+        for (lambda, pointer) in lambdas.into_iter().zip(pointers) {
+            let target = scope.next()?;
+
+            self.compile_closure(lambda, pointer, &free_variables, scope, target)?;
+
+            if let Some(name) = lambda.name {
+                scope.variables.push((name, target));
+            }
+        }
+
+        Ok(())
+    }
+
+    // Compiles a body of a function and returns its scope.
+    fn compile_function(&self, lambda: &Lambda, mut scope: Scope) -> Result<Scope, CompileError> {
+        for statement in &lambda.body {
+            self.compile_statement(statement, &mut scope, false)?;
+        }
+
+        let target = scope.next()?;
+        self.compile_tail(&lambda.result, &mut scope, target)?;
+
+        Ok(scope)
+    }
+
+    fn compile_closure(
+        &self,
+        lambda: &Lambda,
+        pointer: u32,
+        free_variables: &[Symbol],
+        scope: &mut Scope,
+        target: Register,
+    ) -> Result<(), CompileError> {
+        for (index, &name) in free_variables.iter().enumerate() {
+            self.compile_into(
+                &Expression::Variable(name),
+                scope,
+                offset(target, index + 1)?,
+            )?;
+        }
+
+        self.push(RegisterInstruction::Close {
+            destination: target,
+            pointer,
+            arity: u8::try_from(lambda.arguments.len())?,
+            environment: offset(target, 1)?,
+            environment_size: free_variables.len() as u8,
+        });
+
+        Ok(())
+    }
+
+    // Compiles a function in a current group with an environment of a current
+    // closure.
+    fn compile_sibling(
+        &self,
+        index: usize,
+        scope: &mut Scope,
+        target: Register,
+    ) -> Result<(), CompileError> {
+        let environment_size = scope.free_variables.len();
+
+        for environment_index in 0..environment_size {
+            self.push(RegisterInstruction::Environment {
+                destination: offset(target, environment_index + 1)?,
+                index: environment_index as u8,
+            });
+        }
+
+        scope
+            .references
+            .push((self.instructions.borrow().len(), index));
+        self.push(RegisterInstruction::Close {
+            destination: target,
+            pointer: 0,
+            arity: scope.siblings[index].1,
+            environment: offset(target, 1)?,
+            environment_size: environment_size as u8,
+        });
+
+        Ok(())
+    }
+
+    fn push(&self, instruction: RegisterInstruction) {
+        self.instructions.borrow_mut().push(instruction);
+    }
+
+    // Pushes a jump or branch instruction with a stub pointer and returns its
+    // index.
+    fn push_jump(&self, instruction: RegisterInstruction) -> usize {
+        let mut instructions = self.instructions.borrow_mut();
+
+        instructions.push(instruction);
+        instructions.len() - 1
+    }
+
+    // Patches a jump or branch instruction to jump to the current index.
+    fn patch(&self, index: usize) {
+        let mut instructions = self.instructions.borrow_mut();
+        let current = instructions.len() as u32;
+
+        match &mut instructions[index] {
+            RegisterInstruction::Branch { pointer, .. } => *pointer = current,
+            RegisterInstruction::Jump(pointer) => *pointer = current,
+            _ => unreachable!(),
+        }
+    }
+}
+
+enum Variable {
+    Register(Register),
+    Environment(u8),
+    Sibling(usize),
+}
+
+// Variables of a function.
+struct Scope {
+    variables: Vec<(Symbol, Register)>,
+    free_variables: Vec<Symbol>,
+    // Names and arities of functions in a group of mutually recursive ones.
+    siblings: Vec<(Option<Symbol>, u8)>,
+    // Indices of closure instructions with indices of functions in a group.
+    references: Vec<(usize, usize)>,
+    // A lowest register for new variables, under which temporaries of
+    // enclosing expressions can live.
+    base: Register,
+    // Start indices of bodies of loops being compiled with registers of their
+    // variables.
+    loops: Vec<(u32, Vec<Register>)>,
+}
+
+impl Scope {
+    fn new(name: Option<Symbol>, arguments: &[Symbol]) -> Self {
+        Self {
+            variables: name
+                .map(|name| (name, CLOSURE_REGISTER))
+                .into_iter()
+                .chain(
+                    arguments
+                        .iter()
+                        .enumerate()
+                        .map(|(index, &name)| (name, index as Register + 1)),
+                )
+                .collect(),
+            free_variables: vec![],
+            siblings: vec![],
+            references: vec![],
+            base: 1,
+            loops: vec![],
+        }
+    }
+
+    // Returns a next free register.
+    fn next(&self) -> Result<Register, CompileError> {
+        let register = self
+            .variables
+            .iter()
+            .map(|&(_, register)| register as usize + 1)
+            .max()
+            .unwrap_or(1)
+            .max(self.base as usize);
+
+        Ok(Register::try_from(register)?)
+    }
+
+    fn resolve(&mut self, name: Symbol) -> Variable {
+        if let Some(&(_, register)) = self
+            .variables
+            .iter()
+            .rev()
+            .find(|(other, _)| *other == name)
+        {
+            return Variable::Register(register);
+        } else if let Some(index) = self
+            .siblings
+            .iter()
+            .position(|&(other, _)| other == Some(name))
+        {
+            return Variable::Sibling(index);
+        }
+
+        let index = match self.free_variables.iter().position(|&other| other == name) {
+            Some(index) => index,
+            None => {
+                self.free_variables.push(name);
+                self.free_variables.len() - 1
+            }
+        };
+
+        Variable::Environment(index as u8)
+    }
+}
+
+fn offset(register: Register, offset: usize) -> Result<Register, CompileError> {
+    Ok(Register::try_from(register as usize + offset)?)
+}
+
+// Returns `true` if a result of a primitive can hold its operands.
+fn keeps_operands(instruction: Instruction) -> bool {
+    !matches!(
+        instruction,
+        Instruction::Add
+            | Instruction::Display
+            | Instruction::Divide
+            | Instruction::Equal
+            | Instruction::Get
+            | Instruction::GreaterThan
+            | Instruction::GreaterThanOrEqual
+            | Instruction::Length
+            | Instruction::LessThan
+            | Instruction::LessThanOrEqual
+            | Instruction::Multiply
+            | Instruction::Not
+            | Instruction::NotEqual
+            | Instruction::Repr
+            | Instruction::Subtract
+    )
+}
+
+fn binary_operator(instruction: Instruction) -> BinaryOperator {
+    match instruction {
+        Instruction::Add => BinaryOperator::Add,
+        Instruction::And => BinaryOperator::And,
+        Instruction::Concat => BinaryOperator::Concat,
+        Instruction::Divide => BinaryOperator::Divide,
+        Instruction::Equal => BinaryOperator::Equal,
+        Instruction::Filter => BinaryOperator::Filter,
+        Instruction::Get => BinaryOperator::Get,
+        Instruction::GreaterThan => BinaryOperator::GreaterThan,
+        Instruction::GreaterThanOrEqual => BinaryOperator::GreaterThanOrEqual,
+        Instruction::LessThan => BinaryOperator::LessThan,
+        Instruction::LessThanOrEqual => BinaryOperator::LessThanOrEqual,
+        Instruction::Map => BinaryOperator::Map,
+        Instruction::Multiply => BinaryOperator::Multiply,
+        Instruction::NotEqual => BinaryOperator::NotEqual,
+        Instruction::Or => BinaryOperator::Or,
+        Instruction::Push => BinaryOperator::Push,
+        Instruction::Range => BinaryOperator::Range,
+        Instruction::SetRef => BinaryOperator::SetRef,
+        Instruction::Sort => BinaryOperator::Sort,
+        Instruction::Subtract => BinaryOperator::Subtract,
+        _ => unreachable!("binary primitive expected: {instruction:?}"),
+    }
+}
+
+fn unary_operator(instruction: Instruction) -> UnaryOperator {
+    match instruction {
+        Instruction::Deref => UnaryOperator::Deref,
+        Instruction::Display => UnaryOperator::Display,
+        Instruction::Length => UnaryOperator::Length,
+        Instruction::Not => UnaryOperator::Not,
+        Instruction::Pop => UnaryOperator::Pop,
+        Instruction::Ref => UnaryOperator::Ref,
+        Instruction::Repr => UnaryOperator::Repr,
+        Instruction::Reverse => UnaryOperator::Reverse,
+        _ => unreachable!("unary primitive expected: {instruction:?}"),
+    }
+}
+
+fn ternary_operator(instruction: Instruction) -> TernaryOperator {
+    match instruction {
+        Instruction::Fold => TernaryOperator::Fold,
+        Instruction::Slice => TernaryOperator::Slice,
+        _ => unreachable!("ternary primitive expected: {instruction:?}"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::{pin_mut, stream::iter};
+    use parse::{parse_concrete, SourceMap};
+    use std::io;
+
+    async fn compile_instructions(source: &str) -> Result<String, CompileError> {
+        let (values, _) = SourceMap::new(source, &parse_concrete(source).unwrap());
+        let instructions = vec![].into();
+
+        {
+            let mut compiler = RegisterCompiler::new(&instructions);
+            let values = iter(values).map(Ok);
+
+            pin_mut!(values);
+
+            let results = compiler.compile::<io::Error>(&mut values);
+
+            pin_mut!(results);
+
+            while let Some(result) = results.next().await {
+                result?;
+            }
+        }
+
+        Ok(instructions
+            .into_inner()
+            .iter()
+            .map(|instruction| format!("{instruction}\n"))
+            .collect())
+    }
+
+    async fn compile(source: &str) -> String {
+        compile_instructions(source).await.unwrap()
+    }
+
+    #[tokio::test]
+    async fn compile_let() {
+        insta::assert_snapshot!(compile("(let x 1)\n(let y 2)\n(+ x y)").await);
+    }
+
+    #[tokio::test]
+    async fn compile_nested_primitives() {
+        insta::assert_snapshot!(compile("(let x 1)\n(* (+ x 2) (- x 3))").await);
+    }
+
+    #[tokio::test]
+    async fn compile_if() {
+        insta::assert_snapshot!(compile("(let x 1)\n(if x 2 x)").await);
+    }
+
+    #[tokio::test]
+    async fn compile_call() {
+        insta::assert_snapshot!(compile("(let f (fn (x y) (+ x y)))\n(f 1 2)").await);
+    }
+
+    #[tokio::test]
+    async fn compile_tail_call() {
+        insta::assert_snapshot!(
+            compile("(let-rec f (fn (x) (if (= x 0) x (f (- x 1)))))\n(f 3)").await
+        );
+    }
+
+    #[tokio::test]
+    async fn compile_mutual_recursion() {
+        insta::assert_snapshot!(
+            compile("(let y 1)\n(let-rec f (fn (x) (g x)) g (fn (x) (f y)))\n(f 3)").await
+        );
+    }
+
+    #[tokio::test]
+    async fn compile_or() {
+        insta::assert_snapshot!(compile("(let x 1)\n(or x 2)\n(fn (y) (or y (y)))").await);
+    }
+
+    #[tokio::test]
+    async fn compile_let_expression() {
+        insta::assert_snapshot!(
+            compile("(let-rec f (fn (x) (+ (f) (let ((y x)) (+ (f) y)))))").await
+        );
+    }
+
+    #[tokio::test]
+    async fn compile_loop() {
+        insta::assert_snapshot!(
+            compile("(let n 3)\n(loop (i 0 x 1) (if (< i n) (recur (+ i 1) (* x i)) x))").await
+        );
+    }
+
+    #[tokio::test]
+    async fn compile_ternary_primitives() {
+        insta::assert_snapshot!(
+            compile("(let xs (range 3))\n(fold (fn (x y) (+ x y)) 0 (slice xs 1 2))").await
+        );
+    }
+
+    #[tokio::test]
+    async fn compile_closure() {
+        insta::assert_snapshot!(compile("(let x 1)\n(fn () (fn () x))").await);
+    }
+
+    #[tokio::test]
+    async fn compile_undefined_variable() {
+        assert!(matches!(
+            compile_instructions("(let x y)").await,
+            Err(CompileError::VariableNotDefined(name)) if name == "y"
+        ));
+    }
+}
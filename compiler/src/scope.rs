@@ -0,0 +1,351 @@
+use crate::lower::Lowerer;
+use parse::{SourceMap, Span};
+use runtime::{Symbol, Value};
+use std::cell::RefCell;
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum BindingKind {
+    Argument,
+    Let,
+    LetRec,
+}
+
+impl BindingKind {
+    pub const fn as_str(self) -> &'static str {
+        match self {
+            Self::Argument => "argument",
+            Self::Let => "let",
+            Self::LetRec => "let-rec",
+        }
+    }
+}
+
+/// A variable bound in a source code.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Binding {
+    name: String,
+    kind: BindingKind,
+    span: Span,
+    scope: Span,
+}
+
+impl Binding {
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub const fn kind(&self) -> BindingKind {
+        self.kind
+    }
+
+    pub const fn span(&self) -> Span {
+        self.span
+    }
+
+    /// Returns a span in which a variable is visible.
+    pub const fn scope(&self) -> Span {
+        self.scope
+    }
+}
+
+/// A variable referenced in a source code.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Reference {
+    span: Span,
+    binding: Option<usize>,
+}
+
+impl Reference {
+    pub const fn span(&self) -> Span {
+        self.span
+    }
+
+    /// Returns an index of a binding or `None` for a global variable.
+    pub const fn binding(&self) -> Option<usize> {
+        self.binding
+    }
+}
+
+/// Bindings of variables and references to them in a source code.
+#[derive(Debug, Default)]
+pub struct Scopes {
+    bindings: Vec<Binding>,
+    references: Vec<Reference>,
+}
+
+impl Scopes {
+    /// Resolves variables in values converted by [`SourceMap::new`] with the
+    /// same scoping rules as compilation.
+    ///
+    /// Statements with compile errors are resolved up to the errors.
+    pub fn resolve(values: &[Value], source_map: &SourceMap) -> Self {
+        let resolver = Resolver::default();
+        let lowerer = Lowerer::with_resolver(source_map, &resolver);
+
+        for value in values {
+            let scope = resolver.scope();
+
+            if lowerer.lower(value).is_err() {
+                resolver.exit(scope);
+            }
+        }
+
+        let mut scopes = resolver.state.into_inner().scopes;
+        scopes
+            .references
+            .sort_by_key(|reference| reference.span.start());
+        scopes
+    }
+
+    pub fn bindings(&self) -> &[Binding] {
+        &self.bindings
+    }
+
+    /// Returns references in an order of their positions.
+    pub fn references(&self) -> &[Reference] {
+        &self.references
+    }
+}
+
+/// A resolver of variables which records bindings and references while
+/// values are lowered.
+#[derive(Debug, Default)]
+pub(crate) struct Resolver {
+    state: RefCell<State>,
+}
+
+#[derive(Debug, Default)]
+struct State {
+    scopes: Scopes,
+    // Indices of bindings visible in a current scope.
+    environment: Vec<usize>,
+    // Ends of nested scopes. Top-level variables are visible until the end of
+    // a source code.
+    ends: Vec<usize>,
+}
+
+/// A scope entered by a resolver.
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct Scope {
+    environment: usize,
+    ends: usize,
+}
+
+impl Resolver {
+    /// Returns a current scope.
+    pub fn scope(&self) -> Scope {
+        let state = self.state.borrow();
+
+        Scope {
+            environment: state.environment.len(),
+            ends: state.ends.len(),
+        }
+    }
+
+    /// Enters a nested scope ending at an offset or at the end of a current
+    /// scope.
+    pub fn enter(&self, end: Option<usize>) -> Scope {
+        let scope = self.scope();
+        let mut state = self.state.borrow_mut();
+        let end = end.unwrap_or_else(|| state.end());
+
+        state.ends.push(end);
+
+        scope
+    }
+
+    /// Exits a scope dropping variables bound in it and its nested scopes.
+    pub fn exit(&self, scope: Scope) {
+        let mut state = self.state.borrow_mut();
+
+        state.environment.truncate(scope.environment);
+        state.ends.truncate(scope.ends);
+    }
+
+    /// Binds a variable visible from an offset to the end of a current scope.
+    pub fn bind(&self, name: Symbol, kind: BindingKind, span: Span, start: usize) {
+        let mut state = self.state.borrow_mut();
+        let scope = Span::new(start, state.end());
+
+        state.scopes.bindings.push(Binding {
+            name: name.as_str().into(),
+            kind,
+            span,
+            scope,
+        });
+        let index = state.scopes.bindings.len() - 1;
+        state.environment.push(index);
+    }
+
+    pub fn refer(&self, name: Symbol, span: Span) {
+        let mut state = self.state.borrow_mut();
+        let binding = state
+            .environment
+            .iter()
+            .rev()
+            .copied()
+            .find(|&index| state.scopes.bindings[index].name == name.as_str());
+
+        state.scopes.references.push(Reference { span, binding });
+    }
+}
+
+impl State {
+    fn end(&self) -> usize {
+        self.ends.last().copied().unwrap_or(usize::MAX)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use parse::parse_concrete;
+    use pretty_assertions::assert_eq;
+
+    fn resolve(source: &str) -> Scopes {
+        let (values, source_map) = SourceMap::new(source, &parse_concrete(source).unwrap());
+
+        Scopes::resolve(&values, &source_map)
+    }
+
+    fn binding_names(scopes: &Scopes) -> Vec<(&str, BindingKind)> {
+        scopes
+            .bindings()
+            .iter()
+            .map(|binding| (binding.name(), binding.kind()))
+            .collect()
+    }
+
+    fn reference_bindings(scopes: &Scopes) -> Vec<Option<usize>> {
+        scopes.references().iter().map(Reference::binding).collect()
+    }
+
+    #[test]
+    fn resolve_let() {
+        let scopes = resolve("(let x 42)\nx");
+
+        assert_eq!(binding_names(&scopes), [("x", BindingKind::Let)]);
+        assert_eq!(scopes.bindings()[0].span(), Span::new(5, 6));
+        assert_eq!(scopes.bindings()[0].scope(), Span::new(10, usize::MAX));
+        assert_eq!(
+            scopes.references(),
+            [Reference {
+                span: Span::new(11, 12),
+                binding: Some(0)
+            }]
+        );
+    }
+
+    #[test]
+    fn resolve_let_without_self_reference() {
+        assert_eq!(reference_bindings(&resolve("(let x x)")), [None]);
+    }
+
+    #[test]
+    fn resolve_let_rec() {
+        let scopes = resolve("(let-rec f (fn (x) (f x)))");
+
+        assert_eq!(
+            binding_names(&scopes),
+            [("f", BindingKind::LetRec), ("x", BindingKind::Argument)]
+        );
+        assert_eq!(reference_bindings(&scopes), [Some(0), Some(1)]);
+    }
+
+    #[test]
+    fn resolve_mutual_let_rec() {
+        let scopes = resolve("(let-rec f (fn () (g)) g (fn () (f)))");
+
+        assert_eq!(
+            binding_names(&scopes),
+            [("f", BindingKind::LetRec), ("g", BindingKind::LetRec)]
+        );
+        assert_eq!(reference_bindings(&scopes), [Some(1), Some(0)]);
+    }
+
+    #[test]
+    fn resolve_let_expression() {
+        let scopes = resolve("(let ((x 1) (y x)) (do (let z y) z))\nx");
+
+        assert_eq!(
+            binding_names(&scopes),
+            [
+                ("x", BindingKind::Let),
+                ("y", BindingKind::Let),
+                ("z", BindingKind::Let)
+            ]
+        );
+        assert_eq!(
+            reference_bindings(&scopes),
+            [Some(0), Some(1), Some(2), None]
+        );
+    }
+
+    #[test]
+    fn resolve_loop() {
+        let scopes = resolve("(loop (i 0 j i) (recur j i))\ni");
+
+        assert_eq!(
+            binding_names(&scopes),
+            [("i", BindingKind::Let), ("j", BindingKind::Let)]
+        );
+        assert_eq!(
+            reference_bindings(&scopes),
+            [Some(0), Some(1), Some(0), None]
+        );
+    }
+
+    #[test]
+    fn resolve_while() {
+        let scopes = resolve("(while x (let y x) y)\ny");
+
+        assert_eq!(reference_bindings(&scopes), [None, None, Some(0), None]);
+    }
+
+    #[test]
+    fn resolve_match() {
+        let scopes = resolve("(match x ((y 1 & z) when y z) (_ y))");
+
+        assert_eq!(
+            binding_names(&scopes),
+            [("y", BindingKind::Let), ("z", BindingKind::Let)]
+        );
+        assert_eq!(reference_bindings(&scopes), [None, Some(0), Some(1), None]);
+    }
+
+    #[test]
+    fn resolve_shadowed_variable() {
+        assert_eq!(
+            reference_bindings(&resolve("(let x 1)\n(let x 2)\nx")),
+            [Some(1)]
+        );
+    }
+
+    #[test]
+    fn resolve_let_in_function() {
+        assert_eq!(
+            reference_bindings(&resolve("(fn (x) (let y x) y)\ny")),
+            [Some(0), Some(1), None]
+        );
+    }
+
+    #[test]
+    fn resolve_statements_after_errors() {
+        let scopes = resolve("(let x 1)\n(fn (y) (let z y) (recur))\nx");
+
+        assert_eq!(
+            binding_names(&scopes),
+            [
+                ("x", BindingKind::Let),
+                ("y", BindingKind::Argument),
+                ("z", BindingKind::Let)
+            ]
+        );
+        assert_eq!(reference_bindings(&scopes), [Some(1), Some(0)]);
+    }
+
+    #[test]
+    fn skip_numbers_and_primitives() {
+        assert!(resolve("(+ 1 (get () 0))").references().is_empty());
+    }
+}
@@ -2,4 +2,5 @@
 pub enum Variable {
     Bound(usize),
     Free(usize),
+    Sibling(usize),
 }
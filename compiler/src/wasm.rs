@@ -0,0 +1,1350 @@
+mod helper;
+
+use crate::{
+    free_variable::collect_free_variables,
+    ir::{Expression, Lambda, Statement},
+    lower::Lowerer,
+    CompileError,
+};
+use async_stream::try_stream;
+use futures::{Stream, StreamExt};
+use helper::{
+    value_memory, word_memory, Helper, CLOSURE_MASK, DUMP_FUNCTION, HEADER_SIZE, IMPORT_COUNT,
+    INTEGER32_MASK, VALUE_SIZE,
+};
+use runtime::{Symbol, Value};
+use std::{borrow::Cow, error::Error};
+use vm::Instruction as Primitive;
+use wasm_encoder::{
+    BlockType, CodeSection, ConstExpr, ElementSection, Elements, EntityType, ExportKind,
+    ExportSection, Function, FunctionSection, GlobalSection, GlobalType, ImportSection,
+    Instruction, MemorySection, MemoryType, Module, RefType, TableSection, TableType, TypeSection,
+    ValType,
+};
+
+// A local of a closure being called in each function.
+const CLOSURE_LOCAL: u32 = 0;
+
+/// A compiler targeting WebAssembly.
+///
+/// A module exports a `main` function running top-level statements and a
+/// `memory`. Values of top-level expressions are passed to an imported
+/// `arachne.dump` function as NaN-boxed `i64`s.
+///
+/// Closures are compiled into functions called indirectly through a table.
+/// Calls with arities different from callees' go through adapters, and tail
+/// calls use the tail call proposal.
+#[derive(Debug, Default)]
+pub struct WasmCompiler {
+    types: Vec<(Vec<ValType>, Vec<ValType>)>,
+    // Functions and adapters of closures with their type indices.
+    functions: Vec<(u32, Function)>,
+    main: Body,
+    scope: Scope,
+}
+
+impl WasmCompiler {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn compile<'a, E: Error + 'static>(
+        &'a mut self,
+        values: &'a mut (impl Stream<Item = Result<Value, E>> + Unpin),
+    ) -> impl Stream<Item = Result<(), CompileError>> + 'a {
+        try_stream! {
+            let lowerer = Lowerer::new(None);
+
+            while let Some(value) = values.next().await {
+                let statement = lowerer.lower(&value.map_err(|error| CompileError::Other(error.into()))?)?;
+                let start = self.main.instructions.len();
+                let function_count = self.functions.len();
+                let variables = self.scope.variables.clone();
+                let mut main = std::mem::take(&mut self.main);
+                let mut scope = std::mem::take(&mut self.scope);
+                let result = self.compile_statement(&statement, &mut main, &mut scope, true);
+                let name = scope.free_variables.first().map(ToString::to_string);
+
+                // Roll back a failed statement so that later ones can be compiled.
+                if result.is_err() || name.is_some() {
+                    main.instructions.truncate(start);
+                    self.functions.truncate(function_count);
+                    scope.variables = variables;
+                    scope.free_variables.clear();
+                }
+
+                self.main = main;
+                self.scope = scope;
+
+                result?;
+
+                if let Some(name) = name {
+                    Err(CompileError::VariableNotDefined(name))?;
+                }
+
+                yield ();
+            }
+        }
+    }
+
+    /// Encodes compiled statements into a module.
+    pub fn module(&self) -> Vec<u8> {
+        let mut types = self.types.clone();
+        let mut module = Module::new();
+
+        let mut imports = ImportSection::new();
+        imports.import(
+            "arachne",
+            "dump",
+            EntityType::Function(intern_type(&mut types, &[ValType::I64], &[])),
+        );
+
+        let mut functions = FunctionSection::new();
+        let mut code = CodeSection::new();
+
+        for &helper in Helper::ALL {
+            let (parameters, results) = helper.signature();
+
+            functions.function(intern_type(&mut types, parameters, results));
+            code.function(&helper.function());
+        }
+
+        for (r#type, function) in &self.functions {
+            functions.function(*r#type);
+            code.function(function);
+        }
+
+        functions.function(intern_type(&mut types, &[], &[]));
+        code.function(&self.main.function());
+
+        let mut type_section = TypeSection::new();
+
+        for (parameters, results) in &types {
+            type_section
+                .ty()
+                .function(parameters.iter().copied(), results.iter().copied());
+        }
+
+        let mut tables = TableSection::new();
+        tables.table(TableType {
+            element_type: RefType::FUNCREF,
+            table64: false,
+            minimum: self.functions.len() as u64,
+            maximum: Some(self.functions.len() as u64),
+            shared: false,
+        });
+
+        let mut memories = MemorySection::new();
+        memories.memory(MemoryType {
+            minimum: 1,
+            maximum: None,
+            memory64: false,
+            shared: false,
+            page_size_log2: None,
+        });
+
+        let mut globals = GlobalSection::new();
+        globals.global(
+            GlobalType {
+                val_type: ValType::I32,
+                mutable: true,
+                shared: false,
+            },
+            &ConstExpr::i32_const(0),
+        );
+
+        let mut exports = ExportSection::new();
+        exports.export("memory", ExportKind::Memory, 0);
+        exports.export(
+            "main",
+            ExportKind::Func,
+            function_base() + self.functions.len() as u32,
+        );
+
+        let mut elements = ElementSection::new();
+        elements.active(
+            Some(0),
+            &ConstExpr::i32_const(0),
+            Elements::Functions(Cow::Owned(
+                (0..self.functions.len() as u32)
+                    .map(|index| function_base() + index)
+                    .collect(),
+            )),
+        );
+
+        module
+            .section(&type_section)
+            .section(&imports)
+            .section(&functions)
+            .section(&tables)
+            .section(&memories)
+            .section(&globals)
+            .section(&exports)
+            .section(&elements)
+            .section(&code);
+
+        module.finish()
+    }
+
+    fn compile_statement(
+        &mut self,
+        statement: &Statement,
+        body: &mut Body,
+        scope: &mut Scope,
+        dump: bool,
+    ) -> Result<(), CompileError> {
+        match statement {
+            Statement::Expression(expression) => {
+                self.compile_expression(expression, body, scope)?;
+                body.push(if dump {
+                    Instruction::Call(DUMP_FUNCTION)
+                } else {
+                    Instruction::Drop
+                });
+            }
+            Statement::Let { name, value, .. } => {
+                self.compile_expression(value, body, scope)?;
+                let local = body.local();
+                body.push(Instruction::LocalSet(local));
+                scope.variables.push((*name, local));
+            }
+            Statement::LetRec { functions, .. } => {
+                self.compile_functions(functions, body, scope)?
+            }
+        }
+
+        Ok(())
+    }
+
+    fn compile_expression(
+        &mut self,
+        expression: &Expression,
+        body: &mut Body,
+        scope: &mut Scope,
+    ) -> Result<(), CompileError> {
+        match expression {
+            Expression::Call {
+                function,
+                arguments,
+            } => self.compile_call(function, arguments, body, scope, false)?,
+            Expression::Float64(value) => body.push(Instruction::I64Const(value.to_bits() as i64)),
+            Expression::If {
+                condition,
+                then,
+                r#else,
+            } => {
+                self.compile_condition(condition, body, scope)?;
+                self.compile_expression(then, body, scope)?;
+                body.push(Instruction::Else);
+                self.compile_expression(r#else, body, scope)?;
+                body.push(Instruction::End);
+            }
+            Expression::Integer32(value) => {
+                body.push(Instruction::I64Const(INTEGER32_MASK | *value as u32 as i64))
+            }
+            Expression::Lambda(lambda) => self.compile_lambda(lambda, body, scope)?,
+            Expression::Located { expression, .. } => {
+                self.compile_expression(expression, body, scope)?
+            }
+            Expression::Loop {
+                bindings,
+                body: loop_body,
+            } => self.compile_loop(bindings, loop_body, body, scope, false)?,
+            Expression::Nil => body.push(Instruction::I64Const(0)),
+            Expression::Or { left, right } => {
+                let local = self.compile_left(left, body, scope)?;
+                body.push(Instruction::LocalGet(local));
+                body.push(Instruction::Else);
+                self.compile_expression(right, body, scope)?;
+                body.push(Instruction::End);
+            }
+            Expression::Primitive {
+                instruction,
+                arguments,
+            } => self.compile_primitive(*instruction, arguments, body, scope)?,
+            Expression::Recur { arguments, .. } => self.compile_recur(arguments, body, scope)?,
+            Expression::Sequence {
+                body: statements,
+                result,
+            } => {
+                let length = scope.variables.len();
+
+                for statement in statements {
+                    self.compile_statement(statement, body, scope, false)?;
+                }
+
+                self.compile_expression(result, body, scope)?;
+                scope.variables.truncate(length);
+            }
+            Expression::Move(name) | Expression::Variable(name) => match scope.resolve(*name) {
+                Variable::Local(local) => body.push(Instruction::LocalGet(local)),
+                Variable::Environment(index) => load_environment(body, index),
+                Variable::Sibling(index) => {
+                    let (_, table_index, arity) = scope.siblings[index];
+                    let pointer =
+                        allocate_closure(body, table_index, arity, scope.free_variables.len());
+
+                    for index in 0..scope.free_variables.len() {
+                        body.push(Instruction::LocalGet(pointer));
+                        body.push(Instruction::I32WrapI64);
+                        load_environment(body, index);
+                        body.push(Instruction::I64Store(value_memory(
+                            HEADER_SIZE + index as u64 * VALUE_SIZE,
+                        )));
+                    }
+
+                    finish_closure(body, pointer);
+                }
+            },
+            Expression::Closure(_)
+            | Expression::Environment(_)
+            | Expression::Local(_)
+            | Expression::Sibling(_)
+            | Expression::Take(_) => {
+                unreachable!("converted expression: {expression:?}")
+            }
+        }
+
+        Ok(())
+    }
+
+    fn compile_tail(
+        &mut self,
+        expression: &Expression,
+        body: &mut Body,
+        scope: &mut Scope,
+    ) -> Result<(), CompileError> {
+        match expression {
+            Expression::Call {
+                function,
+                arguments,
+            } => self.compile_call(function, arguments, body, scope, true),
+            Expression::If {
+                condition,
+                then,
+                r#else,
+            } => {
+                self.compile_condition(condition, body, scope)?;
+                self.compile_tail(then, body, scope)?;
+                body.push(Instruction::Else);
+                self.compile_tail(r#else, body, scope)?;
+                body.push(Instruction::End);
+
+                Ok(())
+            }
+            Expression::Or { left, right } => {
+                let local = self.compile_left(left, body, scope)?;
+                body.push(Instruction::LocalGet(local));
+                body.push(Instruction::Else);
+                self.compile_tail(right, body, scope)?;
+                body.push(Instruction::End);
+
+                Ok(())
+            }
+            Expression::Located { expression, .. } => self.compile_tail(expression, body, scope),
+            Expression::Loop {
+                bindings,
+                body: loop_body,
+            } => self.compile_loop(bindings, loop_body, body, scope, true),
+            Expression::Sequence {
+                body: statements,
+                result,
+            } => {
+                let length = scope.variables.len();
+
+                for statement in statements {
+                    self.compile_statement(statement, body, scope, false)?;
+                }
+
+                self.compile_tail(result, body, scope)?;
+                scope.variables.truncate(length);
+
+                Ok(())
+            }
+            _ => self.compile_expression(expression, body, scope),
+        }
+    }
+
+    // Compiles loop variables into locals followed by a `loop` block.
+    fn compile_loop(
+        &mut self,
+        bindings: &[(Symbol, Expression)],
+        loop_body: &Expression,
+        body: &mut Body,
+        scope: &mut Scope,
+        tail: bool,
+    ) -> Result<(), CompileError> {
+        let length = scope.variables.len();
+        let mut locals = Vec::with_capacity(bindings.len());
+
+        for (name, value) in bindings {
+            self.compile_expression(value, body, scope)?;
+            let local = body.local();
+            body.push(Instruction::LocalSet(local));
+            scope.variables.push((*name, local));
+            locals.push(local);
+        }
+
+        body.push(Instruction::Loop(BlockType::Result(ValType::I64)));
+        scope.loops.push((locals, body.instructions.len()));
+
+        if tail {
+            self.compile_tail(loop_body, body, scope)?;
+        } else {
+            self.compile_expression(loop_body, body, scope)?;
+        }
+
+        scope.loops.pop();
+        body.push(Instruction::End);
+        scope.variables.truncate(length);
+
+        Ok(())
+    }
+
+    // Sets new values of loop variables and branches to the innermost loop
+    // through blocks opened in it.
+    fn compile_recur(
+        &mut self,
+        arguments: &[Expression],
+        body: &mut Body,
+        scope: &mut Scope,
+    ) -> Result<(), CompileError> {
+        for argument in arguments {
+            self.compile_expression(argument, body, scope)?;
+        }
+
+        let (locals, start) = scope.loops.last().expect("loop");
+        let depth = body.instructions[*start..]
+            .iter()
+            .map(|instruction| match instruction {
+                Instruction::Block(_) | Instruction::If(_) | Instruction::Loop(_) => 1,
+                Instruction::End => -1,
+                _ => 0,
+            })
+            .sum::<i32>();
+
+        for &local in locals.iter().rev() {
+            body.push(Instruction::LocalSet(local));
+        }
+
+        body.push(Instruction::Br(depth as u32));
+
+        Ok(())
+    }
+
+    // Compiles a condition and opens an `if` block.
+    fn compile_condition(
+        &mut self,
+        condition: &Expression,
+        body: &mut Body,
+        scope: &mut Scope,
+    ) -> Result<(), CompileError> {
+        self.compile_expression(condition, body, scope)?;
+        body.push(Instruction::I64Const(0));
+        body.push(Instruction::I64Ne);
+        body.push(Instruction::If(BlockType::Result(ValType::I64)));
+
+        Ok(())
+    }
+
+    // Compiles a left operand of `or` into a local and opens an `if` block
+    // taken if it is not `nil`.
+    fn compile_left(
+        &mut self,
+        left: &Expression,
+        body: &mut Body,
+        scope: &mut Scope,
+    ) -> Result<u32, CompileError> {
+        let local = body.local();
+
+        self.compile_expression(left, body, scope)?;
+        body.push(Instruction::LocalTee(local));
+        body.push(Instruction::I64Const(0));
+        body.push(Instruction::I64Ne);
+        body.push(Instruction::If(BlockType::Result(ValType::I64)));
+
+        Ok(local)
+    }
+
+    fn compile_call(
+        &mut self,
+        function: &Expression,
+        arguments: &[Expression],
+        body: &mut Body,
+        scope: &mut Scope,
+        tail: bool,
+    ) -> Result<(), CompileError> {
+        let arity = u8::try_from(arguments.len())?;
+
+        // A closure calling itself with a matching arity is called directly.
+        if let (Some((index, own_arity)), Expression::Variable(name) | Expression::Move(name)) =
+            (scope.function, strip_location(function))
+        {
+            if own_arity == arity && matches!(scope.resolve(*name), Variable::Local(CLOSURE_LOCAL))
+            {
+                body.push(Instruction::LocalGet(CLOSURE_LOCAL));
+
+                for argument in arguments {
+                    self.compile_expression(argument, body, scope)?;
+                }
+
+                body.push(if tail {
+                    Instruction::ReturnCall(index)
+                } else {
+                    Instruction::Call(index)
+                });
+
+                return Ok(());
+            }
+        }
+
+        let closure = body.local();
+        self.compile_expression(function, body, scope)?;
+        body.push(Instruction::LocalSet(closure));
+
+        let mut locals = Vec::with_capacity(arguments.len());
+
+        for argument in arguments {
+            let local = body.local();
+            self.compile_expression(argument, body, scope)?;
+            body.push(Instruction::LocalSet(local));
+            locals.push(local);
+        }
+
+        let arity_type =
+            self.intern_type(&vec![ValType::I64; arguments.len() + 1], &[ValType::I64]);
+        let adapter_type = self.adapter_type();
+        let table_index = body.local();
+        let arguments_pointer = body.local();
+        let call = |type_index| {
+            if tail {
+                Instruction::ReturnCallIndirect {
+                    type_index,
+                    table_index: 0,
+                }
+            } else {
+                Instruction::CallIndirect {
+                    type_index,
+                    table_index: 0,
+                }
+            }
+        };
+
+        body.extend([
+            Instruction::LocalGet(closure),
+            Instruction::I32Const(arity as i32),
+            Instruction::Call(Helper::Callee.index()),
+            Instruction::I64ExtendI32S,
+            Instruction::LocalTee(table_index),
+            Instruction::I64Const(-1),
+            Instruction::I64Eq,
+            // A value which is not a function results in `nil`.
+            Instruction::If(BlockType::Result(ValType::I64)),
+            Instruction::I64Const(0),
+            Instruction::Else,
+            Instruction::LocalGet(table_index),
+            Instruction::I64Const(1),
+            Instruction::I64And,
+            Instruction::I64Eqz,
+            Instruction::If(BlockType::Result(ValType::I64)),
+            Instruction::LocalGet(closure),
+        ]);
+        body.extend(locals.iter().map(|&local| Instruction::LocalGet(local)));
+        body.extend([
+            Instruction::LocalGet(table_index),
+            Instruction::I32WrapI64,
+            call(arity_type),
+            Instruction::Else,
+            Instruction::I32Const(arity as i32 * VALUE_SIZE as i32),
+            Instruction::Call(Helper::Allocate.index()),
+            Instruction::I64ExtendI32U,
+            Instruction::LocalSet(arguments_pointer),
+        ]);
+
+        for (index, &local) in locals.iter().enumerate() {
+            body.extend([
+                Instruction::LocalGet(arguments_pointer),
+                Instruction::I32WrapI64,
+                Instruction::LocalGet(local),
+                Instruction::I64Store(value_memory(index as u64 * VALUE_SIZE)),
+            ]);
+        }
+
+        body.extend([
+            Instruction::LocalGet(closure),
+            Instruction::LocalGet(arguments_pointer),
+            Instruction::I32WrapI64,
+            Instruction::I32Const(arity as i32),
+            Instruction::LocalGet(table_index),
+            Instruction::I32WrapI64,
+            call(adapter_type),
+            Instruction::End,
+            Instruction::End,
+        ]);
+
+        Ok(())
+    }
+
+    fn compile_primitive(
+        &mut self,
+        instruction: Primitive,
+        arguments: &[Expression],
+        body: &mut Body,
+        scope: &mut Scope,
+    ) -> Result<(), CompileError> {
+        let Some(helper) = Helper::primitive(instruction) else {
+            return Err(CompileError::UnsupportedPrimitive(
+                format!("{instruction:?}").to_lowercase(),
+            ));
+        };
+        let arity = helper.signature().0.len();
+
+        // Missing operands are `nil` while extra ones are evaluated and ignored.
+        for index in 0..arity.max(arguments.len()) {
+            match arguments.get(index) {
+                Some(argument) => self.compile_expression(argument, body, scope)?,
+                None => body.push(Instruction::I64Const(0)),
+            }
+
+            if index >= arity {
+                body.push(Instruction::Drop);
+            }
+        }
+
+        body.push(Instruction::Call(helper.index()));
+
+        Ok(())
+    }
+
+    fn compile_lambda(
+        &mut self,
+        lambda: &Lambda,
+        body: &mut Body,
+        scope: &mut Scope,
+    ) -> Result<(), CompileError> {
+        let (table_index, arity) = self.reserve_function(lambda)?;
+        let free_variables = self
+            .compile_function(
+                lambda,
+                Scope::new(
+                    lambda.name.filter(|_| lambda.recursive),
+                    &lambda.arguments,
+                    (function_base() + table_index, arity),
+                ),
+            )?
+            .free_variables;
+
+        self.compile_closure(table_index, arity, &free_variables, body, scope)
+    }
+
+    // Compiles mutually recursive functions followed by their closures.
+    //
+    // The functions share their environment to create each other.
+    fn compile_functions(
+        &mut self,
+        functions: &[Expression],
+        body: &mut Body,
+        scope: &mut Scope,
+    ) -> Result<(), CompileError> {
+        let lambdas = functions
+            .iter()
+            .map(|function| match function {
+                Expression::Lambda(lambda) => lambda,
+                function => unreachable!("unlowered function: {function:?}"),
+            })
+            .collect::<Vec<_>>();
+        let free_variables = collect_free_variables(&lambdas);
+        let siblings = lambdas
+            .iter()
+            .map(|lambda| {
+                let (table_index, arity) = self.reserve_function(lambda)?;
+
+                Ok((lambda.name, table_index, arity))
+            })
+            .collect::<Result<Vec<_>, CompileError>>()?;
+
+        for (lambda, &(name, table_index, arity)) in lambdas.iter().zip(&siblings) {
+            let mut function_scope = Scope::new(
+                name,
+                &lambda.arguments,
+                (function_base() + table_index, arity),
+            );
+            function_scope.free_variables = free_variables.clone();
+            function_scope.siblings = siblings.clone();
+
+            self.compile_function(lambda, function_scope)?;
+        }
+
+        for (name, table_index, arity) in siblings {
+            self.compile_closure(table_index, arity, &free_variables, body, scope)?;
+
+            let local = body.local();
+            body.push(Instruction::LocalSet(local));
+
+            if let Some(name) = name {
+                scope.variables.push((name, local));
+            }
+        }
+
+        Ok(())
+    }
+
+    // Reserves a function and its adapter and returns a table index and arity
+    // of the function.
+    fn reserve_function(&mut self, lambda: &Lambda) -> Result<(u32, u8), CompileError> {
+        let arity = u8::try_from(lambda.arguments.len())?;
+        let table_index = self.functions.len() as u32;
+        let function_type =
+            self.intern_type(&vec![ValType::I64; arity as usize + 1], &[ValType::I64]);
+        let adapter_type = self.adapter_type();
+
+        self.functions.push((function_type, Function::new([])));
+        self.functions
+            .push((adapter_type, adapter(function_base() + table_index, arity)));
+
+        Ok((table_index, arity))
+    }
+
+    // Compiles a body of a reserved function and returns its scope.
+    fn compile_function(
+        &mut self,
+        lambda: &Lambda,
+        mut scope: Scope,
+    ) -> Result<Scope, CompileError> {
+        let (index, arity) = scope.function.expect("function");
+        let mut body = Body::new(arity as u32 + 1);
+
+        for statement in &lambda.body {
+            self.compile_statement(statement, &mut body, &mut scope, false)?;
+        }
+
+        self.compile_tail(&lambda.result, &mut body, &mut scope)?;
+        self.functions[(index - function_base()) as usize].1 = body.function();
+
+        Ok(scope)
+    }
+
+    fn compile_closure(
+        &mut self,
+        table_index: u32,
+        arity: u8,
+        free_variables: &[Symbol],
+        body: &mut Body,
+        scope: &mut Scope,
+    ) -> Result<(), CompileError> {
+        let pointer = allocate_closure(body, table_index, arity, free_variables.len());
+
+        for (index, &name) in free_variables.iter().enumerate() {
+            body.push(Instruction::LocalGet(pointer));
+            body.push(Instruction::I32WrapI64);
+            self.compile_expression(&Expression::Variable(name), body, scope)?;
+            body.push(Instruction::I64Store(value_memory(
+                HEADER_SIZE + index as u64 * VALUE_SIZE,
+            )));
+        }
+
+        finish_closure(body, pointer);
+
+        Ok(())
+    }
+
+    fn adapter_type(&mut self) -> u32 {
+        self.intern_type(&[ValType::I64, ValType::I32, ValType::I32], &[ValType::I64])
+    }
+
+    fn intern_type(&mut self, parameters: &[ValType], results: &[ValType]) -> u32 {
+        intern_type(&mut self.types, parameters, results)
+    }
+}
+
+// Returns an index of a function type adding it if it does not exist.
+fn intern_type(
+    types: &mut Vec<(Vec<ValType>, Vec<ValType>)>,
+    parameters: &[ValType],
+    results: &[ValType],
+) -> u32 {
+    let index = types
+        .iter()
+        .position(|(other_parameters, other_results)| {
+            other_parameters == parameters && other_results == results
+        })
+        .unwrap_or_else(|| {
+            types.push((parameters.to_vec(), results.to_vec()));
+            types.len() - 1
+        });
+
+    index as u32
+}
+
+// Returns an index of a first function of closures.
+fn function_base() -> u32 {
+    IMPORT_COUNT + Helper::ALL.len() as u32
+}
+
+// Compiles an adapter of a function which reads arguments from memory.
+//
+// Missing arguments are `nil` while extra ones are ignored.
+fn adapter(index: u32, arity: u8) -> Function {
+    let mut function = Function::new([]);
+
+    function.instruction(&Instruction::LocalGet(CLOSURE_LOCAL));
+
+    for argument in 0..arity as u64 {
+        function
+            .instruction(&Instruction::LocalGet(2))
+            .instruction(&Instruction::I32Const(argument as i32))
+            .instruction(&Instruction::I32GtU)
+            .instruction(&Instruction::If(BlockType::Result(ValType::I64)))
+            .instruction(&Instruction::LocalGet(1))
+            .instruction(&Instruction::I64Load(value_memory(argument * VALUE_SIZE)))
+            .instruction(&Instruction::Else)
+            .instruction(&Instruction::I64Const(0))
+            .instruction(&Instruction::End);
+    }
+
+    function
+        .instruction(&Instruction::ReturnCall(index))
+        .instruction(&Instruction::End);
+
+    function
+}
+
+// Allocates a closure without its environment and returns a local of its
+// pointer.
+fn allocate_closure(body: &mut Body, table_index: u32, arity: u8, environment_size: usize) -> u32 {
+    let pointer = body.local();
+
+    body.extend([
+        Instruction::I32Const((HEADER_SIZE + environment_size as u64 * VALUE_SIZE) as i32),
+        Instruction::Call(Helper::Allocate.index()),
+        Instruction::I64ExtendI32U,
+        Instruction::LocalTee(pointer),
+        Instruction::I32WrapI64,
+        Instruction::I32Const(table_index as i32),
+        Instruction::I32Store(word_memory(0)),
+        Instruction::LocalGet(pointer),
+        Instruction::I32WrapI64,
+        Instruction::I32Const(arity as i32),
+        Instruction::I32Store(word_memory(4)),
+    ]);
+
+    pointer
+}
+
+// Pushes a closure value of a pointer.
+fn finish_closure(body: &mut Body, pointer: u32) {
+    body.extend([
+        Instruction::LocalGet(pointer),
+        Instruction::I64Const(CLOSURE_MASK),
+        Instruction::I64Or,
+    ]);
+}
+
+// Loads a value at an index in an environment of a closure being called.
+fn load_environment(body: &mut Body, index: usize) {
+    body.extend([
+        Instruction::LocalGet(CLOSURE_LOCAL),
+        Instruction::I32WrapI64,
+        Instruction::I64Load(value_memory(HEADER_SIZE + index as u64 * VALUE_SIZE)),
+    ]);
+}
+
+fn strip_location(expression: &Expression) -> &Expression {
+    match expression {
+        Expression::Located { expression, .. } => strip_location(expression),
+        _ => expression,
+    }
+}
+
+// Instructions and locals of a function being compiled.
+#[derive(Debug, Default)]
+struct Body {
+    instructions: Vec<Instruction<'static>>,
+    parameter_count: u32,
+    local_count: u32,
+}
+
+impl Body {
+    fn new(parameter_count: u32) -> Self {
+        Self {
+            instructions: vec![],
+            parameter_count,
+            local_count: 0,
+        }
+    }
+
+    // Allocates a new `i64` local.
+    fn local(&mut self) -> u32 {
+        self.local_count += 1;
+        self.parameter_count + self.local_count - 1
+    }
+
+    fn push(&mut self, instruction: Instruction<'static>) {
+        self.instructions.push(instruction);
+    }
+
+    fn extend(&mut self, instructions: impl IntoIterator<Item = Instruction<'static>>) {
+        self.instructions.extend(instructions);
+    }
+
+    fn function(&self) -> Function {
+        let mut function = Function::new([(self.local_count, ValType::I64)]);
+
+        for instruction in &self.instructions {
+            function.instruction(instruction);
+        }
+
+        function.instruction(&Instruction::End);
+
+        function
+    }
+}
+
+enum Variable {
+    Local(u32),
+    Environment(usize),
+    Sibling(usize),
+}
+
+// Variables of a function.
+#[derive(Debug, Default)]
+struct Scope {
+    variables: Vec<(Symbol, u32)>,
+    free_variables: Vec<Symbol>,
+    // An index and arity of a function itself.
+    function: Option<(u32, u8)>,
+    // Names, table indices and arities of functions in a group of mutually
+    // recursive ones.
+    siblings: Vec<(Option<Symbol>, u32, u8)>,
+    // Locals of variables of loops being compiled with indices of
+    // instructions starting their bodies.
+    loops: Vec<(Vec<u32>, usize)>,
+}
+
+impl Scope {
+    fn new(name: Option<Symbol>, arguments: &[Symbol], function: (u32, u8)) -> Self {
+        Self {
+            variables: name
+                .map(|name| (name, CLOSURE_LOCAL))
+                .into_iter()
+                .chain(
+                    arguments
+                        .iter()
+                        .enumerate()
+                        .map(|(index, &name)| (name, index as u32 + 1)),
+                )
+                .collect(),
+            free_variables: vec![],
+            function: Some(function),
+            siblings: vec![],
+            loops: vec![],
+        }
+    }
+
+    fn resolve(&mut self, name: Symbol) -> Variable {
+        if let Some(&(_, local)) = self
+            .variables
+            .iter()
+            .rev()
+            .find(|(other, _)| *other == name)
+        {
+            return Variable::Local(local);
+        } else if let Some(index) = self
+            .siblings
+            .iter()
+            .position(|&(other, _, _)| other == Some(name))
+        {
+            return Variable::Sibling(index);
+        }
+
+        let index = match self.free_variables.iter().position(|&other| other == name) {
+            Some(index) => index,
+            None => {
+                self.free_variables.push(name);
+                self.free_variables.len() - 1
+            }
+        };
+
+        Variable::Environment(index)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::{pin_mut, stream::iter};
+    use parse::{parse_concrete, SourceMap};
+    use pretty_assertions::assert_eq;
+    use std::io;
+    use wasmi::{Caller, Config, Engine, Linker, Store};
+
+    async fn compile_module(source: &str) -> Result<Vec<u8>, CompileError> {
+        let (values, _) = SourceMap::new(source, &parse_concrete(source).unwrap());
+        let mut compiler = WasmCompiler::new();
+
+        {
+            let values = iter(values).map(Ok);
+
+            pin_mut!(values);
+
+            let results = compiler.compile::<io::Error>(&mut values);
+
+            pin_mut!(results);
+
+            while let Some(result) = results.next().await {
+                result?;
+            }
+        }
+
+        Ok(compiler.module())
+    }
+
+    // Decodes a value in a memory into its string representation.
+    fn decode(memory: &[u8], value: i64) -> String {
+        let read = |address: usize, size: usize| {
+            let mut bytes = [0; 8];
+            bytes[..size].copy_from_slice(&memory[address..address + size]);
+            u64::from_le_bytes(bytes)
+        };
+        let value = value as u64;
+        let pointer = value as u32 as usize;
+
+        match (value >> 50 & 0x1fff == 0x1fff).then_some(value >> 47 & 0b111) {
+            _ if value == 0 => "()".into(),
+            Some(1) => (value as u32 as i32).to_string(),
+            Some(2) => format!("<closure {:x} {}>", read(pointer, 4), read(pointer + 4, 4)),
+            Some(4) => format!(
+                "({})",
+                (0..read(pointer, 4) as usize)
+                    .map(|index| decode(memory, read(pointer + 8 + 8 * index, 8) as i64))
+                    .collect::<Vec<_>>()
+                    .join(" ")
+            ),
+            Some(5) => "<ref>".into(),
+            _ => Value::from(f64::from_bits(value)).to_string(),
+        }
+    }
+
+    async fn run(source: &str) -> Vec<String> {
+        let module = compile_module(source).await.unwrap();
+
+        wasmparser::validate(&module).unwrap();
+
+        let mut config = Config::default();
+        config.wasm_tail_call(true);
+        let engine = Engine::new(&config);
+        let mut store = Store::new(&engine, vec![]);
+        let mut linker = Linker::new(&engine);
+
+        linker
+            .func_wrap(
+                "arachne",
+                "dump",
+                |mut caller: Caller<'_, Vec<i64>>, value: i64| caller.data_mut().push(value),
+            )
+            .unwrap();
+
+        let instance = linker
+            .instantiate(&mut store, &wasmi::Module::new(&engine, &module).unwrap())
+            .unwrap()
+            .start(&mut store)
+            .unwrap();
+
+        instance
+            .get_typed_func::<(), ()>(&store, "main")
+            .unwrap()
+            .call(&mut store, ())
+            .unwrap();
+
+        let memory = instance.get_memory(&store, "memory").unwrap();
+
+        store
+            .data()
+            .iter()
+            .map(|&value| decode(memory.data(&store), value))
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn run_nothing() {
+        assert_eq!(run("").await, Vec::<String>::new());
+    }
+
+    #[tokio::test]
+    async fn run_arithmetic() {
+        assert_eq!(
+            run("(+ 1 2)\n(- 1 2)\n(* 2 3)\n(/ 1 2)\n(+ 1 (set () 0 1))").await,
+            ["3", "-1", "6", "0.5", "()"]
+        );
+    }
+
+    #[tokio::test]
+    async fn run_let() {
+        assert_eq!(
+            run("(let x 1)\n(let y 2)\n(+ x y)\n(let x 3)\nx").await,
+            ["3", "3"]
+        );
+    }
+
+    #[tokio::test]
+    async fn run_if() {
+        assert_eq!(
+            run("(if 1 2 3)\n(if () 2 3)\n(if () 1 () 2 3)\n(if () 1)").await,
+            ["2", "3", "3", "()"]
+        );
+    }
+
+    #[tokio::test]
+    async fn run_logical_operations() {
+        assert_eq!(
+            run("(not ())\n(not 0)\n(not 1)\n(and 1 2)\n(and () 2)\n(or 1 2)\n(or () 2)").await,
+            ["1", "1", "()", "2", "()", "1", "2"]
+        );
+    }
+
+    #[tokio::test]
+    async fn run_comparisons() {
+        assert_eq!(
+            run("(= 1 1)\n(!= 1 2)\n(< 1 2)\n(<= 2 1)\n(> 2 1)\n(>= 1 1)\n(< 1 (set () 0 1))")
+                .await,
+            ["1", "1", "1", "()", "1", "1", "()"]
+        );
+    }
+
+    #[tokio::test]
+    async fn run_array_comparisons() {
+        assert_eq!(
+            run("
+                (let x (set () 0 1))
+                (= x (set () 0 1))
+                (= x (set () 0 2))
+                (= (set x 2 ()) x)
+                (< x (set () 0 2))
+                (< x (set x 1 1))
+                (> x ())
+                (= (fn () 1) (fn () 1))
+                ")
+            .await,
+            ["1", "()", "()", "1", "1", "1", "()"]
+        );
+    }
+
+    #[tokio::test]
+    async fn run_arrays() {
+        assert_eq!(
+            run("
+                (let x (set () 0 1))
+                (let y (set x 2 3))
+                x
+                y
+                (len y)
+                (len 1)
+                (get y 2)
+                (get y 3)
+                (get y -1)
+                (get 1 0)
+                (set y -1 0)
+                (set 1 0 0)
+                (set (set () 0 (set () 0 1)) 1 2)
+                ")
+            .await,
+            ["(1)", "(1 () 3)", "3", "()", "3", "()", "()", "()", "(1 () 3)", "()", "((1) 2)"]
+        );
+    }
+
+    #[tokio::test]
+    async fn run_closures() {
+        assert_eq!(
+            run("
+                (let x 1)
+                (let f (fn (y) (+ x y)))
+                (f 2)
+                (let g (fn (y) (fn (z) (+ x y z))))
+                ((g 2) 3)
+                ((fn () x))
+                ")
+            .await,
+            ["3", "6", "1"]
+        );
+    }
+
+    #[tokio::test]
+    async fn run_recursion() {
+        assert_eq!(
+            run("
+                (let-rec fibonacci
+                  (fn (x)
+                    (if (< x 2)
+                      x
+                      (+ (fibonacci (- x 1)) (fibonacci (- x 2))))))
+                (fibonacci 20)
+                ")
+            .await,
+            ["6765"]
+        );
+    }
+
+    #[tokio::test]
+    async fn run_tail_calls() {
+        assert_eq!(
+            run("
+                (let-rec sum (fn (x y) (if (= x 0) y (sum (- x 1) (+ x y)))))
+                (sum 100000 0)
+                (let f (fn (g x) (if (= x 0) 42 (g g (- x 1)))))
+                (f f 100000)
+                ")
+            .await,
+            ["5000050000", "42"]
+        );
+    }
+
+    #[tokio::test]
+    async fn run_mutual_recursion() {
+        assert_eq!(
+            run("
+                (let zero 0)
+                (let-rec
+                  even (fn (x) (if (= x zero) 1 (odd (- x 1))))
+                  odd (fn (x) (if (= x zero) 0 (even (- x 1)))))
+                (even 100000)
+                (odd 100001)
+                ")
+            .await,
+            ["1", "1"]
+        );
+    }
+
+    #[tokio::test]
+    async fn run_logical_operators() {
+        assert_eq!(
+            run("
+                (let xs (set () 0 42))
+                (and xs (get xs 0))
+                (and () (get () 0))
+                (or () 1 2)
+                (let-rec f (fn (x) (or (= x 0) (f (- x 1)))))
+                (f 100000)
+                ")
+            .await,
+            ["42", "()", "1", "1"]
+        );
+    }
+
+    #[tokio::test]
+    async fn run_variadic_primitives() {
+        assert_eq!(
+            run("(+ 1 2 3)\n(- 10 1 2)\n(< 1 2 3)\n(< 1 3 2)\n(let f (fn () 2))\n(<= 1 (f) 2)")
+                .await,
+            ["6", "7", "1", "()", "1"]
+        );
+    }
+
+    #[tokio::test]
+    async fn run_sequences() {
+        assert_eq!(
+            run("
+                (let x 1)
+                (+ x (let ((x 2) (y (+ x 1))) (* x y)))
+                (do (let z 3) (+ x z))
+                (let-rec f (fn (x) (if (= x 0) 42 (let ((y (- x 1))) (f y)))))
+                (f 100000)
+                ")
+            .await,
+            ["7", "4", "42"]
+        );
+    }
+
+    #[tokio::test]
+    async fn run_loops() {
+        assert_eq!(
+            run("
+                (loop (i 0 x 0) (if (< i 10) (recur (+ i 1) (+ x i)) x))
+                (let-rec f (fn (n) (loop (i n x 1) (let ((y (* x 2))) (if (> i 1) (recur (- i 1) y) y)))))
+                (+ 1 (f 10))
+                (loop (i 0 j 1) (if (< i 3) (recur (+ j 1) i) j))
+                (while () 1)
+                ")
+            .await,
+            ["45", "1025", "1", "()"]
+        );
+    }
+
+    #[tokio::test]
+    async fn run_references() {
+        assert_eq!(
+            run("
+                (let x (ref 1))
+                (let f (fn () (set! x (+ (deref x) 1))))
+                (f)
+                (f)
+                (deref x)
+                x
+                (= x x)
+                (= x (ref 3))
+                (deref 3)
+                (set! 3 1)
+                ")
+            .await,
+            ["2", "3", "3", "<ref>", "1", "()", "()", "()"]
+        );
+    }
+
+    #[tokio::test]
+    async fn run_match() {
+        assert_eq!(
+            run("
+                (let xs (set (set (set () 0 1) 1 2) 2 3))
+                (match xs ((x y) y) ((x 2 & ys) (+ x (get ys 0))) (_ 0))
+                (match xs ((x & ys) when (> x 1) x) ((x & ys) ys))
+                (match 5 (x when (< x 0) 0) (1 1) (_ 2))
+                ")
+            .await,
+            ["4", "(2 3)", "2"]
+        );
+    }
+
+    #[tokio::test]
+    async fn run_calls_with_different_arities() {
+        assert_eq!(
+            run(
+                "(let f (fn (x y) y))\n(f 1)\n(f 1 2 3)\n(let-rec g (fn (x) (if x (g) 42)))\n(g 1)"
+            )
+            .await,
+            ["()", "2", "42"]
+        );
+    }
+
+    #[tokio::test]
+    async fn run_non_function_call() {
+        assert_eq!(run("(1 2)\n(() 2)").await, ["()", "()"]);
+    }
+
+    #[tokio::test]
+    async fn run_closure_value() {
+        assert_eq!(run("(fn (x) x)").await, ["<closure 0 1>"]);
+    }
+
+    #[tokio::test]
+    async fn compile_unsupported_primitive() {
+        assert!(matches!(
+            compile_module("(display 42)").await,
+            Err(CompileError::UnsupportedPrimitive(name)) if name == "display"
+        ));
+    }
+
+    #[tokio::test]
+    async fn compile_undefined_variable() {
+        assert!(matches!(
+            compile_module("(let x y)").await,
+            Err(CompileError::VariableNotDefined(name)) if name == "y"
+        ));
+    }
+
+    #[tokio::test]
+    async fn compile_undefined_free_variable() {
+        assert!(matches!(
+            compile_module("(fn () x)").await,
+            Err(CompileError::VariableNotDefined(name)) if name == "x"
+        ));
+    }
+}
@@ -0,0 +1,893 @@
+//! Runtime functions generated into WebAssembly modules.
+//!
+//! Values are NaN-boxed `i64`s in the same representation as the runtime.
+//! Arrays and closures live in a linear memory allocated by bumping a heap
+//! pointer and are never freed. An array is its length followed by its
+//! elements. A closure is its table index, its arity and its environment. A
+//! reference is its value.
+
+use wasm_encoder::{BlockType, Function, Instruction, MemArg, ValType};
+
+const EXPONENT_MASK: i64 = 0x7ffc << 48;
+const PAYLOAD_MASK: i64 = !(0xfffc << 48);
+const TYPE_MASK_OFFSET: i64 = 47;
+
+const FLOAT64_TYPE: i32 = 0;
+const INTEGER32_TYPE: i32 = 1;
+const CLOSURE_TYPE: i32 = 2;
+const ARRAY_TYPE: i32 = 4;
+const REFERENCE_TYPE: i32 = 5;
+
+pub const INTEGER32_MASK: i64 = EXPONENT_MASK | (INTEGER32_TYPE as i64) << TYPE_MASK_OFFSET;
+pub const CLOSURE_MASK: i64 = EXPONENT_MASK | (CLOSURE_TYPE as i64) << TYPE_MASK_OFFSET;
+const ARRAY_MASK: i64 = EXPONENT_MASK | (ARRAY_TYPE as i64) << TYPE_MASK_OFFSET;
+const REFERENCE_MASK: i64 = EXPONENT_MASK | (REFERENCE_TYPE as i64) << TYPE_MASK_OFFSET;
+
+const TRUE: i64 = 0x3ff0 << 48;
+const PAGE_SIZE_OFFSET: i64 = 16;
+const MAXIMUM_ARRAY_LENGTH: i64 = 1 << 28;
+
+/// A size of a header of arrays and closures in bytes.
+pub const HEADER_SIZE: u64 = 8;
+/// A size of values in bytes.
+pub const VALUE_SIZE: u64 = 8;
+
+/// A number of imported functions.
+pub const IMPORT_COUNT: u32 = 1;
+/// An index of a function dumping a value to a host.
+pub const DUMP_FUNCTION: u32 = 0;
+/// An index of a global of a heap pointer.
+pub const HEAP_GLOBAL: u32 = 0;
+
+/// A runtime function.
+///
+/// Runtime functions follow imported ones in a function index space in an
+/// order of their declarations.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Helper {
+    /// Allocates memory of a size and returns its pointer.
+    Allocate,
+    /// Returns a type of a value.
+    Type,
+    /// Returns `1` if a value is `nil` or of a type.
+    Is,
+    ArrayLength,
+    Element,
+    /// Returns `1` if two values are equal.
+    IsEqual,
+    /// Returns `-1`, `0` or `1` on ordered values and `2` otherwise.
+    Order,
+    /// Returns an index of a function in a table for a callee and its arity, or
+    /// `-1` if the callee is not a closure.
+    ///
+    /// Functions at even indices take arguments as they are. Adapters at odd
+    /// ones take a pointer to arguments and their count.
+    Callee,
+    Add,
+    Subtract,
+    Multiply,
+    Divide,
+    Equal,
+    NotEqual,
+    LessThan,
+    LessThanOrEqual,
+    GreaterThan,
+    GreaterThanOrEqual,
+    Not,
+    And,
+    Or,
+    Get,
+    Set,
+    Length,
+    Ref,
+    Deref,
+    SetRef,
+}
+
+impl Helper {
+    pub const ALL: &[Self] = &[
+        Self::Allocate,
+        Self::Type,
+        Self::Is,
+        Self::ArrayLength,
+        Self::Element,
+        Self::IsEqual,
+        Self::Order,
+        Self::Callee,
+        Self::Add,
+        Self::Subtract,
+        Self::Multiply,
+        Self::Divide,
+        Self::Equal,
+        Self::NotEqual,
+        Self::LessThan,
+        Self::LessThanOrEqual,
+        Self::GreaterThan,
+        Self::GreaterThanOrEqual,
+        Self::Not,
+        Self::And,
+        Self::Or,
+        Self::Get,
+        Self::Set,
+        Self::Length,
+        Self::Ref,
+        Self::Deref,
+        Self::SetRef,
+    ];
+
+    /// Returns a runtime function of a primitive.
+    pub fn primitive(instruction: vm::Instruction) -> Option<Self> {
+        Some(match instruction {
+            vm::Instruction::Add => Self::Add,
+            vm::Instruction::And => Self::And,
+            vm::Instruction::Deref => Self::Deref,
+            vm::Instruction::Divide => Self::Divide,
+            vm::Instruction::Equal => Self::Equal,
+            vm::Instruction::Get => Self::Get,
+            vm::Instruction::GreaterThan => Self::GreaterThan,
+            vm::Instruction::GreaterThanOrEqual => Self::GreaterThanOrEqual,
+            vm::Instruction::Length => Self::Length,
+            vm::Instruction::LessThan => Self::LessThan,
+            vm::Instruction::LessThanOrEqual => Self::LessThanOrEqual,
+            vm::Instruction::Multiply => Self::Multiply,
+            vm::Instruction::Not => Self::Not,
+            vm::Instruction::NotEqual => Self::NotEqual,
+            vm::Instruction::Or => Self::Or,
+            vm::Instruction::Ref => Self::Ref,
+            vm::Instruction::Set => Self::Set,
+            vm::Instruction::SetRef => Self::SetRef,
+            vm::Instruction::Subtract => Self::Subtract,
+            _ => return None,
+        })
+    }
+
+    /// Returns an index in a function index space.
+    pub fn index(self) -> u32 {
+        IMPORT_COUNT + self as u32
+    }
+
+    pub fn signature(self) -> (&'static [ValType], &'static [ValType]) {
+        use ValType::{I32, I64};
+
+        match self {
+            Self::Allocate => (&[I32], &[I32]),
+            Self::Type | Self::ArrayLength => (&[I64], &[I32]),
+            Self::Is | Self::Callee => (&[I64, I32], &[I32]),
+            Self::Element => (&[I64, I32], &[I64]),
+            Self::IsEqual | Self::Order => (&[I64, I64], &[I32]),
+            Self::Not | Self::Length | Self::Ref | Self::Deref => (&[I64], &[I64]),
+            Self::Set => (&[I64, I64, I64], &[I64]),
+            Self::Add
+            | Self::Subtract
+            | Self::Multiply
+            | Self::Divide
+            | Self::Equal
+            | Self::NotEqual
+            | Self::LessThan
+            | Self::LessThanOrEqual
+            | Self::GreaterThan
+            | Self::GreaterThanOrEqual
+            | Self::And
+            | Self::Or
+            | Self::Get
+            | Self::SetRef => (&[I64, I64], &[I64]),
+        }
+    }
+
+    pub fn function(self) -> Function {
+        use ValType::{I32, I64};
+
+        let (locals, instructions) = match self {
+            Self::Allocate => (vec![I64], allocate()),
+            Self::Type => (vec![I32], r#type()),
+            Self::Is => (vec![], is()),
+            Self::ArrayLength => (vec![], array_length()),
+            Self::Element => (vec![], element()),
+            Self::IsEqual => (vec![I32, I32, I32], is_equal()),
+            Self::Order => (vec![I32, I32, I32], order()),
+            Self::Callee => (vec![I32], callee()),
+            Self::Add => (vec![], arithmetic(Instruction::F64Add)),
+            Self::Subtract => (vec![], arithmetic(Instruction::F64Sub)),
+            Self::Multiply => (vec![], arithmetic(Instruction::F64Mul)),
+            Self::Divide => (vec![], arithmetic(Instruction::F64Div)),
+            Self::Equal => (vec![], equal(false)),
+            Self::NotEqual => (vec![], equal(true)),
+            Self::LessThan => (vec![], comparison(-1, 1)),
+            Self::LessThanOrEqual => (vec![], comparison(-1, 2)),
+            Self::GreaterThan => (vec![], comparison(1, 1)),
+            Self::GreaterThanOrEqual => (vec![], comparison(0, 2)),
+            Self::Not => (vec![], not()),
+            Self::And => (vec![], select_nil(false)),
+            Self::Or => (vec![], select_nil(true)),
+            Self::Get => (vec![I64], get()),
+            Self::Set => (vec![I64, I32, I32, I32], set()),
+            Self::Length => (vec![], length()),
+            Self::Ref => (vec![I32], r#ref()),
+            Self::Deref => (vec![], deref()),
+            Self::SetRef => (vec![], set_ref()),
+        };
+        let mut function = Function::new_with_locals_types(locals);
+
+        for instruction in instructions {
+            function.instruction(&instruction);
+        }
+
+        function.instruction(&Instruction::End);
+
+        function
+    }
+}
+
+/// Returns a memory argument at an offset for 8-byte values.
+pub fn value_memory(offset: u64) -> MemArg {
+    MemArg {
+        offset,
+        align: 3,
+        memory_index: 0,
+    }
+}
+
+/// Returns a memory argument at an offset for 4-byte values.
+pub fn word_memory(offset: u64) -> MemArg {
+    MemArg {
+        offset,
+        align: 2,
+        memory_index: 0,
+    }
+}
+
+fn call(helper: Helper) -> Instruction<'static> {
+    Instruction::Call(helper.index())
+}
+
+fn allocate() -> Vec<Instruction<'static>> {
+    use Instruction::*;
+
+    vec![
+        GlobalGet(HEAP_GLOBAL),
+        I64ExtendI32U,
+        LocalGet(0),
+        I64ExtendI32U,
+        I64Add,
+        LocalTee(1),
+        MemorySize(0),
+        I64ExtendI32U,
+        I64Const(PAGE_SIZE_OFFSET),
+        I64Shl,
+        I64GtU,
+        If(BlockType::Empty),
+        LocalGet(1),
+        I64Const((1 << PAGE_SIZE_OFFSET) - 1),
+        I64Add,
+        I64Const(PAGE_SIZE_OFFSET),
+        I64ShrU,
+        I32WrapI64,
+        MemorySize(0),
+        I32Sub,
+        MemoryGrow(0),
+        I32Const(-1),
+        I32Eq,
+        If(BlockType::Empty),
+        Unreachable,
+        End,
+        End,
+        GlobalGet(HEAP_GLOBAL),
+        LocalGet(1),
+        I32WrapI64,
+        GlobalSet(HEAP_GLOBAL),
+    ]
+}
+
+fn r#type() -> Vec<Instruction<'static>> {
+    use Instruction::*;
+
+    vec![
+        LocalGet(0),
+        I64Const(EXPONENT_MASK),
+        I64And,
+        I64Const(EXPONENT_MASK),
+        I64Ne,
+        If(BlockType::Result(ValType::I32)),
+        I32Const(FLOAT64_TYPE),
+        Else,
+        LocalGet(0),
+        I64Const(PAYLOAD_MASK),
+        I64And,
+        I64Const(TYPE_MASK_OFFSET),
+        I64ShrU,
+        I32WrapI64,
+        LocalTee(1),
+        I32Const(FLOAT64_TYPE),
+        // Unknown types are floating-point numbers.
+        LocalGet(1),
+        I32Const(1),
+        I32Sub,
+        I32Const(REFERENCE_TYPE),
+        I32LtU,
+        Select,
+        End,
+    ]
+}
+
+fn is() -> Vec<Instruction<'static>> {
+    use Instruction::*;
+
+    vec![
+        LocalGet(0),
+        I64Eqz,
+        LocalGet(0),
+        call(Helper::Type),
+        LocalGet(1),
+        I32Eq,
+        I32Or,
+    ]
+}
+
+fn array_length() -> Vec<Instruction<'static>> {
+    use Instruction::*;
+
+    vec![
+        LocalGet(0),
+        I64Eqz,
+        If(BlockType::Result(ValType::I32)),
+        I32Const(0),
+        Else,
+        LocalGet(0),
+        I32WrapI64,
+        I32Load(word_memory(0)),
+        End,
+    ]
+}
+
+fn element() -> Vec<Instruction<'static>> {
+    use Instruction::*;
+
+    vec![
+        LocalGet(1),
+        LocalGet(0),
+        call(Helper::ArrayLength),
+        I32LtU,
+        If(BlockType::Result(ValType::I64)),
+        LocalGet(0),
+        I32WrapI64,
+        LocalGet(1),
+        I32Const(3),
+        I32Shl,
+        I32Add,
+        I64Load(value_memory(HEADER_SIZE)),
+        Else,
+        I64Const(0),
+        End,
+    ]
+}
+
+// Returns early with a constant if a condition is true.
+fn return_if(value: i32) -> [Instruction<'static>; 4] {
+    use Instruction::*;
+
+    [If(BlockType::Empty), I32Const(value), Return, End]
+}
+
+fn is_equal() -> Vec<Instruction<'static>> {
+    use Instruction::*;
+
+    let mut instructions = vec![
+        LocalGet(0),
+        I64Eqz,
+        If(BlockType::Empty),
+        LocalGet(1),
+        I64Eqz,
+        Return,
+        End,
+        LocalGet(0),
+        call(Helper::Type),
+        LocalSet(2),
+        // Floating-point numbers
+        LocalGet(2),
+        I32Const(FLOAT64_TYPE),
+        I32Eq,
+        If(BlockType::Empty),
+        LocalGet(1),
+        I32Const(FLOAT64_TYPE),
+        call(Helper::Is),
+        If(BlockType::Result(ValType::I32)),
+        LocalGet(0),
+        F64ReinterpretI64,
+        LocalGet(1),
+        F64ReinterpretI64,
+        F64Eq,
+        Else,
+        I32Const(0),
+        End,
+        Return,
+        End,
+        // Integers
+        LocalGet(2),
+        I32Const(INTEGER32_TYPE),
+        I32Eq,
+        If(BlockType::Empty),
+        LocalGet(1),
+        I32Const(INTEGER32_TYPE),
+        call(Helper::Is),
+        If(BlockType::Result(ValType::I32)),
+        LocalGet(0),
+        I32WrapI64,
+        LocalGet(1),
+        I32WrapI64,
+        I32Eq,
+        Else,
+        I32Const(0),
+        End,
+        Return,
+        End,
+        // References are equal only to themselves.
+        LocalGet(2),
+        I32Const(REFERENCE_TYPE),
+        I32Eq,
+        If(BlockType::Empty),
+        LocalGet(0),
+        LocalGet(1),
+        I64Eq,
+        Return,
+        End,
+        // Closures are never equal.
+        LocalGet(2),
+        I32Const(ARRAY_TYPE),
+        I32Ne,
+    ];
+    instructions.extend(return_if(0));
+    instructions.extend([LocalGet(1), I32Const(ARRAY_TYPE), call(Helper::Is), I32Eqz]);
+    instructions.extend(return_if(0));
+    instructions.extend([
+        LocalGet(0),
+        call(Helper::ArrayLength),
+        LocalTee(4),
+        LocalGet(1),
+        call(Helper::ArrayLength),
+        I32Ne,
+    ]);
+    instructions.extend(return_if(0));
+    instructions.extend([
+        Block(BlockType::Empty),
+        Loop(BlockType::Empty),
+        LocalGet(3),
+        LocalGet(4),
+        I32GeU,
+        BrIf(1),
+        LocalGet(0),
+        LocalGet(3),
+        call(Helper::Element),
+        LocalGet(1),
+        LocalGet(3),
+        call(Helper::Element),
+        call(Helper::IsEqual),
+        I32Eqz,
+    ]);
+    instructions.extend(return_if(0));
+    instructions.extend([
+        LocalGet(3),
+        I32Const(1),
+        I32Add,
+        LocalSet(3),
+        Br(0),
+        End,
+        End,
+        I32Const(1),
+    ]);
+
+    instructions
+}
+
+fn order() -> Vec<Instruction<'static>> {
+    use Instruction::*;
+
+    // Compares two values in a given type after checking the type of the
+    // second one.
+    let compare = |r#type, convert: Instruction<'static>, less, greater, equal| {
+        let mut instructions = vec![LocalGet(1), I32Const(r#type), call(Helper::Is), I32Eqz];
+
+        instructions.extend(return_if(2));
+
+        for (instruction, value) in [(less, -1), (greater, 1), (equal, 0)] {
+            instructions.extend([
+                LocalGet(0),
+                convert.clone(),
+                LocalGet(1),
+                convert.clone(),
+                instruction,
+            ]);
+            instructions.extend(return_if(value));
+        }
+
+        instructions.extend([I32Const(2), Return]);
+        instructions
+    };
+
+    let mut instructions = vec![
+        LocalGet(0),
+        I64Eqz,
+        If(BlockType::Empty),
+        LocalGet(1),
+        I64Eqz,
+    ];
+    instructions.extend(return_if(0));
+    // Reverse an order of `nil` and a non-`nil` value.
+    instructions.extend([
+        LocalGet(1),
+        LocalGet(0),
+        call(Helper::Order),
+        LocalTee(2),
+        I32Const(0),
+        LocalGet(2),
+        I32Sub,
+        LocalGet(2),
+        I32Const(2),
+        I32Eq,
+        Select,
+        Return,
+        End,
+        LocalGet(0),
+        call(Helper::Type),
+        LocalSet(2),
+        LocalGet(2),
+        I32Const(FLOAT64_TYPE),
+        I32Eq,
+        If(BlockType::Empty),
+    ]);
+    instructions.extend(compare(
+        FLOAT64_TYPE,
+        F64ReinterpretI64,
+        F64Lt,
+        F64Gt,
+        F64Eq,
+    ));
+    instructions.extend([
+        End,
+        LocalGet(2),
+        I32Const(INTEGER32_TYPE),
+        I32Eq,
+        If(BlockType::Empty),
+    ]);
+    instructions.extend(compare(INTEGER32_TYPE, I32WrapI64, I32LtS, I32GtS, I32Eq));
+    instructions.extend([End, LocalGet(2), I32Const(ARRAY_TYPE), I32Ne]);
+    instructions.extend(return_if(2));
+    instructions.extend([LocalGet(1), I32Const(ARRAY_TYPE), call(Helper::Is), I32Eqz]);
+    instructions.extend(return_if(2));
+    // Compare arrays lexicographically with missing elements as `nil`.
+    instructions.extend([
+        LocalGet(0),
+        call(Helper::ArrayLength),
+        LocalTee(4),
+        LocalGet(1),
+        call(Helper::ArrayLength),
+        LocalTee(3),
+        LocalGet(4),
+        LocalGet(3),
+        I32GtU,
+        Select,
+        LocalSet(4),
+        I32Const(0),
+        LocalSet(3),
+        Block(BlockType::Empty),
+        Loop(BlockType::Empty),
+        LocalGet(3),
+        LocalGet(4),
+        I32GeU,
+        BrIf(1),
+        LocalGet(0),
+        LocalGet(3),
+        call(Helper::Element),
+        LocalGet(1),
+        LocalGet(3),
+        call(Helper::Element),
+        call(Helper::Order),
+        LocalTee(2),
+        If(BlockType::Empty),
+        LocalGet(2),
+        Return,
+        End,
+        LocalGet(3),
+        I32Const(1),
+        I32Add,
+        LocalSet(3),
+        Br(0),
+        End,
+        End,
+        I32Const(0),
+    ]);
+
+    instructions
+}
+
+fn callee() -> Vec<Instruction<'static>> {
+    use Instruction::*;
+
+    let mut instructions = vec![
+        LocalGet(0),
+        I64Eqz,
+        LocalGet(0),
+        call(Helper::Type),
+        I32Const(CLOSURE_TYPE),
+        I32Ne,
+        I32Or,
+    ];
+    instructions.extend(return_if(-1));
+    instructions.extend([
+        LocalGet(0),
+        I32WrapI64,
+        LocalTee(2),
+        I32Load(word_memory(0)),
+        LocalGet(2),
+        I32Load(word_memory(4)),
+        LocalGet(1),
+        I32Ne,
+        I32Add,
+    ]);
+
+    instructions
+}
+
+fn arithmetic(operator: Instruction<'static>) -> Vec<Instruction<'static>> {
+    use Instruction::*;
+
+    vec![
+        LocalGet(0),
+        I32Const(FLOAT64_TYPE),
+        call(Helper::Is),
+        LocalGet(1),
+        I32Const(FLOAT64_TYPE),
+        call(Helper::Is),
+        I32And,
+        If(BlockType::Result(ValType::I64)),
+        LocalGet(0),
+        F64ReinterpretI64,
+        LocalGet(1),
+        F64ReinterpretI64,
+        operator,
+        I64ReinterpretF64,
+        Else,
+        I64Const(0),
+        End,
+    ]
+}
+
+fn equal(negated: bool) -> Vec<Instruction<'static>> {
+    use Instruction::*;
+
+    let mut instructions = vec![I64Const(TRUE), I64Const(0), LocalGet(0), LocalGet(1)];
+    instructions.push(call(Helper::IsEqual));
+
+    if negated {
+        instructions.push(I32Eqz);
+    }
+
+    instructions.push(Select);
+    instructions
+}
+
+// Returns `true` if an order minus a minimum is less than a range.
+fn comparison(minimum: i32, range: i32) -> Vec<Instruction<'static>> {
+    use Instruction::*;
+
+    vec![
+        I64Const(TRUE),
+        I64Const(0),
+        LocalGet(0),
+        LocalGet(1),
+        call(Helper::Order),
+        I32Const(minimum),
+        I32Sub,
+        I32Const(range),
+        I32LtU,
+        Select,
+    ]
+}
+
+fn not() -> Vec<Instruction<'static>> {
+    use Instruction::*;
+
+    vec![I64Const(TRUE), I64Const(0), LocalGet(0), I64Eqz, Select]
+}
+
+// Selects a left-hand side if it is `nil` for `and` or if it is not for `or`.
+fn select_nil(or: bool) -> Vec<Instruction<'static>> {
+    use Instruction::*;
+
+    let (nil, other) = if or { (1, 0) } else { (0, 1) };
+
+    vec![LocalGet(nil), LocalGet(other), LocalGet(0), I64Eqz, Select]
+}
+
+// Checks an array and an index, and pushes the index as an integer.
+fn array_index(invalid_index: Instruction<'static>) -> Vec<Instruction<'static>> {
+    use Instruction::*;
+
+    vec![
+        LocalGet(0),
+        I32Const(ARRAY_TYPE),
+        call(Helper::Is),
+        I32Eqz,
+        If(BlockType::Empty),
+        I64Const(0),
+        Return,
+        End,
+        LocalGet(1),
+        I32Const(FLOAT64_TYPE),
+        call(Helper::Is),
+        I32Eqz,
+        If(BlockType::Empty),
+        I64Const(0),
+        Return,
+        End,
+        LocalGet(1),
+        F64ReinterpretI64,
+        F64Const(0.0),
+        F64Lt,
+        If(BlockType::Empty),
+        invalid_index,
+        Return,
+        End,
+        LocalGet(1),
+        F64ReinterpretI64,
+        I64TruncSatF64U,
+    ]
+}
+
+fn get() -> Vec<Instruction<'static>> {
+    use Instruction::*;
+
+    let mut instructions = array_index(I64Const(0));
+    instructions.extend([
+        LocalTee(2),
+        LocalGet(0),
+        call(Helper::ArrayLength),
+        I64ExtendI32U,
+        I64GeU,
+        If(BlockType::Empty),
+        I64Const(0),
+        Return,
+        End,
+        LocalGet(0),
+        LocalGet(2),
+        I32WrapI64,
+        call(Helper::Element),
+    ]);
+
+    instructions
+}
+
+fn set() -> Vec<Instruction<'static>> {
+    use Instruction::*;
+
+    // An array is copied on every update.
+    let mut instructions = array_index(LocalGet(0));
+    instructions.extend([
+        LocalTee(3),
+        I64Const(MAXIMUM_ARRAY_LENGTH),
+        I64GeU,
+        If(BlockType::Empty),
+        Unreachable,
+        End,
+        LocalGet(3),
+        I32WrapI64,
+        I32Const(1),
+        I32Add,
+        LocalTee(4),
+        LocalGet(0),
+        call(Helper::ArrayLength),
+        LocalTee(6),
+        LocalGet(4),
+        LocalGet(6),
+        I32GtU,
+        Select,
+        LocalTee(4),
+        I32Const(3),
+        I32Shl,
+        I32Const(HEADER_SIZE as i32),
+        I32Add,
+        call(Helper::Allocate),
+        LocalTee(5),
+        LocalGet(4),
+        I32Store(word_memory(0)),
+        LocalGet(5),
+        I32Const(HEADER_SIZE as i32),
+        I32Add,
+        LocalGet(0),
+        I32WrapI64,
+        I32Const(HEADER_SIZE as i32),
+        I32Add,
+        LocalGet(6),
+        I32Const(3),
+        I32Shl,
+        MemoryCopy {
+            src_mem: 0,
+            dst_mem: 0,
+        },
+        LocalGet(5),
+        LocalGet(3),
+        I32WrapI64,
+        I32Const(3),
+        I32Shl,
+        I32Add,
+        LocalGet(2),
+        I64Store(value_memory(HEADER_SIZE)),
+        LocalGet(5),
+        I64ExtendI32U,
+        I64Const(ARRAY_MASK),
+        I64Or,
+    ]);
+
+    instructions
+}
+
+fn length() -> Vec<Instruction<'static>> {
+    use Instruction::*;
+
+    vec![
+        LocalGet(0),
+        I32Const(ARRAY_TYPE),
+        call(Helper::Is),
+        If(BlockType::Result(ValType::I64)),
+        LocalGet(0),
+        call(Helper::ArrayLength),
+        F64ConvertI32U,
+        I64ReinterpretF64,
+        Else,
+        I64Const(0),
+        End,
+    ]
+}
+
+fn r#ref() -> Vec<Instruction<'static>> {
+    use Instruction::*;
+
+    vec![
+        I32Const(VALUE_SIZE as i32),
+        call(Helper::Allocate),
+        LocalTee(1),
+        LocalGet(0),
+        I64Store(value_memory(0)),
+        LocalGet(1),
+        I64ExtendI32U,
+        I64Const(REFERENCE_MASK),
+        I64Or,
+    ]
+}
+
+fn deref() -> Vec<Instruction<'static>> {
+    use Instruction::*;
+
+    vec![
+        LocalGet(0),
+        call(Helper::Type),
+        I32Const(REFERENCE_TYPE),
+        I32Eq,
+        If(BlockType::Result(ValType::I64)),
+        LocalGet(0),
+        I32WrapI64,
+        I64Load(value_memory(0)),
+        Else,
+        I64Const(0),
+        End,
+    ]
+}
+
+fn set_ref() -> Vec<Instruction<'static>> {
+    use Instruction::*;
+
+    vec![
+        LocalGet(0),
+        call(Helper::Type),
+        I32Const(REFERENCE_TYPE),
+        I32Eq,
+        If(BlockType::Result(ValType::I64)),
+        LocalGet(0),
+        I32WrapI64,
+        LocalGet(1),
+        I64Store(value_memory(0)),
+        LocalGet(1),
+        Else,
+        I64Const(0),
+        End,
+    ]
+}
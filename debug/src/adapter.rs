@@ -0,0 +1,577 @@
+use crate::{
+    transport::{read_message, write_message},
+    DebugError, Debugger, Frontend, Program, Resume, Scope, StopReason,
+};
+use serde_json::{json, Value};
+use std::{
+    fs,
+    io::{BufRead, Write},
+};
+use vm::Vm;
+
+const THREAD_ID: u64 = 1;
+const SCOPES: [(Scope, &str); 3] = [
+    (Scope::Arguments, "Arguments"),
+    (Scope::Environment, "Environment"),
+    (Scope::Stack, "Stack"),
+];
+
+type RequestResult = Result<Value, String>;
+
+/// A debug adapter communicating in the Debug Adapter Protocol over a pair of
+/// streams.
+pub struct Adapter<R: BufRead, W: Write> {
+    input: R,
+    output: W,
+    sequence: u64,
+    path: String,
+    disconnected: bool,
+}
+
+impl<R: BufRead, W: Write> Adapter<R, W> {
+    pub fn new(input: R, output: W) -> Self {
+        Self {
+            input,
+            output,
+            sequence: 0,
+            path: Default::default(),
+            disconnected: false,
+        }
+    }
+
+    /// Serves requests until a `disconnect` request or the end of input.
+    pub fn run(&mut self) -> Result<(), DebugError> {
+        let Some((program, stop_on_entry)) = self.launch()? else {
+            return Ok(());
+        };
+        let mut debugger = Debugger::new(&program, stop_on_entry);
+
+        // Wait for `configurationDone`.
+        if self.serve(&mut debugger, None)? == Resume::Quit {
+            return Ok(());
+        }
+
+        match program.debug(&mut debugger, self) {
+            Ok(()) => {}
+            Err(DebugError::Runtime(error)) => self.send_event(
+                "output",
+                json!({ "category": "stderr", "output": format!("{error}\n") }),
+            )?,
+            Err(error) => return Err(error),
+        }
+
+        if self.disconnected {
+            return Ok(());
+        }
+
+        self.send_event("terminated", json!({}))?;
+        self.send_event("exited", json!({ "exitCode": 0 }))?;
+
+        while self.serve(&mut debugger, None)? != Resume::Quit {}
+
+        Ok(())
+    }
+
+    fn launch(&mut self) -> Result<Option<(Program, bool)>, DebugError> {
+        while let Some(request) = read_message(&mut self.input)? {
+            let arguments = &request["arguments"];
+
+            let result = match request["command"].as_str().unwrap_or_default() {
+                "initialize" => Ok(json!({
+                    "supportsConfigurationDoneRequest": true,
+                    "supportsFunctionBreakpoints": true,
+                })),
+                "launch" => {
+                    let path = arguments["program"].as_str().unwrap_or_default();
+
+                    match fs::read_to_string(path)
+                        .map_err(DebugError::from)
+                        .and_then(Program::new)
+                    {
+                        Ok(program) => {
+                            self.path = path.into();
+                            self.respond(&request, Ok(json!({})))?;
+                            self.send_event("initialized", json!({}))?;
+
+                            return Ok(Some((
+                                program,
+                                arguments["stopOnEntry"].as_bool().unwrap_or_default(),
+                            )));
+                        }
+                        Err(error) => Err(error.to_string()),
+                    }
+                }
+                "disconnect" => {
+                    self.respond(&request, Ok(json!({})))?;
+
+                    return Ok(None);
+                }
+                command => Err(format!("program not launched: {command}")),
+            };
+
+            self.respond(&request, result)?;
+        }
+
+        Ok(None)
+    }
+
+    /// Serves requests until one resuming or quitting a program.
+    fn serve(&mut self, debugger: &mut Debugger, vm: Option<&Vm>) -> Result<Resume, DebugError> {
+        while let Some(request) = read_message(&mut self.input)? {
+            let arguments = &request["arguments"];
+            let (result, resume) = match request["command"].as_str().unwrap_or_default() {
+                "threads" => (
+                    Ok(json!({ "threads": [{ "id": THREAD_ID, "name": "main" }] })),
+                    None,
+                ),
+                "setBreakpoints" => (Ok(self.set_breakpoints(debugger, arguments)), None),
+                "setFunctionBreakpoints" => {
+                    (Ok(self.set_function_breakpoints(debugger, arguments)), None)
+                }
+                "configurationDone" => (Ok(json!({})), Some(Resume::Continue)),
+                "stackTrace" => (self.stack_trace(debugger, vm), None),
+                "scopes" => (self.scopes(vm, arguments), None),
+                "variables" => (self.variables(debugger, vm, arguments), None),
+                command @ ("continue" | "next" | "stepIn" | "stepOut") if vm.is_some() => (
+                    Ok(json!({ "allThreadsContinued": true })),
+                    Some(match command {
+                        "continue" => Resume::Continue,
+                        "next" => Resume::StepOver,
+                        "stepIn" => Resume::StepIn,
+                        _ => Resume::StepOut,
+                    }),
+                ),
+                "disconnect" | "terminate" => {
+                    self.disconnected = true;
+
+                    (Ok(json!({})), Some(Resume::Quit))
+                }
+                command => (Err(format!("invalid request: {command}")), None),
+            };
+
+            self.respond(&request, result)?;
+
+            if let Some(resume) = resume {
+                return Ok(resume);
+            }
+        }
+
+        self.disconnected = true;
+
+        Ok(Resume::Quit)
+    }
+
+    fn set_breakpoints(&self, debugger: &mut Debugger, arguments: &Value) -> Value {
+        debugger.clear_line_breakpoints();
+
+        json!({
+            "breakpoints": arguments["breakpoints"]
+                .as_array()
+                .into_iter()
+                .flatten()
+                .map(|breakpoint| {
+                    let line = breakpoint["line"].as_u64().unwrap_or_default() as usize;
+
+                    json!({
+                        "verified": debugger.insert_line_breakpoint(line),
+                        "line": line,
+                    })
+                })
+                .collect::<Vec<_>>(),
+        })
+    }
+
+    fn set_function_breakpoints(&self, debugger: &mut Debugger, arguments: &Value) -> Value {
+        debugger.clear_function_breakpoints();
+
+        json!({
+            "breakpoints": arguments["breakpoints"]
+                .as_array()
+                .into_iter()
+                .flatten()
+                .map(|breakpoint| {
+                    json!({
+                        "verified": debugger.insert_function_breakpoint(
+                            breakpoint["name"].as_str().unwrap_or_default(),
+                        ),
+                    })
+                })
+                .collect::<Vec<_>>(),
+        })
+    }
+
+    fn stack_trace(&self, debugger: &Debugger, vm: Option<&Vm>) -> RequestResult {
+        let backtrace = stopped(vm)?.backtrace();
+
+        Ok(json!({
+            "stackFrames": backtrace
+                .iter()
+                .enumerate()
+                .map(|(index, frame)| {
+                    json!({
+                        "id": index,
+                        "name": debugger.function_name(frame),
+                        "line": debugger.line(frame).unwrap_or_default(),
+                        "column": 1,
+                        "source": { "path": self.path },
+                    })
+                })
+                .collect::<Vec<_>>(),
+            "totalFrames": backtrace.len(),
+        }))
+    }
+
+    fn scopes(&self, vm: Option<&Vm>, arguments: &Value) -> RequestResult {
+        stopped(vm)?;
+        let frame = arguments["frameId"].as_u64().unwrap_or_default() as usize;
+
+        Ok(json!({
+            "scopes": SCOPES
+                .iter()
+                .enumerate()
+                .map(|(index, (_, name))| {
+                    json!({
+                        "name": name,
+                        "variablesReference": frame * SCOPES.len() + index + 1,
+                        "expensive": false,
+                    })
+                })
+                .collect::<Vec<_>>(),
+        }))
+    }
+
+    fn variables(&self, debugger: &Debugger, vm: Option<&Vm>, arguments: &Value) -> RequestResult {
+        let backtrace = stopped(vm)?.backtrace();
+        let Some(reference) = arguments["variablesReference"]
+            .as_u64()
+            .and_then(|reference| (reference as usize).checked_sub(1))
+        else {
+            return Err("invalid variables reference".into());
+        };
+        let Some(frame) = backtrace.get(reference / SCOPES.len()) else {
+            return Err(format!("frame not found: {}", reference / SCOPES.len()));
+        };
+
+        Ok(json!({
+            "variables": debugger
+                .variables(frame, SCOPES[reference % SCOPES.len()].0)
+                .into_iter()
+                .map(|(name, value)| {
+                    json!({
+                        "name": name,
+                        "value": value.repr().to_string(),
+                        "variablesReference": 0,
+                    })
+                })
+                .collect::<Vec<_>>(),
+        }))
+    }
+
+    fn respond(&mut self, request: &Value, result: RequestResult) -> Result<(), DebugError> {
+        let mut response = json!({
+            "type": "response",
+            "request_seq": request["seq"],
+            "command": request["command"],
+            "success": result.is_ok(),
+        });
+
+        match result {
+            Ok(body) => response["body"] = body,
+            Err(message) => response["message"] = message.into(),
+        }
+
+        self.send(response)
+    }
+
+    fn send_event(&mut self, event: &str, body: Value) -> Result<(), DebugError> {
+        self.send(json!({ "type": "event", "event": event, "body": body }))
+    }
+
+    fn send(&mut self, mut message: Value) -> Result<(), DebugError> {
+        self.sequence += 1;
+        message["seq"] = self.sequence.into();
+
+        write_message(&mut self.output, &message)
+    }
+}
+
+impl<R: BufRead, W: Write> Frontend for Adapter<R, W> {
+    fn stop(
+        &mut self,
+        debugger: &mut Debugger,
+        vm: &Vm,
+        reason: StopReason,
+    ) -> Result<Resume, DebugError> {
+        self.send_event(
+            "stopped",
+            json!({
+                "reason": reason.as_str(),
+                "threadId": THREAD_ID,
+                "allThreadsStopped": true,
+            }),
+        )?;
+
+        self.serve(debugger, Some(vm))
+    }
+
+    fn output(&mut self, value: &runtime::Value) -> Result<(), DebugError> {
+        self.send_event(
+            "output",
+            json!({ "category": "stdout", "output": format!("{value}\n") }),
+        )
+    }
+}
+
+fn stopped(vm: Option<&Vm>) -> Result<&Vm, String> {
+    vm.ok_or_else(|| "program not stopped".into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+    use std::io::Write;
+    use tempfile::{Builder, NamedTempFile};
+
+    /// A scripted client which sends requests to an adapter at once and
+    /// collects its messages.
+    struct Client {
+        messages: Vec<u8>,
+        sequence: u64,
+        // Programs which are deleted when a client is dropped.
+        programs: Vec<NamedTempFile>,
+    }
+
+    impl Client {
+        fn new() -> Self {
+            let mut client = Self {
+                messages: vec![],
+                sequence: 0,
+                programs: vec![],
+            };
+
+            client.request("initialize", json!({ "adapterID": "arachne" }));
+
+            client
+        }
+
+        fn launch(&mut self, name: &str, source: &str, stop_on_entry: bool) -> &mut Self {
+            let mut program = Builder::new()
+                .prefix(&format!("arachne-debug-{name}-"))
+                .suffix(".arc")
+                .tempfile()
+                .unwrap();
+
+            program.write_all(source.as_bytes()).unwrap();
+            let path = program.path().to_owned();
+            self.programs.push(program);
+
+            self.request(
+                "launch",
+                json!({ "program": path, "stopOnEntry": stop_on_entry }),
+            )
+        }
+
+        fn request(&mut self, command: &str, arguments: Value) -> &mut Self {
+            self.sequence += 1;
+
+            write_message(
+                &mut self.messages,
+                &json!({
+                    "seq": self.sequence,
+                    "type": "request",
+                    "command": command,
+                    "arguments": arguments,
+                }),
+            )
+            .unwrap();
+
+            self
+        }
+
+        fn run(&mut self) -> Vec<Value> {
+            self.request("disconnect", json!({}));
+
+            let mut output = vec![];
+
+            Adapter::new(self.messages.as_slice(), &mut output)
+                .run()
+                .unwrap();
+
+            let mut output = output.as_slice();
+            let mut messages = vec![];
+
+            while let Some(message) = read_message(&mut output).unwrap() {
+                messages.push(message);
+            }
+
+            messages
+        }
+    }
+
+    fn summarize(message: &Value) -> String {
+        match message["type"].as_str() {
+            Some("response") => format!(
+                "{} {}",
+                message["command"].as_str().unwrap(),
+                message["success"]
+            ),
+            _ => format!("{} event", message["event"].as_str().unwrap()),
+        }
+    }
+
+    #[test]
+    fn initialize() {
+        let messages = Client::new().run();
+
+        assert_eq!(messages[0]["request_seq"], 1);
+        assert_eq!(messages[0]["body"]["supportsFunctionBreakpoints"], true);
+        assert_eq!(
+            messages.iter().map(summarize).collect::<Vec<_>>(),
+            ["initialize true", "disconnect true"]
+        );
+    }
+
+    #[test]
+    fn fail_to_launch_missing_program() {
+        let messages = Client::new()
+            .request("launch", json!({ "program": "/arachne/missing.arc" }))
+            .run();
+
+        assert_eq!(
+            messages.iter().map(summarize).collect::<Vec<_>>(),
+            ["initialize true", "launch false", "disconnect true"]
+        );
+    }
+
+    #[test]
+    fn run_to_end() {
+        let messages = Client::new()
+            .launch("run_to_end", "(+ 1 2)", false)
+            .request("configurationDone", json!({}))
+            .run();
+
+        assert_eq!(
+            messages.iter().map(summarize).collect::<Vec<_>>(),
+            [
+                "initialize true",
+                "launch true",
+                "initialized event",
+                "configurationDone true",
+                "output event",
+                "terminated event",
+                "exited event",
+                "disconnect true",
+            ]
+        );
+        assert_eq!(messages[4]["body"]["output"], "3\n");
+    }
+
+    #[test]
+    fn stop_at_breakpoint() {
+        let messages = Client::new()
+            .launch(
+                "stop_at_breakpoint",
+                "(let x 1)\n(let f\n  (fn (y)\n    (+ x y)))\n(f 2)",
+                false,
+            )
+            .request(
+                "setBreakpoints",
+                json!({ "source": {}, "breakpoints": [{ "line": 4 }, { "line": 42 }] }),
+            )
+            .request("configurationDone", json!({}))
+            .request("threads", json!({}))
+            .request("stackTrace", json!({ "threadId": 1 }))
+            .request("scopes", json!({ "frameId": 0 }))
+            .request("variables", json!({ "variablesReference": 1 }))
+            .request("variables", json!({ "variablesReference": 2 }))
+            .request("continue", json!({ "threadId": 1 }))
+            .run();
+
+        assert_eq!(
+            messages.iter().map(summarize).collect::<Vec<_>>(),
+            [
+                "initialize true",
+                "launch true",
+                "initialized event",
+                "setBreakpoints true",
+                "configurationDone true",
+                "stopped event",
+                "threads true",
+                "stackTrace true",
+                "scopes true",
+                "variables true",
+                "variables true",
+                "continue true",
+                "output event",
+                "terminated event",
+                "exited event",
+                "disconnect true",
+            ]
+        );
+        assert_eq!(
+            messages[3]["body"]["breakpoints"],
+            json!([{ "verified": true, "line": 4 }, { "verified": false, "line": 42 }])
+        );
+        assert_eq!(messages[5]["body"]["reason"], "breakpoint");
+        assert_eq!(
+            messages[7]["body"]["stackFrames"]
+                .as_array()
+                .unwrap()
+                .iter()
+                .map(|frame| (frame["name"].clone(), frame["line"].clone()))
+                .collect::<Vec<_>>(),
+            [(json!("f"), json!(4)), (json!("<top level>"), json!(5))]
+        );
+        assert_eq!(
+            messages[9]["body"]["variables"],
+            json!([{ "name": "y", "value": "2", "variablesReference": 0 }])
+        );
+        assert_eq!(
+            messages[10]["body"]["variables"],
+            json!([{ "name": "x", "value": "1", "variablesReference": 0 }])
+        );
+        assert_eq!(messages[12]["body"]["output"], "3\n");
+    }
+
+    #[test]
+    fn step_over() {
+        let messages = Client::new()
+            .launch("step_over", "(+ 1 2)\n(+ 3 4)", true)
+            .request("configurationDone", json!({}))
+            .request("next", json!({ "threadId": 1 }))
+            .request("stackTrace", json!({ "threadId": 1 }))
+            .request("disconnect", json!({}))
+            .run();
+
+        assert_eq!(
+            messages.iter().map(summarize).collect::<Vec<_>>(),
+            [
+                "initialize true",
+                "launch true",
+                "initialized event",
+                "configurationDone true",
+                "stopped event",
+                "next true",
+                "output event",
+                "stopped event",
+                "stackTrace true",
+                "disconnect true",
+            ]
+        );
+        assert_eq!(messages[4]["body"]["reason"], "entry");
+        assert_eq!(messages[7]["body"]["reason"], "step");
+        assert_eq!(messages[8]["body"]["stackFrames"][0]["line"], 2);
+    }
+
+    #[test]
+    fn fail_to_inspect_running_program() {
+        let messages = Client::new()
+            .launch("inspect_running_program", "(+ 1 2)", false)
+            .request("stackTrace", json!({ "threadId": 1 }))
+            .run();
+
+        assert_eq!(messages[3]["success"], false);
+        assert_eq!(messages[3]["message"], "program not stopped");
+    }
+}
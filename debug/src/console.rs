@@ -0,0 +1,269 @@
+use crate::{DebugError, Debugger, Frontend, Resume, Scope, StopReason};
+use runtime::Value;
+use std::io::{BufRead, Write};
+use vm::{StackFrame, Vm};
+
+const PROMPT: &str = "(debug) ";
+const HELP: &str = "\
+break <line | function>   set a breakpoint
+delete <line | function>  delete a breakpoint
+breakpoints               list breakpoints
+continue                  continue execution
+step                      step into the next line
+next                      step over the next line
+finish                    run until the current function returns
+stepi                     step a single instruction
+backtrace                 print a backtrace
+stack                     print arguments and values on a stack
+environment               print variables captured by a closure
+quit                      quit a program";
+
+/// A command line interface of a debugger.
+pub struct Console<R: BufRead, W: Write> {
+    input: R,
+    output: W,
+}
+
+impl<R: BufRead, W: Write> Console<R, W> {
+    pub fn new(input: R, output: W) -> Self {
+        Self { input, output }
+    }
+
+    fn print_location(
+        &mut self,
+        debugger: &Debugger,
+        frame: &StackFrame,
+        reason: StopReason,
+    ) -> Result<(), DebugError> {
+        let name = debugger.function_name(frame);
+
+        let Some(line) = debugger.line(frame) else {
+            writeln!(
+                self.output,
+                "stopped at {:#x} in {name} ({})",
+                frame.address(),
+                reason.as_str()
+            )?;
+            return Ok(());
+        };
+
+        writeln!(
+            self.output,
+            "stopped at line {line} in {name} ({})",
+            reason.as_str()
+        )?;
+
+        if let Some(source) = debugger.program().line(line) {
+            writeln!(self.output, "{line:>4} | {source}")?;
+        }
+
+        Ok(())
+    }
+
+    fn print_variables(
+        &mut self,
+        debugger: &Debugger,
+        frame: &StackFrame,
+        scopes: &[Scope],
+    ) -> Result<(), DebugError> {
+        for &scope in scopes {
+            for (name, value) in debugger.variables(frame, scope) {
+                writeln!(self.output, "{name} = {}", value.repr())?;
+            }
+        }
+
+        Ok(())
+    }
+
+    fn execute(
+        &mut self,
+        debugger: &mut Debugger,
+        vm: &Vm,
+        command: &str,
+        argument: Option<&str>,
+    ) -> Result<Option<Resume>, DebugError> {
+        match (command, argument) {
+            ("break" | "b", Some(argument)) => {
+                let inserted = match argument.parse::<usize>() {
+                    Ok(line) => debugger.insert_line_breakpoint(line),
+                    Err(_) => debugger.insert_function_breakpoint(argument),
+                };
+
+                if inserted {
+                    writeln!(self.output, "breakpoint set at {argument}")?;
+                } else {
+                    writeln!(self.output, "no code found at {argument}")?;
+                }
+            }
+            ("delete" | "d", Some(argument)) => {
+                let removed = match argument.parse::<usize>() {
+                    Ok(line) => debugger.remove_line_breakpoint(line),
+                    Err(_) => debugger.remove_function_breakpoint(argument),
+                };
+
+                if !removed {
+                    writeln!(self.output, "no breakpoint found at {argument}")?;
+                }
+            }
+            ("breakpoints", None) => {
+                for line in debugger.line_breakpoints() {
+                    writeln!(self.output, "line {line}")?;
+                }
+
+                for name in debugger.function_breakpoints() {
+                    writeln!(self.output, "function {name}")?;
+                }
+            }
+            ("continue" | "c", None) => return Ok(Some(Resume::Continue)),
+            ("step" | "s", None) => return Ok(Some(Resume::StepIn)),
+            ("next" | "n", None) => return Ok(Some(Resume::StepOver)),
+            ("finish", None) => return Ok(Some(Resume::StepOut)),
+            ("stepi" | "si", None) => return Ok(Some(Resume::StepInstruction)),
+            ("quit" | "q", None) => return Ok(Some(Resume::Quit)),
+            ("backtrace" | "bt", None) => {
+                for (index, frame) in vm.backtrace().iter().enumerate() {
+                    let name = debugger.function_name(frame);
+
+                    match debugger.line(frame) {
+                        Some(line) => writeln!(self.output, "#{index} {name} at line {line}")?,
+                        None => writeln!(self.output, "#{index} {name}")?,
+                    }
+
+                    match frame.tail_call_count() {
+                        0 => {}
+                        1 => writeln!(self.output, "   (1 frame elided by tail calls)")?,
+                        count => writeln!(self.output, "   ({count} frames elided by tail calls)")?,
+                    }
+                }
+            }
+            ("stack", None) => self.print_variables(
+                debugger,
+                &vm.backtrace()[0],
+                &[Scope::Arguments, Scope::Stack],
+            )?,
+            ("environment" | "env", None) => {
+                self.print_variables(debugger, &vm.backtrace()[0], &[Scope::Environment])?
+            }
+            ("help" | "h", None) => writeln!(self.output, "{HELP}")?,
+            _ => writeln!(self.output, "invalid command: {command}")?,
+        }
+
+        Ok(None)
+    }
+}
+
+impl<R: BufRead, W: Write> Frontend for Console<R, W> {
+    fn stop(
+        &mut self,
+        debugger: &mut Debugger,
+        vm: &Vm,
+        reason: StopReason,
+    ) -> Result<Resume, DebugError> {
+        self.print_location(debugger, &vm.backtrace()[0], reason)?;
+
+        loop {
+            write!(self.output, "{PROMPT}")?;
+            self.output.flush()?;
+
+            let mut line = String::new();
+
+            if self.input.read_line(&mut line)? == 0 {
+                return Ok(Resume::Quit);
+            }
+
+            let mut words = line.split_whitespace();
+            let Some(command) = words.next() else {
+                continue;
+            };
+
+            if let Some(resume) = self.execute(debugger, vm, command, words.next())? {
+                return Ok(resume);
+            }
+        }
+    }
+
+    fn output(&mut self, value: &Value) -> Result<(), DebugError> {
+        writeln!(self.output, "{value}")?;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Program;
+    use pretty_assertions::assert_eq;
+
+    fn debug(source: &str, input: &str) -> String {
+        let program = Program::new(source.into()).unwrap();
+        let mut debugger = Debugger::new(&program, true);
+        let mut output = vec![];
+
+        program
+            .debug(
+                &mut debugger,
+                &mut Console::new(input.as_bytes(), &mut output),
+            )
+            .unwrap();
+
+        String::from_utf8(output).unwrap()
+    }
+
+    #[test]
+    fn continue_program() {
+        assert_eq!(
+            debug("(+ 1 2)", "continue\n"),
+            "stopped at line 1 in <top level> (entry)\n   1 | (+ 1 2)\n(debug) 3\n"
+        );
+    }
+
+    #[test]
+    fn quit_at_end_of_input() {
+        assert_eq!(
+            debug("(+ 1 2)", ""),
+            "stopped at line 1 in <top level> (entry)\n   1 | (+ 1 2)\n(debug) "
+        );
+    }
+
+    #[test]
+    fn print_backtrace_and_variables() {
+        assert_eq!(
+            debug(
+                "(let x 1)\n(let f\n  (fn (y)\n    (+ x y)))\n(f 2)",
+                "break f\nc\nbt\nstack\nenv\nq\n"
+            ),
+            "\
+stopped at line 1 in <top level> (entry)
+   1 | (let x 1)
+(debug) breakpoint set at f
+(debug) stopped at line 4 in f (breakpoint)
+   4 |     (+ x y)))
+(debug) #0 f at line 4
+#1 <top level> at line 5
+(debug) y = 2
+(debug) x = 1
+(debug) "
+        );
+    }
+
+    #[test]
+    fn manage_breakpoints() {
+        assert_eq!(
+            debug(
+                "(+ 1 2)\n\n(+ 3 4)",
+                "b 3\nb 2\nb foo\nbreakpoints\nd 3\nd 3\nbreakpoints\nfoo\nq\n"
+            ),
+            "\
+stopped at line 1 in <top level> (entry)
+   1 | (+ 1 2)
+(debug) breakpoint set at 3
+(debug) no code found at 2
+(debug) no code found at foo
+(debug) line 3
+(debug) (debug) no breakpoint found at 3
+(debug) (debug) invalid command: foo
+(debug) "
+        );
+    }
+}
@@ -0,0 +1,597 @@
+use crate::{DebugError, Program};
+use core::{fmt, ops::ControlFlow};
+use runtime::{ClosureId, Value};
+use std::collections::BTreeSet;
+use vm::{FunctionInfo, Hook, StackFrame, Vm};
+
+/// A reason why a program stops.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum StopReason {
+    Entry,
+    Breakpoint,
+    Step,
+}
+
+impl StopReason {
+    pub const fn as_str(self) -> &'static str {
+        match self {
+            Self::Entry => "entry",
+            Self::Breakpoint => "breakpoint",
+            Self::Step => "step",
+        }
+    }
+}
+
+/// A way to resume a stopped program.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Resume {
+    Continue,
+    /// Stops at the next line including ones in called functions.
+    StepIn,
+    /// Stops at the next line in the current function or its callers.
+    StepOver,
+    /// Stops after the current function returns.
+    StepOut,
+    /// Stops at the next instruction.
+    StepInstruction,
+    Quit,
+}
+
+/// A scope of variables in a frame.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Scope {
+    Arguments,
+    /// Free variables captured by a closure.
+    Environment,
+    /// Values on a stack other than arguments.
+    Stack,
+}
+
+/// A user interface of a debugger.
+pub trait Frontend {
+    /// Called when a program stops.
+    ///
+    /// It returns how to resume the program.
+    fn stop(
+        &mut self,
+        debugger: &mut Debugger,
+        vm: &Vm,
+        reason: StopReason,
+    ) -> Result<Resume, DebugError>;
+
+    /// Called when a program dumps a value.
+    fn output(&mut self, value: &Value) -> Result<(), DebugError>;
+}
+
+#[derive(Clone, Copy, Debug)]
+enum Mode {
+    Entry,
+    Continue,
+    StepIn { line: Option<usize>, depth: usize },
+    StepOver { line: Option<usize>, depth: usize },
+    StepOut { depth: usize },
+    StepInstruction,
+}
+
+/// A debugger managing breakpoints and stepping.
+#[derive(Debug)]
+pub struct Debugger<'a> {
+    program: &'a Program,
+    line_breakpoints: BTreeSet<usize>,
+    function_breakpoints: BTreeSet<String>,
+    mode: Mode,
+}
+
+impl<'a> Debugger<'a> {
+    pub fn new(program: &'a Program, stop_on_entry: bool) -> Self {
+        Self {
+            program,
+            line_breakpoints: Default::default(),
+            function_breakpoints: Default::default(),
+            mode: if stop_on_entry {
+                Mode::Entry
+            } else {
+                Mode::Continue
+            },
+        }
+    }
+
+    pub const fn program(&self) -> &'a Program {
+        self.program
+    }
+
+    /// Sets a breakpoint at a line.
+    ///
+    /// It returns `false` if no code starts at the line.
+    pub fn insert_line_breakpoint(&mut self, line: usize) -> bool {
+        if !self
+            .program
+            .debug_info()
+            .lines()
+            .iter()
+            .any(|&(_, other)| other == line)
+        {
+            return false;
+        }
+
+        self.line_breakpoints.insert(line);
+
+        true
+    }
+
+    pub fn remove_line_breakpoint(&mut self, line: usize) -> bool {
+        self.line_breakpoints.remove(&line)
+    }
+
+    /// Sets a breakpoint at entries of functions with a name.
+    ///
+    /// It returns `false` if no function has the name.
+    pub fn insert_function_breakpoint(&mut self, name: &str) -> bool {
+        if !self
+            .program
+            .debug_info()
+            .functions()
+            .iter()
+            .any(|function| function.name() == Some(name))
+        {
+            return false;
+        }
+
+        self.function_breakpoints.insert(name.into());
+
+        true
+    }
+
+    pub fn remove_function_breakpoint(&mut self, name: &str) -> bool {
+        self.function_breakpoints.remove(name)
+    }
+
+    pub fn clear_line_breakpoints(&mut self) {
+        self.line_breakpoints.clear();
+    }
+
+    pub fn clear_function_breakpoints(&mut self) {
+        self.function_breakpoints.clear();
+    }
+
+    pub fn line_breakpoints(&self) -> impl Iterator<Item = usize> + '_ {
+        self.line_breakpoints.iter().copied()
+    }
+
+    pub fn function_breakpoints(&self) -> impl Iterator<Item = &str> {
+        self.function_breakpoints.iter().map(String::as_str)
+    }
+
+    /// Returns a name of a function in a frame.
+    pub fn function_name(&self, frame: &StackFrame) -> String {
+        match frame.closure() {
+            Some(closure) => self
+                .function(closure.id())
+                .and_then(FunctionInfo::name)
+                .unwrap_or("<anonymous>")
+                .into(),
+            None => "<top level>".into(),
+        }
+    }
+
+    /// Returns a line executed in a frame.
+    pub fn line(&self, frame: &StackFrame) -> Option<usize> {
+        self.program.debug_info().line(frame.address())
+    }
+
+    /// Returns named variables in a scope of a frame.
+    pub fn variables<'b>(&self, frame: &StackFrame<'b>, scope: Scope) -> Vec<(String, &'b Value)> {
+        let closure = frame.closure();
+        let function = closure.and_then(|closure| self.function(closure.id()));
+        let arity = closure
+            .map(|closure| closure.arity() as usize)
+            .unwrap_or_default();
+        let values = frame.values();
+
+        match scope {
+            Scope::Arguments => values
+                .iter()
+                .take(arity)
+                .enumerate()
+                .map(|(index, value)| {
+                    (
+                        function
+                            .and_then(|function| function.arguments().get(index))
+                            .cloned()
+                            .unwrap_or_else(|| index.to_string()),
+                        value,
+                    )
+                })
+                .collect(),
+            Scope::Environment => closure
+                .map(|closure| {
+                    (0..closure.environment_size() as usize)
+                        .map(|index| {
+                            (
+                                function
+                                    .and_then(|function| function.environment().get(index))
+                                    .cloned()
+                                    .unwrap_or_else(|| index.to_string()),
+                                closure.get_environment(index),
+                            )
+                        })
+                        .collect()
+                })
+                .unwrap_or_default(),
+            Scope::Stack => values
+                .iter()
+                .enumerate()
+                .skip(arity)
+                .map(|(index, value)| (index.to_string(), value))
+                .collect(),
+        }
+    }
+
+    fn function(&self, id: ClosureId) -> Option<&FunctionInfo> {
+        self.program.debug_info().function(id)
+    }
+
+    fn check(&self, vm: &Vm) -> Option<StopReason> {
+        let debug_info = self.program.debug_info();
+        let address = vm.program_counter();
+        let depth = vm.frames().len();
+
+        match self.mode {
+            Mode::Entry => return Some(StopReason::Entry),
+            Mode::StepInstruction => return Some(StopReason::Step),
+            _ => {}
+        }
+
+        if debug_info
+            .line_start(address)
+            .is_some_and(|line| self.line_breakpoints.contains(&line))
+            || debug_info
+                .function(address as ClosureId)
+                .and_then(FunctionInfo::name)
+                .is_some_and(|name| self.function_breakpoints.contains(name))
+        {
+            return Some(StopReason::Breakpoint);
+        }
+
+        let line = debug_info
+            .line_boundary(address)
+            .or_else(|| debug_info.line_start(address));
+
+        match self.mode {
+            Mode::StepIn { depth: origin, .. }
+            | Mode::StepOver { depth: origin, .. }
+            | Mode::StepOut { depth: origin }
+                if depth < origin =>
+            {
+                Some(StopReason::Step)
+            }
+            Mode::StepIn {
+                line: origin_line,
+                depth: origin,
+            } if line.is_some() && (line != origin_line || depth != origin) => {
+                Some(StopReason::Step)
+            }
+            Mode::StepOver {
+                line: origin_line,
+                depth: origin,
+            } if line.is_some() && line != origin_line && depth == origin => Some(StopReason::Step),
+            _ => None,
+        }
+    }
+
+    fn resume(&mut self, vm: &Vm, resume: Resume) {
+        let line = self.program.debug_info().line(vm.program_counter());
+        let depth = vm.frames().len();
+
+        self.mode = match resume {
+            Resume::Continue | Resume::Quit => Mode::Continue,
+            Resume::StepIn => Mode::StepIn { line, depth },
+            Resume::StepOver => Mode::StepOver { line, depth },
+            Resume::StepOut => Mode::StepOut { depth },
+            Resume::StepInstruction => Mode::StepInstruction,
+        };
+    }
+}
+
+/// A hook connecting a debugger and its frontend to a VM.
+pub(crate) struct Session<'a, 'b, F> {
+    debugger: &'a mut Debugger<'b>,
+    frontend: &'a mut F,
+    error: Option<DebugError>,
+}
+
+impl<'a, 'b, F: Frontend> Session<'a, 'b, F> {
+    pub fn new(debugger: &'a mut Debugger<'b>, frontend: &'a mut F) -> Self {
+        Self {
+            debugger,
+            frontend,
+            error: None,
+        }
+    }
+
+    pub fn finish(self) -> Result<(), DebugError> {
+        self.error.map_or(Ok(()), Err)
+    }
+}
+
+impl<F: Frontend> Hook for Session<'_, '_, F> {
+    fn before_instruction(&mut self, vm: &Vm, _codes: &[u8]) -> ControlFlow<()> {
+        if self.error.is_some() {
+            return ControlFlow::Break(());
+        }
+
+        let Some(reason) = self.debugger.check(vm) else {
+            return ControlFlow::Continue(());
+        };
+
+        match self.frontend.stop(self.debugger, vm, reason) {
+            Ok(Resume::Quit) => ControlFlow::Break(()),
+            Ok(resume) => {
+                self.debugger.resume(vm, resume);
+                ControlFlow::Continue(())
+            }
+            Err(error) => {
+                self.error = Some(error);
+                ControlFlow::Break(())
+            }
+        }
+    }
+
+    fn dump(&mut self, value: &Value, _output: &mut dyn fmt::Write) -> fmt::Result {
+        if let Err(error) = self.frontend.output(value) {
+            self.error.get_or_insert(error);
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    const SOURCE: &str = "\
+(let-rec f
+  (fn (n)
+    (if (= n 0)
+      0
+      (+ n (f (- n 1))))))
+(let x 42)
+(let g (fn (y) (+ x y)))
+(f 2)
+(g 1)
+";
+
+    type Stop = (StopReason, String, Option<usize>);
+
+    /// A frontend resuming a program as scripted.
+    #[derive(Default)]
+    struct Script {
+        resumes: Vec<Resume>,
+        stops: Vec<Stop>,
+        variables: Vec<Vec<(String, String)>>,
+        outputs: Vec<String>,
+    }
+
+    impl Script {
+        fn new(resumes: &[Resume]) -> Self {
+            Self {
+                resumes: resumes.iter().rev().copied().collect(),
+                ..Default::default()
+            }
+        }
+    }
+
+    impl Frontend for Script {
+        fn stop(
+            &mut self,
+            debugger: &mut Debugger,
+            vm: &Vm,
+            reason: StopReason,
+        ) -> Result<Resume, DebugError> {
+            let frame = &vm.backtrace()[0];
+
+            self.stops
+                .push((reason, debugger.function_name(frame), debugger.line(frame)));
+            self.variables.push(
+                [Scope::Arguments, Scope::Environment]
+                    .into_iter()
+                    .flat_map(|scope| debugger.variables(frame, scope))
+                    .map(|(name, value)| (name, value.to_string()))
+                    .collect(),
+            );
+
+            Ok(self.resumes.pop().unwrap_or(Resume::Quit))
+        }
+
+        fn output(&mut self, value: &Value) -> Result<(), DebugError> {
+            self.outputs.push(value.to_string());
+
+            Ok(())
+        }
+    }
+
+    fn debug(
+        stop_on_entry: bool,
+        breakpoints: impl Fn(&mut Debugger),
+        resumes: &[Resume],
+    ) -> Script {
+        let program = Program::new(SOURCE.into()).unwrap();
+        let mut debugger = Debugger::new(&program, stop_on_entry);
+        let mut script = Script::new(resumes);
+
+        breakpoints(&mut debugger);
+        program.debug(&mut debugger, &mut script).unwrap();
+
+        script
+    }
+
+    fn stop(reason: StopReason, name: &str, line: usize) -> Stop {
+        (reason, name.into(), Some(line))
+    }
+
+    #[test]
+    fn run_without_stop() {
+        let script = debug(false, |_| {}, &[]);
+
+        assert_eq!(script.stops, []);
+        assert_eq!(script.outputs, ["3", "43"]);
+    }
+
+    #[test]
+    fn stop_on_entry() {
+        let script = debug(true, |_| {}, &[Resume::Continue]);
+
+        assert_eq!(script.stops, [stop(StopReason::Entry, "<top level>", 1)]);
+        assert_eq!(script.outputs, ["3", "43"]);
+    }
+
+    #[test]
+    fn quit() {
+        let script = debug(true, |_| {}, &[Resume::Quit]);
+
+        assert_eq!(script.outputs, Vec::<String>::new());
+    }
+
+    #[test]
+    fn stop_at_line_breakpoint() {
+        let script = debug(
+            false,
+            |debugger| assert!(debugger.insert_line_breakpoint(3)),
+            &[Resume::Continue; 3],
+        );
+
+        assert_eq!(script.stops, vec![stop(StopReason::Breakpoint, "f", 3); 3]);
+        assert_eq!(
+            script
+                .variables
+                .iter()
+                .map(|variables| variables[0].1.as_str())
+                .collect::<Vec<_>>(),
+            ["2", "1", "()"]
+        );
+    }
+
+    #[test]
+    fn stop_at_function_breakpoint() {
+        let script = debug(
+            false,
+            |debugger| assert!(debugger.insert_function_breakpoint("g")),
+            &[Resume::Continue],
+        );
+
+        assert_eq!(script.stops, [stop(StopReason::Breakpoint, "g", 7)]);
+        assert_eq!(
+            script.variables[0],
+            [("y".into(), "1".into()), ("x".into(), "42".into())]
+        );
+    }
+
+    #[test]
+    fn reject_breakpoints_without_code() {
+        let program = Program::new(SOURCE.into()).unwrap();
+        let mut debugger = Debugger::new(&program, false);
+
+        assert!(!debugger.insert_line_breakpoint(4));
+        assert!(!debugger.insert_line_breakpoint(42));
+        assert!(!debugger.insert_function_breakpoint("h"));
+        assert_eq!(debugger.line_breakpoints().count(), 0);
+    }
+
+    #[test]
+    fn remove_breakpoints() {
+        let script = debug(
+            false,
+            |debugger| {
+                debugger.insert_line_breakpoint(3);
+                debugger.insert_function_breakpoint("g");
+                assert!(debugger.remove_line_breakpoint(3));
+                assert!(debugger.remove_function_breakpoint("g"));
+            },
+            &[],
+        );
+
+        assert_eq!(script.stops, []);
+    }
+
+    #[test]
+    fn step_in() {
+        let script = debug(
+            false,
+            |debugger| {
+                debugger.insert_line_breakpoint(8);
+            },
+            &[Resume::StepIn, Resume::StepIn, Resume::StepIn],
+        );
+
+        assert_eq!(
+            script.stops,
+            [
+                stop(StopReason::Breakpoint, "<top level>", 8),
+                stop(StopReason::Step, "f", 3),
+                stop(StopReason::Step, "f", 5),
+                stop(StopReason::Step, "f", 3),
+            ]
+        );
+    }
+
+    #[test]
+    fn step_over() {
+        let script = debug(
+            false,
+            |debugger| {
+                debugger.insert_line_breakpoint(6);
+            },
+            &[Resume::StepOver, Resume::StepOver, Resume::StepOver],
+        );
+
+        assert_eq!(
+            script.stops,
+            [
+                stop(StopReason::Breakpoint, "<top level>", 6),
+                stop(StopReason::Step, "<top level>", 7),
+                stop(StopReason::Step, "<top level>", 8),
+                stop(StopReason::Step, "<top level>", 9),
+            ]
+        );
+        assert_eq!(script.outputs, ["3"]);
+    }
+
+    #[test]
+    fn step_out() {
+        let script = debug(
+            false,
+            |debugger| {
+                debugger.insert_function_breakpoint("g");
+            },
+            &[Resume::StepOut],
+        );
+
+        assert_eq!(
+            script.stops,
+            [
+                stop(StopReason::Breakpoint, "g", 7),
+                stop(StopReason::Step, "<top level>", 9),
+            ]
+        );
+    }
+
+    #[test]
+    fn step_instruction() {
+        let script = debug(
+            true,
+            |_| {},
+            &[Resume::StepInstruction, Resume::StepInstruction],
+        );
+
+        assert_eq!(
+            script.stops,
+            [
+                stop(StopReason::Entry, "<top level>", 1),
+                stop(StopReason::Step, "<top level>", 1),
+                stop(StopReason::Step, "<top level>", 6),
+            ]
+        );
+    }
+}
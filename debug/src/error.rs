@@ -0,0 +1,63 @@
+use compiler::CompileError;
+use parse::SyntaxError;
+use std::{
+    error::Error,
+    fmt::{self, Display, Formatter},
+    io,
+};
+use vm::RuntimeError;
+
+#[derive(Debug)]
+pub enum DebugError {
+    Compile(CompileError),
+    Io(io::Error),
+    Json(serde_json::Error),
+    Protocol(String),
+    Runtime(RuntimeError),
+    Syntax(SyntaxError),
+}
+
+impl Error for DebugError {}
+
+impl Display for DebugError {
+    fn fmt(&self, formatter: &mut Formatter) -> fmt::Result {
+        match self {
+            Self::Compile(error) => write!(formatter, "{error}"),
+            Self::Io(error) => write!(formatter, "{error}"),
+            Self::Json(error) => write!(formatter, "{error}"),
+            Self::Protocol(message) => write!(formatter, "protocol error: {message}"),
+            Self::Runtime(error) => write!(formatter, "{error}"),
+            Self::Syntax(error) => write!(formatter, "{error}"),
+        }
+    }
+}
+
+impl From<CompileError> for DebugError {
+    fn from(error: CompileError) -> Self {
+        Self::Compile(error)
+    }
+}
+
+impl From<io::Error> for DebugError {
+    fn from(error: io::Error) -> Self {
+        Self::Io(error)
+    }
+}
+
+impl From<serde_json::Error> for DebugError {
+    fn from(error: serde_json::Error) -> Self {
+        Self::Json(error)
+    }
+}
+
+impl From<RuntimeError> for DebugError {
+    fn from(error: RuntimeError) -> Self {
+        Self::Runtime(error)
+    }
+}
+
+impl From<SyntaxError> for DebugError {
+    fn from(error: SyntaxError) -> Self {
+        Self::Syntax(error)
+    }
+}
@@ -0,0 +1,14 @@
+mod adapter;
+mod console;
+mod debugger;
+mod error;
+mod profiler;
+mod program;
+mod transport;
+
+pub use adapter::Adapter;
+pub use console::Console;
+pub use debugger::{Debugger, Frontend, Resume, Scope, StopReason};
+pub use error::DebugError;
+pub use profiler::{Profile, Profiler};
+pub use program::Program;
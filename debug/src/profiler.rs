@@ -0,0 +1,256 @@
+use crate::{DebugError, Program};
+use core::ops::ControlFlow;
+use std::{
+    collections::BTreeMap,
+    fmt::{self, Display, Formatter},
+};
+use vm::{Hook, Vm};
+
+const TOP_LEVEL_NAME: &str = "<top level>";
+
+/// A sampling profiler.
+///
+/// It samples call stacks every fixed number of executed instructions so
+/// that profiles are deterministic.
+pub struct Profiler<'a> {
+    program: &'a Program,
+    period: u64,
+    countdown: u64,
+    stacks: BTreeMap<Vec<String>, u64>,
+}
+
+impl<'a> Profiler<'a> {
+    pub fn new(program: &'a Program, period: u64) -> Self {
+        let period = period.max(1);
+
+        Self {
+            program,
+            period,
+            countdown: period,
+            stacks: Default::default(),
+        }
+    }
+
+    /// Runs a program and returns its profile.
+    pub fn run(mut self) -> Result<Profile, DebugError> {
+        self.program.run(&mut self)?;
+
+        Ok(Profile {
+            period: self.period,
+            stacks: self.stacks,
+        })
+    }
+
+    fn function_name(&self, address: usize) -> String {
+        let Some(function) = self.program.debug_info().function_at(address) else {
+            return TOP_LEVEL_NAME.into();
+        };
+
+        match function.name() {
+            Some(name) => name.into(),
+            None => match function.span() {
+                Some(span) => format!("<anonymous:{}>", self.program.source_map().line(span.start)),
+                None => "<anonymous>".into(),
+            },
+        }
+    }
+}
+
+impl Hook for Profiler<'_> {
+    fn before_instruction(&mut self, vm: &Vm, _: &[u8]) -> ControlFlow<()> {
+        self.countdown -= 1;
+
+        if self.countdown == 0 {
+            self.countdown = self.period;
+
+            let stack = vm
+                .backtrace()
+                .iter()
+                .rev()
+                .map(|frame| self.function_name(frame.address()))
+                .collect();
+
+            *self.stacks.entry(stack).or_default() += 1;
+        }
+
+        ControlFlow::Continue(())
+    }
+}
+
+/// A profile of sampled call stacks.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct Profile {
+    period: u64,
+    stacks: BTreeMap<Vec<String>, u64>,
+}
+
+impl Profile {
+    /// Returns a number of instructions between samples.
+    pub const fn period(&self) -> u64 {
+        self.period
+    }
+
+    pub fn sample_count(&self) -> u64 {
+        self.stacks.values().sum()
+    }
+
+    /// Returns sampled call stacks from top levels to leaves and their counts.
+    pub fn stacks(&self) -> impl Iterator<Item = (&[String], u64)> {
+        self.stacks
+            .iter()
+            .map(|(stack, &count)| (stack.as_slice(), count))
+    }
+
+    /// Returns functions with their self and total sample counts sorted by
+    /// self counts.
+    pub fn functions(&self) -> Vec<(&str, u64, u64)> {
+        let mut functions = BTreeMap::<&str, (u64, u64)>::new();
+
+        for (stack, count) in self.stacks() {
+            for (index, name) in stack.iter().enumerate() {
+                // Count recursive calls only once per sample.
+                if !stack[index + 1..].contains(name) {
+                    functions.entry(name).or_default().1 += count;
+                }
+            }
+
+            if let Some(name) = stack.last() {
+                functions.entry(name).or_default().0 += count;
+            }
+        }
+
+        let mut functions = functions
+            .into_iter()
+            .map(|(name, (self_count, total_count))| (name, self_count, total_count))
+            .collect::<Vec<_>>();
+
+        functions.sort_by(|one, other| other.1.cmp(&one.1).then(other.2.cmp(&one.2)));
+        functions
+    }
+
+    /// Returns folded stacks for flame graph tools.
+    pub fn folded(&self) -> String {
+        self.stacks()
+            .map(|(stack, count)| format!("{} {count}\n", stack.join(";")))
+            .collect()
+    }
+}
+
+impl Display for Profile {
+    fn fmt(&self, formatter: &mut Formatter) -> fmt::Result {
+        let sample_count = self.sample_count();
+        let percentage = |count| 100.0 * count as f64 / sample_count.max(1) as f64;
+
+        writeln!(
+            formatter,
+            "{sample_count} samples every {} instructions",
+            self.period
+        )?;
+        writeln!(formatter)?;
+        write!(formatter, "   self   total  function")?;
+
+        for (name, self_count, total_count) in self.functions() {
+            write!(
+                formatter,
+                "\n{:>6.2}% {:>6.2}%  {name}",
+                percentage(self_count),
+                percentage(total_count)
+            )?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    fn profile(source: &str, period: u64) -> Profile {
+        Profiler::new(&Program::new(source.into()).unwrap(), period)
+            .run()
+            .unwrap()
+    }
+
+    const SOURCE: &str = "\
+(let-rec
+  f
+  (fn (x)
+    (if (= x 0)
+      0
+      (+ 1 (f (- x 1))))))
+(let g (fn () (+ 1 (f 3))))
+((fn () (+ 1 (g))))";
+
+    #[test]
+    fn sample_every_instruction() {
+        let profile = profile("(+ 1 2)", 1);
+
+        assert_eq!(profile.sample_count(), 2);
+        assert_eq!(profile.folded(), "<top level> 2\n");
+    }
+
+    #[test]
+    fn sample_periodically() {
+        assert_eq!(
+            profile(SOURCE, 3).sample_count(),
+            profile(SOURCE, 1).sample_count() / 3
+        );
+    }
+
+    #[test]
+    fn fold_stacks() {
+        assert_eq!(
+            profile(SOURCE, 1)
+                .stacks()
+                .map(|(stack, _)| stack.join(";"))
+                .collect::<Vec<_>>(),
+            [
+                "<top level>",
+                "<top level>;<anonymous:8>",
+                "<top level>;<anonymous:8>;g",
+                "<top level>;<anonymous:8>;g;f",
+                "<top level>;<anonymous:8>;g;f;f",
+                "<top level>;<anonymous:8>;g;f;f;f",
+                "<top level>;<anonymous:8>;g;f;f;f;f",
+            ]
+        );
+    }
+
+    #[test]
+    fn count_functions() {
+        let profile = profile(SOURCE, 1);
+        let functions = profile.functions();
+        let total = |name| {
+            functions
+                .iter()
+                .find(|function| function.0 == name)
+                .unwrap()
+                .2
+        };
+
+        assert_eq!(functions[0].0, "f");
+        assert_eq!(total("<top level>"), profile.sample_count());
+        assert!(total("g") > total("f"));
+        assert_eq!(
+            functions.iter().map(|function| function.1).sum::<u64>(),
+            profile.sample_count()
+        );
+    }
+
+    #[test]
+    fn display_report() {
+        let report = profile("(let f (fn () 42))\n(f)", 1).to_string();
+
+        assert_eq!(
+            report,
+            "\
+7 samples every 1 instructions
+
+   self   total  function
+ 71.43% 100.00%  <top level>
+ 28.57%  28.57%  f"
+        );
+    }
+}
@@ -0,0 +1,92 @@
+use crate::{debugger::Session, DebugError, Debugger, Frontend};
+use compiler::Compiler;
+use futures::{executor::block_on, pin_mut, stream::iter, StreamExt};
+use parse::{parse_concrete, SourceMap};
+use std::{cell::RefCell, convert::Infallible};
+use vm::{DebugInfo, Hook, Vm};
+
+/// A program compiled with debug information.
+#[derive(Debug)]
+pub struct Program {
+    source: String,
+    source_map: SourceMap,
+    codes: Vec<u8>,
+    debug_info: DebugInfo,
+}
+
+impl Program {
+    pub fn new(source: String) -> Result<Self, DebugError> {
+        let (values, source_map) = SourceMap::new(&source, &parse_concrete(&source)?);
+        let codes = RefCell::new(vec![]);
+        let debug_info = RefCell::new(DebugInfo::new());
+
+        {
+            let mut compiler = Compiler::with_debug_info(&codes, &source_map, &debug_info);
+            let mut values = iter(values.iter().cloned().map(Ok::<_, Infallible>));
+
+            block_on(async {
+                let results = compiler.compile(&mut values);
+
+                pin_mut!(results);
+
+                while let Some(result) = results.next().await {
+                    result?;
+                }
+
+                Ok::<_, DebugError>(())
+            })?;
+        }
+
+        Ok(Self {
+            source,
+            source_map,
+            codes: codes.into_inner(),
+            debug_info: debug_info.into_inner(),
+        })
+    }
+
+    /// Returns a source line numbered from 1.
+    pub fn line(&self, line: usize) -> Option<&str> {
+        self.source.lines().nth(line.checked_sub(1)?)
+    }
+
+    pub fn source_map(&self) -> &SourceMap {
+        &self.source_map
+    }
+
+    pub fn codes(&self) -> &[u8] {
+        &self.codes
+    }
+
+    pub fn debug_info(&self) -> &DebugInfo {
+        &self.debug_info
+    }
+
+    /// Runs a program calling a hook before each instruction.
+    pub fn run(&self, hook: &mut impl Hook) -> Result<(), DebugError> {
+        Vm::new()
+            .run_with_hook(&self.codes, hook)
+            .map_err(|mut error| {
+                error.backtrace_mut().symbolize(&self.debug_info);
+                error.into()
+            })
+    }
+
+    /// Runs a program under a debugger.
+    pub fn debug(
+        &self,
+        debugger: &mut Debugger,
+        frontend: &mut impl Frontend,
+    ) -> Result<(), DebugError> {
+        let mut session = Session::new(debugger, frontend);
+
+        let result = Vm::new().run_with_hook(&self.codes, &mut session);
+
+        session.finish()?;
+
+        result.map_err(|mut error| {
+            error.backtrace_mut().symbolize(&self.debug_info);
+            error.into()
+        })
+    }
+}
@@ -20,3 +20,65 @@ Feature: Array
       | (= () ())                     | 1       |
       | (= () (set () 0 1))           | ()      |
       | (= (set () 0 1) (set () 0 1)) | 1       |
+
+  Scenario Outline: Use array operations
+    Given a file named "main.arc" with:
+    """
+    (let xs (range 1 5))
+    <literal>
+    """
+    When I successfully run `arachne main.arc`
+    Then the stdout should contain exactly:
+    """
+    <result>
+    """
+
+    Examples:
+      | literal                            | result            |
+      | (slice xs 1 3)                     | (2 3)             |
+      | (concat xs (range 5 7))            | (1 2 3 4 5 6)     |
+      | (push xs 5)                        | (1 2 3 4 5)       |
+      | (pop xs)                           | (1 2 3)           |
+      | (reverse xs)                       | (4 3 2 1)         |
+      | (sort (reverse xs))                | (1 2 3 4)         |
+      | (sort xs (fn (x y) (> x y)))       | (4 3 2 1)         |
+      | (map (fn (x) (* x x)) xs)          | (1 4 9 16)        |
+      | (filter (fn (x) (> x 2)) xs)       | (3 4)             |
+      | (fold (fn (x y) (+ x y)) 0 xs)     | 10                |
+
+  Scenario: Map an array with a recursive function
+    Given a file named "main.arc" with:
+    """
+    (let-rec
+      sum
+      (fn (xs)
+        (fold (fn (x y) (+ x y)) 0 (map (fn (x) (if (> x 1) (sum (range x)) x)) xs))))
+
+    (sum (range 5))
+    """
+    When I successfully run `arachne main.arc`
+    Then the stdout should contain exactly:
+    """
+    8
+    """
+
+  Scenario: Update an array in a loop
+    Given a file named "main.arc" with:
+    """
+    (let-rec
+      fill
+      (fn (xs i n)
+        (if (= i n)
+          xs
+          (fill (set xs i i) (+ i 1) n))))
+
+    (let xs (fill () 0 100000))
+    (len xs)
+    (get xs 99999)
+    """
+    When I successfully run `arachne main.arc`
+    Then the stdout should contain exactly:
+    """
+    100000
+    99999
+    """
@@ -0,0 +1,30 @@
+Feature: Backtrace
+  Scenario: Print a backtrace on stack overflow
+    Given a file named "main.arc" with:
+    """
+    (let-rec
+      g
+      (fn (x)
+        (+ 1 (g x))))
+
+    (let-rec
+      f
+      (fn (x)
+        (if (= x 0)
+          (g x)
+          (f (- x 1)))))
+
+    (f 3)
+    """
+    When I run `arachne main.arc`
+    Then the exit status should not be 0
+    And the stderr should contain:
+    """
+    call stack overflow
+      #0 g at line 4
+    """
+    And the stderr should contain:
+    """
+         (4 frames elided by tail calls)
+      #256 <top level> at line 13
+    """
@@ -0,0 +1,52 @@
+Feature: Build
+  Background:
+    Given a file named "main.arc" with:
+    """
+    (let-rec
+      f
+      (fn (x)
+        (if (= x 0)
+          1
+          (* x (f (- x 1))))))
+
+    (f 5)
+    """
+
+  Scenario: Build an executable
+    When I successfully run `arachne build main.arc`
+    And I successfully run `./main`
+    Then the stdout should contain exactly:
+    """
+    120
+    """
+
+  Scenario: Build an executable at a path
+    When I successfully run `arachne build --output factorial main.arc`
+    And I successfully run `./factorial`
+    Then the stdout should contain exactly:
+    """
+    120
+    """
+
+  Scenario: Build a WebAssembly module
+    When I successfully run `arachne build --wasm main.arc`
+    Then a file named "main.wasm" should exist
+    And a file named "main" should not exist
+
+  Scenario: Fail to build a WebAssembly module with an unsupported primitive
+    Given a file named "main.arc" with:
+    """
+    (display 42)
+    """
+    When I run `arachne build --wasm main.arc`
+    Then the exit status should not be 0
+    And the stderr should contain "primitive not supported: display"
+
+  Scenario: Fail to build an invalid source file
+    Given a file named "main.arc" with:
+    """
+    (+ 1
+    """
+    When I run `arachne build main.arc`
+    Then the exit status should not be 0
+    And a file named "main" should not exist
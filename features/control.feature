@@ -19,3 +19,90 @@ Feature: Control flow
       | (if 1 42 0 13 13) | 42     |
       | (if 0 13 1 42 13) | 42     |
       | (if 0 13 0 13 42) | 42     |
+
+  Scenario Outline: Use a do expression
+    Given a file named "main.arc" with:
+    """
+    <expression>
+    """
+    When I run `arachne` interactively
+    And I pipe in the file "main.arc"
+    Then the stdout should contain exactly:
+    """
+    <result>
+    """
+
+    Examples:
+      | expression                | result |
+      | (do)                      | ()     |
+      | (do 42)                   | 42     |
+      | (do 13 42)                | 42     |
+      | (do (let x 42) x)         | 42     |
+      | (if 1 (do (let x 42) x))  | 42     |
+
+  Scenario Outline: Use a loop expression
+    Given a file named "main.arc" with:
+    """
+    <expression>
+    """
+    When I run `arachne` interactively
+    And I pipe in the file "main.arc"
+    Then the stdout should contain exactly:
+    """
+    <result>
+    """
+
+    Examples:
+      | expression                                                 | result |
+      | (loop (x 42) x)                                            | 42     |
+      | (loop (i 0 x 0) (if (< i 10) (recur (+ i 1) (+ x i)) x))   | 45     |
+      | (loop (i 0 j 1) (if (< i 3) (recur (+ j 1) i) j))          | 1      |
+      | (+ 1 (loop (i 3) (if (> i 1) (recur (- i 1)) i)))          | 2      |
+      | (while 0 42)                                               | ()     |
+
+  Scenario: Recur in a non-tail position
+    Given a file named "main.arc" with:
+    """
+    (loop (x 1) (+ 1 (recur x)))
+    """
+    When I run `arachne` interactively
+    And I pipe in the file "main.arc"
+    Then the exit status should not be 0
+    And the stderr should contain:
+    """
+    recur not in tail position of loop: (recur x)
+    """
+
+  Scenario Outline: Use a match expression
+    Given a file named "main.arc" with:
+    """
+    <expression>
+    """
+    When I run `arachne` interactively
+    And I pipe in the file "main.arc"
+    Then the stdout should contain exactly:
+    """
+    <result>
+    """
+
+    Examples:
+      | expression                                                  | result |
+      | (match 1 (1 42) (_ 13))                                     | 42     |
+      | (match 2 (1 42) (x x))                                      | 2      |
+      | (match (set () 0 1) ((x) x))                                | 1      |
+      | (match (set (set () 0 1) 1 2) ((x & xs) xs))                | (2)    |
+      | (match 3 (x when (< x 0) 0) (x x))                          | 3      |
+      | (match 3 (1 42))                                            | ()     |
+
+  Scenario: Match an unreachable pattern
+    Given a file named "main.arc" with:
+    """
+    (match 1 (x 1) (2 2))
+    """
+    When I run `arachne` interactively
+    And I pipe in the file "main.arc"
+    Then the exit status should not be 0
+    And the stderr should contain:
+    """
+    unreachable pattern: 2
+    """
@@ -0,0 +1,59 @@
+Feature: Debug
+  Scenario: Stop at a breakpoint
+    Given a file named "main.arc" with:
+    """
+    (let-rec
+      f
+      (fn (x)
+        (if
+          (= x 0) 42
+          (f (- x 1)))))
+
+    (f 2)
+    """
+    And a file named "commands.txt" with:
+    """
+    break f
+    continue
+    backtrace
+    stack
+    quit
+    """
+    When I run `arachne debug main.arc` interactively
+    And I pipe in the file "commands.txt"
+    Then the stdout should contain:
+    """
+    stopped at line 5 in f (breakpoint)
+    """
+    And the stdout should contain:
+    """
+    #0 f at line 5
+    #1 <top level> at line 8
+    """
+    And the stdout should contain:
+    """
+    x = 2
+    """
+
+  Scenario: Step over lines
+    Given a file named "main.arc" with:
+    """
+    (+ 1 2)
+    (+ 3 4)
+    """
+    And a file named "commands.txt" with:
+    """
+    next
+    next
+    """
+    When I run `arachne debug main.arc` interactively
+    And I pipe in the file "commands.txt"
+    Then the stdout should contain exactly:
+    """
+    stopped at line 1 in <top level> (entry)
+       1 | (+ 1 2)
+    (debug) 3
+    stopped at line 2 in <top level> (step)
+       2 | (+ 3 4)
+    (debug) 7
+    """
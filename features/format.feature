@@ -0,0 +1,50 @@
+Feature: Format
+  Scenario: Format a file
+    Given a file named "main.arc" with:
+    """
+    (let   x 42) ; foo
+
+
+    (let-rec f (fn (x) (if (= x 0) 42 (= x 1) 13 (f (- x 1)))))
+    """
+    When I successfully run `arachne fmt main.arc`
+    Then the file "main.arc" should contain exactly:
+    """
+    (let x 42) ; foo
+
+    (let-rec
+      f
+      (fn (x)
+        (if
+          (= x 0) 42
+          (= x 1) 13
+          (f (- x 1)))))
+
+    """
+
+  Scenario: Check a formatted file
+    Given a file named "main.arc" with:
+    """
+    (let x 42)
+
+    """
+    When I successfully run `arachne fmt --check main.arc`
+    Then the stdout should contain exactly ""
+
+  Scenario: Check an unformatted file
+    Given a file named "main.arc" with:
+    """
+    (let   x 42)
+    """
+    When I run `arachne fmt --check main.arc`
+    Then the exit status should not be 0
+
+  Scenario: Check unformatted standard input
+    Given a file named "main.arc" with:
+    """
+    (let   x 42)
+    """
+    When I run `arachne fmt --check` interactively
+    And I pipe in the file "main.arc"
+    Then the exit status should not be 0
+    And the stdout should contain exactly ""
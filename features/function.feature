@@ -41,6 +41,29 @@ Feature: Function
     42
     """
 
+  Scenario: Define mutually recursive functions
+    Given a file named "main.arc" with:
+    """
+    (let-rec
+      even
+      (fn (x)
+        (if (= x 0)
+          1
+          (odd (- x 1))))
+      odd
+      (fn (x)
+        (if (= x 0)
+          0
+          (even (- x 1)))))
+    (even 1000000)
+    """
+    When I run `arachne` interactively
+    And I pipe in the file "main.arc"
+    Then the stdout should contain exactly:
+    """
+    1
+    """
+
   Scenario: Create a closure
     Given a file named "main.arc" with:
     """
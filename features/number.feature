@@ -29,11 +29,13 @@ Feature: Number
     """
 
     Examples:
-      | literal | result |
-      | (+ 1 2) | 3      |
-      | (- 2 1) | 1      |
-      | (* 2 3) | 6      |
-      | (/ 6 2) | 3      |
+      | literal   | result |
+      | (+ 1 2)   | 3      |
+      | (- 2 1)   | 1      |
+      | (* 2 3)   | 6      |
+      | (/ 6 2)   | 3      |
+      | (+ 1 2 3) | 6      |
+      | (- 6 2 1) | 3      |
 
   Scenario Outline: Use comparison operations
     Given a file named "main.arc" with:
@@ -63,6 +65,9 @@ Feature: Number
       | (>= -1 0) | ()     |
       | (>= 0 0)  | 1      |
       | (>= 1 0)  | 1      |
+      | (< 0 1 2) | 1      |
+      | (< 0 2 1) | ()     |
+      | (= 1 1 1) | 1      |
 
   Scenario Outline: Use boolean operations
     Given a file named "main.arc" with:
@@ -77,12 +82,14 @@ Feature: Number
     """
 
     Examples:
-      | literal   | result |
-      | (not 0)   | 1      |
-      | (not 1)   | ()     |
-      | (and 0 1) | ()     |
-      | (and 1 1) | 1      |
-      | (and 1 2) | 2      |
-      | (or 0 0)  | ()     |
-      | (or 0 1)  | 1      |
-      | (or 0 2)  | 2      |
+      | literal     | result |
+      | (not 0)     | 1      |
+      | (not 1)     | ()     |
+      | (and 0 1)   | ()     |
+      | (and 1 1)   | 1      |
+      | (and 1 2)   | 2      |
+      | (or 0 0)    | ()     |
+      | (or 0 1)    | 1      |
+      | (or 0 2)    | 2      |
+      | (and 1 2 3) | 3      |
+      | (or 0 0 3)  | 3      |
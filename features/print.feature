@@ -0,0 +1,21 @@
+Feature: Print
+  Scenario Outline: Print values
+    Given a file named "main.arc" with:
+    """
+    <literal>
+    """
+    When I run `arachne` interactively
+    And I pipe in the file "main.arc"
+    Then the stdout should contain exactly:
+    """
+    <result>
+    """
+
+    Examples:
+      | literal                    | result   |
+      | (repr 42)                  | 42       |
+      | (repr ())                  | ()       |
+      | (repr (set () 0 42))       | (42)     |
+      | (repr (repr 42))           | \\42     |
+      | (display (repr 42))        | 42       |
+      | (display (set () 1 42))    | (() 42)  |
@@ -0,0 +1,21 @@
+Feature: Profile
+  Scenario: Profile a program
+    Given a file named "main.arc" with:
+    """
+    (let-rec
+      f
+      (fn (x)
+        (if (= x 0)
+          0
+          (+ 1 (f (- x 1))))))
+
+    (f 3)
+    """
+    When I successfully run `arachne profile --period 1 --folded stacks.txt main.arc`
+    Then the stdout should contain exactly:
+    """
+    3
+    """
+    And the stderr should contain "samples every 1 instructions"
+    And the stderr should contain "100.00%  <top level>"
+    And the file "stacks.txt" should contain "<top level>;f;f;f;f "
@@ -0,0 +1,38 @@
+Feature: Reference
+  Scenario Outline: Use primitive operations
+    Given a file named "main.arc" with:
+    """
+    <literal>
+    """
+    When I run `arachne` interactively
+    And I pipe in the file "main.arc"
+    Then the stdout should contain exactly:
+    """
+    <result>
+    """
+
+    Examples:
+      | literal                          | result |
+      | (deref (ref 42))                 | 42     |
+      | (set! (ref 0) 42)                | 42     |
+      | (ref 42)                         | <ref>  |
+      | (let ((x (ref 42))) (= x x))     | 1      |
+      | (= (ref 42) (ref 42))            | ()     |
+      | (deref 42)                       | ()     |
+
+  Scenario: Share a counter between closures
+    Given a file named "main.arc" with:
+    """
+    (let n (ref 0))
+    (let increment (fn () (set! n (+ (deref n) 1))))
+    (increment)
+    (increment)
+    (deref n)
+    """
+    When I successfully run `arachne main.arc`
+    Then the stdout should contain exactly:
+    """
+    1
+    2
+    2
+    """
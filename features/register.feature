@@ -0,0 +1,19 @@
+Feature: Register machine
+  Scenario Outline: Run a program on a register machine
+    Given a file named "main.arc" with:
+    """
+    <source>
+    """
+    When I successfully run `arachne --register main.arc`
+    Then the stdout should contain exactly:
+    """
+    <result>
+    """
+
+    Examples:
+      | source                                                                  | result |
+      | (+ 1 2)                                                                 | 3      |
+      | (let x 42) (if x (- x 1) x)                                             | 41     |
+      | (let f (fn (x y) (+ x y))) (f 42 2045)                                  | 2087   |
+      | (let x 1) (let f (fn (y) (fn (z) (+ x (+ y z))))) ((f 2) 3)             | 6      |
+      | (let-rec f (fn (x y) (if (= x 0) y (f (- x 1) (+ x y))))) (f 100000 0) | 5000050000 |
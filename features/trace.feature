@@ -0,0 +1,41 @@
+Feature: Trace
+  Background:
+    Given a file named "main.arc" with:
+    """
+    (let x 1)
+    (let f
+      (fn (y)
+        (+ x y)))
+    (f 2)
+    """
+
+  Scenario: Trace instructions
+    When I successfully run `arachne trace main.arc`
+    Then the stdout should contain exactly:
+    """
+    3
+    """
+    And the stderr should contain "call 1"
+    And the stderr should contain "environment 0"
+
+  Scenario: Trace instructions as JSON lines
+    When I successfully run `arachne trace --json --output trace.jsonl main.arc`
+    Then the file "trace.jsonl" should contain:
+    """
+    {"address":0,"depth":0,"instruction":"float64 1","top":null}
+    """
+
+  Scenario: Measure line coverage
+    When I successfully run `arachne coverage --lcov lcov.info main.arc`
+    Then the stderr should contain "4/4 lines covered"
+    And the file "lcov.info" should contain:
+    """
+    SF:main.arc
+    DA:1,1
+    DA:2,1
+    DA:4,1
+    DA:5,1
+    LF:4
+    LH:4
+    end_of_record
+    """
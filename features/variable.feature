@@ -41,3 +41,18 @@ Feature: Variable
     """
     2
     """
+
+  Scenario: Define local variables in an expression
+    Given a file named "main.arc" with:
+    """
+    (let x 1)
+    (+ x (let ((x 2) (y (+ x 1))) (* x y)))
+    x
+    """
+    When I run `arachne` interactively
+    And I pipe in the file "main.arc"
+    Then the stdout should contain exactly:
+    """
+    7
+    1
+    """
@@ -0,0 +1,241 @@
+use parse::{Node, NodeKind};
+
+const MAX_WIDTH: usize = 80;
+const INDENT_WIDTH: usize = 2;
+// `if` expressions with more than one condition are always broken into lines.
+const MAX_FLAT_IF_LENGTH: usize = 4;
+
+enum Item<'a> {
+    Node(&'a Node),
+    Comment { text: &'a str, trailing: bool },
+    BlankLine,
+}
+
+/// Formats a concrete syntax tree into a canonical source code.
+pub fn format(nodes: &[Node]) -> String {
+    let mut string = String::new();
+
+    for item in collect_items(nodes) {
+        match item {
+            Item::Node(node) => {
+                string.push_str(&format_node(node, 0));
+                string.push('\n');
+            }
+            Item::Comment { text, trailing } => {
+                if trailing {
+                    string.pop();
+                    string.push(' ');
+                }
+
+                string.push_str(&format_comment(text));
+                string.push('\n');
+            }
+            Item::BlankLine => string.push('\n'),
+        }
+    }
+
+    string
+}
+
+fn collect_items(nodes: &[Node]) -> Vec<Item<'_>> {
+    let mut items = vec![];
+    let mut newline_count = 0;
+
+    for node in nodes {
+        match node.kind() {
+            NodeKind::Whitespace(string) => {
+                newline_count += string.matches('\n').count();
+                continue;
+            }
+            NodeKind::Comment(text) if newline_count == 0 && !items.is_empty() => {
+                items.push(Item::Comment {
+                    text,
+                    trailing: true,
+                });
+            }
+            kind => {
+                if newline_count > 1 && !items.is_empty() {
+                    items.push(Item::BlankLine);
+                }
+
+                items.push(if let NodeKind::Comment(text) = kind {
+                    Item::Comment {
+                        text,
+                        trailing: false,
+                    }
+                } else {
+                    Item::Node(node)
+                });
+            }
+        }
+
+        newline_count = 0;
+    }
+
+    items
+}
+
+fn format_node(node: &Node, indent: usize) -> String {
+    if let Some(string) = format_flat(node) {
+        if indent + string.len() <= MAX_WIDTH {
+            return string;
+        }
+    }
+
+    match node.kind() {
+        NodeKind::Array(nodes) => format_array(nodes, indent),
+        _ => node.to_string(),
+    }
+}
+
+fn format_flat(node: &Node) -> Option<String> {
+    match node.kind() {
+        NodeKind::Array(nodes) => {
+            let head = node.elements()?.next().and_then(Node::atom);
+
+            if head.as_deref() == Some("if") && node.elements()?.count() > MAX_FLAT_IF_LENGTH {
+                return None;
+            }
+
+            // Bindings, function bodies and conditions broken into lines are
+            // kept broken.
+            if matches!(head.as_deref(), Some("let" | "let-rec" | "fn" | "if"))
+                && nodes.iter().any(
+                    |node| matches!(node.kind(), NodeKind::Whitespace(string) if string.contains('\n')),
+                )
+            {
+                return None;
+            }
+
+            let mut strings = vec![];
+
+            for node in nodes {
+                match node.kind() {
+                    NodeKind::Comment(_) => return None,
+                    NodeKind::Whitespace(_) => {}
+                    _ => strings.push(format_flat(node)?),
+                }
+            }
+
+            Some(format!("({})", strings.join(" ")))
+        }
+        NodeKind::Atom(string) => Some(string.clone()),
+        NodeKind::Comment(_) | NodeKind::Whitespace(_) => None,
+    }
+}
+
+fn format_array(nodes: &[Node], indent: usize) -> String {
+    let items = collect_items(nodes);
+    let head = match items.first() {
+        Some(Item::Node(node)) => node.atom(),
+        _ => None,
+    };
+    let (head_count, body_indent, paired) = match head.as_deref() {
+        Some("let" | "let-rec" | "if") => (1, indent + INDENT_WIDTH, true),
+        Some("fn") => (2, indent + INDENT_WIDTH, false),
+        Some(head) => (2, indent + head.len() + 2, false),
+        None => (1, indent + 1, false),
+    };
+
+    let mut string = String::from("(");
+    let mut items = items.into_iter().peekable();
+
+    for index in 0..head_count {
+        let Some(Item::Node(node)) = items.next_if(|item| matches!(item, Item::Node(_))) else {
+            break;
+        };
+
+        if index > 0 {
+            string.push(' ');
+        }
+
+        string.push_str(&format_node(node, column(indent, &string)));
+    }
+
+    let mut group = vec![];
+    let mut commented = false;
+
+    for item in items {
+        commented = matches!(item, Item::Comment { .. });
+
+        match item {
+            Item::Node(node) => {
+                group.push(node);
+
+                if !paired || group.len() == 2 {
+                    push_group(&mut string, &group, body_indent);
+                    group.clear();
+                }
+            }
+            Item::Comment { text, trailing } => {
+                push_group(&mut string, &group, body_indent);
+                group.clear();
+
+                if !trailing {
+                    push_line(&mut string, body_indent);
+                } else {
+                    string.push(' ');
+                }
+
+                string.push_str(&format_comment(text));
+            }
+            Item::BlankLine => {
+                push_group(&mut string, &group, body_indent);
+                group.clear();
+                string.push('\n');
+            }
+        }
+    }
+
+    push_group(&mut string, &group, body_indent);
+
+    if commented {
+        push_line(&mut string, body_indent);
+    }
+
+    string.push(')');
+
+    string
+}
+
+fn push_group(string: &mut String, nodes: &[&Node], indent: usize) {
+    if nodes.is_empty() {
+        return;
+    }
+
+    if let Some(flat) = nodes
+        .iter()
+        .map(|node| format_flat(node))
+        .collect::<Option<Vec<_>>>()
+        .map(|strings| strings.join(" "))
+    {
+        if indent + flat.len() <= MAX_WIDTH {
+            push_line(string, indent);
+            string.push_str(&flat);
+
+            return;
+        }
+    }
+
+    for node in nodes {
+        push_line(string, indent);
+        string.push_str(&format_node(node, indent));
+    }
+}
+
+fn push_line(string: &mut String, indent: usize) {
+    string.push('\n');
+    string.extend((0..indent).map(|_| ' '));
+}
+
+fn format_comment(text: &str) -> String {
+    format!(";{}", text.trim_end())
+}
+
+fn column(indent: usize, string: &str) -> usize {
+    if let Some(index) = string.rfind('\n') {
+        string.len() - index - 1
+    } else {
+        indent + string.len()
+    }
+}
@@ -0,0 +1,166 @@
+mod formatter;
+
+pub use formatter::format;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use parse::parse_concrete;
+    use pretty_assertions::assert_eq;
+
+    fn format_source(source: &str) -> String {
+        format(&parse_concrete(source).unwrap())
+    }
+
+    fn assert_formatted(source: &str) {
+        assert_eq!(format_source(source), source);
+    }
+
+    #[test]
+    fn format_empty() {
+        assert_eq!(format_source(""), "");
+        assert_eq!(format_source("\n\n"), "");
+    }
+
+    #[test]
+    fn format_atom() {
+        assert_eq!(format_source("foo"), "foo\n");
+    }
+
+    #[test]
+    fn format_array() {
+        assert_eq!(format_source("(  foo\n bar\tbaz )"), "(foo bar baz)\n");
+    }
+
+    #[test]
+    fn format_escaped_atom() {
+        assert_formatted("(foo\\ bar \\42)\n");
+    }
+
+    #[test]
+    fn format_top_level_forms() {
+        assert_eq!(format_source("(foo)\n(bar)"), "(foo)\n(bar)\n");
+    }
+
+    #[test]
+    fn keep_blank_line() {
+        assert_eq!(format_source("(foo)\n\n\n\n(bar)\n\n"), "(foo)\n\n(bar)\n");
+    }
+
+    #[test]
+    fn keep_comments() {
+        assert_formatted("; foo\n(foo) ; bar\n\n; baz\n");
+    }
+
+    #[test]
+    fn trim_comment() {
+        assert_eq!(format_source(";foo  \n"), ";foo\n");
+    }
+
+    #[test]
+    fn format_let_rec() {
+        assert_formatted(
+            &[
+                "(let-rec",
+                "  fibonacci",
+                "  (fn (x)",
+                "    (if",
+                "      (= x 0) 0",
+                "      (= x 1) 1",
+                "      (+ (fibonacci (- x 1)) (fibonacci (- x 2))))))",
+                "",
+            ]
+            .join("\n"),
+        );
+    }
+
+    #[test]
+    fn format_if() {
+        assert_formatted("(if x 1 2)\n");
+        assert_formatted(&["(if", "  x 1", "  y 2", "  3)", ""].join("\n"));
+    }
+
+    #[test]
+    fn keep_broken_if() {
+        assert_formatted(&["(fn (x y)", "  (if", "    (= x 0) y", "    (f x y)))", ""].join("\n"));
+        assert_eq!(
+            format_source("(if x\n1 2)"),
+            ["(if", "  x 1", "  2)", ""].join("\n")
+        );
+    }
+
+    #[test]
+    fn keep_broken_function() {
+        assert_formatted(&["(let-rec", "  f", "  (fn (x)", "    (f x)))", ""].join("\n"));
+        assert_eq!(
+            format_source("(let-rec f (fn (x)\n(f x)))"),
+            ["(let-rec", "  f", "  (fn (x)", "    (f x)))", ""].join("\n")
+        );
+        assert_formatted("(let-rec f (fn (x) (f x)))\n");
+    }
+
+    #[test]
+    fn format_let_rec_with_multiple_bindings() {
+        assert_formatted(
+            &[
+                "(let-rec",
+                "  foo (fn () (bar 0 1 2 3 4 5 6 7 8 9 10 11 12 13 14 15 16 17 18 19 20))",
+                "  bar (fn () (foo)))",
+                "",
+            ]
+            .join("\n"),
+        );
+    }
+
+    #[test]
+    fn format_let() {
+        assert_formatted(
+            &[
+                "(let",
+                "  foo",
+                "  (fn (x)",
+                "    (let y (+ x 1))",
+                "    (+ x y aaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaa)))",
+                "",
+            ]
+            .join("\n"),
+        );
+    }
+
+    #[test]
+    fn format_call() {
+        assert_formatted(
+            &[
+                "(foo (aaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaa bbbbbbbbbbbbbbbbbbbbbbbbbbbbb)",
+                "     (ccccccccccccccccccccccccccccccccccccccccccccccccccccccccccccccccccccccc)",
+                "     d)",
+                "",
+            ]
+            .join("\n"),
+        );
+    }
+
+    #[test]
+    fn format_comment_in_array() {
+        assert_eq!(
+            format_source("(fn (x) ; foo\n ; bar\n x)"),
+            ["(fn (x) ; foo", "  ; bar", "  x)", ""].join("\n"),
+        );
+    }
+
+    #[test]
+    fn format_comment_at_end_of_array() {
+        assert_eq!(
+            format_source("(foo x ; bar\n)"),
+            ["(foo x ; bar", "     )", ""].join("\n"),
+        );
+    }
+
+    #[test]
+    fn format_idempotently() {
+        let source = "(let-rec f (fn (x) (if (= x 0) 42 ; foo\n (f (- x 1)))))\n\n\n(f 3)";
+        let formatted = format_source(source);
+
+        assert_eq!(format_source(&formatted), formatted);
+    }
+}
@@ -2,16 +2,19 @@ use std::{
     error::Error,
     fmt::{self, Display, Formatter},
 };
+use vm::RuntimeError;
 
 #[derive(Debug)]
 pub enum InterpretError {
     Other(Box<dyn Error>),
+    Runtime(RuntimeError),
 }
 
 impl Display for InterpretError {
     fn fmt(&self, formatter: &mut Formatter) -> fmt::Result {
         match self {
             Self::Other(error) => write!(formatter, "{error}"),
+            Self::Runtime(error) => write!(formatter, "{error}"),
         }
     }
 }
@@ -0,0 +1,62 @@
+use std::{
+    cell::RefCell,
+    fmt::{self, Debug, Formatter, Write},
+    rc::Rc,
+};
+use vm::{Input, Stdin, Stdout};
+
+/// An output shared by VMs of an interpreter.
+#[derive(Clone)]
+pub struct SharedOutput(Rc<RefCell<dyn Write>>);
+
+impl SharedOutput {
+    pub fn new(output: impl Write + 'static) -> Self {
+        Self(Rc::new(RefCell::new(output)))
+    }
+}
+
+impl Write for SharedOutput {
+    fn write_str(&mut self, string: &str) -> fmt::Result {
+        self.0.borrow_mut().write_str(string)
+    }
+}
+
+impl Default for SharedOutput {
+    fn default() -> Self {
+        Self::new(Stdout)
+    }
+}
+
+impl Debug for SharedOutput {
+    fn fmt(&self, formatter: &mut Formatter) -> fmt::Result {
+        write!(formatter, "SharedOutput")
+    }
+}
+
+/// An input source shared by VMs of an interpreter.
+#[derive(Clone)]
+pub struct SharedInput(Rc<RefCell<dyn Input>>);
+
+impl SharedInput {
+    pub fn new(input: impl Input + 'static) -> Self {
+        Self(Rc::new(RefCell::new(input)))
+    }
+}
+
+impl Input for SharedInput {
+    fn read_line(&mut self) -> Result<Option<String>, fmt::Error> {
+        self.0.borrow_mut().read_line()
+    }
+}
+
+impl Default for SharedInput {
+    fn default() -> Self {
+        Self::new(Stdin)
+    }
+}
+
+impl Debug for SharedInput {
+    fn fmt(&self, formatter: &mut Formatter) -> fmt::Result {
+        write!(formatter, "SharedInput")
+    }
+}
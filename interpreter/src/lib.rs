@@ -1,41 +1,319 @@
 mod error;
+mod io;
 
 use async_stream::try_stream;
-use compiler::Compiler;
+use compiler::{Compiler, RegisterCompiler};
 use error::InterpretError;
-use futures::{pin_mut, Stream, StreamExt};
-use runtime::Value;
-use std::{cell::RefCell, error::Error};
-use vm::Vm;
+use futures::{pin_mut, ready, stream::poll_fn, Stream, StreamExt};
+use io::{SharedInput, SharedOutput};
+use parse::SourceMap;
+use runtime::{Isolate, Value};
+use std::{
+    cell::{Cell, RefCell},
+    error::Error,
+    io::{BufRead, Write},
+    rc::Rc,
+    task::Poll,
+};
+use vm::{DebugInfo, Reader, RegisterInstruction, RegisterVm, Vm, Writer};
 
+/// An interpreter.
+///
+/// Each interpreter has its own symbol table. All of its memory is released
+/// when it is dropped. Symbols in given values are copied into the table, so
+/// the values can outlive the interpreter.
+///
+/// Programs write values of top-level expressions into standard output and
+/// read standard input by default.
 #[derive(Debug, Default)]
 pub struct Interpreter {
     codes: RefCell<Vec<u8>>,
+    debug_info: RefCell<DebugInfo>,
+    register_instructions: Option<RefCell<Vec<RegisterInstruction>>>,
+    isolate: Isolate,
+    output: SharedOutput,
+    input: SharedInput,
 }
 
 impl Interpreter {
     pub fn new() -> Self {
         Self {
             codes: Default::default(),
+            debug_info: Default::default(),
+            register_instructions: None,
+            isolate: Isolate::new(),
+            output: Default::default(),
+            input: Default::default(),
         }
     }
 
+    /// Creates an interpreter running programs on a register machine.
+    pub fn with_register_vm() -> Self {
+        Self {
+            register_instructions: Some(Default::default()),
+            ..Self::new()
+        }
+    }
+
+    /// Sets an output into which values of top-level expressions are written.
+    pub fn set_output(&mut self, output: impl Write + 'static) {
+        self.output = SharedOutput::new(Writer::new(output));
+    }
+
+    /// Sets an input source of programs.
+    pub fn set_input(&mut self, input: impl BufRead + 'static) {
+        self.input = SharedInput::new(Reader::new(input));
+    }
+
     pub fn interpret<'a, E: Error + 'static>(
         &'a self,
         values: &'a mut (impl Stream<Item = Result<Value, E>> + Unpin),
     ) -> impl Stream<Item = Result<(), InterpretError>> + 'a {
         try_stream! {
-            let mut compiler = Compiler::new(&self.codes);
-            let mut vm = Vm::new();
-            let results = compiler.compile(values);
+            let source_map = SourceMap::default();
+            let results = self.interpret_with_source_map(values, &source_map);
+
+            pin_mut!(results);
+
+            while let Some(result) = results.next().await {
+                yield result?;
+            }
+        }
+    }
+
+    /// Interprets values with their spans in a source map.
+    ///
+    /// Spans are used to report locations on runtime errors.
+    pub fn interpret_with_source_map<'a, E: Error + 'static>(
+        &'a self,
+        values: &'a mut (impl Stream<Item = Result<Value, E>> + Unpin),
+        source_map: &'a SourceMap,
+    ) -> impl Stream<Item = Result<(), InterpretError>> + 'a {
+        let next = Rc::new(Cell::new(None));
+        let mut results = Box::pin(self.interpret_in_isolate(
+            {
+                let next = next.clone();
+                poll_fn(move |_| Poll::Ready(next.take()))
+            },
+            source_map,
+        ));
+
+        // The isolate is entered only while the VM works on a value so that
+        // streams of different interpreters can be polled and dropped in any
+        // order and on any thread. Values are received out of the isolate and
+        // their symbols are interned into it.
+        poll_fn(move |context| {
+            let Some(result) = ready!(values.poll_next_unpin(context)) else {
+                return Poll::Ready(None);
+            };
+            // SAFETY: The guard is dropped before the end of the poll and
+            // values interned into the isolate are not leaked out of the
+            // interpreter.
+            let _guard = unsafe { self.isolate.enter() };
+
+            next.set(Some(result.map(|value| intern(&value))));
+
+            // A value is interpreted without waiting for anything else.
+            results.as_mut().poll_next(context)
+        })
+    }
+
+    fn interpret_in_isolate<'a, E: Error + 'static>(
+        &'a self,
+        mut values: impl Stream<Item = Result<Value, E>> + Unpin + 'a,
+        source_map: &'a SourceMap,
+    ) -> impl Stream<Item = Result<(), InterpretError>> + 'a {
+        try_stream! {
+            if let Some(instructions) = &self.register_instructions {
+                let mut compiler = RegisterCompiler::new(instructions);
+                let mut vm = RegisterVm::with_output(self.output.clone());
+                vm.set_input(self.input.clone());
+                let results = compiler.compile(&mut values);
+
+                pin_mut!(results);
+
+                while let Some(result) = results.next().await {
+                    result.map_err(|error| InterpretError::Other(error.into()))?;
+                    let result = vm.run(&instructions.borrow());
+
+                    result.map_err(InterpretError::Runtime)?;
+                    yield ();
+                }
+
+                return;
+            }
+
+            let mut compiler = Compiler::with_debug_info(&self.codes, source_map, &self.debug_info);
+            let mut vm = Vm::with_output(self.output.clone());
+            vm.set_input(self.input.clone());
+            let results = compiler.compile(&mut values);
 
             pin_mut!(results);
 
             while let Some(result) = results.next().await {
                 result.map_err(|error| InterpretError::Other(error.into()))?;
-                vm.run(&self.codes.borrow());
+                let result = vm.run(&self.codes.borrow());
+
+                result.map_err(|mut error| {
+                    error.backtrace_mut().symbolize(&self.debug_info.borrow());
+                    InterpretError::Runtime(error)
+                })?;
                 yield ();
             }
         }
     }
 }
+
+// Interns symbols in a value into the current symbol table.
+fn intern(value: &Value) -> Value {
+    if let Some(symbol) = value.to_symbol() {
+        symbol.as_str().into()
+    } else if let Some(array) = value.as_array() {
+        (0..array.len_usize())
+            .map(|index| intern(array.get_usize(index)))
+            .collect::<Vec<_>>()
+            .into()
+    } else {
+        value.clone()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::stream::iter;
+    use pretty_assertions::assert_eq;
+    use runtime::Symbol;
+    use std::{convert::Infallible, io, rc::Rc};
+
+    #[derive(Clone, Default)]
+    struct Buffer(Rc<RefCell<Vec<u8>>>);
+
+    impl Write for Buffer {
+        fn write(&mut self, bytes: &[u8]) -> io::Result<usize> {
+            self.0.borrow_mut().write(bytes)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    async fn interpret(mut interpreter: Interpreter, source: &str) -> String {
+        let buffer = Buffer::default();
+        interpreter.set_output(buffer.clone());
+
+        let mut lines = iter(
+            source
+                .lines()
+                .map(|line| Ok::<_, Infallible>(line.to_owned())),
+        );
+        let values = parse::parse(&mut lines);
+
+        pin_mut!(values);
+
+        let results = interpreter.interpret(&mut values);
+
+        pin_mut!(results);
+
+        while let Some(result) = results.next().await {
+            result.unwrap();
+        }
+
+        String::from_utf8(buffer.0.take()).unwrap()
+    }
+
+    /// Sources and their outputs, which are the same on stack and register
+    /// VMs.
+    const CASES: &[(&str, &str)] = &[
+        ("(let x 42)\nx\n(+ x 1)", "42\n43\n"),
+        (
+            "(let-rec even (fn (x) (if (= x 0) 1 (odd (- x 1)))) odd (fn (x) (if (= x 0) 0 (even (- x 1)))))\n(even 100000)\n(odd 100001)",
+            "1\n1\n",
+        ),
+        (
+            "(let-rec sum (fn (n) (loop (i 0 x 0) (if (> i n) x (recur (+ i 1) (+ x i))))))\n(sum 100000)\n(+ 1 (loop (i 3) (if (> i 1) (recur (- i 1)) i)))",
+            "5000050000\n2\n",
+        ),
+        (
+            "(let n (ref 0))\n(let f (fn () (set! n (+ (deref n) 1))))\n(f)\n(f)\n(deref n)",
+            "1\n2\n2\n",
+        ),
+        (
+            "(let xs (range 1 6))\n(fold (fn (x y) (+ x y)) 0 (map (fn (x) (* x x)) (filter (fn (x) (!= x 3)) xs)))\n(sort (concat (reverse xs) (slice xs 1 3)) (fn (x y) (< x y)))\n(pop (push xs 42))",
+            "46\n(1 2 2 3 3 4 5)\n(1 2 3 4 5)\n",
+        ),
+        ("(- 5)\n(- 10 3 2)\n(and)\n(or)\n(or () 3)", "-5\n5\n1\n()\n3\n"),
+    ];
+
+    #[tokio::test]
+    async fn interpret_on_stack_and_register_vms() {
+        for (source, output) in CASES {
+            for new in [Interpreter::new, Interpreter::with_register_vm] {
+                assert_eq!(&interpret(new(), source).await, output, "{source}");
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn drop_interleaved_interpreters() {
+        let mut values = iter([Ok::<_, Infallible>(Value::from([
+            "let".into(),
+            "x".into(),
+            1.0.into(),
+        ]))]);
+        let mut other_values = iter([
+            Ok::<_, Infallible>(Value::from(["let".into(), "y".into(), 2.0.into()])),
+            Ok("y".into()),
+        ]);
+        let buffer = Buffer::default();
+        let mut other = Interpreter::new();
+        other.set_output(buffer.clone());
+        let mut other_results = Box::pin(other.interpret(&mut other_values));
+
+        {
+            let mut one = Interpreter::new();
+            one.set_output(Buffer::default());
+            let mut results = Box::pin(one.interpret(&mut values));
+
+            results.next().await.unwrap().unwrap();
+            other_results.next().await.unwrap().unwrap();
+        }
+
+        other_results.next().await.unwrap().unwrap();
+        assert!(other_results.next().await.is_none());
+        drop(other_results);
+
+        assert_eq!(String::from_utf8(buffer.0.take()).unwrap(), "2\n");
+        assert_eq!(other.isolate.symbol_count(), 2);
+    }
+
+    #[tokio::test]
+    async fn create_values_out_of_isolate() {
+        let symbols = RefCell::new(vec![]);
+
+        {
+            let mut interpreter = Interpreter::new();
+            interpreter.set_output(Buffer::default());
+            let mut values = iter(0..1).map(|_| {
+                let symbol = Value::from("x");
+                symbols.borrow_mut().push(symbol.clone());
+
+                Ok::<_, Infallible>(Value::from(["let".into(), symbol, 42.0.into()]))
+            });
+            let results = interpreter.interpret(&mut values);
+
+            pin_mut!(results);
+
+            while let Some(result) = results.next().await {
+                result.unwrap();
+            }
+
+            assert_eq!(interpreter.isolate.symbol_count(), 2);
+            // Symbols are identified by their memory in symbol tables.
+            assert_eq!(symbols.borrow()[0].to_symbol(), Some(Symbol::from("x")));
+        }
+
+        assert_eq!(symbols.borrow()[0].to_symbol().unwrap().as_str(), "x");
+    }
+}
@@ -0,0 +1,120 @@
+use compiler::{Binding, BindingKind, Reference, Scopes, PRIMITIVES, SPECIAL_FORMS};
+use parse::{Node, SourceMap, Span};
+
+/// A result of scope resolution by the compiler on concrete syntax trees.
+#[derive(Debug, Default)]
+pub struct Analysis {
+    scopes: Scopes,
+}
+
+impl Analysis {
+    pub fn new(source: &str, nodes: &[Node]) -> Self {
+        let (values, source_map) = SourceMap::new(source, nodes);
+
+        Self {
+            scopes: Scopes::resolve(&values, &source_map),
+        }
+    }
+
+    pub fn bindings(&self) -> &[Binding] {
+        self.scopes.bindings()
+    }
+
+    /// Finds a binding defined or referenced at an offset.
+    pub fn find_binding(&self, offset: usize) -> Option<usize> {
+        self.bindings()
+            .iter()
+            .position(|binding| binding.span().contains(offset))
+            .or_else(|| {
+                self.scopes
+                    .references()
+                    .iter()
+                    .find(|reference| reference.span().contains(offset))?
+                    .binding()
+            })
+    }
+
+    /// Returns spans of a binding and its references.
+    pub fn occurrences(&self, binding: usize) -> impl Iterator<Item = Span> + '_ {
+        [self.bindings()[binding].span()].into_iter().chain(
+            self.scopes
+                .references()
+                .iter()
+                .filter(move |reference| reference.binding() == Some(binding))
+                .map(Reference::span),
+        )
+    }
+
+    /// Returns names visible at an offset including primitives.
+    pub fn visible_names(&self, offset: usize) -> Vec<(&str, Option<BindingKind>)> {
+        let mut names = Vec::<(&str, Option<BindingKind>)>::new();
+
+        for binding in self.bindings().iter().rev() {
+            if binding.scope().contains(offset)
+                && !names.iter().any(|(name, _)| *name == binding.name())
+            {
+                names.push((binding.name(), Some(binding.kind())));
+            }
+        }
+
+        names.extend(
+            SPECIAL_FORMS
+                .iter()
+                .chain(PRIMITIVES)
+                .map(|&name| (name, None)),
+        );
+
+        names
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use parse::parse_concrete;
+    use pretty_assertions::assert_eq;
+
+    fn analyze(source: &str) -> Analysis {
+        Analysis::new(source, &parse_concrete(source).unwrap())
+    }
+
+    #[test]
+    fn find_binding() {
+        let source = "(let x 42)\n(+ x x)";
+        let analysis = analyze(source);
+
+        assert_eq!(analysis.find_binding(5), Some(0));
+        assert_eq!(analysis.find_binding(source.rfind('x').unwrap()), Some(0));
+        assert_eq!(analysis.find_binding(0), None);
+    }
+
+    #[test]
+    fn find_occurrences() {
+        let source = "(let x 42)\n(+ x x)";
+        let analysis = analyze(source);
+
+        assert_eq!(
+            analysis.occurrences(0).collect::<Vec<_>>(),
+            [Span::new(5, 6), Span::new(14, 15), Span::new(16, 17)]
+        );
+    }
+
+    #[test]
+    fn list_visible_names() {
+        let source = "(let x 42)\n(fn (y) y)";
+        let analysis = analyze(source);
+        let names = analysis.visible_names(source.rfind('y').unwrap());
+
+        assert_eq!(
+            &names[..2],
+            [
+                ("y", Some(BindingKind::Argument)),
+                ("x", Some(BindingKind::Let))
+            ]
+        );
+        assert!(names.contains(&("get", None)));
+        assert!(!analysis
+            .visible_names(0)
+            .contains(&("x", Some(BindingKind::Let))));
+    }
+}
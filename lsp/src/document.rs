@@ -0,0 +1,114 @@
+use crate::analysis::Analysis;
+use parse::{parse_concrete, Node, Span, SyntaxError};
+
+/// A text document opened in an editor.
+#[derive(Debug)]
+pub struct Document {
+    text: String,
+    nodes: Result<Vec<Node>, SyntaxError>,
+    analysis: Analysis,
+}
+
+impl Document {
+    pub fn new(text: String) -> Self {
+        let nodes = parse_concrete(&text);
+        let analysis = nodes
+            .as_ref()
+            .map(|nodes| Analysis::new(&text, nodes))
+            .unwrap_or_default();
+
+        Self {
+            text,
+            nodes,
+            analysis,
+        }
+    }
+
+    pub fn nodes(&self) -> &Result<Vec<Node>, SyntaxError> {
+        &self.nodes
+    }
+
+    pub fn analysis(&self) -> &Analysis {
+        &self.analysis
+    }
+
+    /// Converts a byte offset into a line and a character offset in UTF-16.
+    pub fn position(&self, offset: usize) -> (usize, usize) {
+        let prefix = &self.text[..offset.min(self.text.len())];
+        let line_start = prefix
+            .rfind('\n')
+            .map(|index| index + 1)
+            .unwrap_or_default();
+
+        (
+            prefix.matches('\n').count(),
+            prefix[line_start..].encode_utf16().count(),
+        )
+    }
+
+    /// Converts a line and a character offset in UTF-16 into a byte offset.
+    pub fn offset(&self, line: usize, character: usize) -> usize {
+        let line_start = if let Some(line) = line.checked_sub(1) {
+            match self.text.match_indices('\n').nth(line) {
+                Some((index, _)) => index + 1,
+                // Positions after the end of a document are clamped to its end.
+                None => return self.text.len(),
+            }
+        } else {
+            0
+        };
+        let mut count = 0;
+
+        for (index, current) in self.text[line_start..].char_indices() {
+            if count >= character || current == '\n' {
+                return line_start + index;
+            }
+
+            count += current.len_utf16();
+        }
+
+        self.text.len()
+    }
+
+    pub fn range(&self, span: Span) -> ((usize, usize), (usize, usize)) {
+        (self.position(span.start()), self.position(span.end()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn convert_positions() {
+        let document = Document::new("(let x 42)\n(f x)\n".into());
+
+        assert_eq!(document.position(0), (0, 0));
+        assert_eq!(document.position(5), (0, 5));
+        assert_eq!(document.position(11), (1, 0));
+        assert_eq!(document.position(14), (1, 3));
+
+        assert_eq!(document.offset(0, 0), 0);
+        assert_eq!(document.offset(0, 5), 5);
+        assert_eq!(document.offset(1, 0), 11);
+        assert_eq!(document.offset(1, 3), 14);
+        assert_eq!(document.offset(1, 42), 16);
+    }
+
+    #[test]
+    fn clamp_positions_after_end() {
+        let document = Document::new("(let x 42)\n(f x)".into());
+
+        assert_eq!(document.offset(1, 42), 16);
+        assert_eq!(document.offset(2, 0), 16);
+        assert_eq!(document.offset(42, 3), 16);
+    }
+
+    #[test]
+    fn convert_positions_in_utf16() {
+        let document = Document::new("(let 🕷 42)".into());
+
+        assert_eq!(document.position(9), (0, 7));
+        assert_eq!(document.offset(0, 7), 9);
+    }
+}
@@ -0,0 +1,36 @@
+use std::{
+    error::Error,
+    fmt::{self, Display, Formatter},
+    io,
+};
+
+#[derive(Debug)]
+pub enum LspError {
+    Io(io::Error),
+    Json(serde_json::Error),
+    Protocol(String),
+}
+
+impl Error for LspError {}
+
+impl Display for LspError {
+    fn fmt(&self, formatter: &mut Formatter) -> fmt::Result {
+        match self {
+            Self::Io(error) => write!(formatter, "{error}"),
+            Self::Json(error) => write!(formatter, "{error}"),
+            Self::Protocol(message) => write!(formatter, "protocol error: {message}"),
+        }
+    }
+}
+
+impl From<io::Error> for LspError {
+    fn from(error: io::Error) -> Self {
+        Self::Io(error)
+    }
+}
+
+impl From<serde_json::Error> for LspError {
+    fn from(error: serde_json::Error) -> Self {
+        Self::Json(error)
+    }
+}
@@ -0,0 +1,8 @@
+mod analysis;
+mod document;
+mod error;
+mod server;
+mod transport;
+
+pub use error::LspError;
+pub use server::Server;
@@ -0,0 +1,595 @@
+use crate::{
+    document::Document,
+    transport::{read_message, write_message},
+    LspError,
+};
+use compiler::{CompileError, Compiler, SPECIAL_FORMS};
+use futures::{executor::block_on, pin_mut, stream::iter, StreamExt};
+use parse::{Node, Span};
+use runtime::{Isolate, SPECIAL_CHARACTERS};
+use serde_json::{json, Value};
+use std::{
+    cell::RefCell,
+    collections::HashMap,
+    convert::Infallible,
+    io::{BufRead, Write},
+};
+
+const METHOD_NOT_FOUND: i64 = -32601;
+const INVALID_PARAMS: i64 = -32602;
+
+const FULL_SYNC: u64 = 1;
+const SEVERITY_ERROR: u64 = 1;
+
+const COMPLETION_FUNCTION: u64 = 3;
+const COMPLETION_VARIABLE: u64 = 6;
+const COMPLETION_KEYWORD: u64 = 14;
+
+type RequestResult = Result<Value, (i64, String)>;
+
+/// A language server communicating over a pair of streams.
+pub struct Server<R: BufRead, W: Write> {
+    input: R,
+    output: W,
+    documents: HashMap<String, Document>,
+}
+
+impl<R: BufRead, W: Write> Server<R, W> {
+    pub fn new(input: R, output: W) -> Self {
+        Self {
+            input,
+            output,
+            documents: Default::default(),
+        }
+    }
+
+    /// Serves requests until an `exit` notification or the end of input.
+    pub fn run(&mut self) -> Result<(), LspError> {
+        while let Some(message) = read_message(&mut self.input)? {
+            let method = message["method"].as_str().unwrap_or_default();
+            let params = &message["params"];
+
+            if let Some(id) = message.get("id") {
+                let response = match self.handle_request(method, params) {
+                    Ok(result) => json!({ "jsonrpc": "2.0", "id": id, "result": result }),
+                    Err((code, message)) => json!({
+                        "jsonrpc": "2.0",
+                        "id": id,
+                        "error": { "code": code, "message": message },
+                    }),
+                };
+
+                write_message(&mut self.output, &response)?;
+            } else if method == "exit" {
+                break;
+            } else {
+                self.handle_notification(method, params)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    fn handle_request(&mut self, method: &str, params: &Value) -> RequestResult {
+        match method {
+            "initialize" => Ok(json!({
+                "capabilities": {
+                    "textDocumentSync": FULL_SYNC,
+                    "definitionProvider": true,
+                    "hoverProvider": true,
+                    "completionProvider": {},
+                    "renameProvider": true,
+                },
+                "serverInfo": { "name": "arachne" },
+            })),
+            "shutdown" => Ok(Value::Null),
+            "textDocument/definition" => self.definition(params),
+            "textDocument/hover" => self.hover(params),
+            "textDocument/completion" => self.completion(params),
+            "textDocument/rename" => self.rename(params),
+            _ => Err((METHOD_NOT_FOUND, format!("method not found: {method}"))),
+        }
+    }
+
+    fn handle_notification(&mut self, method: &str, params: &Value) -> Result<(), LspError> {
+        let uri = params["textDocument"]["uri"].as_str().unwrap_or_default();
+
+        match method {
+            "textDocument/didOpen" => {
+                let text = params["textDocument"]["text"].as_str().unwrap_or_default();
+
+                self.update_document(uri, text.into())
+            }
+            "textDocument/didChange" => {
+                let Some(text) = params["contentChanges"]
+                    .as_array()
+                    .and_then(|changes| changes.last())
+                    .and_then(|change| change["text"].as_str())
+                else {
+                    return Ok(());
+                };
+
+                self.update_document(uri, text.into())
+            }
+            "textDocument/didClose" => {
+                self.documents.remove(uri);
+                self.publish_diagnostics(uri, vec![])
+            }
+            _ => Ok(()),
+        }
+    }
+
+    fn update_document(&mut self, uri: &str, text: String) -> Result<(), LspError> {
+        let document = Document::new(text);
+        let diagnostics = diagnose(&document);
+
+        self.documents.insert(uri.into(), document);
+        self.publish_diagnostics(uri, diagnostics)
+    }
+
+    fn publish_diagnostics(&mut self, uri: &str, diagnostics: Vec<Value>) -> Result<(), LspError> {
+        write_message(
+            &mut self.output,
+            &json!({
+                "jsonrpc": "2.0",
+                "method": "textDocument/publishDiagnostics",
+                "params": { "uri": uri, "diagnostics": diagnostics },
+            }),
+        )
+    }
+
+    fn definition(&self, params: &Value) -> RequestResult {
+        let (uri, document, offset) = self.locate(params)?;
+
+        Ok(document
+            .analysis()
+            .find_binding(offset)
+            .map(|binding| {
+                json!({
+                    "uri": uri,
+                    "range": range(document, document.analysis().bindings()[binding].span()),
+                })
+            })
+            .unwrap_or_default())
+    }
+
+    fn hover(&self, params: &Value) -> RequestResult {
+        let (_, document, offset) = self.locate(params)?;
+
+        Ok(document
+            .analysis()
+            .find_binding(offset)
+            .map(|binding| {
+                let binding = &document.analysis().bindings()[binding];
+
+                json!({
+                    "contents": {
+                        "kind": "markdown",
+                        "value": format!("`{}` {}", binding.kind().as_str(), binding.name()),
+                    },
+                    "range": range(document, binding.span()),
+                })
+            })
+            .unwrap_or_default())
+    }
+
+    fn completion(&self, params: &Value) -> RequestResult {
+        let (_, document, offset) = self.locate(params)?;
+
+        Ok(Value::Array(
+            document
+                .analysis()
+                .visible_names(offset)
+                .into_iter()
+                .map(|(name, kind)| {
+                    let (kind, detail) = match kind {
+                        Some(kind) => (COMPLETION_VARIABLE, kind.as_str()),
+                        None if SPECIAL_FORMS.contains(&name) => {
+                            (COMPLETION_KEYWORD, "special form")
+                        }
+                        None => (COMPLETION_FUNCTION, "primitive"),
+                    };
+
+                    json!({ "label": name, "kind": kind, "detail": detail })
+                })
+                .collect(),
+        ))
+    }
+
+    fn rename(&self, params: &Value) -> RequestResult {
+        let (uri, document, offset) = self.locate(params)?;
+        let name = params["newName"].as_str().unwrap_or_default();
+
+        if name.is_empty()
+            || name.contains(|character| SPECIAL_CHARACTERS.contains(character))
+            || name.parse::<f64>().is_ok()
+        {
+            return Err((INVALID_PARAMS, format!("invalid name: {name}")));
+        }
+
+        let Some(binding) = document.analysis().find_binding(offset) else {
+            return Ok(Value::Null);
+        };
+
+        Ok(json!({
+            "changes": {
+                uri: document
+                    .analysis()
+                    .occurrences(binding)
+                    .map(|span| json!({ "range": range(document, span), "newText": name }))
+                    .collect::<Vec<_>>(),
+            },
+        }))
+    }
+
+    fn locate<'a>(
+        &'a self,
+        params: &'a Value,
+    ) -> Result<(&'a str, &'a Document, usize), (i64, String)> {
+        let uri = params["textDocument"]["uri"].as_str().unwrap_or_default();
+        let Some(document) = self.documents.get(uri) else {
+            return Err((INVALID_PARAMS, format!("document not found: {uri}")));
+        };
+        let position = &params["position"];
+
+        Ok((
+            uri,
+            document,
+            document.offset(
+                position["line"].as_u64().unwrap_or_default() as usize,
+                position["character"].as_u64().unwrap_or_default() as usize,
+            ),
+        ))
+    }
+}
+
+fn diagnose(document: &Document) -> Vec<Value> {
+    let (span, message) = match document.nodes() {
+        Err(error) => (error.span(), error.to_string()),
+        Ok(nodes) => match compile(nodes) {
+            Some((span, error)) => (span, error.to_string()),
+            None => return vec![],
+        },
+    };
+
+    vec![json!({
+        "range": range(document, span),
+        "severity": SEVERITY_ERROR,
+        "source": "arachne",
+        "message": message,
+    })]
+}
+
+fn compile(nodes: &[Node]) -> Option<(Span, CompileError)> {
+    let isolate = Isolate::new();
+    // SAFETY: Values are dropped before the isolate.
+    let _guard = unsafe { isolate.enter() };
+    let statements = nodes
+        .iter()
+        .filter(|node| !node.is_trivia())
+        .collect::<Vec<_>>();
+    let codes = RefCell::new(vec![]);
+    let mut compiler = Compiler::new(&codes);
+    let mut values = iter(
+        statements
+            .iter()
+            .filter_map(|node| node.to_value())
+            .map(Ok::<_, Infallible>),
+    );
+
+    block_on(async {
+        let results = compiler.compile(&mut values);
+
+        pin_mut!(results);
+
+        let mut index = 0;
+
+        while let Some(result) = results.next().await {
+            if let Err(error) = result {
+                return Some((statements[index].span(), error));
+            }
+
+            index += 1;
+        }
+
+        None
+    })
+}
+
+fn range(document: &Document, span: Span) -> Value {
+    let ((start_line, start_character), (end_line, end_character)) = document.range(span);
+
+    json!({
+        "start": { "line": start_line, "character": start_character },
+        "end": { "line": end_line, "character": end_character },
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    const URI: &str = "file:///main.arc";
+
+    /// A scripted client which sends messages to a server at once and
+    /// collects its responses.
+    struct Client {
+        messages: Vec<u8>,
+        id: u64,
+    }
+
+    impl Client {
+        fn new() -> Self {
+            let mut client = Self {
+                messages: vec![],
+                id: 0,
+            };
+
+            client.request("initialize", json!({ "capabilities": {} }));
+            client.notify("initialized", json!({}));
+
+            client
+        }
+
+        fn request(&mut self, method: &str, params: Value) -> &mut Self {
+            self.id += 1;
+            self.send(
+                json!({ "jsonrpc": "2.0", "id": self.id, "method": method, "params": params }),
+            )
+        }
+
+        fn notify(&mut self, method: &str, params: Value) -> &mut Self {
+            self.send(json!({ "jsonrpc": "2.0", "method": method, "params": params }))
+        }
+
+        fn open(&mut self, text: &str) -> &mut Self {
+            self.notify(
+                "textDocument/didOpen",
+                json!({
+                    "textDocument": { "uri": URI, "languageId": "arachne", "version": 1, "text": text },
+                }),
+            )
+        }
+
+        fn request_at(&mut self, method: &str, line: u64, character: u64) -> &mut Self {
+            self.request(
+                method,
+                json!({
+                    "textDocument": { "uri": URI },
+                    "position": { "line": line, "character": character },
+                    "newName": "bar",
+                }),
+            )
+        }
+
+        fn send(&mut self, message: Value) -> &mut Self {
+            write_message(&mut self.messages, &message).unwrap();
+            self
+        }
+
+        fn run(&mut self) -> Vec<Value> {
+            self.request("shutdown", Value::Null);
+            self.notify("exit", Value::Null);
+
+            let mut output = vec![];
+
+            Server::new(self.messages.as_slice(), &mut output)
+                .run()
+                .unwrap();
+
+            let mut output = output.as_slice();
+            let mut messages = vec![];
+
+            while let Some(message) = read_message(&mut output).unwrap() {
+                messages.push(message);
+            }
+
+            // Skip responses to `initialize` and `shutdown`.
+            messages[1..messages.len() - 1].to_vec()
+        }
+    }
+
+    #[test]
+    fn initialize() {
+        let mut output = vec![];
+        let mut client = Client::new();
+
+        client.notify("exit", Value::Null);
+        Server::new(client.messages.as_slice(), &mut output)
+            .run()
+            .unwrap();
+
+        let message = read_message(&mut output.as_slice()).unwrap().unwrap();
+
+        assert_eq!(message["id"], 1);
+        assert_eq!(
+            message["result"]["capabilities"]["definitionProvider"],
+            true
+        );
+    }
+
+    #[test]
+    fn fail_on_unknown_method() {
+        let messages = Client::new().request("foo", Value::Null).run();
+
+        assert_eq!(messages[0]["error"]["code"], METHOD_NOT_FOUND);
+    }
+
+    mod diagnostics {
+        use super::*;
+        use pretty_assertions::assert_eq;
+
+        #[test]
+        fn publish_no_diagnostics() {
+            let messages = Client::new().open("(let x 42)\nx").run();
+
+            assert_eq!(messages[0]["method"], "textDocument/publishDiagnostics");
+            assert_eq!(messages[0]["params"]["diagnostics"], json!([]));
+        }
+
+        #[test]
+        fn publish_parse_error() {
+            let messages = Client::new().open("(let x 42))").run();
+
+            assert_eq!(
+                messages[0]["params"]["diagnostics"][0]["message"],
+                "unexpected closed parenthesis"
+            );
+            assert_eq!(
+                messages[0]["params"]["diagnostics"][0]["range"]["start"],
+                json!({ "line": 0, "character": 10 })
+            );
+        }
+
+        #[test]
+        fn publish_compile_error() {
+            let messages = Client::new().open("(let x 42)\n(+ x y)").run();
+
+            assert_eq!(
+                messages[0]["params"]["diagnostics"][0]["message"],
+                "variable not found: y"
+            );
+            assert_eq!(
+                messages[0]["params"]["diagnostics"][0]["range"],
+                json!({
+                    "start": { "line": 1, "character": 0 },
+                    "end": { "line": 1, "character": 7 },
+                })
+            );
+        }
+
+        #[test]
+        fn publish_diagnostics_on_change() {
+            let messages = Client::new()
+                .open("(let x 42)")
+                .notify(
+                    "textDocument/didChange",
+                    json!({
+                        "textDocument": { "uri": URI, "version": 2 },
+                        "contentChanges": [{ "text": "(let x" }],
+                    }),
+                )
+                .run();
+
+            assert_eq!(
+                messages[1]["params"]["diagnostics"][0]["message"],
+                "unexpected end of file"
+            );
+        }
+
+        #[test]
+        fn clear_diagnostics_on_close() {
+            let messages = Client::new()
+                .open("(let x")
+                .notify(
+                    "textDocument/didClose",
+                    json!({ "textDocument": { "uri": URI } }),
+                )
+                .run();
+
+            assert_eq!(messages[1]["params"]["diagnostics"], json!([]));
+        }
+    }
+
+    #[test]
+    fn go_to_definition() {
+        let messages = Client::new()
+            .open("(let-rec f (fn (x) (f x)))")
+            .request_at("textDocument/definition", 0, 20)
+            .run();
+
+        assert_eq!(
+            messages[1]["result"],
+            json!({
+                "uri": URI,
+                "range": {
+                    "start": { "line": 0, "character": 9 },
+                    "end": { "line": 0, "character": 10 },
+                },
+            })
+        );
+    }
+
+    #[test]
+    fn go_to_no_definition() {
+        let messages = Client::new()
+            .open("(+ 1 2)")
+            .request_at("textDocument/definition", 0, 1)
+            .run();
+
+        assert_eq!(messages[1]["result"], Value::Null);
+    }
+
+    #[test]
+    fn hover() {
+        let messages = Client::new()
+            .open("(let x 42)\nx")
+            .request_at("textDocument/hover", 1, 0)
+            .run();
+
+        assert_eq!(messages[1]["result"]["contents"]["value"], "`let` x");
+    }
+
+    #[test]
+    fn complete() {
+        let messages = Client::new()
+            .open("(let x 42)\n(fn (y) y)")
+            .request_at("textDocument/completion", 1, 8)
+            .run();
+        let items = messages[1]["result"].as_array().unwrap();
+
+        assert_eq!(
+            items[0],
+            json!({ "label": "y", "kind": COMPLETION_VARIABLE, "detail": "argument" })
+        );
+        assert_eq!(
+            items[1],
+            json!({ "label": "x", "kind": COMPLETION_VARIABLE, "detail": "let" })
+        );
+        assert!(items.contains(
+            &json!({ "label": "fn", "kind": COMPLETION_KEYWORD, "detail": "special form" })
+        ));
+        assert!(items.contains(
+            &json!({ "label": "get", "kind": COMPLETION_FUNCTION, "detail": "primitive" })
+        ));
+    }
+
+    #[test]
+    fn rename() {
+        let messages = Client::new()
+            .open("(let foo 42)\n(+ foo foo)")
+            .request_at("textDocument/rename", 0, 6)
+            .run();
+        let edits = messages[1]["result"]["changes"][URI].as_array().unwrap();
+
+        assert_eq!(edits.len(), 3);
+        assert_eq!(
+            edits[2],
+            json!({
+                "range": {
+                    "start": { "line": 1, "character": 7 },
+                    "end": { "line": 1, "character": 10 },
+                },
+                "newText": "bar",
+            })
+        );
+    }
+
+    #[test]
+    fn fail_to_rename_with_invalid_name() {
+        let messages = Client::new()
+            .open("(let foo 42)")
+            .request(
+                "textDocument/rename",
+                json!({
+                    "textDocument": { "uri": URI },
+                    "position": { "line": 0, "character": 6 },
+                    "newName": "a b",
+                }),
+            )
+            .run();
+
+        assert_eq!(messages[1]["error"]["code"], INVALID_PARAMS);
+    }
+}
@@ -0,0 +1,78 @@
+use crate::LspError;
+use serde_json::Value;
+use std::io::{BufRead, Write};
+
+const CONTENT_LENGTH: &str = "Content-Length";
+
+/// Reads a JSON-RPC message with its headers.
+pub fn read_message(input: &mut impl BufRead) -> Result<Option<Value>, LspError> {
+    let mut length = None;
+
+    loop {
+        let mut line = String::new();
+
+        if input.read_line(&mut line)? == 0 {
+            return Ok(None);
+        }
+
+        let line = line.trim_end();
+
+        if line.is_empty() {
+            break;
+        } else if let Some((name, value)) = line.split_once(':') {
+            if name.eq_ignore_ascii_case(CONTENT_LENGTH) {
+                length = Some(
+                    value
+                        .trim()
+                        .parse::<usize>()
+                        .map_err(|error| LspError::Protocol(error.to_string()))?,
+                );
+            }
+        }
+    }
+
+    let Some(length) = length else {
+        return Err(LspError::Protocol("missing content length".into()));
+    };
+    let mut body = vec![0; length];
+
+    input.read_exact(&mut body)?;
+
+    Ok(Some(serde_json::from_slice(&body)?))
+}
+
+/// Writes a JSON-RPC message with its headers.
+pub fn write_message(output: &mut impl Write, message: &Value) -> Result<(), LspError> {
+    let body = serde_json::to_string(message)?;
+
+    write!(output, "{CONTENT_LENGTH}: {}\r\n\r\n{body}", body.len())?;
+    output.flush()?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn write_and_read_message() {
+        let message = json!({ "jsonrpc": "2.0", "method": "exit" });
+        let mut buffer = vec![];
+
+        write_message(&mut buffer, &message).unwrap();
+
+        assert_eq!(read_message(&mut buffer.as_slice()).unwrap(), Some(message));
+    }
+
+    #[test]
+    fn read_end_of_input() {
+        assert_eq!(read_message(&mut [].as_slice()).unwrap(), None);
+    }
+
+    #[test]
+    fn fail_to_read_message_without_content_length() {
+        assert!(read_message(&mut "Foo: 42\r\n\r\n".as_bytes()).is_err());
+    }
+}
@@ -0,0 +1,339 @@
+use crate::{ParseError, SyntaxError};
+use core::fmt::{self, Display, Formatter};
+use runtime::{unescape_character, Value, ESCAPE_CHARACTER, SPECIAL_CHARACTERS};
+use std::{iter::Peekable, str::CharIndices};
+
+/// A span in a source code as byte offsets.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct Span {
+    start: usize,
+    end: usize,
+}
+
+impl Span {
+    pub const fn new(start: usize, end: usize) -> Self {
+        Self { start, end }
+    }
+
+    pub const fn start(&self) -> usize {
+        self.start
+    }
+
+    pub const fn end(&self) -> usize {
+        self.end
+    }
+
+    pub const fn contains(&self, offset: usize) -> bool {
+        self.start <= offset && offset < self.end
+    }
+}
+
+/// A node in a concrete syntax tree.
+///
+/// Concrete syntax trees keep comments and whitespaces, so that they are
+/// printed back into their original source codes.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Node {
+    kind: NodeKind,
+    span: Span,
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum NodeKind {
+    Array(Vec<Node>),
+    /// An atom with its raw text including escapes.
+    Atom(String),
+    /// A comment without its leading `;`.
+    Comment(String),
+    Whitespace(String),
+}
+
+impl Node {
+    pub const fn new(kind: NodeKind, span: Span) -> Self {
+        Self { kind, span }
+    }
+
+    pub const fn kind(&self) -> &NodeKind {
+        &self.kind
+    }
+
+    pub const fn span(&self) -> Span {
+        self.span
+    }
+
+    pub fn is_trivia(&self) -> bool {
+        matches!(self.kind, NodeKind::Comment(_) | NodeKind::Whitespace(_))
+    }
+
+    /// Returns child nodes except trivia if a node is an array.
+    pub fn elements(&self) -> Option<impl Iterator<Item = &Node>> {
+        if let NodeKind::Array(nodes) = &self.kind {
+            Some(nodes.iter().filter(|node| !node.is_trivia()))
+        } else {
+            None
+        }
+    }
+
+    /// Returns an unescaped atom.
+    pub fn atom(&self) -> Option<String> {
+        let NodeKind::Atom(raw) = &self.kind else {
+            return None;
+        };
+        let mut string = String::with_capacity(raw.len());
+        let mut characters = raw.chars();
+
+        while let Some(character) = characters.next() {
+            string.push(if character == ESCAPE_CHARACTER {
+                unescape_character(characters.next()?)
+            } else {
+                character
+            });
+        }
+
+        Some(string)
+    }
+
+    /// Converts a node into a value as the parser does.
+    pub fn to_value(&self) -> Option<Value> {
+        Some(match &self.kind {
+            NodeKind::Array(_) => self
+                .elements()?
+                .filter_map(Self::to_value)
+                .collect::<Vec<_>>()
+                .into(),
+            NodeKind::Atom(raw) => {
+                let atom = self.atom()?;
+
+                if raw.contains(ESCAPE_CHARACTER) {
+                    atom.into()
+                } else {
+                    str::parse::<f64>(&atom)
+                        .map(Into::into)
+                        .unwrap_or_else(|_| atom.into())
+                }
+            }
+            NodeKind::Comment(_) | NodeKind::Whitespace(_) => return None,
+        })
+    }
+}
+
+impl Display for Node {
+    fn fmt(&self, formatter: &mut Formatter) -> fmt::Result {
+        match &self.kind {
+            NodeKind::Array(nodes) => {
+                write!(formatter, "(")?;
+
+                for node in nodes {
+                    write!(formatter, "{node}")?;
+                }
+
+                write!(formatter, ")")
+            }
+            NodeKind::Atom(string) | NodeKind::Whitespace(string) => {
+                write!(formatter, "{string}")
+            }
+            NodeKind::Comment(string) => write!(formatter, ";{string}"),
+        }
+    }
+}
+
+/// Parses a source code into a concrete syntax tree.
+pub fn parse_concrete(source: &str) -> Result<Vec<Node>, SyntaxError> {
+    let mut characters = source.char_indices().peekable();
+    let mut nodes = vec![];
+
+    while let Some(node) = parse_node(source, &mut characters)? {
+        nodes.push(node);
+    }
+
+    Ok(nodes)
+}
+
+fn parse_node(
+    source: &str,
+    characters: &mut Peekable<CharIndices>,
+) -> Result<Option<Node>, SyntaxError> {
+    let Some(&(start, character)) = characters.peek() else {
+        return Ok(None);
+    };
+
+    Ok(Some(match character {
+        '(' => {
+            characters.next();
+            let mut nodes = vec![];
+
+            loop {
+                match characters.peek() {
+                    Some(&(end, ')')) => {
+                        characters.next();
+
+                        break Node::new(NodeKind::Array(nodes), Span::new(start, end + 1));
+                    }
+                    Some(_) => nodes.extend(parse_node(source, characters)?),
+                    None => {
+                        return Err(SyntaxError::new(
+                            ParseError::EndOfFile,
+                            Span::new(start, source.len()),
+                        ))
+                    }
+                }
+            }
+        }
+        ')' => {
+            return Err(SyntaxError::new(
+                ParseError::ClosedParenthesis,
+                Span::new(start, start + 1),
+            ))
+        }
+        ';' => {
+            characters.next();
+            let end = skip_while(source, characters, |character| character != '\n');
+
+            Node::new(
+                NodeKind::Comment(source[start + 1..end].into()),
+                Span::new(start, end),
+            )
+        }
+        ' ' | '\t' | '\n' | '\r' => {
+            let end = skip_while(source, characters, |character| {
+                matches!(character, ' ' | '\t' | '\n' | '\r')
+            });
+
+            Node::new(
+                NodeKind::Whitespace(source[start..end].into()),
+                Span::new(start, end),
+            )
+        }
+        _ => {
+            let mut escaped = false;
+            let end = skip_while(source, characters, |character| {
+                let special = !escaped && SPECIAL_CHARACTERS.contains(character);
+
+                escaped = !escaped && character == ESCAPE_CHARACTER;
+
+                !special
+            });
+
+            Node::new(
+                NodeKind::Atom(source[start..end].into()),
+                Span::new(start, end),
+            )
+        }
+    }))
+}
+
+fn skip_while(
+    source: &str,
+    characters: &mut Peekable<CharIndices>,
+    mut predicate: impl FnMut(char) -> bool,
+) -> usize {
+    while let Some(&(index, character)) = characters.peek() {
+        if !predicate(character) {
+            return index;
+        }
+
+        characters.next();
+    }
+
+    source.len()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    fn parse_values(source: &str) -> Vec<Value> {
+        parse_concrete(source)
+            .unwrap()
+            .iter()
+            .filter_map(Node::to_value)
+            .collect()
+    }
+
+    #[test]
+    fn parse_atom() {
+        assert_eq!(
+            parse_concrete("foo").unwrap(),
+            vec![Node::new(NodeKind::Atom("foo".into()), Span::new(0, 3))]
+        );
+    }
+
+    #[test]
+    fn parse_array() {
+        assert_eq!(
+            parse_concrete("(foo)").unwrap(),
+            vec![Node::new(
+                NodeKind::Array(vec![Node::new(
+                    NodeKind::Atom("foo".into()),
+                    Span::new(1, 4)
+                )]),
+                Span::new(0, 5)
+            )]
+        );
+    }
+
+    #[test]
+    fn parse_comment() {
+        assert_eq!(
+            parse_concrete("; foo\nbar").unwrap(),
+            vec![
+                Node::new(NodeKind::Comment(" foo".into()), Span::new(0, 5)),
+                Node::new(NodeKind::Whitespace("\n".into()), Span::new(5, 6)),
+                Node::new(NodeKind::Atom("bar".into()), Span::new(6, 9)),
+            ]
+        );
+    }
+
+    #[test]
+    fn parse_escaped_atom() {
+        assert_eq!(
+            parse_concrete("a\\ b").unwrap(),
+            vec![Node::new(NodeKind::Atom("a\\ b".into()), Span::new(0, 4))]
+        );
+    }
+
+    #[test]
+    fn fail_to_parse_unclosed_parenthesis() {
+        let error = parse_concrete("(foo").unwrap_err();
+
+        assert!(matches!(error.error(), ParseError::EndOfFile));
+        assert_eq!(error.span(), Span::new(0, 4));
+    }
+
+    #[test]
+    fn fail_to_parse_closed_parenthesis() {
+        let error = parse_concrete("foo)").unwrap_err();
+
+        assert!(matches!(error.error(), ParseError::ClosedParenthesis));
+        assert_eq!(error.span(), Span::new(3, 4));
+    }
+
+    #[test]
+    fn print_source() {
+        let source = "; comment\n(let-rec f\n  (fn (x) ; foo\n    (f x)))\n\n\n(f\t42)\n";
+
+        assert_eq!(
+            parse_concrete(source)
+                .unwrap()
+                .iter()
+                .map(ToString::to_string)
+                .collect::<String>(),
+            source
+        );
+    }
+
+    #[test]
+    fn convert_to_values() {
+        assert_eq!(
+            parse_values("foo 42 \\42 (a ; comment\n (b)) ()"),
+            vec![
+                "foo".into(),
+                42.0.into(),
+                "42".into(),
+                ["a".into(), ["b".into()].into()].into(),
+                [].into(),
+            ]
+        );
+    }
+}
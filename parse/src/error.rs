@@ -1,3 +1,4 @@
+use crate::Span;
 use std::{
     convert::Infallible,
     error::Error,
@@ -49,3 +50,32 @@ impl From<Infallible> for ParseError {
         Self::Other(error.into())
     }
 }
+
+/// A parse error located in a source code.
+#[derive(Debug)]
+pub struct SyntaxError {
+    error: ParseError,
+    span: Span,
+}
+
+impl SyntaxError {
+    pub const fn new(error: ParseError, span: Span) -> Self {
+        Self { error, span }
+    }
+
+    pub const fn error(&self) -> &ParseError {
+        &self.error
+    }
+
+    pub const fn span(&self) -> Span {
+        self.span
+    }
+}
+
+impl Error for SyntaxError {}
+
+impl Display for SyntaxError {
+    fn fmt(&self, formatter: &mut Formatter) -> fmt::Result {
+        write!(formatter, "{}", self.error)
+    }
+}
@@ -1,8 +1,14 @@
+mod cst;
 mod error;
 mod parser;
+mod source_map;
 mod utility;
 
-pub use self::error::ParseError;
+pub use self::{
+    cst::{parse_concrete, Node, NodeKind, Span},
+    error::{ParseError, SyntaxError},
+    source_map::{SourceMap, Spans},
+};
 use async_stream::try_stream;
 use futures::Stream;
 use parser::Parser;
@@ -12,9 +18,22 @@ use std::error::Error;
 pub fn parse<E: Error + 'static>(
     stream: &mut (impl Stream<Item = Result<String, E>> + Unpin),
 ) -> impl Stream<Item = Result<Value, ParseError>> + '_ {
-    try_stream! {
-        let mut parser = Parser::new();
+    parse_with_parser(stream, Parser::new())
+}
+
+/// Parses values recording their spans in a source map.
+pub fn parse_with_source_map<'a, E: Error + 'static>(
+    stream: &'a mut (impl Stream<Item = Result<String, E>> + Unpin),
+    source_map: &'a SourceMap,
+) -> impl Stream<Item = Result<Value, ParseError>> + 'a {
+    parse_with_parser(stream, Parser::with_source_map(source_map))
+}
 
+fn parse_with_parser<'a, E: Error + 'static>(
+    stream: &'a mut (impl Stream<Item = Result<String, E>> + Unpin),
+    mut parser: Parser<'a>,
+) -> impl Stream<Item = Result<Value, ParseError>> + 'a {
+    try_stream! {
         loop {
             let expression = parser.parse_expression(stream).await?;
 
@@ -90,6 +109,51 @@ mod tests {
         );
     }
 
+    #[tokio::test]
+    async fn parse_repr() {
+        for value in [
+            Value::from(42.0),
+            (-0.5).into(),
+            "foo".into(),
+            "42".into(),
+            "a (b) ;c\\d\te\n".into(),
+            ["foo".into(), "42".into(), 42.0.into()].into(),
+            [[].into(), [["a b".into()].into()].into()].into(),
+        ] {
+            assert_eq!(
+                parse_string(&value.repr().to_string()).await.unwrap(),
+                vec![value]
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn parse_with_spans() {
+        let source_map = SourceMap::default();
+        let stream = lines_stream("foo\n(bar\n(baz))");
+
+        pin_mut!(stream);
+
+        let values = parse_with_source_map(&mut stream, &source_map)
+            .collect::<Vec<_>>()
+            .await
+            .into_iter()
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        let foo = source_map.take_spans(&values[0]);
+        let spans = source_map.take_spans(&values[1]);
+        let array = values[1].as_array().unwrap();
+
+        assert_eq!(foo.value(&values[0]), Some(Span::new(0, 3)));
+        assert_eq!(spans.array(array), Some(Span::new(4, 15)));
+        assert_eq!(
+            spans.array(array.get_usize(1).as_array().unwrap()),
+            Some(Span::new(9, 14))
+        );
+        assert_eq!(spans.value(array.get_usize(0)), Some(Span::new(5, 8)));
+        assert_eq!(source_map.line(9), 3);
+    }
+
     #[tokio::test]
     async fn parse_nested_array() {
         assert_eq!(
@@ -1,28 +1,60 @@
-use super::error::ParseError;
+use super::{error::ParseError, SourceMap, Span};
 use async_recursion::async_recursion;
 use futures::{Stream, StreamExt};
-use runtime::Value;
+use runtime::{unescape_character, Value, ESCAPE_CHARACTER, SPECIAL_CHARACTERS};
 use std::{collections::VecDeque, error::Error, marker::Unpin};
 
-const SPECIAL_CHARACTERS: &str = "(); \t\n";
 const SYMBOL_CAPACITY: usize = 8;
 const ARRAY_CAPACITY: usize = 8;
 const BUFFER_CAPACITY: usize = 2 << 6;
 
-pub struct Parser {
+pub struct Parser<'a> {
     buffer: VecDeque<char>,
+    source_map: Option<&'a SourceMap>,
+    // Spans of values in a top-level value being parsed in a preorder.
+    spans: Vec<Span>,
+    // Byte offsets of the next character and the end of read lines.
+    offset: usize,
+    end: usize,
 }
 
-impl Parser {
+impl<'a> Parser<'a> {
     pub fn new() -> Self {
         Self {
             buffer: VecDeque::with_capacity(BUFFER_CAPACITY),
+            source_map: None,
+            spans: vec![],
+            offset: 0,
+            end: 0,
+        }
+    }
+
+    /// Creates a parser recording spans of values in a source map.
+    pub fn with_source_map(source_map: &'a SourceMap) -> Self {
+        Self {
+            source_map: Some(source_map),
+            ..Self::new()
         }
     }
 
     pub async fn parse_expression<E: Error + 'static>(
         &mut self,
         lines: &mut (impl Stream<Item = Result<String, E>> + Unpin),
+    ) -> Result<Option<Value>, ParseError> {
+        self.spans.clear();
+
+        let value = self.parse_value(lines).await?;
+
+        if let (Some(source_map), Some(_)) = (self.source_map, &value) {
+            source_map.insert_spans(self.spans.split_off(0));
+        }
+
+        Ok(value)
+    }
+
+    async fn parse_value<E: Error + 'static>(
+        &mut self,
+        lines: &mut (impl Stream<Item = Result<String, E>> + Unpin),
     ) -> Result<Option<Value>, ParseError> {
         loop {
             if let Some(character) = self.read_character(lines).await? {
@@ -42,17 +74,29 @@ impl Parser {
         }
     }
 
-    #[allow(clippy::multiple_bound_locations)]
+    // `async_recursion` adds `#[must_use]` to a boxed future which is already
+    // `#[must_use]`.
+    #[allow(clippy::double_must_use, clippy::multiple_bound_locations)]
     #[async_recursion(?Send)]
     async fn parse_parentheses<E: Error + 'static>(
         &mut self,
         lines: &mut (impl Stream<Item = Result<String, E>> + Unpin),
     ) -> Result<Value, ParseError> {
+        let start = self.offset - 1;
+        let index = self.spans.len();
         let mut vector = Vec::with_capacity(ARRAY_CAPACITY);
 
+        self.insert_span(start);
+
         loop {
-            match self.parse_expression(lines).await {
-                Err(ParseError::ClosedParenthesis) => return Ok(vector.into()),
+            match self.parse_value(lines).await {
+                Err(ParseError::ClosedParenthesis) => {
+                    if let Some(span) = self.spans.get_mut(index) {
+                        *span = Span::new(start, self.offset);
+                    }
+
+                    return Ok(Value::from(vector));
+                }
                 Err(error) => return Err(error),
                 Ok(None) => return Err(ParseError::EndOfFile),
                 Ok(Some(expression)) => vector.push(expression),
@@ -65,25 +109,45 @@ impl Parser {
         lines: &mut (impl Stream<Item = Result<String, E>> + Unpin),
         character: char,
     ) -> Result<Value, ParseError> {
+        let start = self.offset - character.len_utf8();
         let mut string = String::with_capacity(SYMBOL_CAPACITY);
+        let mut escaped = false;
+        let mut character = Some(character);
+
+        while let Some(current) = character {
+            if current == ESCAPE_CHARACTER {
+                string.push(self.parse_escape(lines).await?);
+                escaped = true;
+            } else if SPECIAL_CHARACTERS.contains(current) {
+                self.offset -= current.len_utf8();
+                self.buffer.push_front(current);
+                break;
+            } else {
+                string.push(current);
+            }
 
-        string.push(character);
-
-        loop {
-            let Some(character) = self.read_character(lines).await? else {
-                return Ok(string.into());
-            };
+            character = self.read_character(lines).await?;
+        }
 
-            if SPECIAL_CHARACTERS.contains(character) {
-                self.buffer.push_front(character);
+        self.insert_span(start);
 
-                return Ok(str::parse::<f64>(&string)
-                    .map(Into::into)
-                    .unwrap_or_else(|_| string.into()));
-            }
+        Ok(if escaped {
+            string.into()
+        } else {
+            str::parse::<f64>(&string)
+                .map(Into::into)
+                .unwrap_or_else(|_| string.into())
+        })
+    }
 
-            string.push(character);
-        }
+    async fn parse_escape<E: Error + 'static>(
+        &mut self,
+        lines: &mut (impl Stream<Item = Result<String, E>> + Unpin),
+    ) -> Result<char, ParseError> {
+        self.read_character(lines)
+            .await?
+            .map(unescape_character)
+            .ok_or(ParseError::EndOfFile)
     }
 
     async fn parse_comment<E: Error + 'static>(
@@ -101,16 +165,33 @@ impl Parser {
     ) -> Result<Option<char>, ParseError> {
         if self.buffer.is_empty() {
             if let Some(result) = lines.next().await {
-                self.buffer.extend(
-                    result
-                        .map_err(|error| ParseError::Other(error.into()))?
-                        .chars(),
-                );
+                let line = result.map_err(|error| ParseError::Other(error.into()))?;
+
+                if let Some(source_map) = self.source_map {
+                    source_map.insert_line(self.end);
+                }
+
+                self.end += line.len() + 1;
+                self.buffer.extend(line.chars());
                 self.buffer.push_back('\n');
             }
         }
 
-        Ok(self.buffer.pop_front())
+        let character = self.buffer.pop_front();
+
+        if let Some(character) = character {
+            self.offset += character.len_utf8();
+        }
+
+        Ok(character)
+    }
+
+    // Records a span of a value from an offset to the current one. Spans of
+    // arrays are updated when they are closed.
+    fn insert_span(&mut self, start: usize) {
+        if self.source_map.is_some() {
+            self.spans.push(Span::new(start, self.offset));
+        }
     }
 }
 
@@ -153,4 +234,18 @@ mod tests {
     async fn parse_array() {
         assert_eq!(parse("(foo)").await.unwrap(), Some(["foo".into()].into()));
     }
+
+    #[tokio::test]
+    async fn parse_escaped_number() {
+        assert_eq!(parse("\\42").await.unwrap(), Some("42".into()));
+    }
+
+    #[tokio::test]
+    async fn parse_escaped_characters() {
+        assert_eq!(parse("a\\ b").await.unwrap(), Some("a b".into()));
+        assert_eq!(parse("\\(a\\)").await.unwrap(), Some("(a)".into()));
+        assert_eq!(parse("a\\;b").await.unwrap(), Some("a;b".into()));
+        assert_eq!(parse("a\\tb\\nc").await.unwrap(), Some("a\tb\nc".into()));
+        assert_eq!(parse("a\\\\b").await.unwrap(), Some("a\\b".into()));
+    }
 }
@@ -0,0 +1,237 @@
+use crate::{Node, NodeKind, Span};
+use runtime::{Array, Value};
+use std::{
+    cell::RefCell,
+    collections::{HashMap, VecDeque},
+};
+
+/// A map from values to their spans in a source code.
+///
+/// Spans of each top-level value are recorded in a preorder of its values
+/// until they are taken by [`SourceMap::take_spans`]. So a map does not grow
+/// with values which are already compiled.
+///
+/// A map can be shared with a streaming parser which records spans while
+/// values are consumed.
+#[derive(Debug, Default)]
+pub struct SourceMap {
+    line_starts: RefCell<Vec<usize>>,
+    statements: RefCell<VecDeque<Vec<Span>>>,
+}
+
+impl SourceMap {
+    /// Converts concrete syntax trees into values recording their spans.
+    pub fn new(source: &str, nodes: &[Node]) -> (Vec<Value>, Self) {
+        let map = Self {
+            line_starts: RefCell::new(
+                [0].into_iter()
+                    .chain(source.match_indices('\n').map(|(index, _)| index + 1))
+                    .collect(),
+            ),
+            statements: Default::default(),
+        };
+        let values = nodes
+            .iter()
+            .filter_map(|node| {
+                let mut spans = vec![];
+                let value = convert(node, &mut spans)?;
+                map.insert_spans(spans);
+                Some(value)
+            })
+            .collect();
+
+        (values, map)
+    }
+
+    /// Takes spans of the next top-level value.
+    ///
+    /// Top-level values must be passed in the order they are converted or
+    /// parsed. If a value does not match its spans, no span is found.
+    pub fn take_spans(&self, value: &Value) -> Spans {
+        let mut spans = Spans::default();
+
+        if let Some(statement) = self.statements.borrow_mut().pop_front() {
+            if count_values(value) == statement.len() {
+                spans.insert(value, &mut statement.into_iter());
+            }
+        }
+
+        spans
+    }
+
+    /// Returns a line number from 1 at a byte offset.
+    pub fn line(&self, offset: usize) -> usize {
+        self.line_starts
+            .borrow()
+            .partition_point(|&start| start <= offset)
+    }
+
+    pub(crate) fn insert_line(&self, start: usize) {
+        self.line_starts.borrow_mut().push(start);
+    }
+
+    /// Inserts spans of a top-level value in a preorder.
+    pub(crate) fn insert_spans(&self, spans: Vec<Span>) {
+        self.statements.borrow_mut().push_back(spans);
+    }
+}
+
+/// Spans of values in a top-level value.
+///
+/// Values are identified by their memory. So they must be kept alive while
+/// spans are used.
+#[derive(Debug, Default)]
+pub struct Spans {
+    arrays: HashMap<u64, Span>,
+    values: HashMap<usize, Span>,
+}
+
+impl Spans {
+    pub fn array(&self, array: &Array) -> Option<Span> {
+        self.arrays.get(&array.id()).copied()
+    }
+
+    pub fn value(&self, value: &Value) -> Option<Span> {
+        self.values
+            .get(&(core::ptr::from_ref(value) as usize))
+            .copied()
+    }
+
+    fn insert(&mut self, value: &Value, spans: &mut impl Iterator<Item = Span>) {
+        let Some(span) = spans.next() else {
+            return;
+        };
+
+        self.values
+            .insert(core::ptr::from_ref(value) as usize, span);
+
+        if let Some(array) = value.as_array() {
+            if !array.is_nil() {
+                self.arrays.insert(array.id(), span);
+            }
+
+            for index in 0..array.len_usize() {
+                self.insert(array.get_usize(index), spans);
+            }
+        }
+    }
+}
+
+fn convert(node: &Node, spans: &mut Vec<Span>) -> Option<Value> {
+    let NodeKind::Array(_) = node.kind() else {
+        let value = node.to_value()?;
+        spans.push(node.span());
+        return Some(value);
+    };
+    let elements = node.elements()?;
+
+    spans.push(node.span());
+
+    Some(
+        elements
+            .filter_map(|node| convert(node, spans))
+            .collect::<Vec<_>>()
+            .into(),
+    )
+}
+
+fn count_values(value: &Value) -> usize {
+    1 + value.as_array().map_or(0, |array| {
+        (0..array.len_usize())
+            .map(|index| count_values(array.get_usize(index)))
+            .sum()
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse_concrete;
+    use pretty_assertions::assert_eq;
+
+    fn convert(source: &str) -> (Vec<Value>, SourceMap) {
+        SourceMap::new(source, &parse_concrete(source).unwrap())
+    }
+
+    #[test]
+    fn convert_values() {
+        let (values, _) = convert("foo ; comment\n(bar (baz) ())");
+
+        assert_eq!(
+            values,
+            vec![
+                "foo".into(),
+                ["bar".into(), ["baz".into()].into(), [].into()].into()
+            ]
+        );
+    }
+
+    #[test]
+    fn find_spans() {
+        let (values, map) = convert("foo\n(bar\n  (baz))");
+        let foo = map.take_spans(&values[0]);
+        let spans = map.take_spans(&values[1]);
+        let array = values[1].as_array().unwrap();
+
+        assert_eq!(foo.value(&values[0]), Some(Span::new(0, 3)));
+        assert_eq!(spans.array(array), Some(Span::new(4, 17)));
+        assert_eq!(
+            spans.array(array.get_usize(1).as_array().unwrap()),
+            Some(Span::new(11, 16))
+        );
+        assert_eq!(spans.array(&Array::from([42.0.into()])), None);
+    }
+
+    #[test]
+    fn find_value_spans() {
+        let (values, map) = convert("(bar ; comment\n  baz)");
+        let spans = map.take_spans(&values[0]);
+        let array = values[0].as_array().unwrap();
+
+        assert_eq!(spans.value(&values[0]), Some(Span::new(0, 21)));
+        assert_eq!(spans.value(array.get_usize(0)), Some(Span::new(1, 4)));
+        assert_eq!(spans.value(array.get_usize(1)), Some(Span::new(17, 20)));
+        assert_eq!(spans.value(&"foo".into()), None);
+    }
+
+    #[test]
+    fn find_spans_of_copied_values() {
+        let (values, map) = convert("(foo (bar))");
+        let value = Value::from(["foo".into(), ["bar".into()].into()]);
+        let spans = map.take_spans(&value);
+        let array = value.as_array().unwrap();
+
+        assert_eq!(values[0], value);
+        assert_eq!(
+            spans.array(array.get_usize(1).as_array().unwrap()),
+            Some(Span::new(5, 10))
+        );
+    }
+
+    #[test]
+    fn take_spans_once() {
+        let (values, map) = convert("(foo)");
+
+        assert!(map.take_spans(&values[0]).value(&values[0]).is_some());
+        assert_eq!(map.take_spans(&values[0]).value(&values[0]), None);
+    }
+
+    #[test]
+    fn skip_spans_of_different_values() {
+        let (_, map) = convert("(foo)");
+        let value = Value::from(["foo".into(), "bar".into()]);
+
+        assert_eq!(map.take_spans(&value).value(&value), None);
+    }
+
+    #[test]
+    fn find_lines() {
+        let (_, map) = convert("foo\n(bar\n  (baz))");
+
+        assert_eq!(map.line(0), 1);
+        assert_eq!(map.line(3), 1);
+        assert_eq!(map.line(4), 2);
+        assert_eq!(map.line(11), 3);
+        assert_eq!(map.line(42), 3);
+    }
+}
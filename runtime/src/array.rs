@@ -3,14 +3,15 @@ use super::{
     Float64, Value,
 };
 use alloc::{
-    alloc::{alloc_zeroed, dealloc, realloc, Layout},
+    alloc::{alloc, alloc_zeroed, dealloc, realloc, Layout},
     vec::Vec,
 };
 use core::{
     cmp::Ordering,
     fmt::{self, Display, Formatter},
-    mem::forget,
-    ptr::{drop_in_place, write},
+    mem::{forget, replace},
+    ptr::{drop_in_place, read, write},
+    slice,
 };
 
 // TODO Inline functions.
@@ -25,6 +26,7 @@ pub struct Array(u64);
 struct Header {
     count: usize,
     len: usize,
+    capacity: usize,
 }
 
 impl Array {
@@ -33,7 +35,11 @@ impl Array {
             return Self(0);
         }
 
-        Self::from_ptr(unsafe { alloc_zeroed(Self::layout(capacity)) })
+        let array = Self::from_ptr(unsafe { alloc_zeroed(Self::layout(capacity)) });
+
+        unsafe { &mut *array.header_mut() }.capacity = capacity;
+
+        array
     }
 
     fn from_ptr(ptr: *const u8) -> Self {
@@ -120,26 +126,243 @@ impl Array {
         self
     }
 
+    /// Returns a sub-array between indices.
+    ///
+    /// Indices are clamped into the array and non-number indices result in
+    /// an empty array.
+    pub fn slice(&self, start: Value, end: Value) -> Self {
+        let (Ok(start), Ok(end)) = (Float64::try_from(start), Float64::try_from(end)) else {
+            return Self::new(0);
+        };
+        let len = self.len_usize();
+        let clamp = |index: Float64| (index.to_f64().max(0.0) as usize).min(len);
+        let start = clamp(start);
+
+        self.slice_usize(start, clamp(end).max(start))
+    }
+
+    pub fn slice_usize(&self, start: usize, end: usize) -> Self {
+        self.as_slice()[start..end].to_vec().into()
+    }
+
+    /// Appends elements of another array.
+    pub fn concat(self, other: &Self) -> Self {
+        self.extend_from_slice(other.as_slice())
+    }
+
+    pub fn push(self, value: Value) -> Self {
+        let len = self.len_usize();
+
+        self.set_usize(len, value)
+    }
+
+    /// Removes the last element.
+    pub fn pop(self) -> Self {
+        let len = self.len_usize();
+
+        self.truncate(len.saturating_sub(1))
+    }
+
+    pub fn truncate(self, len: usize) -> Self {
+        if len >= self.len_usize() {
+            return self;
+        } else if len == 0 {
+            return Self::new(0);
+        } else if self.header().count != UNIQUE_COUNT {
+            return self.slice_usize(0, len);
+        }
+
+        for index in len..self.header().len {
+            unsafe { drop_in_place(self.element_ptr(index)) };
+        }
+
+        // Capacity is kept so that pushes after pops do not reallocate.
+        unsafe { &mut *self.header_mut() }.len = len;
+
+        self
+    }
+
+    pub fn reverse(mut self) -> Self {
+        self.as_mut_slice().reverse();
+        self
+    }
+
+    /// Sorts elements with a comparator returning if one is less than the
+    /// other.
+    ///
+    /// The sort is stable and never panics even if the comparator is not a
+    /// total order. It stops at the first error of the comparator.
+    pub fn sort_by<E>(
+        self,
+        mut less: impl FnMut(&Value, &Value) -> Result<bool, E>,
+    ) -> Result<Self, E> {
+        let mut values = self.into_vec();
+        let len = values.len();
+        let mut indices = (0..len).collect::<Vec<_>>();
+        let mut buffer = Vec::with_capacity(len);
+        let mut width = 1;
+
+        // Merge runs of indices bottom-up so that values are never moved until
+        // their order is known.
+        while width < len {
+            for start in (0..len).step_by(2 * width) {
+                let middle = (start + width).min(len);
+                let end = (start + 2 * width).min(len);
+                let (mut left, mut right) = (start, middle);
+
+                while left < middle && right < end {
+                    if less(&values[indices[right]], &values[indices[left]])? {
+                        buffer.push(indices[right]);
+                        right += 1;
+                    } else {
+                        buffer.push(indices[left]);
+                        left += 1;
+                    }
+                }
+
+                buffer.extend_from_slice(&indices[left..middle]);
+                buffer.extend_from_slice(&indices[right..end]);
+            }
+
+            indices.clear();
+            (indices, buffer) = (buffer, indices);
+            width *= 2;
+        }
+
+        Ok(indices
+            .into_iter()
+            .map(|index| replace(&mut values[index], NIL))
+            .collect::<Vec<_>>()
+            .into())
+    }
+
+    /// A maximum length of arrays created by [`Array::range`].
+    pub const MAX_RANGE_LENGTH: usize = 1 << 24;
+
+    /// Creates an array of consecutive numbers from a start to an end
+    /// exclusively.
+    ///
+    /// It returns `None` if an array is longer than
+    /// [`Array::MAX_RANGE_LENGTH`].
+    pub fn range(start: f64, end: f64) -> Option<Self> {
+        if start.partial_cmp(&end) != Some(Ordering::Less) {
+            return Some(Self::new(0));
+        }
+
+        let distance = end - start;
+
+        if distance > Self::MAX_RANGE_LENGTH as f64 {
+            return None;
+        }
+
+        // A distance is rounded up without `ceil`, which is unavailable in
+        // `no_std`.
+        let mut length = distance as usize;
+
+        if start + (length as f64) < end {
+            length += 1;
+        }
+
+        if length > Self::MAX_RANGE_LENGTH {
+            return None;
+        }
+
+        Some(
+            (0..length)
+                .map(|index| Value::from(start + index as f64))
+                .collect::<Vec<_>>()
+                .into(),
+        )
+    }
+
+    pub fn as_slice(&self) -> &[Value] {
+        if self.is_nil() {
+            &[]
+        } else {
+            unsafe { slice::from_raw_parts(self.element_ptr(0), self.header().len) }
+        }
+    }
+
+    /// Returns elements as a mutable slice cloning them if they are shared.
+    pub fn as_mut_slice(&mut self) -> &mut [Value] {
+        if self.is_nil() {
+            return &mut [];
+        } else if self.header().count != UNIQUE_COUNT {
+            *self = self.deep_clone(0);
+        }
+
+        unsafe { slice::from_raw_parts_mut(self.element_ptr(0), self.header().len) }
+    }
+
+    /// Converts an array into its elements moving them if it is unique.
+    pub fn into_vec(self) -> Vec<Value> {
+        if self.is_nil() {
+            return Vec::new();
+        } else if self.header().count != UNIQUE_COUNT {
+            return self.as_slice().to_vec();
+        }
+
+        let len = self.header().len;
+        let values = (0..len)
+            .map(|index| unsafe { read(self.element_ptr(index)) })
+            .collect();
+
+        unsafe { dealloc(self.as_ptr(), Self::layout(self.header().capacity)) };
+        forget(self);
+
+        values
+    }
+
     pub fn is_nil(&self) -> bool {
         self.0 == 0
     }
 
+    /// Returns an identity of an array.
+    ///
+    /// Arrays sharing the same memory have the same identity while they are alive.
+    pub fn id(&self) -> u64 {
+        self.0
+    }
+
     fn set_usize_unchecked(&mut self, index: usize, value: Value) {
         *unsafe { &mut *self.element_ptr(index) } = value;
     }
 
+    fn extend_from_slice(self, values: &[Value]) -> Self {
+        let Some(last) = values.len().checked_sub(1) else {
+            return self;
+        };
+        let len = self.len_usize();
+        let mut array = self.set_usize(len + last, NIL);
+
+        for (index, value) in values.iter().enumerate() {
+            array.set_usize_unchecked(len + index, value.clone());
+        }
+
+        array
+    }
+
     fn extend(&mut self, len: usize) {
         if len <= self.header().len {
             return;
         }
 
-        self.0 = Self::mask_ptr(unsafe {
-            realloc(
-                self.as_ptr(),
-                Self::layout(self.header().len),
-                Self::layout(len).size(),
-            )
-        });
+        let capacity = self.header().capacity;
+
+        if len > capacity {
+            // Capacity grows geometrically so that repeated pushes take
+            // amortized constant time.
+            let new_capacity = len.max(2 * capacity);
+
+            self.0 = Self::mask_ptr(unsafe {
+                realloc(
+                    self.as_ptr(),
+                    Self::layout(capacity),
+                    Self::layout(new_capacity).size(),
+                )
+            });
+            unsafe { &mut *self.header_mut() }.capacity = new_capacity;
+        }
 
         for index in self.header().len..len {
             unsafe { write(self.element_ptr(index), NIL) };
@@ -163,8 +386,10 @@ impl Array {
     fn deep_clone(&self, len: usize) -> Self {
         let len = self.header().len.max(len);
         let mut other = Self::from_ptr(unsafe { alloc_zeroed(Self::layout(len)) });
+        let header = unsafe { &mut *other.header_mut() };
 
-        unsafe { &mut *other.header_mut() }.len = len;
+        header.len = len;
+        header.capacity = len;
 
         for index in 0..self.header().len {
             other.set_usize_unchecked(index, self.get_usize_unchecked(index).clone());
@@ -247,7 +472,7 @@ impl Drop for Array {
                     drop_in_place(self.element_ptr(index));
                 }
 
-                dealloc(self.as_ptr(), Layout::new::<Header>());
+                dealloc(self.as_ptr(), Self::layout(self.header().capacity));
             }
         } else {
             unsafe { &mut *self.header_mut() }.count -= 1;
@@ -299,22 +524,32 @@ impl TryFrom<&Value> for &Array {
 
 impl<const N: usize> From<[Value; N]> for Array {
     fn from(values: [Value; N]) -> Self {
-        let mut array = Self::new(0);
-
-        for (index, value) in values.into_iter().enumerate() {
-            array = array.set_usize(index, value);
-        }
-
-        array
+        Vec::from(values).into()
     }
 }
 
 impl From<Vec<Value>> for Array {
     fn from(values: Vec<Value>) -> Self {
-        let mut array = Self::new(0);
+        if values.is_empty() {
+            return Self::new(0);
+        }
+
+        let len = values.len();
+        let array = Self::from_ptr(unsafe { alloc(Self::layout(len)) });
+
+        unsafe {
+            write(
+                array.header_mut(),
+                Header {
+                    count: UNIQUE_COUNT,
+                    len,
+                    capacity: len,
+                },
+            )
+        };
 
         for (index, value) in values.into_iter().enumerate() {
-            array = array.set_usize(index, value);
+            unsafe { write(array.element_ptr(index), value) };
         }
 
         array
@@ -343,6 +578,14 @@ mod tests {
         let _ = Array::from([[42.0.into()].into()]).clone();
     }
 
+    #[test]
+    fn id() {
+        let array = Array::from([42.0.into()]);
+
+        assert_eq!(array.clone().id(), array.id());
+        assert_ne!(Array::from([42.0.into()]).id(), array.id());
+    }
+
     #[test]
     fn get() {
         assert_eq!(Array::new(0).get((-1.0).into()), &NIL);
@@ -394,6 +637,194 @@ mod tests {
         );
     }
 
+    #[test]
+    fn slice() {
+        let array = Array::from([1.0.into(), 2.0.into(), 3.0.into()]);
+
+        assert_eq!(
+            array.slice(1.0.into(), 3.0.into()),
+            Array::from([2.0.into(), 3.0.into()])
+        );
+        assert_eq!(
+            array.slice((-1.0).into(), 1.0.into()),
+            Array::from([1.0.into()])
+        );
+        assert_eq!(
+            array.slice(2.0.into(), 42.0.into()),
+            Array::from([3.0.into()])
+        );
+        assert_eq!(array.slice(2.0.into(), 1.0.into()), Array::new(0));
+        assert_eq!(array.slice("foo".into(), 1.0.into()), Array::new(0));
+    }
+
+    #[test]
+    fn concat() {
+        let one = Array::from([1.0.into()]);
+        let other = one.clone().concat(&Array::from([2.0.into(), 3.0.into()]));
+
+        assert_eq!(one, Array::from([1.0.into()]));
+        assert_eq!(other, Array::from([1.0.into(), 2.0.into(), 3.0.into()]));
+        assert_eq!(Array::new(0).concat(&one), one);
+        assert_eq!(
+            one.clone().concat(&one),
+            Array::from([1.0.into(), 1.0.into()])
+        );
+    }
+
+    #[test]
+    fn push_and_pop() {
+        let array = Array::new(0).push(1.0.into()).push(2.0.into());
+
+        assert_eq!(array, Array::from([1.0.into(), 2.0.into()]));
+        assert_eq!(array.clone().pop(), Array::from([1.0.into()]));
+        assert_eq!(array.pop().pop().pop(), Array::new(0));
+    }
+
+    #[test]
+    fn push_with_capacity() {
+        let mut array = Array::new(0);
+
+        for index in 0..5 {
+            array = array.push((index as f64).into());
+        }
+
+        assert_eq!(array.header().len, 5);
+        assert_eq!(array.header().capacity, 8);
+    }
+
+    #[test]
+    fn pop_without_shrinking_capacity() {
+        let array = Array::from([1.0.into(), 2.0.into(), 3.0.into()]).pop();
+
+        assert_eq!(array, Array::from([1.0.into(), 2.0.into()]));
+        assert_eq!(array.header().capacity, 3);
+        assert_eq!(
+            array.push(4.0.into()),
+            Array::from([1.0.into(), 2.0.into(), 4.0.into()])
+        );
+    }
+
+    #[test]
+    fn pop_shared_array() {
+        let one = Array::from([1.0.into(), 2.0.into()]);
+        let other = one.clone().pop();
+
+        assert_eq!(one, Array::from([1.0.into(), 2.0.into()]));
+        assert_eq!(other, Array::from([1.0.into()]));
+    }
+
+    #[test]
+    fn reverse() {
+        let one = Array::from([1.0.into(), 2.0.into()]);
+
+        assert_eq!(one.clone().reverse(), Array::from([2.0.into(), 1.0.into()]));
+        assert_eq!(one, Array::from([1.0.into(), 2.0.into()]));
+    }
+
+    mod sort_by {
+        use super::*;
+        use pretty_assertions::assert_eq;
+
+        fn sort(values: &[f64]) -> Array {
+            Array::from(values.iter().map(|&value| value.into()).collect::<Vec<_>>())
+                .sort_by(|one, other| Ok::<_, ()>(one < other))
+                .unwrap()
+        }
+
+        #[test]
+        fn sort_numbers() {
+            assert_eq!(sort(&[]), Array::new(0));
+            assert_eq!(sort(&[1.0]), Array::from([1.0.into()]));
+            assert_eq!(
+                sort(&[3.0, 1.0, 4.0, 1.0, 5.0, 9.0, 2.0]),
+                sort(&[1.0, 1.0, 2.0, 3.0, 4.0, 5.0, 9.0])
+            );
+            assert_eq!(
+                sort(&[2.0, 1.0, 0.0]).as_slice(),
+                &[0.0.into(), 1.0.into(), 2.0.into()]
+            );
+        }
+
+        #[test]
+        fn sort_stably() {
+            let array = Array::from([
+                Array::from([2.0.into(), "a".into()]).into(),
+                Array::from([1.0.into(), "b".into()]).into(),
+                Array::from([2.0.into(), "c".into()]).into(),
+            ])
+            .sort_by(|one, other| {
+                Ok::<_, ()>(
+                    one.as_array().unwrap().get_usize(0) < other.as_array().unwrap().get_usize(0),
+                )
+            })
+            .unwrap();
+
+            assert_eq!(array.to_string(), "((1 b) (2 a) (2 c))");
+        }
+
+        #[test]
+        fn sort_with_inconsistent_comparator() {
+            let array = Array::from([1.0.into(), 2.0.into(), 3.0.into()])
+                .sort_by(|_, _| Ok::<_, ()>(true))
+                .unwrap();
+
+            assert_eq!(array.len_usize(), 3);
+        }
+
+        #[test]
+        fn fail_to_sort() {
+            assert_eq!(
+                Array::from([1.0.into(), 2.0.into()]).sort_by(|_, _| Err(42)),
+                Err(42)
+            );
+        }
+    }
+
+    #[test]
+    fn range() {
+        assert_eq!(Array::range(0.0, 0.0), Some(Array::new(0)));
+        assert_eq!(Array::range(1.0, 0.0), Some(Array::new(0)));
+        assert_eq!(
+            Array::range(0.0, 3.0),
+            Some(Array::from([0.0.into(), 1.0.into(), 2.0.into()]))
+        );
+        assert_eq!(
+            Array::range(0.5, 2.0),
+            Some(Array::from([0.5.into(), 1.5.into()]))
+        );
+        assert_eq!(Array::range(f64::NAN, 1.0), Some(Array::new(0)));
+    }
+
+    #[test]
+    fn range_too_long() {
+        let length = Array::MAX_RANGE_LENGTH as f64;
+
+        assert_eq!(Array::range(0.0, length).unwrap().len_usize(), 1 << 24);
+        assert_eq!(Array::range(-0.5, length), None);
+        assert_eq!(Array::range(0.0, 1e300), None);
+        assert_eq!(Array::range(0.0, f64::INFINITY), None);
+    }
+
+    #[test]
+    fn into_vec() {
+        let one = Array::from([1.0.into(), 2.0.into()]);
+
+        assert_eq!(one.clone().into_vec(), [1.0.into(), 2.0.into()]);
+        assert_eq!(one.into_vec(), [1.0.into(), 2.0.into()]);
+        assert_eq!(Array::new(0).into_vec(), []);
+    }
+
+    #[test]
+    fn update_shared_slice() {
+        let one = Array::from([1.0.into()]);
+        let mut other = one.clone();
+
+        other.as_mut_slice()[0] = 2.0.into();
+
+        assert_eq!(one, Array::from([1.0.into()]));
+        assert_eq!(other, Array::from([2.0.into()]));
+    }
+
     mod set {
         use super::*;
         use pretty_assertions::assert_eq;
@@ -47,6 +47,11 @@ impl Closure {
         self.header().arity
     }
 
+    #[inline]
+    pub fn environment_size(&self) -> u8 {
+        self.header().environment_size
+    }
+
     #[inline]
     pub fn get_environment(&self, index: usize) -> &Value {
         debug_assert!(index < self.header().environment_size as usize);
@@ -145,7 +150,7 @@ impl Drop for Closure {
 
 impl Display for Closure {
     fn fmt(&self, formatter: &mut Formatter) -> fmt::Result {
-        write!(formatter, "<closure {:x}>", self.0)
+        write!(formatter, "<closure {:x} {}>", self.id(), self.arity())
     }
 }
 
@@ -0,0 +1,121 @@
+use crate::symbol::SymbolTable;
+use core::marker::PhantomData;
+
+/// An isolate with its own symbol table.
+///
+/// While an isolate is entered, symbols are interned into its table instead of
+/// the global one. All of them are freed when the isolate is dropped.
+#[derive(Debug, Default)]
+pub struct Isolate {
+    symbols: SymbolTable,
+}
+
+impl Isolate {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Enters the isolate in the current thread until the returned guard is
+    /// dropped.
+    ///
+    /// # Safety
+    ///
+    /// Symbols created while the isolate is entered must not be used after the
+    /// isolate is dropped. Guards must be dropped in the reverse order of their
+    /// creation.
+    pub unsafe fn enter(&self) -> IsolateGuard<'_> {
+        IsolateGuard {
+            previous: SymbolTable::replace_current(&self.symbols),
+            _isolate: PhantomData,
+        }
+    }
+
+    pub fn symbol_count(&self) -> usize {
+        self.symbols.len()
+    }
+}
+
+pub struct IsolateGuard<'a> {
+    previous: *const SymbolTable,
+    _isolate: PhantomData<&'a Isolate>,
+}
+
+impl Drop for IsolateGuard<'_> {
+    fn drop(&mut self) {
+        SymbolTable::replace_current(self.previous);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Symbol;
+
+    #[test]
+    fn intern_symbols() {
+        let isolate = Isolate::new();
+        let _guard = unsafe { isolate.enter() };
+
+        assert_eq!(Symbol::from("foo"), Symbol::from("foo"));
+        assert_ne!(Symbol::from("foo"), Symbol::from("bar"));
+        assert_eq!(isolate.symbol_count(), 2);
+    }
+
+    #[test]
+    fn separate_symbol_tables() {
+        let one = Isolate::new();
+        let other = Isolate::new();
+
+        let foo = {
+            let _guard = unsafe { one.enter() };
+            Symbol::from("foo")
+        };
+        let bar = {
+            let _guard = unsafe { other.enter() };
+            Symbol::from("bar")
+        };
+
+        assert_eq!(foo.as_str(), "foo");
+        assert_eq!(bar.as_str(), "bar");
+        assert_eq!(one.symbol_count(), 1);
+        assert_eq!(other.symbol_count(), 1);
+    }
+
+    #[test]
+    fn enter_nested_isolates() {
+        let one = Isolate::new();
+        let other = Isolate::new();
+
+        {
+            let _guard = unsafe { one.enter() };
+
+            let _ = Symbol::from("foo");
+
+            {
+                let _guard = unsafe { other.enter() };
+
+                let _ = Symbol::from("bar");
+            }
+
+            let _ = Symbol::from("baz");
+        }
+
+        assert_eq!(one.symbol_count(), 2);
+        assert_eq!(other.symbol_count(), 1);
+    }
+
+    #[test]
+    fn leave_isolate() {
+        let isolate = Isolate::new();
+
+        {
+            let _guard = unsafe { isolate.enter() };
+
+            let _ = Symbol::from("foo");
+        }
+
+        let _ = Symbol::from("bar");
+
+        assert_eq!(isolate.symbol_count(), 1);
+    }
+}
@@ -1,12 +1,16 @@
 #![no_std]
 
 extern crate alloc;
+#[cfg(feature = "std")]
 extern crate std;
 
 mod array;
 mod closure;
 mod float64;
 mod integer32;
+mod isolate;
+mod reference;
+mod repr;
 mod symbol;
 mod r#type;
 mod typed_value;
@@ -15,7 +19,10 @@ mod value;
 pub use array::Array;
 pub use closure::{Closure, ClosureId};
 pub use float64::Float64;
+pub use isolate::{Isolate, IsolateGuard};
 pub use r#type::Type;
+pub use reference::Reference;
+pub use repr::{escape_character, unescape_character, Repr, ESCAPE_CHARACTER, SPECIAL_CHARACTERS};
 pub use symbol::Symbol;
 pub use typed_value::{TypedValue, TypedValueRef};
 pub use value::{Value, NIL};
@@ -0,0 +1,209 @@
+use crate::{value::REFERENCE_MASK, Value};
+use alloc::alloc::{alloc, dealloc};
+use core::{
+    alloc::Layout,
+    fmt::{self, Display, Formatter},
+    marker::PhantomData,
+    mem::{forget, replace},
+    ptr::{drop_in_place, write},
+};
+
+/// A mutable reference to a value shared by its clones.
+///
+/// References are the only values which can contain themselves. Such cycles
+/// are never freed by reference counting, and a cycle collector can trace them
+/// through clones of values returned by [`Reference::get`].
+///
+/// References are neither `Send` nor `Sync` as their counts are not atomic.
+#[repr(transparent)]
+pub struct Reference(u64, PhantomData<*const ()>);
+
+#[repr(C)]
+struct Cell {
+    count: usize,
+    value: Value,
+}
+
+impl Reference {
+    pub fn new(value: Value) -> Self {
+        let ptr = unsafe { alloc(Layout::new::<Cell>()) };
+
+        unsafe { write(ptr as *mut Cell, Cell { count: 0, value }) };
+
+        Self(
+            nonbox::f64::box_unsigned(ptr as u64 | REFERENCE_MASK),
+            PhantomData,
+        )
+    }
+
+    /// Returns a clone of a value in a reference.
+    ///
+    /// A value is cloned because it can be replaced through any clone of a
+    /// reference.
+    #[inline]
+    pub fn get(&self) -> Value {
+        self.cell().value.clone()
+    }
+
+    /// Replaces a value in a reference and returns the old one.
+    #[inline]
+    pub fn replace(&self, value: Value) -> Value {
+        replace(&mut unsafe { &mut *self.cell_mut() }.value, value)
+    }
+
+    /// Returns an identity of a reference.
+    ///
+    /// Clones of a reference have the same identity while they are alive.
+    pub fn id(&self) -> u64 {
+        self.0
+    }
+
+    /// # Safety
+    ///
+    /// The pointer must be valid.
+    #[inline]
+    pub(crate) unsafe fn from_raw(ptr: u64) -> Self {
+        Self(ptr, PhantomData)
+    }
+
+    #[inline]
+    pub(crate) fn into_raw(self) -> u64 {
+        let ptr = self.0;
+
+        forget(self);
+
+        ptr
+    }
+
+    #[inline]
+    fn cell(&self) -> &Cell {
+        unsafe { &*self.cell_mut() }
+    }
+
+    #[inline]
+    fn cell_mut(&self) -> *mut Cell {
+        (nonbox::f64::unbox_unsigned(self.0).unwrap() & !REFERENCE_MASK) as *mut _
+    }
+}
+
+impl Clone for Reference {
+    #[inline]
+    fn clone(&self) -> Self {
+        unsafe { &mut *self.cell_mut() }.count += 1;
+
+        Self(self.0, PhantomData)
+    }
+}
+
+impl Drop for Reference {
+    fn drop(&mut self) {
+        if self.cell().count == 0 {
+            unsafe {
+                drop_in_place(self.cell_mut());
+                dealloc(self.cell_mut() as *mut u8, Layout::new::<Cell>());
+            }
+        } else {
+            unsafe { &mut *self.cell_mut() }.count -= 1;
+        }
+    }
+}
+
+impl PartialEq for Reference {
+    fn eq(&self, other: &Self) -> bool {
+        self.id() == other.id()
+    }
+}
+
+impl Eq for Reference {}
+
+impl Display for Reference {
+    fn fmt(&self, formatter: &mut Formatter) -> fmt::Result {
+        write!(formatter, "<ref>")
+    }
+}
+
+impl TryFrom<Value> for Reference {
+    type Error = Value;
+
+    #[inline]
+    fn try_from(value: Value) -> Result<Self, Self::Error> {
+        if value.is_reference() {
+            Ok(unsafe { Reference::from_raw(value.into_raw()) })
+        } else {
+            Err(value)
+        }
+    }
+}
+
+impl TryFrom<&Value> for &Reference {
+    type Error = ();
+
+    #[inline]
+    fn try_from(value: &Value) -> Result<Self, Self::Error> {
+        if value.is_reference() {
+            let ptr = value as *const _ as *const _;
+
+            Ok(unsafe { &*ptr })
+        } else {
+            Err(())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Array;
+
+    #[test]
+    fn new() {
+        Reference::new(42.0.into());
+    }
+
+    #[test]
+    fn get() {
+        assert_eq!(Reference::new(42.0.into()).get(), 42.0.into());
+    }
+
+    #[test]
+    fn replace() {
+        let reference = Reference::new(1.0.into());
+
+        assert_eq!(reference.replace(2.0.into()), 1.0.into());
+        assert_eq!(reference.get(), 2.0.into());
+    }
+
+    #[test]
+    fn share_value() {
+        let reference = Reference::new(1.0.into());
+        #[allow(clippy::redundant_clone)]
+        let other = reference.clone();
+
+        other.replace(2.0.into());
+
+        assert_eq!(reference.get(), 2.0.into());
+        assert!(reference == other);
+    }
+
+    #[test]
+    fn get_replaced_value() {
+        let reference = Reference::new(Array::from([1.0.into()]).into());
+        let value = reference.get();
+
+        reference.replace(2.0.into());
+
+        assert_eq!(value, Array::from([1.0.into()]).into());
+    }
+
+    #[test]
+    fn drop_array() {
+        let reference = Reference::new(Array::from([42.0.into()]).into());
+
+        reference.replace(Array::from([13.0.into()]).into());
+    }
+
+    #[test]
+    fn compare_identities() {
+        assert!(Reference::new(1.0.into()) != Reference::new(1.0.into()));
+    }
+}
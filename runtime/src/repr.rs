@@ -0,0 +1,156 @@
+use crate::{TypedValueRef, Value};
+use core::fmt::{self, Display, Formatter, Write};
+
+/// Characters which terminate symbols in source codes.
+pub const SPECIAL_CHARACTERS: &str = "(); \t\n";
+
+/// A character which escapes the next one in symbols.
+pub const ESCAPE_CHARACTER: char = '\\';
+
+/// Returns a character written after an escape character for a character
+/// which cannot appear in symbols as it is.
+pub fn escape_character(character: char) -> Option<char> {
+    match character {
+        '\n' => Some('n'),
+        '\t' => Some('t'),
+        character if character == ESCAPE_CHARACTER || SPECIAL_CHARACTERS.contains(character) => {
+            Some(character)
+        }
+        _ => None,
+    }
+}
+
+/// Returns a character escaped by an escape character and a character after
+/// it.
+pub fn unescape_character(character: char) -> char {
+    match character {
+        'n' => '\n',
+        't' => '\t',
+        character => character,
+    }
+}
+
+/// A printer of values in a form which the parser reads back to equal values.
+///
+/// Closures and references are printed as `<closure ...>` and `<ref>` which
+/// the parser does not read back.
+pub struct Repr<'a>(&'a Value);
+
+impl<'a> Repr<'a> {
+    pub(crate) fn new(value: &'a Value) -> Self {
+        Self(value)
+    }
+}
+
+impl Display for Repr<'_> {
+    fn fmt(&self, formatter: &mut Formatter) -> fmt::Result {
+        match self.0.as_typed() {
+            None => write!(formatter, "()"),
+            Some(TypedValueRef::Array(array)) => {
+                write!(formatter, "(")?;
+
+                for index in 0..array.len_usize() {
+                    if index != 0 {
+                        write!(formatter, " ")?;
+                    }
+
+                    write!(formatter, "{}", array.get_usize(index).repr())?;
+                }
+
+                write!(formatter, ")")
+            }
+            Some(TypedValueRef::Symbol(symbol)) => {
+                let string = symbol.as_str();
+
+                for (index, character) in string.chars().enumerate() {
+                    // Symbols which look like numbers are escaped by their first characters.
+                    if index == 0 && string.parse::<f64>().is_ok() {
+                        formatter.write_char(ESCAPE_CHARACTER)?;
+                    }
+
+                    if let Some(character) = escape_character(character) {
+                        write!(formatter, "{ESCAPE_CHARACTER}{character}")?;
+                    } else {
+                        formatter.write_char(character)?;
+                    }
+                }
+
+                Ok(())
+            }
+            Some(
+                TypedValueRef::Closure(_)
+                | TypedValueRef::Float64(_)
+                | TypedValueRef::Integer32(_)
+                | TypedValueRef::Reference(_),
+            ) => write!(formatter, "{}", self.0),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Closure, NIL};
+    use alloc::string::ToString;
+
+    #[test]
+    fn repr_nil() {
+        assert_eq!(NIL.repr().to_string(), "()");
+    }
+
+    #[test]
+    fn repr_float64() {
+        assert_eq!(Value::from(42.0).repr().to_string(), "42");
+        assert_eq!(Value::from(-0.5).repr().to_string(), "-0.5");
+    }
+
+    #[test]
+    fn repr_symbol() {
+        assert_eq!(Value::from("foo").repr().to_string(), "foo");
+    }
+
+    #[test]
+    fn repr_numeric_symbol() {
+        assert_eq!(Value::from("42").repr().to_string(), "\\42");
+        assert_eq!(Value::from("-1.5").repr().to_string(), "\\-1.5");
+    }
+
+    #[test]
+    fn repr_symbol_with_special_characters() {
+        assert_eq!(Value::from("a b").repr().to_string(), "a\\ b");
+        assert_eq!(Value::from("(a)").repr().to_string(), "\\(a\\)");
+        assert_eq!(Value::from("a;b").repr().to_string(), "a\\;b");
+        assert_eq!(Value::from("a\tb\nc").repr().to_string(), "a\\tb\\nc");
+        assert_eq!(Value::from("a\\b").repr().to_string(), "a\\\\b");
+    }
+
+    #[test]
+    fn unescape_escaped_characters() {
+        for character in SPECIAL_CHARACTERS.chars().chain([ESCAPE_CHARACTER]) {
+            assert_eq!(
+                unescape_character(escape_character(character).unwrap()),
+                character
+            );
+        }
+
+        assert_eq!(escape_character('a'), None);
+    }
+
+    #[test]
+    fn repr_array() {
+        assert_eq!(
+            Value::from(["foo".into(), "42".into(), 42.0.into(), NIL])
+                .repr()
+                .to_string(),
+            "(foo \\42 42 ())"
+        );
+    }
+
+    #[test]
+    fn repr_closure() {
+        assert_eq!(
+            Value::from(Closure::new(42, 2, 0)).repr().to_string(),
+            "<closure 2a 2>"
+        );
+    }
+}
@@ -4,21 +4,136 @@ use alloc::{borrow::ToOwned, boxed::Box, string::String};
 use core::{
     cmp::Ordering,
     fmt::{self, Debug, Display, Formatter},
-    ops::Deref,
     pin::Pin,
 };
-use dashmap::DashMap;
-use std::sync::LazyLock;
+#[cfg(not(feature = "std"))]
+use {
+    alloc::collections::BTreeSet,
+    core::{
+        borrow::Borrow,
+        ptr::null_mut,
+        sync::atomic::{self, AtomicPtr},
+    },
+    spin::Mutex,
+};
+#[cfg(feature = "std")]
+use {
+    core::ops::Deref,
+    dashmap::DashMap,
+    std::{cell::Cell, ptr::null, sync::LazyLock},
+};
 
 // TODO Inline functions.
 
+#[cfg(feature = "std")]
+static GLOBAL_TABLE: LazyLock<SymbolTable> = LazyLock::new(Default::default);
+
+#[cfg(not(feature = "std"))]
+static GLOBAL_TABLE: SymbolTable = SymbolTable {
+    symbols: Mutex::new(BTreeSet::new()),
+};
+
+#[cfg(feature = "std")]
+std::thread_local! {
+    static CURRENT_TABLE: Cell<*const SymbolTable> = const { Cell::new(null()) };
+}
+
+// Without threads, a current table is shared globally.
+#[cfg(not(feature = "std"))]
+static CURRENT_TABLE: AtomicPtr<SymbolTable> = AtomicPtr::new(null_mut());
+
+/// A symbol table.
+#[derive(Debug, Default)]
+pub(crate) struct SymbolTable {
+    #[cfg(feature = "std")]
+    #[allow(clippy::box_collection)]
+    symbols: DashMap<Pin<Box<String>>, ()>,
+    #[cfg(not(feature = "std"))]
+    symbols: Mutex<BTreeSet<Key>>,
+}
+
+impl SymbolTable {
+    #[cfg(feature = "std")]
+    pub fn len(&self) -> usize {
+        self.symbols.len()
+    }
+
+    #[cfg(not(feature = "std"))]
+    pub fn len(&self) -> usize {
+        self.symbols.lock().len()
+    }
+
+    #[cfg(feature = "std")]
+    fn intern(&self, symbol: String) -> Symbol {
+        let entry = self.symbols.entry(Box::pin(symbol)).or_default();
+
+        Symbol::from_string(entry.key().deref())
+    }
+
+    #[cfg(not(feature = "std"))]
+    fn intern(&self, symbol: String) -> Symbol {
+        let mut symbols = self.symbols.lock();
+
+        if let Some(Key(string)) = symbols.get(symbol.as_str()) {
+            return Symbol::from_string(string);
+        }
+
+        let string = Box::pin(symbol);
+        let symbol = Symbol::from_string(&string);
+        symbols.insert(Key(string));
+
+        symbol
+    }
+
+    /// Makes the table current in the current thread and returns a previous one.
+    #[cfg(feature = "std")]
+    pub(crate) fn replace_current(table: *const Self) -> *const Self {
+        CURRENT_TABLE.with(|current| current.replace(table))
+    }
+
+    /// Makes the table current globally and returns a previous one.
+    #[cfg(not(feature = "std"))]
+    pub(crate) fn replace_current(table: *const Self) -> *const Self {
+        CURRENT_TABLE.swap(table as *mut _, atomic::Ordering::AcqRel)
+    }
+
+    fn with_current<T>(callback: impl FnOnce(&Self) -> T) -> T {
+        #[cfg(feature = "std")]
+        let table = CURRENT_TABLE.with(Cell::get);
+        #[cfg(not(feature = "std"))]
+        let table = CURRENT_TABLE.load(atomic::Ordering::Acquire);
+
+        if table.is_null() {
+            callback(&GLOBAL_TABLE)
+        } else {
+            callback(unsafe { &*table })
+        }
+    }
+}
+
+// A key of a symbol table looked up by a string.
+#[cfg(not(feature = "std"))]
+#[derive(Debug, Eq, Ord, PartialEq, PartialOrd)]
 #[allow(clippy::box_collection)]
-static CACHE: LazyLock<DashMap<Pin<Box<String>>, ()>> = LazyLock::new(Default::default);
+struct Key(Pin<Box<String>>);
+
+#[cfg(not(feature = "std"))]
+impl Borrow<str> for Key {
+    fn borrow(&self) -> &str {
+        self.0.as_str()
+    }
+}
 
 #[derive(Clone, Copy, Eq, Hash, PartialEq)]
 pub struct Symbol(u64);
 
 impl Symbol {
+    fn from_string(string: &String) -> Self {
+        Self(nonbox::f64::box_unsigned(
+            string as *const String as u64 | SYMBOL_MASK,
+        ))
+    }
+
     pub(crate) fn to_raw(self) -> u64 {
         self.0
     }
@@ -61,11 +176,7 @@ impl Display for Symbol {
 
 impl From<String> for Symbol {
     fn from(symbol: String) -> Self {
-        let entry = CACHE.entry(Box::pin(symbol)).or_default();
-
-        Self(nonbox::f64::box_unsigned(
-            entry.key().deref() as *const String as u64 | SYMBOL_MASK,
-        ))
+        SymbolTable::with_current(|table| table.intern(symbol))
     }
 }
 
@@ -4,5 +4,6 @@ pub enum Type {
     Float64,
     Integer32,
     Closure,
+    Reference,
     Symbol,
 }
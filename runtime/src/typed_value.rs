@@ -1,10 +1,11 @@
-use crate::{integer32::Integer32, Array, Closure, Float64, Symbol};
+use crate::{integer32::Integer32, Array, Closure, Float64, Reference, Symbol};
 
 pub enum TypedValue {
     Array(Array),
     Closure(Closure),
     Float64(Float64),
     Integer32(Integer32),
+    Reference(Reference),
     Symbol(Symbol),
 }
 
@@ -13,5 +14,6 @@ pub enum TypedValueRef<'a> {
     Closure(&'a Closure),
     Float64(Float64),
     Integer32(Integer32),
+    Reference(&'a Reference),
     Symbol(Symbol),
 }
@@ -1,7 +1,7 @@
 use super::{Array, Float64};
 use crate::{
-    integer32::Integer32, r#type::Type, symbol::Symbol, typed_value::TypedValueRef, Closure,
-    TypedValue,
+    integer32::Integer32, r#type::Type, repr::Repr, symbol::Symbol, typed_value::TypedValueRef,
+    Closure, Reference, TypedValue,
 };
 use alloc::{string::String, vec::Vec};
 use core::{
@@ -18,11 +18,13 @@ const INTEGER32_SUB_MASK: u64 = 0b001;
 const SYMBOL_SUB_MASK: u64 = 0b011;
 const CLOSURE_SUB_MASK: u64 = 0b010;
 const ARRAY_SUB_MASK: u64 = 0b100;
+const REFERENCE_SUB_MASK: u64 = 0b101;
 
 pub(crate) const INTEGER32_MASK: u64 = INTEGER32_SUB_MASK << TYPE_MASK_OFFSET;
 pub(crate) const SYMBOL_MASK: u64 = SYMBOL_SUB_MASK << TYPE_MASK_OFFSET;
 pub(crate) const CLOSURE_MASK: u64 = CLOSURE_SUB_MASK << TYPE_MASK_OFFSET;
 pub(crate) const ARRAY_MASK: u64 = ARRAY_SUB_MASK << TYPE_MASK_OFFSET;
+pub(crate) const REFERENCE_MASK: u64 = REFERENCE_SUB_MASK << TYPE_MASK_OFFSET;
 
 #[derive(Debug)]
 pub struct Value(u64);
@@ -36,6 +38,7 @@ impl Value {
                 SYMBOL_SUB_MASK => Type::Symbol,
                 CLOSURE_SUB_MASK => Type::Closure,
                 ARRAY_SUB_MASK => Type::Array,
+                REFERENCE_SUB_MASK => Type::Reference,
                 _ => Type::Float64,
             }
         } else {
@@ -68,6 +71,11 @@ impl Value {
         self.is_nil() || self.r#type() == Type::Closure
     }
 
+    #[inline(always)]
+    pub fn is_reference(&self) -> bool {
+        self.r#type() == Type::Reference
+    }
+
     #[inline(always)]
     pub fn is_symbol(&self) -> bool {
         self.r#type() == Type::Symbol
@@ -113,6 +121,16 @@ impl Value {
         self.try_into().ok()
     }
 
+    #[inline(always)]
+    pub fn into_reference(self) -> Option<Reference> {
+        self.try_into().ok()
+    }
+
+    #[inline(always)]
+    pub fn as_reference(&self) -> Option<&Reference> {
+        self.try_into().ok()
+    }
+
     #[inline(always)]
     pub fn as_typed(&self) -> Option<TypedValueRef<'_>> {
         if self.is_nil() {
@@ -125,6 +143,9 @@ impl Value {
                 }
                 Type::Float64 => TypedValueRef::Float64(Float64::from(f64::from_bits(self.0))),
                 Type::Integer32 => TypedValueRef::Integer32(unsafe { Integer32::from_raw(self.0) }),
+                Type::Reference => {
+                    TypedValueRef::Reference(unsafe { &*(self as *const _ as *const _) })
+                }
                 Type::Symbol => TypedValueRef::Symbol(unsafe { Symbol::from_raw(self.0) }),
             })
         }
@@ -140,6 +161,7 @@ impl Value {
                 Type::Closure => TypedValue::Closure(unsafe { Closure::from_raw(self.0) }),
                 Type::Float64 => TypedValue::Float64(Float64::from(f64::from_bits(self.0))),
                 Type::Integer32 => TypedValue::Integer32(unsafe { Integer32::from_raw(self.0) }),
+                Type::Reference => TypedValue::Reference(unsafe { Reference::from_raw(self.0) }),
                 Type::Symbol => TypedValue::Symbol(unsafe { Symbol::from_raw(self.0) }),
             })
         };
@@ -149,6 +171,11 @@ impl Value {
         value
     }
 
+    /// Returns a printer which formats a value readable by the parser.
+    pub fn repr(&self) -> Repr<'_> {
+        Repr::new(self)
+    }
+
     #[inline]
     pub(crate) fn into_raw(self) -> u64 {
         let raw = self.0;
@@ -173,6 +200,7 @@ impl PartialEq for Value {
                 TypedValueRef::Closure(_) => false,
                 TypedValueRef::Integer32(one) => Some(one) == other.to_integer32(),
                 TypedValueRef::Array(one) => Some(one) == other.as_array(),
+                TypedValueRef::Reference(one) => Some(one) == other.as_reference(),
                 TypedValueRef::Symbol(one) => Some(one) == other.to_symbol(),
             }
         } else {
@@ -191,7 +219,7 @@ impl PartialOrd for Value {
                 TypedValueRef::Float64(one) => {
                     other.to_float64().and_then(|other| one.partial_cmp(&other))
                 }
-                TypedValueRef::Closure(_) => None,
+                TypedValueRef::Closure(_) | TypedValueRef::Reference(_) => None,
                 TypedValueRef::Integer32(one) => other
                     .to_integer32()
                     .and_then(|other| one.partial_cmp(&other)),
@@ -217,6 +245,7 @@ impl Clone for Value {
             None => NIL,
             Some(TypedValueRef::Array(array)) => array.clone().into(),
             Some(TypedValueRef::Closure(closure)) => closure.clone().into(),
+            Some(TypedValueRef::Reference(reference)) => reference.clone().into(),
             Some(
                 TypedValueRef::Float64(_) | TypedValueRef::Integer32(_) | TypedValueRef::Symbol(_),
             ) => Self(self.0),
@@ -234,6 +263,9 @@ impl Drop for Value {
             Type::Closure => unsafe {
                 Closure::from_raw(self.0);
             },
+            Type::Reference => unsafe {
+                Reference::from_raw(self.0);
+            },
             Type::Float64 | Type::Integer32 | Type::Symbol => {}
         }
     }
@@ -241,18 +273,14 @@ impl Drop for Value {
 
 impl Display for Value {
     fn fmt(&self, formatter: &mut Formatter) -> fmt::Result {
-        if self.is_nil() {
-            write!(formatter, "()")
-        } else if let Some(number) = self.to_float64() {
-            write!(formatter, "{number}")
-        } else if let Some(closure) = self.as_closure() {
-            write!(formatter, "{closure}")
-        } else if let Some(symbol) = self.to_symbol() {
-            write!(formatter, "{symbol}")
-        } else if let Some(array) = self.as_array() {
-            write!(formatter, "{array}")
-        } else {
-            unreachable!()
+        match self.as_typed() {
+            None => write!(formatter, "()"),
+            Some(TypedValueRef::Array(array)) => write!(formatter, "{array}"),
+            Some(TypedValueRef::Closure(closure)) => write!(formatter, "{closure}"),
+            Some(TypedValueRef::Float64(number)) => write!(formatter, "{number}"),
+            Some(TypedValueRef::Integer32(number)) => write!(formatter, "{number}"),
+            Some(TypedValueRef::Reference(reference)) => write!(formatter, "{reference}"),
+            Some(TypedValueRef::Symbol(symbol)) => write!(formatter, "{symbol}"),
         }
     }
 }
@@ -271,6 +299,13 @@ impl From<Closure> for Value {
     }
 }
 
+impl From<Reference> for Value {
+    #[inline]
+    fn from(reference: Reference) -> Self {
+        Self(reference.into_raw())
+    }
+}
+
 impl From<Float64> for Value {
     #[inline]
     fn from(number: Float64) -> Self {
@@ -393,6 +428,43 @@ mod tests {
         }
     }
 
+    mod display {
+        use super::*;
+        use alloc::string::ToString;
+
+        #[test]
+        fn display_nil() {
+            assert_eq!(NIL.to_string(), "()");
+        }
+
+        #[test]
+        fn display_integer32() {
+            assert_eq!(Value::from(42i32).to_string(), "42");
+        }
+
+        #[test]
+        fn display_symbol() {
+            assert_eq!(Value::from("42").to_string(), "42");
+            assert_eq!(Value::from("a b").to_string(), "a b");
+        }
+
+        #[test]
+        fn display_closure() {
+            assert_eq!(
+                Value::from(Closure::new(42, 1, 0)).to_string(),
+                "<closure 2a 1>"
+            );
+        }
+
+        #[test]
+        fn display_reference() {
+            assert_eq!(
+                Value::from(Reference::new(42.0.into())).to_string(),
+                "<ref>"
+            );
+        }
+    }
+
     #[test]
     fn compare_float64() {
         assert_eq!(Value::from(0.0), Value::from(0.0));
@@ -431,6 +503,17 @@ mod tests {
         );
     }
 
+    #[test]
+    fn compare_references() {
+        let reference = Value::from(Reference::new(42.0.into()));
+
+        assert_eq!(reference, reference.clone());
+        assert_ne!(reference, Value::from(Reference::new(42.0.into())));
+        assert_ne!(reference, 42.0.into());
+        assert_ne!(Value::from(42.0), reference);
+        assert_eq!(reference.partial_cmp(&reference), None);
+    }
+
     #[test]
     fn compare_arrays() {
         assert_eq!(Value::from(Array::new(0)), Value::from(0.0));
@@ -18,6 +18,7 @@ for name in fibonacci sum tak; do
 
   run \
     "target/release/arachne $directory/main.arc" \
+    "target/release/arachne --register $directory/main.arc" \
     "arachne $directory/main.arc" \
     "python3 $directory/main.py"
 
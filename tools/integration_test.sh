@@ -9,5 +9,5 @@ bundler install
 export ROOT=$PWD
 export PATH=$PWD/target/release:$PATH
 
-cargo build --release
+cargo build --release ${FEATURES:+--features $FEATURES}
 cucumber --publish-quiet "$@"
@@ -0,0 +1,217 @@
+use crate::{DebugInfo, StackFrame};
+use alloc::{borrow::ToOwned, string::String, vec::Vec};
+use core::fmt::{self, Display, Formatter};
+use runtime::ClosureId;
+
+/// A frame in a backtrace.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct BacktraceFrame {
+    function: Option<ClosureId>,
+    address: usize,
+    tail_call_count: u32,
+    name: Option<String>,
+    line: Option<usize>,
+}
+
+impl BacktraceFrame {
+    pub const fn new(function: Option<ClosureId>, address: usize, tail_call_count: u32) -> Self {
+        Self {
+            function,
+            address,
+            tail_call_count,
+            name: None,
+            line: None,
+        }
+    }
+
+    /// Returns a function called in a frame or `None` for a top level.
+    pub const fn function(&self) -> Option<ClosureId> {
+        self.function
+    }
+
+    pub const fn address(&self) -> usize {
+        self.address
+    }
+
+    /// Returns a number of frames elided by tail calls below this frame.
+    pub const fn tail_call_count(&self) -> u32 {
+        self.tail_call_count
+    }
+
+    pub fn name(&self) -> &str {
+        match (&self.name, self.function) {
+            (Some(name), _) => name,
+            (None, Some(_)) => "<anonymous>",
+            (None, None) => "<top level>",
+        }
+    }
+
+    pub const fn line(&self) -> Option<usize> {
+        self.line
+    }
+
+    fn symbolize(&mut self, debug_info: &DebugInfo) {
+        if self.function.is_some() {
+            self.name = debug_info
+                .function_at(self.address)
+                .and_then(|function| function.name())
+                .map(ToOwned::to_owned);
+        }
+
+        self.line = debug_info.line(self.address);
+    }
+
+    fn location_eq(&self, other: &Self) -> bool {
+        self.function == other.function
+            && self.name == other.name
+            && self.line == other.line
+            && self.tail_call_count == other.tail_call_count
+            && (self.line.is_some() || self.address == other.address)
+    }
+}
+
+impl From<&StackFrame<'_>> for BacktraceFrame {
+    fn from(frame: &StackFrame) -> Self {
+        Self::new(
+            frame.closure().map(|closure| closure.id()),
+            frame.address(),
+            frame.tail_call_count(),
+        )
+    }
+}
+
+/// A backtrace of call frames from the innermost one to a top level.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct Backtrace {
+    frames: Vec<BacktraceFrame>,
+}
+
+impl Backtrace {
+    pub const fn new(frames: Vec<BacktraceFrame>) -> Self {
+        Self { frames }
+    }
+
+    pub fn frames(&self) -> &[BacktraceFrame] {
+        &self.frames
+    }
+
+    /// Resolves names and lines of frames with debug information.
+    pub fn symbolize(&mut self, debug_info: &DebugInfo) {
+        for frame in &mut self.frames {
+            frame.symbolize(debug_info);
+        }
+    }
+}
+
+impl Display for Backtrace {
+    fn fmt(&self, formatter: &mut Formatter) -> fmt::Result {
+        let mut index = 0;
+
+        while index < self.frames.len() {
+            let frame = &self.frames[index];
+
+            if index != 0 {
+                writeln!(formatter)?;
+            }
+
+            write!(formatter, "  #{index} {}", frame.name())?;
+
+            match frame.line {
+                Some(line) => write!(formatter, " at line {line}")?,
+                None => write!(formatter, " at {:#x}", frame.address)?,
+            }
+
+            match frame.tail_call_count {
+                0 => {}
+                1 => write!(formatter, "\n     (1 frame elided by tail calls)")?,
+                count => write!(formatter, "\n     ({count} frames elided by tail calls)")?,
+            }
+
+            // Collapse frames of deep recursion.
+            let count = self.frames[index + 1..]
+                .iter()
+                .take_while(|other| frame.location_eq(other))
+                .count();
+
+            match count {
+                0 | 1 => index += 1,
+                count => {
+                    write!(formatter, "\n     ({count} more frames of the same)")?;
+                    index += count + 1;
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::FunctionInfo;
+    use alloc::string::ToString;
+    use pretty_assertions::assert_eq;
+
+    fn debug_info() -> DebugInfo {
+        let mut info = DebugInfo::new();
+
+        info.insert_line(0, 1);
+        info.insert_line(3, 2);
+        info.insert_line(6, 3);
+        info.insert_function(FunctionInfo::new(
+            Some("f".into()),
+            2..6,
+            None,
+            vec![],
+            vec![],
+        ));
+
+        info
+    }
+
+    #[test]
+    fn display_raw() {
+        assert_eq!(
+            Backtrace::new(vec![
+                BacktraceFrame::new(Some(2), 4, 0),
+                BacktraceFrame::new(None, 7, 0)
+            ])
+            .to_string(),
+            "  #0 <anonymous> at 0x4\n  #1 <top level> at 0x7"
+        );
+    }
+
+    #[test]
+    fn display_symbolized() {
+        let mut backtrace = Backtrace::new(vec![
+            BacktraceFrame::new(Some(2), 4, 3),
+            BacktraceFrame::new(None, 7, 0),
+        ]);
+
+        backtrace.symbolize(&debug_info());
+
+        assert_eq!(
+            backtrace.to_string(),
+            "  #0 f at line 2\n     (3 frames elided by tail calls)\n  #1 <top level> at line 3"
+        );
+    }
+
+    #[test]
+    fn collapse_recursion() {
+        let mut backtrace = Backtrace::new(vec![
+            BacktraceFrame::new(Some(2), 3, 0),
+            BacktraceFrame::new(Some(2), 4, 0),
+            BacktraceFrame::new(Some(2), 4, 0),
+            BacktraceFrame::new(Some(2), 4, 0),
+            BacktraceFrame::new(None, 7, 0),
+        ]);
+
+        backtrace.symbolize(&debug_info());
+
+        assert_eq!(
+            backtrace.to_string(),
+            "  #0 f at line 2\n     (3 more frames of the same)\n  #4 <top level> at line 3"
+        );
+    }
+}
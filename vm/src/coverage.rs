@@ -0,0 +1,87 @@
+use crate::DebugInfo;
+use alloc::{collections::BTreeMap, format, string::String};
+
+/// Line coverage of a program.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct Coverage {
+    lines: BTreeMap<usize, u64>,
+}
+
+impl Coverage {
+    /// Aggregates hit counts of instructions into ones of lines.
+    ///
+    /// A line is hit when an instruction starting the line is executed.
+    pub fn new(instruction_counts: &[u64], debug_info: &DebugInfo) -> Self {
+        let mut lines = debug_info
+            .lines()
+            .iter()
+            .map(|&(_, line)| (line, 0))
+            .collect::<BTreeMap<_, _>>();
+
+        for (address, &count) in instruction_counts.iter().enumerate() {
+            if let Some(line) = debug_info.line_start(address) {
+                *lines.entry(line).or_default() += count;
+            }
+        }
+
+        Self { lines }
+    }
+
+    /// Returns hit counts of lines with code.
+    pub fn lines(&self) -> &BTreeMap<usize, u64> {
+        &self.lines
+    }
+
+    /// Returns a number of lines hit at least once.
+    pub fn hit_count(&self) -> usize {
+        self.lines.values().filter(|&&count| count > 0).count()
+    }
+
+    /// Formats coverage in the lcov tracefile format.
+    pub fn to_lcov(&self, path: &str) -> String {
+        let mut string = format!("TN:\nSF:{path}\n");
+
+        for (line, count) in &self.lines {
+            string += &format!("DA:{line},{count}\n");
+        }
+
+        string += &format!(
+            "LF:{}\nLH:{}\nend_of_record\n",
+            self.lines.len(),
+            self.hit_count()
+        );
+
+        string
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::FunctionInfo;
+    use alloc::vec::Vec;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn count_lines() {
+        let mut info = DebugInfo::new();
+
+        info.insert_line(0, 1);
+        info.insert_line(2, 2);
+        info.continue_line(3, 1);
+        info.insert_line(4, 3);
+        info.insert_line(6, 5);
+        info.insert_function(FunctionInfo::new(None, 4..6, None, vec![], vec![]));
+
+        let coverage = Coverage::new(&[1, 1, 1, 1, 3, 3, 0], &info);
+
+        assert_eq!(
+            coverage.lines().iter().collect::<Vec<_>>(),
+            [(&1, &1), (&2, &1), (&3, &3), (&5, &0)]
+        );
+        assert_eq!(
+            coverage.to_lcov("main.arc"),
+            "TN:\nSF:main.arc\nDA:1,1\nDA:2,1\nDA:3,3\nDA:5,0\nLF:4\nLH:3\nend_of_record\n"
+        );
+    }
+}
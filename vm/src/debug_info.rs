@@ -0,0 +1,299 @@
+use alloc::{string::String, vec::Vec};
+use core::ops::Range;
+use runtime::ClosureId;
+
+/// Debug information of a function.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct FunctionInfo {
+    name: Option<String>,
+    codes: Range<usize>,
+    span: Option<Range<usize>>,
+    arguments: Vec<String>,
+    environment: Vec<String>,
+}
+
+impl FunctionInfo {
+    pub fn new(
+        name: Option<String>,
+        codes: Range<usize>,
+        span: Option<Range<usize>>,
+        arguments: Vec<String>,
+        environment: Vec<String>,
+    ) -> Self {
+        Self {
+            name,
+            codes,
+            span,
+            arguments,
+            environment,
+        }
+    }
+
+    pub fn name(&self) -> Option<&str> {
+        self.name.as_deref()
+    }
+
+    pub const fn id(&self) -> ClosureId {
+        self.codes.start as ClosureId
+    }
+
+    /// Returns a range of addresses of a function body.
+    pub fn codes(&self) -> Range<usize> {
+        self.codes.clone()
+    }
+
+    /// Returns a byte range of a function in a source code.
+    pub fn span(&self) -> Option<Range<usize>> {
+        self.span.clone()
+    }
+
+    /// Returns names of arguments.
+    pub fn arguments(&self) -> &[String] {
+        &self.arguments
+    }
+
+    /// Returns names of free variables in an order of closure environments.
+    pub fn environment(&self) -> &[String] {
+        &self.environment
+    }
+}
+
+/// Debug information of bytecodes.
+///
+/// It maps instruction addresses to source lines and functions.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct DebugInfo {
+    lines: Vec<(usize, usize)>,
+    starts: Vec<usize>,
+    functions: Vec<FunctionInfo>,
+}
+
+impl DebugInfo {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Marks a start of a line at an address.
+    ///
+    /// Addresses must be inserted in an ascending order.
+    pub fn insert_line(&mut self, address: usize, line: usize) {
+        if self.push_line(address, line) && self.starts.last() != Some(&address) {
+            self.starts.push(address);
+        }
+    }
+
+    /// Marks a line continued at an address after its inner lines.
+    ///
+    /// Addresses must be inserted in an ascending order.
+    pub fn continue_line(&mut self, address: usize, line: usize) {
+        self.push_line(address, line);
+    }
+
+    fn push_line(&mut self, address: usize, line: usize) -> bool {
+        match self.lines.last_mut() {
+            Some((_, last)) if *last == line => return false,
+            Some((last, last_line)) if *last == address => *last_line = line,
+            _ => self.lines.push((address, line)),
+        }
+
+        true
+    }
+
+    pub fn insert_function(&mut self, function: FunctionInfo) {
+        self.functions.push(function);
+    }
+
+    /// Relocates addresses from a start address after bytecodes there are
+    /// rewritten.
+    ///
+    /// `addresses` maps each old offset from the start to a new address and
+    /// has an extra element for the end of the old bytecodes.
+    pub fn relocate(&mut self, start: usize, addresses: &[usize]) {
+        let relocate = |address: usize| {
+            if address < start {
+                address
+            } else {
+                addresses[(address - start).min(addresses.len() - 1)]
+            }
+        };
+
+        let mut lines = Vec::<(usize, usize)>::with_capacity(self.lines.len());
+
+        for &(address, line) in &self.lines {
+            let address = relocate(address);
+
+            match lines.last_mut() {
+                Some((last, last_line)) if *last == address => *last_line = line,
+                _ => lines.push((address, line)),
+            }
+        }
+
+        lines.dedup_by_key(|&mut (_, line)| line);
+        self.lines = lines;
+
+        for address in &mut self.starts {
+            *address = relocate(*address);
+        }
+
+        self.starts.dedup();
+
+        for function in &mut self.functions {
+            function.codes = relocate(function.codes.start)..relocate(function.codes.end);
+        }
+    }
+
+    /// Returns a line of an instruction at an address.
+    pub fn line(&self, address: usize) -> Option<usize> {
+        let index = self
+            .lines
+            .partition_point(|&(start, _)| start <= address)
+            .checked_sub(1)?;
+
+        Some(self.lines[index].1)
+    }
+
+    /// Returns a line if it starts exactly at an address.
+    ///
+    /// Lines also start at entries of functions.
+    pub fn line_start(&self, address: usize) -> Option<usize> {
+        if self.starts.binary_search(&address).is_ok()
+            || self.function(address as ClosureId).is_some()
+        {
+            self.line(address)
+        } else {
+            None
+        }
+    }
+
+    /// Returns a line if it starts or continues exactly at an address.
+    pub fn line_boundary(&self, address: usize) -> Option<usize> {
+        let index = self
+            .lines
+            .binary_search_by_key(&address, |&(start, _)| start)
+            .ok()?;
+
+        Some(self.lines[index].1)
+    }
+
+    /// Returns pairs of addresses and lines starting there.
+    pub fn lines(&self) -> &[(usize, usize)] {
+        &self.lines
+    }
+
+    pub fn function(&self, id: ClosureId) -> Option<&FunctionInfo> {
+        self.functions.iter().find(|function| function.id() == id)
+    }
+
+    /// Returns the innermost function containing an address.
+    pub fn function_at(&self, address: usize) -> Option<&FunctionInfo> {
+        self.functions
+            .iter()
+            .filter(|function| function.codes.contains(&address))
+            .min_by_key(|function| function.codes.len())
+    }
+
+    pub fn functions(&self) -> &[FunctionInfo] {
+        &self.functions
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn insert_lines() {
+        let mut info = DebugInfo::new();
+
+        info.insert_line(0, 1);
+        info.insert_line(2, 1);
+        info.insert_line(4, 2);
+        info.insert_line(4, 3);
+        info.continue_line(8, 1);
+
+        assert_eq!(info.lines(), [(0, 1), (4, 3), (8, 1)]);
+    }
+
+    #[test]
+    fn relocate_lines() {
+        let mut info = DebugInfo::new();
+
+        info.insert_line(0, 1);
+        info.insert_line(2, 2);
+        info.insert_line(3, 3);
+        info.continue_line(5, 2);
+        info.insert_line(6, 4);
+        info.insert_function(FunctionInfo::new(None, 2..6, None, vec![], vec![]));
+
+        info.relocate(2, &[2, 2, 2, 3, 4, 4]);
+
+        assert_eq!(info.lines(), [(0, 1), (2, 3), (3, 2), (4, 4)]);
+        assert_eq!(info.line_start(2), Some(3));
+        assert_eq!(info.line_start(4), Some(4));
+        assert_eq!(info.functions()[0].codes(), 2..4);
+    }
+
+    #[test]
+    fn find_lines() {
+        let mut info = DebugInfo::new();
+
+        info.insert_line(2, 1);
+        info.insert_line(4, 2);
+        info.continue_line(6, 1);
+        info.insert_function(FunctionInfo::new(None, 5..6, None, vec![], vec![]));
+
+        assert_eq!(info.line(0), None);
+        assert_eq!(info.line(2), Some(1));
+        assert_eq!(info.line(3), Some(1));
+        assert_eq!(info.line(42), Some(1));
+        assert_eq!(info.line_start(3), None);
+        assert_eq!(info.line_start(4), Some(2));
+        assert_eq!(info.line_start(5), Some(2));
+        assert_eq!(info.line_start(6), None);
+        assert_eq!(info.line_boundary(5), None);
+        assert_eq!(info.line_boundary(6), Some(1));
+    }
+
+    #[test]
+    fn find_function() {
+        let mut info = DebugInfo::new();
+
+        info.insert_function(FunctionInfo::new(
+            Some("f".into()),
+            3..6,
+            None,
+            vec![],
+            vec![],
+        ));
+
+        assert_eq!(info.function(3).and_then(FunctionInfo::name), Some("f"));
+        assert_eq!(info.function(4), None);
+    }
+
+    #[test]
+    fn find_function_at_address() {
+        let mut info = DebugInfo::new();
+
+        info.insert_function(FunctionInfo::new(
+            Some("g".into()),
+            4..6,
+            None,
+            vec![],
+            vec![],
+        ));
+        info.insert_function(FunctionInfo::new(
+            Some("f".into()),
+            2..10,
+            None,
+            vec![],
+            vec![],
+        ));
+
+        assert_eq!(info.function_at(1), None);
+        assert_eq!(info.function_at(2).and_then(FunctionInfo::name), Some("f"));
+        assert_eq!(info.function_at(5).and_then(FunctionInfo::name), Some("g"));
+        assert_eq!(info.function_at(6).and_then(FunctionInfo::name), Some("f"));
+        assert_eq!(info.function_at(10), None);
+    }
+}
@@ -1,10 +1,5 @@
 use core::mem::size_of;
 
-#[inline]
-pub fn decode_f64(codes: &[u8], index: &mut usize) -> f64 {
-    f64::from_bits(decode_u64(codes, index))
-}
-
 #[inline]
 pub fn decode_u64(codes: &[u8], index: &mut usize) -> u64 {
     const SIZE: usize = size_of::<u64>();
@@ -50,17 +45,6 @@ pub fn decode_u8(codes: &[u8], index: &mut usize) -> u8 {
     value
 }
 
-#[inline]
-pub fn decode_u8_option(codes: &[u8], index: &mut usize) -> Option<u8> {
-    if let Some(code) = codes.get(*index) {
-        *index += 1;
-
-        Some(*code)
-    } else {
-        None
-    }
-}
-
 #[inline]
 pub fn decode_bytes<'a>(codes: &'a [u8], len: usize, index: &mut usize) -> &'a [u8] {
     let value = &codes[*index..*index + len];
@@ -0,0 +1,47 @@
+use crate::Backtrace;
+use core::error::Error;
+use core::fmt::{self, Display, Formatter};
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum RuntimeError {
+    CallStackOverflow(Backtrace),
+    Output(Backtrace),
+    /// A range longer than [`runtime::Array::MAX_RANGE_LENGTH`].
+    Range(Backtrace),
+    StackOverflow(Backtrace),
+}
+
+impl RuntimeError {
+    pub const fn backtrace(&self) -> &Backtrace {
+        match self {
+            Self::CallStackOverflow(backtrace)
+            | Self::Output(backtrace)
+            | Self::Range(backtrace)
+            | Self::StackOverflow(backtrace) => backtrace,
+        }
+    }
+
+    pub fn backtrace_mut(&mut self) -> &mut Backtrace {
+        match self {
+            Self::CallStackOverflow(backtrace)
+            | Self::Output(backtrace)
+            | Self::Range(backtrace)
+            | Self::StackOverflow(backtrace) => backtrace,
+        }
+    }
+}
+
+impl Error for RuntimeError {}
+
+impl Display for RuntimeError {
+    fn fmt(&self, formatter: &mut Formatter) -> fmt::Result {
+        match self {
+            Self::CallStackOverflow(_) => write!(formatter, "call stack overflow")?,
+            Self::Output(_) => write!(formatter, "output error")?,
+            Self::Range(_) => write!(formatter, "range too long")?,
+            Self::StackOverflow(_) => write!(formatter, "stack overflow")?,
+        }
+
+        write!(formatter, "\n{}", self.backtrace())
+    }
+}
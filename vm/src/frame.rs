@@ -2,6 +2,7 @@
 pub struct Frame {
     pointer: u32,
     return_address: u32,
+    tail_call_count: u32,
 }
 
 impl Frame {
@@ -9,6 +10,7 @@ impl Frame {
         Self {
             pointer,
             return_address,
+            tail_call_count: 0,
         }
     }
 
@@ -19,4 +21,13 @@ impl Frame {
     pub fn return_address(&self) -> u32 {
         self.return_address
     }
+
+    /// Returns a number of frames elided by tail calls in place of this frame.
+    pub fn tail_call_count(&self) -> u32 {
+        self.tail_call_count
+    }
+
+    pub(crate) fn elide(&mut self) {
+        self.tail_call_count = self.tail_call_count.saturating_add(1);
+    }
 }
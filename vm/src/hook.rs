@@ -0,0 +1,28 @@
+use crate::Vm;
+use core::{
+    fmt::{self, Write},
+    ops::ControlFlow,
+};
+use runtime::Value;
+
+/// A hook into execution of a VM.
+pub trait Hook {
+    /// Called before each instruction is executed.
+    ///
+    /// A VM stops execution if it returns `ControlFlow::Break`.
+    fn before_instruction(&mut self, vm: &Vm, codes: &[u8]) -> ControlFlow<()>;
+
+    /// Called when a value is dumped.
+    ///
+    /// It writes the value into an output of a VM by default.
+    fn dump(&mut self, value: &Value, output: &mut dyn Write) -> fmt::Result {
+        writeln!(output, "{value}")
+    }
+}
+
+impl Hook for () {
+    #[inline(always)]
+    fn before_instruction(&mut self, _vm: &Vm, _codes: &[u8]) -> ControlFlow<()> {
+        ControlFlow::Continue(())
+    }
+}
@@ -0,0 +1,105 @@
+use alloc::{boxed::Box, string::String};
+use core::fmt::{self, Debug, Formatter};
+
+/// An input source of a VM.
+pub trait Input {
+    /// Reads a line without its line break or returns `None` at the end of
+    /// input.
+    fn read_line(&mut self) -> Result<Option<String>, fmt::Error>;
+}
+
+/// An empty input.
+impl Input for () {
+    fn read_line(&mut self) -> Result<Option<String>, fmt::Error> {
+        Ok(None)
+    }
+}
+
+/// Standard input of a process.
+#[cfg(feature = "std")]
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Stdin;
+
+#[cfg(feature = "std")]
+impl Input for Stdin {
+    fn read_line(&mut self) -> Result<Option<String>, fmt::Error> {
+        Reader::new(std::io::stdin().lock()).read_line()
+    }
+}
+
+/// An input reading lines from `std::io::BufRead`.
+#[cfg(feature = "std")]
+#[derive(Debug)]
+pub struct Reader<R>(R);
+
+#[cfg(feature = "std")]
+impl<R> Reader<R> {
+    pub const fn new(reader: R) -> Self {
+        Self(reader)
+    }
+
+    pub fn into_inner(self) -> R {
+        self.0
+    }
+}
+
+#[cfg(feature = "std")]
+impl<R: std::io::BufRead> Input for Reader<R> {
+    fn read_line(&mut self) -> Result<Option<String>, fmt::Error> {
+        let mut line = String::new();
+
+        if self.0.read_line(&mut line).map_err(|_| fmt::Error)? == 0 {
+            return Ok(None);
+        }
+
+        for suffix in ["\n", "\r"] {
+            if line.ends_with(suffix) {
+                line.pop();
+            }
+        }
+
+        Ok(Some(line))
+    }
+}
+
+/// An input source in a VM.
+pub(crate) struct Source(Box<dyn Input>);
+
+impl Source {
+    pub fn new(input: impl Input + 'static) -> Self {
+        Self(Box::new(input))
+    }
+
+    pub fn input(&mut self) -> &mut dyn Input {
+        &mut *self.0
+    }
+}
+
+impl Debug for Source {
+    fn fmt(&self, formatter: &mut Formatter) -> fmt::Result {
+        write!(formatter, "Source")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn read_nothing() {
+        assert_eq!(().read_line(), Ok(None));
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn read_lines() {
+        let mut reader = Reader::new(b"foo\nbar\r\n\nbaz".as_slice());
+
+        assert_eq!(reader.read_line(), Ok(Some("foo".into())));
+        assert_eq!(reader.read_line(), Ok(Some("bar".into())));
+        assert_eq!(reader.read_line(), Ok(Some("".into())));
+        assert_eq!(reader.read_line(), Ok(Some("baz".into())));
+        assert_eq!(reader.read_line(), Ok(None));
+    }
+}
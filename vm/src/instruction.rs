@@ -1,21 +1,29 @@
 mod format;
 
-pub use format::{format_instructions, FormatError};
+pub use format::{decode_instruction, format_instructions, FormatError, InstructionIr};
 
 #[repr(u8)]
-#[derive(Clone, Copy, Debug, num_derive::FromPrimitive)]
+#[derive(Clone, Copy, Debug, Eq, PartialEq, num_derive::FromPrimitive)]
 pub enum Instruction {
     Add,
+    AddFloat64,
     And,
     Branch,
+    BranchNotEqual,
     Call,
     Close,
+    Concat,
+    Deref,
+    Display,
     Divide,
     Drop,
     Dump,
+    DumpDrop,
     Environment,
     Equal,
+    Filter,
     Float64,
+    Fold,
     Get,
     GreaterThan,
     GreaterThanOrEqual,
@@ -24,31 +32,53 @@ pub enum Instruction {
     Length,
     LessThan,
     LessThanOrEqual,
+    Map,
     Multiply,
     Nil,
+    Nip,
     Not,
     NotEqual,
     Or,
     Peek,
+    Pop,
+    Push,
+    Range,
+    Ref,
+    Repr,
     Return,
+    Reverse,
     Set,
+    SetRef,
+    Slice,
+    Sort,
+    Store,
     Subtract,
+    SubtractFloat64,
     Symbol,
     TailCall,
+    Take,
 }
 
 impl Instruction {
     pub const ADD: u8 = Self::Add as _;
+    pub const ADD_FLOAT64: u8 = Self::AddFloat64 as _;
     pub const AND: u8 = Self::And as _;
     pub const BRANCH: u8 = Self::Branch as _;
+    pub const BRANCH_NOT_EQUAL: u8 = Self::BranchNotEqual as _;
     pub const CALL: u8 = Self::Call as _;
     pub const CLOSE: u8 = Self::Close as _;
+    pub const CONCAT: u8 = Self::Concat as _;
+    pub const DEREF: u8 = Self::Deref as _;
+    pub const DISPLAY: u8 = Self::Display as _;
     pub const DIVIDE: u8 = Self::Divide as _;
     pub const DROP: u8 = Self::Drop as _;
     pub const DUMP: u8 = Self::Dump as _;
+    pub const DUMP_DROP: u8 = Self::DumpDrop as _;
     pub const ENVIRONMENT: u8 = Self::Environment as _;
     pub const EQUAL: u8 = Self::Equal as _;
+    pub const FILTER: u8 = Self::Filter as _;
     pub const FLOAT64: u8 = Self::Float64 as _;
+    pub const FOLD: u8 = Self::Fold as _;
     pub const GET: u8 = Self::Get as _;
     pub const GREATER_THAN: u8 = Self::GreaterThan as _;
     pub const GREATER_THAN_OR_EQUAL: u8 = Self::GreaterThanOrEqual as _;
@@ -57,15 +87,29 @@ impl Instruction {
     pub const LENGTH: u8 = Self::Length as _;
     pub const LESS_THAN: u8 = Self::LessThan as _;
     pub const LESS_THAN_OR_EQUAL: u8 = Self::LessThanOrEqual as _;
+    pub const MAP: u8 = Self::Map as _;
     pub const MULTIPLY: u8 = Self::Multiply as _;
     pub const NIL: u8 = Self::Nil as _;
+    pub const NIP: u8 = Self::Nip as _;
     pub const NOT: u8 = Self::Not as _;
     pub const NOT_EQUAL: u8 = Self::NotEqual as _;
     pub const OR: u8 = Self::Or as _;
     pub const PEEK: u8 = Self::Peek as _;
+    pub const POP: u8 = Self::Pop as _;
+    pub const PUSH: u8 = Self::Push as _;
+    pub const RANGE: u8 = Self::Range as _;
+    pub const REF: u8 = Self::Ref as _;
+    pub const REPR: u8 = Self::Repr as _;
     pub const RETURN: u8 = Self::Return as _;
+    pub const REVERSE: u8 = Self::Reverse as _;
     pub const SET: u8 = Self::Set as _;
+    pub const SET_REF: u8 = Self::SetRef as _;
+    pub const SLICE: u8 = Self::Slice as _;
+    pub const SORT: u8 = Self::Sort as _;
+    pub const STORE: u8 = Self::Store as _;
     pub const SUBTRACT: u8 = Self::Subtract as _;
+    pub const SUBTRACT_FLOAT64: u8 = Self::SubtractFloat64 as _;
     pub const SYMBOL: u8 = Self::Symbol as _;
     pub const TAIL_CALL: u8 = Self::TailCall as _;
+    pub const TAKE: u8 = Self::Take as _;
 }
@@ -1,88 +1,28 @@
 mod ir;
 
+pub use self::ir::InstructionIr;
 use crate::{
     decode::{decode_bytes, decode_u16, decode_u32, decode_u64, decode_u8},
     Instruction,
 };
+use alloc::{
+    string::{String, ToString},
+    vec::Vec,
+};
 use core::fmt;
-use ir::InstructionIr;
-use num_traits::FromPrimitive;
-use std::{
-    self,
+use core::{
     error::Error,
     fmt::{Display, Formatter},
     str::{self, Utf8Error},
 };
+use num_traits::FromPrimitive;
 
 pub fn format_instructions(codes: &[u8]) -> Result<String, FormatError> {
     let mut index = 0;
     let mut instructions = Vec::new();
 
     while index < codes.len() {
-        let instruction = decode_u8(codes, &mut index);
-
-        instructions.push(
-            match Instruction::from_u8(instruction)
-                .ok_or(FormatError::InvalidInstruction(instruction))?
-            {
-                Instruction::Nil => InstructionIr::Nil,
-                Instruction::Float64 => {
-                    InstructionIr::Float64(f64::from_bits(decode_u64(codes, &mut index)))
-                }
-                Instruction::Integer32 => {
-                    InstructionIr::Integer32(decode_u32(codes, &mut index) as i32)
-                }
-                Instruction::Symbol => {
-                    let len = decode_u8(codes, &mut index);
-
-                    InstructionIr::Symbol {
-                        len,
-                        string: str::from_utf8(decode_bytes(codes, len as usize, &mut index))?
-                            .into(),
-                    }
-                }
-                Instruction::Peek => InstructionIr::Peek(decode_u8(codes, &mut index)),
-                Instruction::Get => InstructionIr::Get,
-                Instruction::Set => InstructionIr::Set,
-                Instruction::Length => InstructionIr::Length,
-                Instruction::Add => InstructionIr::Add,
-                Instruction::Subtract => InstructionIr::Subtract,
-                Instruction::Multiply => InstructionIr::Multiply,
-                Instruction::Divide => InstructionIr::Divide,
-                Instruction::Call => InstructionIr::Call {
-                    arity: decode_u8(codes, &mut index),
-                },
-                Instruction::TailCall => InstructionIr::TailCall {
-                    arity: decode_u8(codes, &mut index),
-                },
-                Instruction::Close => InstructionIr::Close {
-                    pointer: decode_u32(codes, &mut index),
-                    arity: decode_u8(codes, &mut index),
-                    environment_size: decode_u8(codes, &mut index),
-                },
-                Instruction::Environment => {
-                    InstructionIr::Environment(decode_u8(codes, &mut index))
-                }
-                Instruction::Equal => InstructionIr::Equal,
-                Instruction::GreaterThan => InstructionIr::GreaterThan,
-                Instruction::GreaterThanOrEqual => InstructionIr::GreaterThanOrEqual,
-                Instruction::LessThan => InstructionIr::LessThan,
-                Instruction::LessThanOrEqual => InstructionIr::LessThanOrEqual,
-                Instruction::Not => InstructionIr::Not,
-                Instruction::NotEqual => InstructionIr::NotEqual,
-                Instruction::And => InstructionIr::And,
-                Instruction::Or => InstructionIr::Or,
-                Instruction::Drop => InstructionIr::Drop,
-                Instruction::Dump => InstructionIr::Dump,
-                Instruction::Jump => InstructionIr::Jump {
-                    pointer: decode_u16(codes, &mut index) as i16,
-                },
-                Instruction::Branch => InstructionIr::Branch {
-                    pointer: decode_u16(codes, &mut index) as i16,
-                },
-                Instruction::Return => InstructionIr::Return,
-            },
-        );
+        instructions.push(decode_instruction(codes, &mut index)?);
     }
 
     Ok(instructions
@@ -92,6 +32,97 @@ pub fn format_instructions(codes: &[u8]) -> Result<String, FormatError> {
         .join("\n"))
 }
 
+/// Decodes an instruction at an index and advances the index.
+pub fn decode_instruction(codes: &[u8], index: &mut usize) -> Result<InstructionIr, FormatError> {
+    let instruction = decode_u8(codes, index);
+
+    Ok(
+        match Instruction::from_u8(instruction)
+            .ok_or(FormatError::InvalidInstruction(instruction))?
+        {
+            Instruction::Nil => InstructionIr::Nil,
+            Instruction::Nip => InstructionIr::Nip(decode_u8(codes, index)),
+            Instruction::Float64 => {
+                InstructionIr::Float64(f64::from_bits(decode_u64(codes, index)))
+            }
+            Instruction::Integer32 => InstructionIr::Integer32(decode_u32(codes, index) as i32),
+            Instruction::Symbol => {
+                let len = decode_u8(codes, index);
+
+                InstructionIr::Symbol {
+                    len,
+                    string: str::from_utf8(decode_bytes(codes, len as usize, index))?.into(),
+                }
+            }
+            Instruction::Peek => InstructionIr::Peek(decode_u8(codes, index)),
+            Instruction::Take => InstructionIr::Take(decode_u8(codes, index)),
+            Instruction::Get => InstructionIr::Get,
+            Instruction::Set => InstructionIr::Set,
+            Instruction::SetRef => InstructionIr::SetRef,
+            Instruction::Store => InstructionIr::Store(decode_u8(codes, index)),
+            Instruction::Length => InstructionIr::Length,
+            Instruction::Ref => InstructionIr::Ref,
+            Instruction::Deref => InstructionIr::Deref,
+            Instruction::Concat => InstructionIr::Concat,
+            Instruction::Filter => InstructionIr::Filter,
+            Instruction::Fold => InstructionIr::Fold,
+            Instruction::Map => InstructionIr::Map,
+            Instruction::Pop => InstructionIr::Pop,
+            Instruction::Push => InstructionIr::Push,
+            Instruction::Range => InstructionIr::Range,
+            Instruction::Reverse => InstructionIr::Reverse,
+            Instruction::Slice => InstructionIr::Slice,
+            Instruction::Sort => InstructionIr::Sort,
+            Instruction::Add => InstructionIr::Add,
+            Instruction::AddFloat64 => {
+                InstructionIr::AddFloat64(f64::from_bits(decode_u64(codes, index)))
+            }
+            Instruction::Subtract => InstructionIr::Subtract,
+            Instruction::SubtractFloat64 => {
+                InstructionIr::SubtractFloat64(f64::from_bits(decode_u64(codes, index)))
+            }
+            Instruction::Multiply => InstructionIr::Multiply,
+            Instruction::Display => InstructionIr::Display,
+            Instruction::Divide => InstructionIr::Divide,
+            Instruction::Call => InstructionIr::Call {
+                arity: decode_u8(codes, index),
+            },
+            Instruction::TailCall => InstructionIr::TailCall {
+                arity: decode_u8(codes, index),
+            },
+            Instruction::Close => InstructionIr::Close {
+                pointer: decode_u32(codes, index),
+                arity: decode_u8(codes, index),
+                environment_size: decode_u8(codes, index),
+            },
+            Instruction::Environment => InstructionIr::Environment(decode_u8(codes, index)),
+            Instruction::Equal => InstructionIr::Equal,
+            Instruction::GreaterThan => InstructionIr::GreaterThan,
+            Instruction::GreaterThanOrEqual => InstructionIr::GreaterThanOrEqual,
+            Instruction::LessThan => InstructionIr::LessThan,
+            Instruction::LessThanOrEqual => InstructionIr::LessThanOrEqual,
+            Instruction::Not => InstructionIr::Not,
+            Instruction::NotEqual => InstructionIr::NotEqual,
+            Instruction::And => InstructionIr::And,
+            Instruction::Or => InstructionIr::Or,
+            Instruction::Drop => InstructionIr::Drop,
+            Instruction::Dump => InstructionIr::Dump,
+            Instruction::DumpDrop => InstructionIr::DumpDrop,
+            Instruction::Jump => InstructionIr::Jump {
+                pointer: decode_u16(codes, index) as i16,
+            },
+            Instruction::Branch => InstructionIr::Branch {
+                pointer: decode_u16(codes, index) as i16,
+            },
+            Instruction::BranchNotEqual => InstructionIr::BranchNotEqual {
+                pointer: decode_u16(codes, index) as i16,
+            },
+            Instruction::Repr => InstructionIr::Repr,
+            Instruction::Return => InstructionIr::Return,
+        },
+    )
+}
+
 #[derive(Debug)]
 pub enum FormatError {
     InvalidInstruction(u8),
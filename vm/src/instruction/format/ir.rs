@@ -1,13 +1,19 @@
+use crate::Instruction;
+use alloc::{string::String, vec::Vec};
 use core::fmt;
-use std::fmt::{Display, Formatter};
+use core::fmt::{Display, Formatter};
 
 #[derive(Clone, Debug)]
 pub enum InstructionIr {
     Add,
+    AddFloat64(f64),
     And,
     Branch {
         pointer: i16,
     },
+    BranchNotEqual {
+        pointer: i16,
+    },
     Call {
         arity: u8,
     },
@@ -16,12 +22,18 @@ pub enum InstructionIr {
         arity: u8,
         environment_size: u8,
     },
+    Concat,
+    Deref,
+    Display,
     Divide,
     Drop,
     Dump,
+    DumpDrop,
     Environment(u8),
     Equal,
+    Filter,
     Float64(f64),
+    Fold,
     Get,
     GreaterThan,
     GreaterThanOrEqual,
@@ -32,15 +44,28 @@ pub enum InstructionIr {
     Length,
     LessThan,
     LessThanOrEqual,
+    Map,
     Multiply,
     Nil,
+    Nip(u8),
     Not,
     NotEqual,
     Or,
     Peek(u8),
+    Pop,
+    Push,
+    Range,
+    Ref,
+    Repr,
     Return,
+    Reverse,
     Set,
+    SetRef,
+    Slice,
+    Sort,
+    Store(u8),
     Subtract,
+    SubtractFloat64(f64),
     Symbol {
         len: u8,
         string: String,
@@ -48,26 +73,127 @@ pub enum InstructionIr {
     TailCall {
         arity: u8,
     },
+    Take(u8),
+}
+
+impl InstructionIr {
+    /// Encodes an instruction into bytecodes.
+    pub fn encode(&self, codes: &mut Vec<u8>) {
+        match self {
+            Self::Add => codes.push(Instruction::Add as u8),
+            Self::AddFloat64(number) => {
+                codes.push(Instruction::AddFloat64 as u8);
+                codes.extend(number.to_le_bytes());
+            }
+            Self::And => codes.push(Instruction::And as u8),
+            Self::Branch { pointer } => {
+                codes.push(Instruction::Branch as u8);
+                codes.extend(pointer.to_le_bytes());
+            }
+            Self::BranchNotEqual { pointer } => {
+                codes.push(Instruction::BranchNotEqual as u8);
+                codes.extend(pointer.to_le_bytes());
+            }
+            Self::Call { arity } => codes.extend([Instruction::Call as u8, *arity]),
+            Self::Close {
+                pointer,
+                arity,
+                environment_size,
+            } => {
+                codes.push(Instruction::Close as u8);
+                codes.extend(pointer.to_le_bytes());
+                codes.extend([*arity, *environment_size]);
+            }
+            Self::Concat => codes.push(Instruction::Concat as u8),
+            Self::Deref => codes.push(Instruction::Deref as u8),
+            Self::Display => codes.push(Instruction::Display as u8),
+            Self::Divide => codes.push(Instruction::Divide as u8),
+            Self::Drop => codes.push(Instruction::Drop as u8),
+            Self::Dump => codes.push(Instruction::Dump as u8),
+            Self::DumpDrop => codes.push(Instruction::DumpDrop as u8),
+            Self::Environment(index) => codes.extend([Instruction::Environment as u8, *index]),
+            Self::Equal => codes.push(Instruction::Equal as u8),
+            Self::Filter => codes.push(Instruction::Filter as u8),
+            Self::Float64(number) => {
+                codes.push(Instruction::Float64 as u8);
+                codes.extend(number.to_le_bytes());
+            }
+            Self::Fold => codes.push(Instruction::Fold as u8),
+            Self::Get => codes.push(Instruction::Get as u8),
+            Self::GreaterThan => codes.push(Instruction::GreaterThan as u8),
+            Self::GreaterThanOrEqual => codes.push(Instruction::GreaterThanOrEqual as u8),
+            Self::Integer32(number) => {
+                codes.push(Instruction::Integer32 as u8);
+                codes.extend(number.to_le_bytes());
+            }
+            Self::Jump { pointer } => {
+                codes.push(Instruction::Jump as u8);
+                codes.extend(pointer.to_le_bytes());
+            }
+            Self::Length => codes.push(Instruction::Length as u8),
+            Self::LessThan => codes.push(Instruction::LessThan as u8),
+            Self::LessThanOrEqual => codes.push(Instruction::LessThanOrEqual as u8),
+            Self::Map => codes.push(Instruction::Map as u8),
+            Self::Multiply => codes.push(Instruction::Multiply as u8),
+            Self::Nil => codes.push(Instruction::Nil as u8),
+            Self::Nip(count) => codes.extend([Instruction::Nip as u8, *count]),
+            Self::Not => codes.push(Instruction::Not as u8),
+            Self::NotEqual => codes.push(Instruction::NotEqual as u8),
+            Self::Or => codes.push(Instruction::Or as u8),
+            Self::Peek(index) => codes.extend([Instruction::Peek as u8, *index]),
+            Self::Pop => codes.push(Instruction::Pop as u8),
+            Self::Push => codes.push(Instruction::Push as u8),
+            Self::Range => codes.push(Instruction::Range as u8),
+            Self::Ref => codes.push(Instruction::Ref as u8),
+            Self::Repr => codes.push(Instruction::Repr as u8),
+            Self::Return => codes.push(Instruction::Return as u8),
+            Self::Reverse => codes.push(Instruction::Reverse as u8),
+            Self::Set => codes.push(Instruction::Set as u8),
+            Self::SetRef => codes.push(Instruction::SetRef as u8),
+            Self::Slice => codes.push(Instruction::Slice as u8),
+            Self::Sort => codes.push(Instruction::Sort as u8),
+            Self::Store(index) => codes.extend([Instruction::Store as u8, *index]),
+            Self::Subtract => codes.push(Instruction::Subtract as u8),
+            Self::SubtractFloat64(number) => {
+                codes.push(Instruction::SubtractFloat64 as u8);
+                codes.extend(number.to_le_bytes());
+            }
+            Self::Symbol { len, string } => {
+                codes.extend([Instruction::Symbol as u8, *len]);
+                codes.extend(string.as_bytes());
+            }
+            Self::TailCall { arity } => codes.extend([Instruction::TailCall as u8, *arity]),
+            Self::Take(index) => codes.extend([Instruction::Take as u8, *index]),
+        }
+    }
 }
 
 impl Display for InstructionIr {
     fn fmt(&self, formatter: &mut Formatter) -> fmt::Result {
         match self {
             Self::Add => write!(formatter, "add"),
+            Self::AddFloat64(number) => write!(formatter, "add_float64 {number}"),
             Self::And => write!(formatter, "and"),
             Self::Branch { pointer } => write!(formatter, "branch {pointer:x}"),
+            Self::BranchNotEqual { pointer } => write!(formatter, "branch_not_equal {pointer:x}"),
             Self::Call { arity } => write!(formatter, "call {arity}"),
             Self::Close {
                 pointer,
                 arity,
                 environment_size,
             } => write!(formatter, "close {pointer:x} {arity} {environment_size}"),
+            Self::Concat => write!(formatter, "concat"),
+            Self::Deref => write!(formatter, "deref"),
+            Self::Display => write!(formatter, "display"),
             Self::Divide => write!(formatter, "divide"),
             Self::Drop => write!(formatter, "drop"),
             Self::Dump => write!(formatter, "dump"),
+            Self::DumpDrop => write!(formatter, "dump_drop"),
             Self::Environment(index) => write!(formatter, "environment {index}"),
             Self::Equal => write!(formatter, "equal"),
+            Self::Filter => write!(formatter, "filter"),
             Self::Float64(number) => write!(formatter, "float64 {number}"),
+            Self::Fold => write!(formatter, "fold"),
             Self::Get => write!(formatter, "get"),
             Self::GreaterThan => write!(formatter, "greater_than"),
             Self::GreaterThanOrEqual => write!(formatter, "greater_than_or_equal"),
@@ -76,17 +202,31 @@ impl Display for InstructionIr {
             Self::Length => write!(formatter, "length"),
             Self::LessThan => write!(formatter, "less_than"),
             Self::LessThanOrEqual => write!(formatter, "less_than_or_equal"),
+            Self::Map => write!(formatter, "map"),
             Self::Multiply => write!(formatter, "multiply"),
             Self::Nil => write!(formatter, "nil"),
+            Self::Nip(count) => write!(formatter, "nip {count}"),
             Self::Not => write!(formatter, "not"),
             Self::NotEqual => write!(formatter, "not_equal"),
             Self::Or => write!(formatter, "or"),
             Self::Peek(index) => write!(formatter, "peek {index}"),
+            Self::Pop => write!(formatter, "pop"),
+            Self::Push => write!(formatter, "push"),
+            Self::Range => write!(formatter, "range"),
+            Self::Ref => write!(formatter, "ref"),
+            Self::Repr => write!(formatter, "repr"),
             Self::Return => write!(formatter, "return"),
+            Self::Reverse => write!(formatter, "reverse"),
             Self::Set => write!(formatter, "set"),
+            Self::SetRef => write!(formatter, "set_ref"),
+            Self::Slice => write!(formatter, "slice"),
+            Self::Sort => write!(formatter, "sort"),
+            Self::Store(index) => write!(formatter, "store {index}"),
             Self::Subtract => write!(formatter, "subtract"),
+            Self::SubtractFloat64(number) => write!(formatter, "subtract_float64 {number}"),
             Self::Symbol { len, string } => write!(formatter, "symbol {len} {string:?}"),
             Self::TailCall { arity } => write!(formatter, "tail_call {arity}"),
+            Self::Take(index) => write!(formatter, "take {index}"),
         }
     }
 }
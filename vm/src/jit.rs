@@ -0,0 +1,325 @@
+//! A baseline JIT compiler of hot closures.
+//!
+//! Closures called many times are compiled into native code through Cranelift.
+//! Compiled code handles only floating-point numbers, `nil` and calls of the
+//! closures themselves. Closures with any other instruction stay interpreted.
+//!
+//! Native code deoptimizes a call into the interpreter by returning with a
+//! flag set when it would exceed limits of call frames or a stack. As compiled
+//! closures have no side effect, the interpreter simply calls them again.
+
+mod compiler;
+
+use self::compiler::{compile, CompiledFunction};
+use crate::program::Program;
+use alloc::{vec, vec::Vec};
+use cranelift_jit::{JITBuilder, JITModule};
+use cranelift_module::default_libcall_names;
+use runtime::{Closure, Value};
+
+/// A number of calls after which a closure is compiled.
+const CALL_THRESHOLD: u32 = 1000;
+
+/// A context shared by native code.
+#[repr(C)]
+#[derive(Debug, Default)]
+pub struct Context {
+    deoptimized: u8,
+}
+
+#[derive(Debug)]
+enum State {
+    Counting(u32),
+    Compiled(CompiledFunction),
+    Interpreted,
+}
+
+/// A JIT compiler.
+pub(crate) struct Jit {
+    module: JITModule,
+    /// States of closures indexed by their IDs.
+    functions: Vec<State>,
+}
+
+impl Jit {
+    pub fn new() -> Self {
+        let isa = cranelift_native::builder()
+            .expect("host machine")
+            .finish(cranelift_codegen::settings::Flags::new(
+                cranelift_codegen::settings::builder(),
+            ))
+            .expect("instruction set");
+
+        Self {
+            module: JITModule::new(JITBuilder::with_isa(isa, default_libcall_names())),
+            functions: vec![],
+        }
+    }
+
+    /// Calls a closure with native code if it is hot.
+    ///
+    /// `depth` is a number of call frames left and `base` is a stack index of
+    /// the closure. It returns `None` if the closure needs to be interpreted.
+    pub fn call(
+        &mut self,
+        program: &Program,
+        closure: &Closure,
+        arguments: &[Value],
+        depth: usize,
+        base: usize,
+        stack_capacity: usize,
+    ) -> Option<Value> {
+        if arguments.len() != closure.arity() as usize {
+            return None;
+        }
+
+        let id = closure.id() as usize;
+
+        if id >= self.functions.len() {
+            self.functions.resize_with(id + 1, || State::Counting(0));
+        }
+
+        let state = &mut self.functions[id];
+
+        if let State::Counting(count) = state {
+            *count += 1;
+
+            if *count < CALL_THRESHOLD {
+                return None;
+            }
+
+            *state = compile(&mut self.module, program, closure, stack_capacity)
+                .map_or(State::Interpreted, State::Compiled);
+        }
+
+        let State::Compiled(function) = state else {
+            return None;
+        };
+
+        if !arguments.iter().all(Value::is_float64) {
+            return None;
+        }
+
+        let arguments = to_floats(arguments.iter())?;
+        let environment = if function.environment {
+            to_floats(
+                (0..closure.environment_size() as usize)
+                    .map(|index| closure.get_environment(index)),
+            )?
+        } else {
+            vec![]
+        };
+        let mut context = Context::default();
+
+        // SAFETY: Arguments and an environment match the arity and environment
+        // size of the closure.
+        let value = unsafe {
+            (function.entry)(
+                &mut context,
+                environment.as_ptr(),
+                arguments.as_ptr(),
+                depth as i64,
+                base as i64,
+            )
+        };
+
+        if context.deoptimized != 0 {
+            *state = State::Interpreted;
+            return None;
+        }
+
+        Some(value.into())
+    }
+
+    #[cfg(test)]
+    pub fn is_compiled(&self, id: runtime::ClosureId) -> bool {
+        matches!(self.functions.get(id as usize), Some(State::Compiled(_)))
+    }
+}
+
+impl core::fmt::Debug for Jit {
+    fn fmt(&self, formatter: &mut core::fmt::Formatter) -> core::fmt::Result {
+        formatter
+            .debug_struct("Jit")
+            .field("functions", &self.functions)
+            .finish_non_exhaustive()
+    }
+}
+
+fn to_floats<'a>(values: impl Iterator<Item = &'a Value>) -> Option<Vec<f64>> {
+    values
+        .map(|value| Some(value.to_float64()?.to_f64()))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Instruction, RuntimeError, Vm};
+    use alloc::vec::Vec;
+    use pretty_assertions::assert_eq;
+    use runtime::{Value, NIL};
+
+    const ID: u32 = 3;
+
+    fn float64(number: f64) -> Vec<u8> {
+        let mut codes = vec![Instruction::Float64 as u8];
+        codes.extend(number.to_le_bytes());
+        codes
+    }
+
+    // Defines a function at the bottom of a stack.
+    fn define(body: &[u8], arity: u8) -> Vec<u8> {
+        let mut codes = vec![Instruction::Jump as u8];
+        codes.extend((body.len() as u16).to_le_bytes());
+        codes.extend(body);
+        codes.push(Instruction::Close as u8);
+        codes.extend(ID.to_le_bytes());
+        codes.extend([arity, 0]);
+        codes
+    }
+
+    // Calls the function with arguments.
+    fn call(codes: &mut Vec<u8>, arguments: &[&[u8]]) {
+        codes.extend([Instruction::Peek as u8, 0]);
+
+        for argument in arguments {
+            codes.extend(*argument);
+        }
+
+        codes.extend([Instruction::Call as u8, arguments.len() as u8]);
+    }
+
+    // A function summing up integers from zero to an argument recursively.
+    fn sum() -> Vec<u8> {
+        let mut body = vec![
+            Instruction::Peek as u8,
+            0,
+            Instruction::Branch as u8,
+            17,
+            0,
+            Instruction::Peek as u8,
+            1,
+            Instruction::Peek as u8,
+            1,
+            Instruction::SubtractFloat64 as u8,
+        ];
+        body.extend(1f64.to_le_bytes());
+        body.extend([
+            Instruction::Call as u8,
+            1,
+            Instruction::Add as u8,
+            Instruction::Return as u8,
+            Instruction::Nil as u8,
+            Instruction::Return as u8,
+        ]);
+
+        define(&body, 1)
+    }
+
+    // Runs a function with warm-up calls and then a call with an argument.
+    fn run(
+        codes: Vec<u8>,
+        warm_up: &[&[u8]],
+        arguments: &[&[u8]],
+    ) -> (Vm, Result<(), RuntimeError>) {
+        let mut codes = codes;
+
+        for _ in 0..2 * super::CALL_THRESHOLD {
+            call(&mut codes, warm_up);
+            codes.push(Instruction::Drop as u8);
+        }
+
+        call(&mut codes, arguments);
+
+        let mut vm = Vm::new();
+        let result = vm.run(&codes);
+
+        (vm, result)
+    }
+
+    #[test]
+    fn compile_recursive_function() {
+        let (vm, result) = run(sum(), &[&float64(10.0)], &[&float64(10.0)]);
+
+        result.unwrap();
+        assert_eq!(vm.stack()[1], 55.0.into());
+        assert!(vm.jit().is_compiled(ID));
+    }
+
+    #[test]
+    fn compile_tail_recursive_function() {
+        let mut body = vec![
+            Instruction::Peek as u8,
+            1,
+            Instruction::Branch as u8,
+            20,
+            0,
+            Instruction::Peek as u8,
+            2,
+            Instruction::Peek as u8,
+            2,
+            Instruction::SubtractFloat64 as u8,
+        ];
+        body.extend(1f64.to_le_bytes());
+        body.extend([
+            Instruction::Peek as u8,
+            3,
+            Instruction::Peek as u8,
+            3,
+            Instruction::Add as u8,
+            Instruction::TailCall as u8,
+            2,
+            Instruction::Peek as u8,
+            0,
+            Instruction::Return as u8,
+        ]);
+        let nil = [Instruction::Nil as u8];
+
+        let (vm, result) = run(
+            define(&body, 2),
+            &[&float64(10.0), &nil],
+            &[&float64(100.0), &nil],
+        );
+
+        result.unwrap();
+        assert_eq!(vm.stack()[1], 5050.0.into());
+        assert!(vm.jit().is_compiled(ID));
+    }
+
+    #[test]
+    fn interpret_non_float_argument() {
+        let mut argument = vec![Instruction::Integer32 as u8];
+        argument.extend(42u32.to_le_bytes());
+
+        let (vm, result) = run(sum(), &[&float64(10.0)], &[&argument]);
+
+        result.unwrap();
+        assert_eq!(vm.stack()[1], NIL);
+        assert!(vm.jit().is_compiled(ID));
+    }
+
+    #[test]
+    fn deoptimize_on_call_stack_overflow() {
+        let (vm, result) = run(sum(), &[&float64(10.0)], &[&float64(1000.0)]);
+
+        let Err(RuntimeError::CallStackOverflow(backtrace)) = result else {
+            panic!("call stack overflow expected");
+        };
+
+        assert_eq!(backtrace.frames().len(), 257);
+        assert!(!vm.jit().is_compiled(ID));
+    }
+
+    #[test]
+    fn keep_function_interpreted() {
+        let mut body = vec![Instruction::Integer32 as u8];
+        body.extend(42u32.to_le_bytes());
+        body.push(Instruction::Return as u8);
+
+        let (vm, result) = run(define(&body, 0), &[], &[]);
+
+        result.unwrap();
+        assert_eq!(vm.stack()[1], Value::from(42u32));
+        assert!(!vm.jit().is_compiled(ID));
+    }
+}
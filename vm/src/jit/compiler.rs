@@ -0,0 +1,624 @@
+use crate::program::{DecodedInstruction, Operation, Program};
+use alloc::{format, vec, vec::Vec};
+use cranelift_codegen::ir::{
+    condcodes::{FloatCC, IntCC},
+    types, AbiParam, Block, FuncRef, InstBuilder, MemFlags, Signature, Value,
+};
+use cranelift_frontend::{FunctionBuilder, FunctionBuilderContext};
+use cranelift_jit::JITModule;
+use cranelift_module::{Linkage, Module};
+use runtime::{Closure, ClosureId};
+use std::collections::{BTreeSet, HashMap, HashSet};
+
+/// An entry point of a compiled closure.
+///
+/// It takes a context, an environment, arguments, a remaining call depth and
+/// a stack index of the closure in order.
+pub type EntryFunction =
+    unsafe extern "C" fn(*mut super::Context, *const f64, *const f64, i64, i64) -> f64;
+
+/// A closure compiled into native code.
+#[derive(Clone, Copy, Debug)]
+pub struct CompiledFunction {
+    pub entry: EntryFunction,
+    /// Whether the function reads its environment.
+    pub environment: bool,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Kind {
+    /// The closure being compiled.
+    Closure,
+    Float,
+}
+
+#[derive(Clone, Copy, Debug)]
+enum Entry {
+    Closure,
+    Float(Value),
+}
+
+impl Entry {
+    fn kind(self) -> Kind {
+        match self {
+            Self::Closure => Kind::Closure,
+            Self::Float(_) => Kind::Float,
+        }
+    }
+}
+
+/// Compiles a closure into native code.
+///
+/// It returns `None` if the closure contains any instruction that compiled
+/// code does not support.
+pub fn compile(
+    module: &mut JITModule,
+    program: &Program,
+    closure: &Closure,
+    stack_capacity: usize,
+) -> Option<CompiledFunction> {
+    let id = closure.id();
+    let arity = closure.arity();
+    let leaders = collect_leaders(program, id)?;
+
+    let pointer_type = module.target_config().pointer_type();
+    let call_conv = module.isa().default_call_conv();
+
+    let mut signature = Signature::new(call_conv);
+    signature.params.extend([AbiParam::new(pointer_type); 2]);
+    signature.params.extend([AbiParam::new(types::I64); 2]);
+    signature
+        .params
+        .extend((0..arity).map(|_| AbiParam::new(types::F64)));
+    signature.returns.push(AbiParam::new(types::F64));
+
+    let function_id = module
+        .declare_function(&format!("closure_{id}"), Linkage::Local, &signature)
+        .ok()?;
+    let mut context = module.make_context();
+    let mut function_context = FunctionBuilderContext::new();
+    context.func.signature = signature;
+
+    let function = module.declare_func_in_func(function_id, &mut context.func);
+    let mut builder = FunctionBuilder::new(&mut context.func, &mut function_context);
+    let entry = builder.create_block();
+    builder.append_block_params_for_function_params(entry);
+
+    let parameters = builder.block_params(entry).to_vec();
+    let deoptimize = builder.create_block();
+    let mut translator = Translator {
+        builder,
+        program,
+        leaders,
+        id,
+        arity,
+        environment_size: closure.environment_size(),
+        function,
+        context: parameters[0],
+        environment: parameters[1],
+        depth: parameters[2],
+        base: parameters[3],
+        blocks: HashMap::new(),
+        worklist: vec![],
+        deoptimize,
+        max_height: 0,
+        environment_used: false,
+    };
+
+    let mut stack = vec![Entry::Closure];
+    stack.extend(parameters[4..].iter().map(|&value| Entry::Float(value)));
+    let (body, arguments) = translator.target(id, &stack)?;
+
+    // Check limits of call frames and a stack at once as native code never
+    // pushes values onto the stack of a VM. A limit of stack indices is known
+    // only after translation.
+    let builder = &mut translator.builder;
+    builder.switch_to_block(entry);
+    let depth = builder
+        .ins()
+        .icmp_imm(IntCC::SignedGreaterThan, parameters[2], 0);
+    let limit = builder.ins().iconst(types::I64, 0);
+    let height = builder
+        .ins()
+        .icmp(IntCC::SignedLessThan, parameters[3], limit);
+    let condition = builder.ins().band(depth, height);
+    builder
+        .ins()
+        .brif(condition, body, &arguments, deoptimize, &[]);
+
+    while let Some(address) = translator.worklist.pop() {
+        translator.translate_block(address)?;
+    }
+
+    let Translator {
+        mut builder,
+        max_height,
+        environment_used,
+        ..
+    } = translator;
+
+    let limit = builder.func.dfg.value_def(limit).unwrap_inst();
+    builder
+        .func
+        .dfg
+        .replace(limit)
+        .iconst(types::I64, (stack_capacity - max_height) as i64);
+
+    builder.switch_to_block(deoptimize);
+    let flag = builder.ins().iconst(types::I8, 1);
+    builder
+        .ins()
+        .store(MemFlags::trusted(), flag, parameters[0], 0);
+    let value = builder.ins().f64const(0.0);
+    builder.ins().return_(&[value]);
+
+    builder.seal_all_blocks();
+    builder.finalize();
+
+    module.define_function(function_id, &mut context).ok()?;
+    module.clear_context(&mut context);
+
+    let entry_id = compile_entry(module, function_id, arity, pointer_type, call_conv)?;
+
+    module.finalize_definitions().ok()?;
+
+    Some(CompiledFunction {
+        // SAFETY: The entry function has the same signature as the type.
+        entry: unsafe {
+            core::mem::transmute::<*const u8, EntryFunction>(
+                module.get_finalized_function(entry_id),
+            )
+        },
+        environment: environment_used,
+    })
+}
+
+// Compiles a function which loads arguments from memory and calls a compiled
+// closure.
+fn compile_entry(
+    module: &mut JITModule,
+    function_id: cranelift_module::FuncId,
+    arity: u8,
+    pointer_type: types::Type,
+    call_conv: cranelift_codegen::isa::CallConv,
+) -> Option<cranelift_module::FuncId> {
+    let mut signature = Signature::new(call_conv);
+    signature.params.extend([AbiParam::new(pointer_type); 3]);
+    signature.params.extend([AbiParam::new(types::I64); 2]);
+    signature.returns.push(AbiParam::new(types::F64));
+
+    let name = format!("entry_{}", function_id.as_u32());
+    let entry_id = module
+        .declare_function(&name, Linkage::Local, &signature)
+        .ok()?;
+    let mut context = module.make_context();
+    let mut function_context = FunctionBuilderContext::new();
+    context.func.signature = signature;
+
+    let function = module.declare_func_in_func(function_id, &mut context.func);
+    let mut builder = FunctionBuilder::new(&mut context.func, &mut function_context);
+    let block = builder.create_block();
+    builder.append_block_params_for_function_params(block);
+    builder.switch_to_block(block);
+
+    let parameters = builder.block_params(block).to_vec();
+    let mut arguments = vec![parameters[0], parameters[1], parameters[3], parameters[4]];
+
+    for index in 0..arity as i32 {
+        arguments.push(builder.ins().load(
+            types::F64,
+            MemFlags::trusted(),
+            parameters[2],
+            index * 8,
+        ));
+    }
+
+    let call = builder.ins().call(function, &arguments);
+    let value = builder.inst_results(call)[0];
+    builder.ins().return_(&[value]);
+    builder.seal_all_blocks();
+    builder.finalize();
+
+    module.define_function(entry_id, &mut context).ok()?;
+    module.clear_context(&mut context);
+
+    Some(entry_id)
+}
+
+// Collects addresses of instructions which start basic blocks.
+fn collect_leaders(program: &Program, id: ClosureId) -> Option<BTreeSet<u32>> {
+    let mut leaders = BTreeSet::from([id]);
+    let mut visited = HashSet::new();
+    let mut addresses = vec![id];
+
+    while let Some(address) = addresses.pop() {
+        if (address as usize) >= program.len() || !visited.insert(address) {
+            continue;
+        }
+
+        let DecodedInstruction { operation, next } = program.get(address as usize);
+
+        match operation {
+            Operation::Jump(target) => {
+                leaders.insert(target);
+                addresses.push(target);
+            }
+            Operation::Branch(target) | Operation::BranchNotEqual(target) => {
+                leaders.extend([target, next]);
+                addresses.extend([target, next]);
+            }
+            Operation::Return | Operation::TailCall { .. } => {}
+            Operation::Invalid => return None,
+            _ => addresses.push(next),
+        }
+    }
+
+    Some(leaders)
+}
+
+struct Translator<'a> {
+    builder: FunctionBuilder<'a>,
+    program: &'a Program,
+    leaders: BTreeSet<u32>,
+    id: ClosureId,
+    arity: u8,
+    environment_size: u8,
+    function: FuncRef,
+    context: Value,
+    environment: Value,
+    depth: Value,
+    base: Value,
+    blocks: HashMap<u32, (Block, Vec<Kind>)>,
+    worklist: Vec<u32>,
+    deoptimize: Block,
+    max_height: usize,
+    environment_used: bool,
+}
+
+impl Translator<'_> {
+    fn translate_block(&mut self, leader: u32) -> Option<()> {
+        let (block, kinds) = self.blocks[&leader].clone();
+        self.builder.switch_to_block(block);
+
+        let mut parameters = self.builder.block_params(block).to_vec().into_iter();
+        let mut stack = kinds
+            .iter()
+            .map(|kind| match kind {
+                Kind::Closure => Some(Entry::Closure),
+                Kind::Float => parameters.next().map(Entry::Float),
+            })
+            .collect::<Option<Vec<_>>>()?;
+        let mut address = leader;
+
+        loop {
+            if address as usize >= self.program.len() {
+                return None;
+            }
+
+            self.max_height = self.max_height.max(stack.len());
+
+            let DecodedInstruction { operation, next } = self.program.get(address as usize);
+
+            match operation {
+                Operation::Add => {
+                    self.binary(&mut stack, |builder, x, y| builder.ins().fadd(x, y))?
+                }
+                Operation::AddFloat64(number) => self.unary(&mut stack, |builder, x| {
+                    let y = builder.ins().f64const(number);
+                    builder.ins().fadd(x, y)
+                })?,
+                Operation::And => self.binary(&mut stack, |builder, x, y| {
+                    let condition = is_nil(builder, x);
+                    builder.ins().select(condition, x, y)
+                })?,
+                Operation::Branch(target) => {
+                    let value = pop_float(&mut stack)?;
+                    let condition = is_nil(&mut self.builder, value);
+
+                    self.branch(condition, target, next, &stack)?;
+                    return Some(());
+                }
+                Operation::BranchNotEqual(target) => {
+                    let rhs = pop_float(&mut stack)?;
+                    let lhs = pop_float(&mut stack)?;
+                    let condition = equal(&mut self.builder, lhs, rhs);
+
+                    self.branch(condition, next, target, &stack)?;
+                    return Some(());
+                }
+                Operation::Call { arity, .. } => {
+                    let (callee, arguments) = split_call(&mut stack, arity)?;
+
+                    let value = match callee {
+                        Entry::Closure if arity == self.arity => self.call(&stack, &arguments),
+                        Entry::Closure => return None,
+                        // A call to a value which is not a function results in `nil`.
+                        Entry::Float(_) => self.builder.ins().f64const(0.0),
+                    };
+
+                    stack.push(Entry::Float(value));
+                }
+                Operation::Divide => {
+                    self.binary(&mut stack, |builder, x, y| builder.ins().fdiv(x, y))?
+                }
+                Operation::Drop => {
+                    stack.pop()?;
+                }
+                Operation::Environment(index) => {
+                    if index >= self.environment_size {
+                        return None;
+                    }
+
+                    self.environment_used = true;
+                    let value = self.builder.ins().load(
+                        types::F64,
+                        MemFlags::trusted().with_readonly(),
+                        self.environment,
+                        index as i32 * 8,
+                    );
+                    stack.push(Entry::Float(value));
+                }
+                Operation::Equal => self.binary(&mut stack, |builder, x, y| {
+                    let condition = equal(builder, x, y);
+                    to_float(builder, condition)
+                })?,
+                Operation::Float64(number) => {
+                    stack.push(Entry::Float(self.builder.ins().f64const(number)))
+                }
+                Operation::GreaterThan => self.comparison(&mut stack, FloatCC::GreaterThan)?,
+                Operation::GreaterThanOrEqual => {
+                    self.comparison(&mut stack, FloatCC::GreaterThanOrEqual)?
+                }
+                Operation::Jump(target) => {
+                    let (block, arguments) = self.target(target, &stack)?;
+                    self.builder.ins().jump(block, &arguments);
+                    return Some(());
+                }
+                Operation::LessThan => self.comparison(&mut stack, FloatCC::LessThan)?,
+                Operation::LessThanOrEqual => {
+                    self.comparison(&mut stack, FloatCC::LessThanOrEqual)?
+                }
+                Operation::Multiply => {
+                    self.binary(&mut stack, |builder, x, y| builder.ins().fmul(x, y))?
+                }
+                Operation::Nil => stack.push(Entry::Float(self.builder.ins().f64const(0.0))),
+                Operation::Nip(count) => {
+                    let entry = stack.pop()?;
+                    stack.truncate(stack.len().checked_sub(count as usize)?);
+                    stack.push(entry);
+                }
+                Operation::Not => self.unary(&mut stack, |builder, x| {
+                    let condition = is_nil(builder, x);
+                    to_float(builder, condition)
+                })?,
+                Operation::NotEqual => self.binary(&mut stack, |builder, x, y| {
+                    let condition = equal(builder, x, y);
+                    let condition = builder.ins().icmp_imm(IntCC::Equal, condition, 0);
+                    to_float(builder, condition)
+                })?,
+                Operation::Or => self.binary(&mut stack, |builder, x, y| {
+                    let condition = is_nil(builder, x);
+                    builder.ins().select(condition, y, x)
+                })?,
+                Operation::Peek(index) => {
+                    let entry = *stack.get(stack.len().checked_sub(index as usize + 1)?)?;
+                    stack.push(entry);
+                }
+                Operation::Return => {
+                    let value = pop_float(&mut stack)?;
+                    self.builder.ins().return_(&[value]);
+                    return Some(());
+                }
+                Operation::Store(index) => {
+                    let entry = stack.pop()?;
+                    let index = stack.len().checked_sub(index as usize + 1)?;
+                    *stack.get_mut(index)? = entry;
+                }
+                Operation::Subtract => {
+                    self.binary(&mut stack, |builder, x, y| builder.ins().fsub(x, y))?
+                }
+                Operation::SubtractFloat64(number) => self.unary(&mut stack, |builder, x| {
+                    let y = builder.ins().f64const(number);
+                    builder.ins().fsub(x, y)
+                })?,
+                Operation::TailCall { arity, .. } => {
+                    let (callee, arguments) = split_call(&mut stack, arity)?;
+
+                    match callee {
+                        Entry::Closure if arity == self.arity => {
+                            let mut stack = vec![Entry::Closure];
+                            stack.extend(arguments.into_iter().map(Entry::Float));
+                            let (block, arguments) = self.target(self.id, &stack)?;
+                            self.builder.ins().jump(block, &arguments);
+                        }
+                        Entry::Closure => return None,
+                        Entry::Float(_) => {
+                            let value = self.builder.ins().f64const(0.0);
+                            self.builder.ins().return_(&[value]);
+                        }
+                    }
+
+                    return Some(());
+                }
+                Operation::Take(index) => {
+                    let index = stack.len().checked_sub(index as usize + 1)?;
+                    let Entry::Float(value) = *stack.get(index)? else {
+                        return None;
+                    };
+                    stack[index] = Entry::Float(self.builder.ins().f64const(0.0));
+                    stack.push(Entry::Float(value));
+                }
+                Operation::Close { .. }
+                | Operation::Concat
+                | Operation::Deref
+                | Operation::Display
+                | Operation::Dump
+                | Operation::DumpDrop
+                | Operation::Filter
+                | Operation::Fold
+                | Operation::Get
+                | Operation::Integer32(_)
+                | Operation::Length
+                | Operation::Map
+                | Operation::Pop
+                | Operation::Push
+                | Operation::Range
+                | Operation::Ref
+                | Operation::Repr
+                | Operation::Reverse
+                | Operation::Set
+                | Operation::SetRef
+                | Operation::Slice
+                | Operation::Sort
+                | Operation::Symbol(_)
+                | Operation::Invalid => return None,
+            }
+
+            address = next;
+
+            if self.leaders.contains(&address) {
+                let (block, arguments) = self.target(address, &stack)?;
+                self.builder.ins().jump(block, &arguments);
+                return Some(());
+            }
+        }
+    }
+
+    // Returns a block at an address and arguments passed to it, creating the
+    // block on the first jump.
+    fn target(&mut self, address: u32, stack: &[Entry]) -> Option<(Block, Vec<Value>)> {
+        let kinds = stack.iter().map(|entry| entry.kind()).collect::<Vec<_>>();
+        let arguments = stack
+            .iter()
+            .filter_map(|entry| match entry {
+                Entry::Closure => None,
+                Entry::Float(value) => Some(*value),
+            })
+            .collect::<Vec<_>>();
+
+        if let Some((block, expected)) = self.blocks.get(&address) {
+            return (expected == &kinds).then_some((*block, arguments));
+        }
+
+        let block = self.builder.create_block();
+
+        for _ in &arguments {
+            self.builder.append_block_param(block, types::F64);
+        }
+
+        self.blocks.insert(address, (block, kinds));
+        self.worklist.push(address);
+
+        Some((block, arguments))
+    }
+
+    fn branch(&mut self, condition: Value, then: u32, r#else: u32, stack: &[Entry]) -> Option<()> {
+        let (then, then_arguments) = self.target(then, stack)?;
+        let (r#else, else_arguments) = self.target(r#else, stack)?;
+
+        self.builder
+            .ins()
+            .brif(condition, then, &then_arguments, r#else, &else_arguments);
+
+        Some(())
+    }
+
+    // Calls the closure itself and propagates deoptimization to a caller.
+    fn call(&mut self, stack: &[Entry], arguments: &[Value]) -> Value {
+        let depth = self.builder.ins().iadd_imm(self.depth, -1);
+        let base = self.builder.ins().iadd_imm(self.base, stack.len() as i64);
+
+        let mut values = vec![self.context, self.environment, depth, base];
+        values.extend(arguments);
+
+        let call = self.builder.ins().call(self.function, &values);
+        let value = self.builder.inst_results(call)[0];
+        let flag = self
+            .builder
+            .ins()
+            .load(types::I8, MemFlags::trusted(), self.context, 0);
+        let block = self.builder.create_block();
+
+        self.builder
+            .ins()
+            .brif(flag, self.deoptimize, &[], block, &[]);
+        self.builder.switch_to_block(block);
+
+        value
+    }
+
+    fn unary(
+        &mut self,
+        stack: &mut Vec<Entry>,
+        build: impl FnOnce(&mut FunctionBuilder, Value) -> Value,
+    ) -> Option<()> {
+        let value = pop_float(stack)?;
+        let value = build(&mut self.builder, value);
+
+        stack.push(Entry::Float(value));
+
+        Some(())
+    }
+
+    fn binary(
+        &mut self,
+        stack: &mut Vec<Entry>,
+        build: impl FnOnce(&mut FunctionBuilder, Value, Value) -> Value,
+    ) -> Option<()> {
+        let rhs = pop_float(stack)?;
+        let lhs = pop_float(stack)?;
+        let value = build(&mut self.builder, lhs, rhs);
+
+        stack.push(Entry::Float(value));
+
+        Some(())
+    }
+
+    fn comparison(&mut self, stack: &mut Vec<Entry>, condition: FloatCC) -> Option<()> {
+        self.binary(stack, |builder, x, y| {
+            let condition = builder.ins().fcmp(condition, x, y);
+            to_float(builder, condition)
+        })
+    }
+}
+
+fn pop_float(stack: &mut Vec<Entry>) -> Option<Value> {
+    match stack.pop()? {
+        Entry::Float(value) => Some(value),
+        Entry::Closure => None,
+    }
+}
+
+// Pops arguments and a callee of a call.
+fn split_call(stack: &mut Vec<Entry>, arity: u8) -> Option<(Entry, Vec<Value>)> {
+    let arguments = (0..arity)
+        .map(|_| pop_float(stack))
+        .collect::<Option<Vec<_>>>()?
+        .into_iter()
+        .rev()
+        .collect();
+
+    Some((stack.pop()?, arguments))
+}
+
+fn is_nil(builder: &mut FunctionBuilder, value: Value) -> Value {
+    let bits = builder.ins().bitcast(types::I64, MemFlags::new(), value);
+    builder.ins().icmp_imm(IntCC::Equal, bits, 0)
+}
+
+// Compares values in the same way as the VM where `nil` is equal only to
+// itself.
+fn equal(builder: &mut FunctionBuilder, lhs: Value, rhs: Value) -> Value {
+    let lhs_nil = is_nil(builder, lhs);
+    let rhs_nil = is_nil(builder, rhs);
+    let equal = builder.ins().fcmp(FloatCC::Equal, lhs, rhs);
+
+    builder.ins().select(lhs_nil, rhs_nil, equal)
+}
+
+fn to_float(builder: &mut FunctionBuilder, condition: Value) -> Value {
+    let one = builder.ins().f64const(1.0);
+    let zero = builder.ins().f64const(0.0);
+
+    builder.ins().select(condition, one, zero)
+}
@@ -1,8 +1,46 @@
+#![no_std]
+
+extern crate alloc;
+#[cfg(any(feature = "std", test))]
+#[cfg_attr(test, macro_use)]
+extern crate std;
+
+mod backtrace;
+mod coverage;
+mod debug_info;
 mod decode;
+mod error;
 mod frame;
+mod hook;
+mod input;
 mod instruction;
+#[cfg(feature = "jit")]
+mod jit;
+mod output;
+mod program;
+mod register;
 mod stack;
+mod stack_frame;
+#[cfg(feature = "std")]
+mod trace;
 mod vm;
 
+pub use backtrace::{Backtrace, BacktraceFrame};
+pub use coverage::Coverage;
+pub use debug_info::{DebugInfo, FunctionInfo};
+pub use error::RuntimeError;
+pub use frame::Frame;
+pub use hook::Hook;
+pub use input::Input;
+#[cfg(feature = "std")]
+pub use input::{Reader, Stdin};
 pub use instruction::*;
+#[cfg(feature = "std")]
+pub use output::{Stdout, Writer};
+pub use register::{
+    BinaryOperator, Register, RegisterInstruction, RegisterVm, TernaryOperator, UnaryOperator,
+};
+pub use stack_frame::StackFrame;
+#[cfg(feature = "std")]
+pub use trace::{TraceFormat, Tracer};
 pub use vm::Vm;
@@ -0,0 +1,56 @@
+#[cfg(feature = "std")]
+use core::fmt::{self, Write};
+
+/// Standard output of a process.
+#[cfg(feature = "std")]
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Stdout;
+
+#[cfg(feature = "std")]
+impl Write for Stdout {
+    fn write_str(&mut self, string: &str) -> fmt::Result {
+        use std::io::Write;
+
+        std::io::stdout()
+            .write_all(string.as_bytes())
+            .map_err(|_| fmt::Error)
+    }
+}
+
+/// An output writing into `std::io::Write`.
+#[cfg(feature = "std")]
+#[derive(Debug)]
+pub struct Writer<W>(W);
+
+#[cfg(feature = "std")]
+impl<W> Writer<W> {
+    pub const fn new(writer: W) -> Self {
+        Self(writer)
+    }
+
+    pub fn into_inner(self) -> W {
+        self.0
+    }
+}
+
+#[cfg(feature = "std")]
+impl<W: std::io::Write> Write for Writer<W> {
+    fn write_str(&mut self, string: &str) -> fmt::Result {
+        self.0.write_all(string.as_bytes()).map_err(|_| fmt::Error)
+    }
+}
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn write_into_writer() {
+        let mut writer = Writer::new(std::vec::Vec::new());
+
+        writeln!(writer, "{}", 42).unwrap();
+
+        assert_eq!(writer.into_inner(), b"42\n");
+    }
+}
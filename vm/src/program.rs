@@ -0,0 +1,419 @@
+use crate::{decode_instruction, InstructionIr};
+use alloc::{collections::BTreeMap, vec, vec::Vec};
+use runtime::{ClosureId, Symbol};
+
+/// A frame size of code whose stack heights are inconsistent, which no stack
+/// can reserve.
+const UNBOUNDED: u32 = u32::MAX;
+
+const INVALID: DecodedInstruction = DecodedInstruction {
+    operation: Operation::Invalid,
+    next: 0,
+};
+
+/// Bytecodes decoded in advance.
+///
+/// Decoded instructions are indexed by their addresses, so that program
+/// counters, return addresses and closure IDs are still bytecode addresses.
+/// Bytecodes are assumed to be only appended between runs.
+///
+/// Frame sizes, the maximum numbers of values pushed by closures and top-level
+/// code, are also computed in advance, so that a VM reserves a stack per frame
+/// instead of checking its capacity on every push.
+#[derive(Debug, Default)]
+pub(crate) struct Program {
+    instructions: Vec<DecodedInstruction>,
+    frame_sizes: Vec<u32>,
+}
+
+impl Program {
+    /// Decodes bytecodes appended since the last call.
+    pub fn extend(&mut self, codes: &[u8]) {
+        let start = self.instructions.len();
+        let mut address = start;
+
+        self.instructions.resize(codes.len().max(address), INVALID);
+        self.frame_sizes.resize(self.instructions.len(), 0);
+
+        while address < codes.len() {
+            let start = address;
+            let Ok(instruction) = decode_instruction(codes, &mut address) else {
+                break;
+            };
+
+            self.instructions[start] = DecodedInstruction {
+                operation: Operation::new(instruction, address),
+                next: address as u32,
+            };
+        }
+
+        if start < self.instructions.len() {
+            self.frame_sizes[start] = self.measure_frame(start as u32);
+        }
+
+        for address in start..self.instructions.len() {
+            if let Operation::Close { id, .. } = self.instructions[address].operation {
+                if let Some(size) = self.frame_sizes.get(id as usize).copied() {
+                    if size == 0 {
+                        self.frame_sizes[id as usize] = self.measure_frame(id);
+                    }
+                }
+            }
+        }
+    }
+
+    /// Returns the maximum number of values pushed by code from an address
+    /// until it returns.
+    ///
+    /// It is `0` for addresses which are not starts of closures or top-level
+    /// code.
+    #[inline(always)]
+    pub fn frame_size(&self, address: usize) -> usize {
+        self.frame_sizes[address] as usize
+    }
+
+    // Finds a maximum stack height relative to an entry by following control
+    // flow. Every address must be reached with the same height, as bytecodes
+    // from a compiler are.
+    fn measure_frame(&self, entry: u32) -> u32 {
+        let mut heights = BTreeMap::new();
+        let mut addresses = vec![(entry, 0)];
+        let mut maximum = 0;
+
+        while let Some((address, height)) = addresses.pop() {
+            if address as usize >= self.instructions.len() {
+                continue;
+            }
+
+            match heights.insert(address, height) {
+                Some(other) if other == height => continue,
+                Some(_) => return UNBOUNDED,
+                None => {}
+            }
+
+            let DecodedInstruction { operation, next } = self.instructions[address as usize];
+            let height = height + operation.stack_effect();
+
+            maximum = maximum.max(height);
+
+            match operation {
+                Operation::Jump(target) => addresses.push((target, height)),
+                Operation::Branch(target) | Operation::BranchNotEqual(target) => {
+                    addresses.extend([(target, height), (next, height)])
+                }
+                Operation::Return | Operation::TailCall { .. } => {}
+                Operation::Invalid => return UNBOUNDED,
+                _ => addresses.push((next, height)),
+            }
+        }
+
+        maximum as u32
+    }
+
+    #[inline(always)]
+    pub fn get(&self, address: usize) -> DecodedInstruction {
+        self.instructions[address]
+    }
+
+    #[inline(always)]
+    pub fn len(&self) -> usize {
+        self.instructions.len()
+    }
+
+    /// Caches a callee of a call instruction at an address.
+    #[inline(always)]
+    pub fn cache_callee(&mut self, address: usize, id: ClosureId) {
+        if let Operation::Call { cache, .. } | Operation::TailCall { cache, .. } =
+            &mut self.instructions[address].operation
+        {
+            *cache = Some(id);
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct DecodedInstruction {
+    pub operation: Operation,
+    /// An address of a next instruction.
+    pub next: u32,
+}
+
+/// An instruction with decoded operands.
+///
+/// Jump targets are absolute addresses and call instructions cache their last
+/// callees whose arities match.
+///
+/// Its tag is explicit so that a VM dispatches instructions on it directly.
+#[derive(Clone, Copy, Debug)]
+#[repr(u8)]
+pub(crate) enum Operation {
+    Add,
+    AddFloat64(f64),
+    And,
+    Branch(u32),
+    BranchNotEqual(u32),
+    Call {
+        arity: u8,
+        cache: Option<ClosureId>,
+    },
+    Close {
+        id: ClosureId,
+        arity: u8,
+        environment_size: u8,
+    },
+    Concat,
+    Deref,
+    Display,
+    Divide,
+    Drop,
+    Dump,
+    DumpDrop,
+    Environment(u8),
+    Equal,
+    Filter,
+    Float64(f64),
+    Fold,
+    Get,
+    GreaterThan,
+    GreaterThanOrEqual,
+    Integer32(u32),
+    Jump(u32),
+    Length,
+    LessThan,
+    LessThanOrEqual,
+    Map,
+    Multiply,
+    Nil,
+    Nip(u8),
+    Not,
+    NotEqual,
+    Or,
+    Peek(u8),
+    Pop,
+    Push,
+    Range,
+    Ref,
+    Repr,
+    Return,
+    Reverse,
+    Set,
+    SetRef,
+    Slice,
+    Sort,
+    Store(u8),
+    Subtract,
+    SubtractFloat64(f64),
+    Symbol(Symbol),
+    TailCall {
+        arity: u8,
+        cache: Option<ClosureId>,
+    },
+    Take(u8),
+    /// A byte in the middle of an instruction or an invalid instruction.
+    Invalid,
+}
+
+impl Operation {
+    // Returns a change of a stack height.
+    //
+    // Instructions push their results after popping their operands, so that a
+    // stack never gets higher than before or after them.
+    fn stack_effect(self) -> i64 {
+        match self {
+            Self::Environment(_)
+            | Self::Float64(_)
+            | Self::Integer32(_)
+            | Self::Nil
+            | Self::Peek(_)
+            | Self::Symbol(_)
+            | Self::Take(_) => 1,
+            Self::AddFloat64(_)
+            | Self::Deref
+            | Self::Display
+            | Self::Dump
+            | Self::Jump(_)
+            | Self::Length
+            | Self::Not
+            | Self::Pop
+            | Self::Ref
+            | Self::Repr
+            | Self::Return
+            | Self::Reverse
+            | Self::SubtractFloat64(_)
+            | Self::Invalid => 0,
+            Self::Add
+            | Self::And
+            | Self::Branch(_)
+            | Self::Concat
+            | Self::Divide
+            | Self::Drop
+            | Self::DumpDrop
+            | Self::Equal
+            | Self::Filter
+            | Self::Get
+            | Self::GreaterThan
+            | Self::GreaterThanOrEqual
+            | Self::LessThan
+            | Self::LessThanOrEqual
+            | Self::Map
+            | Self::Multiply
+            | Self::NotEqual
+            | Self::Or
+            | Self::Push
+            | Self::Range
+            | Self::SetRef
+            | Self::Sort
+            | Self::Store(_)
+            | Self::Subtract => -1,
+            Self::BranchNotEqual(_) | Self::Fold | Self::Set | Self::Slice => -2,
+            Self::Call { arity, .. } | Self::TailCall { arity, .. } => -(arity as i64),
+            Self::Close {
+                environment_size, ..
+            } => 1 - environment_size as i64,
+            Self::Nip(count) => -(count as i64),
+        }
+    }
+
+    fn new(instruction: InstructionIr, next: usize) -> Self {
+        let target = |pointer: i16| next.wrapping_add(pointer as isize as usize) as u32;
+
+        match instruction {
+            InstructionIr::Add => Self::Add,
+            InstructionIr::AddFloat64(number) => Self::AddFloat64(number),
+            InstructionIr::And => Self::And,
+            InstructionIr::Branch { pointer } => Self::Branch(target(pointer)),
+            InstructionIr::BranchNotEqual { pointer } => Self::BranchNotEqual(target(pointer)),
+            InstructionIr::Call { arity } => Self::Call { arity, cache: None },
+            InstructionIr::Close {
+                pointer,
+                arity,
+                environment_size,
+            } => Self::Close {
+                id: pointer,
+                arity,
+                environment_size,
+            },
+            InstructionIr::Concat => Self::Concat,
+            InstructionIr::Deref => Self::Deref,
+            InstructionIr::Display => Self::Display,
+            InstructionIr::Divide => Self::Divide,
+            InstructionIr::Drop => Self::Drop,
+            InstructionIr::Dump => Self::Dump,
+            InstructionIr::DumpDrop => Self::DumpDrop,
+            InstructionIr::Environment(index) => Self::Environment(index),
+            InstructionIr::Equal => Self::Equal,
+            InstructionIr::Filter => Self::Filter,
+            InstructionIr::Float64(number) => Self::Float64(number),
+            InstructionIr::Fold => Self::Fold,
+            InstructionIr::Get => Self::Get,
+            InstructionIr::GreaterThan => Self::GreaterThan,
+            InstructionIr::GreaterThanOrEqual => Self::GreaterThanOrEqual,
+            InstructionIr::Integer32(number) => Self::Integer32(number as u32),
+            InstructionIr::Jump { pointer } => Self::Jump(target(pointer)),
+            InstructionIr::Length => Self::Length,
+            InstructionIr::LessThan => Self::LessThan,
+            InstructionIr::LessThanOrEqual => Self::LessThanOrEqual,
+            InstructionIr::Map => Self::Map,
+            InstructionIr::Multiply => Self::Multiply,
+            InstructionIr::Nil => Self::Nil,
+            InstructionIr::Nip(count) => Self::Nip(count),
+            InstructionIr::Not => Self::Not,
+            InstructionIr::NotEqual => Self::NotEqual,
+            InstructionIr::Or => Self::Or,
+            InstructionIr::Peek(index) => Self::Peek(index),
+            InstructionIr::Pop => Self::Pop,
+            InstructionIr::Push => Self::Push,
+            InstructionIr::Range => Self::Range,
+            InstructionIr::Ref => Self::Ref,
+            InstructionIr::Repr => Self::Repr,
+            InstructionIr::Return => Self::Return,
+            InstructionIr::Reverse => Self::Reverse,
+            InstructionIr::Set => Self::Set,
+            InstructionIr::SetRef => Self::SetRef,
+            InstructionIr::Slice => Self::Slice,
+            InstructionIr::Sort => Self::Sort,
+            InstructionIr::Store(index) => Self::Store(index),
+            InstructionIr::Subtract => Self::Subtract,
+            InstructionIr::SubtractFloat64(number) => Self::SubtractFloat64(number),
+            InstructionIr::Symbol { string, .. } => Self::Symbol(string.as_str().into()),
+            InstructionIr::TailCall { arity } => Self::TailCall { arity, cache: None },
+            InstructionIr::Take(index) => Self::Take(index),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Instruction;
+
+    #[test]
+    fn decode_jump_target() {
+        let mut program = Program::default();
+
+        program.extend(&[Instruction::Nil as u8, Instruction::Jump as u8, 0xfc, 0xff]);
+
+        assert!(matches!(program.get(0).operation, Operation::Nil));
+        assert_eq!(program.get(0).next, 1);
+        assert!(matches!(program.get(1).operation, Operation::Jump(0)));
+        assert!(matches!(program.get(2).operation, Operation::Invalid));
+    }
+
+    #[test]
+    fn decode_appended_codes() {
+        let mut program = Program::default();
+
+        program.extend(&[Instruction::Nil as u8]);
+        program.extend(&[Instruction::Nil as u8, Instruction::Call as u8, 0]);
+
+        assert!(matches!(
+            program.get(1).operation,
+            Operation::Call {
+                arity: 0,
+                cache: None
+            }
+        ));
+        assert_eq!(program.get(1).next, 3);
+    }
+
+    #[test]
+    fn measure_frame_size() {
+        let mut program = Program::default();
+
+        program.extend(&[
+            Instruction::Nil as u8,
+            Instruction::Nil as u8,
+            Instruction::Add as u8,
+            Instruction::Nil as u8,
+            Instruction::Return as u8,
+        ]);
+
+        assert_eq!(program.frame_size(0), 2);
+        assert_eq!(program.frame_size(1), 0);
+    }
+
+    #[test]
+    fn measure_frame_size_of_loop() {
+        let mut program = Program::default();
+
+        program.extend(&[Instruction::Nil as u8, Instruction::Jump as u8, 0xfc, 0xff]);
+
+        assert_eq!(program.frame_size(0), UNBOUNDED as usize);
+    }
+
+    #[test]
+    fn cache_callee() {
+        let mut program = Program::default();
+
+        program.extend(&[Instruction::Call as u8, 0]);
+        program.cache_callee(0, 42);
+
+        assert!(matches!(
+            program.get(0).operation,
+            Operation::Call {
+                arity: 0,
+                cache: Some(42)
+            }
+        ));
+    }
+}
@@ -0,0 +1,13 @@
+//! A register-based alternative to the stack machine.
+//!
+//! Each call frame owns a window of registers starting at a closure being
+//! called followed by its arguments, so that local variables are read in place
+//! instead of being copied onto a stack.
+
+mod instruction;
+mod vm;
+
+pub use self::{
+    instruction::{BinaryOperator, Register, RegisterInstruction, TernaryOperator, UnaryOperator},
+    vm::RegisterVm,
+};
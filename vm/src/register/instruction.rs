@@ -0,0 +1,261 @@
+use core::fmt;
+use core::fmt::{Display, Formatter};
+
+/// An index of a register relative to a frame base.
+pub type Register = u8;
+
+/// An instruction of a register machine.
+///
+/// Pointers are absolute indices of instructions.
+#[derive(Clone, Debug, PartialEq)]
+pub enum RegisterInstruction {
+    Binary {
+        operator: BinaryOperator,
+        destination: Register,
+        lhs: Register,
+        rhs: Register,
+    },
+    /// Jumps if a condition is `nil`.
+    Branch {
+        condition: Register,
+        pointer: u32,
+    },
+    /// Calls a closure in a register with arguments in the following
+    /// registers. A result is written back into the register of the closure.
+    Call {
+        function: Register,
+        arity: u8,
+    },
+    /// Creates a closure with its environment in consecutive registers.
+    Close {
+        destination: Register,
+        pointer: u32,
+        arity: u8,
+        environment: Register,
+        environment_size: u8,
+    },
+    Dump(Register),
+    Environment {
+        destination: Register,
+        index: u8,
+    },
+    Float64 {
+        destination: Register,
+        value: f64,
+    },
+    Integer32 {
+        destination: Register,
+        value: i32,
+    },
+    Jump(u32),
+    Move {
+        destination: Register,
+        source: Register,
+    },
+    Nil(Register),
+    Return(Register),
+    Set {
+        destination: Register,
+        array: Register,
+        index: Register,
+        value: Register,
+    },
+    /// Moves a value leaving `nil` in a source register.
+    Take {
+        destination: Register,
+        source: Register,
+    },
+    TailCall {
+        function: Register,
+        arity: u8,
+    },
+    Ternary {
+        operator: TernaryOperator,
+        destination: Register,
+        first: Register,
+        second: Register,
+        third: Register,
+    },
+    Unary {
+        operator: UnaryOperator,
+        destination: Register,
+        source: Register,
+    },
+}
+
+impl Display for RegisterInstruction {
+    fn fmt(&self, formatter: &mut Formatter) -> fmt::Result {
+        match self {
+            Self::Binary {
+                operator,
+                destination,
+                lhs,
+                rhs,
+            } => write!(formatter, "{operator} r{destination} r{lhs} r{rhs}"),
+            Self::Branch { condition, pointer } => {
+                write!(formatter, "branch r{condition} {pointer:x}")
+            }
+            Self::Call { function, arity } => write!(formatter, "call r{function} {arity}"),
+            Self::Close {
+                destination,
+                pointer,
+                arity,
+                environment,
+                environment_size,
+            } => write!(
+                formatter,
+                "close r{destination} {pointer:x} {arity} r{environment} {environment_size}"
+            ),
+            Self::Dump(source) => write!(formatter, "dump r{source}"),
+            Self::Environment { destination, index } => {
+                write!(formatter, "environment r{destination} {index}")
+            }
+            Self::Float64 { destination, value } => {
+                write!(formatter, "float64 r{destination} {value}")
+            }
+            Self::Integer32 { destination, value } => {
+                write!(formatter, "integer32 r{destination} {value}")
+            }
+            Self::Jump(pointer) => write!(formatter, "jump {pointer:x}"),
+            Self::Move {
+                destination,
+                source,
+            } => write!(formatter, "move r{destination} r{source}"),
+            Self::Nil(destination) => write!(formatter, "nil r{destination}"),
+            Self::Return(source) => write!(formatter, "return r{source}"),
+            Self::Set {
+                destination,
+                array,
+                index,
+                value,
+            } => write!(formatter, "set r{destination} r{array} r{index} r{value}"),
+            Self::Take {
+                destination,
+                source,
+            } => write!(formatter, "take r{destination} r{source}"),
+            Self::TailCall { function, arity } => {
+                write!(formatter, "tail_call r{function} {arity}")
+            }
+            Self::Ternary {
+                operator,
+                destination,
+                first,
+                second,
+                third,
+            } => write!(
+                formatter,
+                "{operator} r{destination} r{first} r{second} r{third}"
+            ),
+            Self::Unary {
+                operator,
+                destination,
+                source,
+            } => write!(formatter, "{operator} r{destination} r{source}"),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum BinaryOperator {
+    Add,
+    And,
+    Concat,
+    Divide,
+    Equal,
+    Filter,
+    Get,
+    GreaterThan,
+    GreaterThanOrEqual,
+    LessThan,
+    LessThanOrEqual,
+    Map,
+    Multiply,
+    NotEqual,
+    Or,
+    Push,
+    Range,
+    SetRef,
+    Sort,
+    Subtract,
+}
+
+impl Display for BinaryOperator {
+    fn fmt(&self, formatter: &mut Formatter) -> fmt::Result {
+        write!(
+            formatter,
+            "{}",
+            match self {
+                Self::Add => "add",
+                Self::And => "and",
+                Self::Concat => "concat",
+                Self::Divide => "divide",
+                Self::Equal => "equal",
+                Self::Filter => "filter",
+                Self::Get => "get",
+                Self::GreaterThan => "greater_than",
+                Self::GreaterThanOrEqual => "greater_than_or_equal",
+                Self::LessThan => "less_than",
+                Self::LessThanOrEqual => "less_than_or_equal",
+                Self::Map => "map",
+                Self::Multiply => "multiply",
+                Self::NotEqual => "not_equal",
+                Self::Or => "or",
+                Self::Push => "push",
+                Self::Range => "range",
+                Self::SetRef => "set_ref",
+                Self::Sort => "sort",
+                Self::Subtract => "subtract",
+            }
+        )
+    }
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum UnaryOperator {
+    Deref,
+    Display,
+    Length,
+    Not,
+    Pop,
+    Ref,
+    Repr,
+    Reverse,
+}
+
+impl Display for UnaryOperator {
+    fn fmt(&self, formatter: &mut Formatter) -> fmt::Result {
+        write!(
+            formatter,
+            "{}",
+            match self {
+                Self::Deref => "deref",
+                Self::Display => "display",
+                Self::Length => "length",
+                Self::Not => "not",
+                Self::Pop => "pop",
+                Self::Ref => "ref",
+                Self::Repr => "repr",
+                Self::Reverse => "reverse",
+            }
+        )
+    }
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum TernaryOperator {
+    Fold,
+    Slice,
+}
+
+impl Display for TernaryOperator {
+    fn fmt(&self, formatter: &mut Formatter) -> fmt::Result {
+        write!(
+            formatter,
+            "{}",
+            match self {
+                Self::Fold => "fold",
+                Self::Slice => "slice",
+            }
+        )
+    }
+}
@@ -0,0 +1,774 @@
+use super::{BinaryOperator, Register, RegisterInstruction, TernaryOperator, UnaryOperator};
+use crate::{
+    frame::Frame, input::Source, stack::Stack, Backtrace, BacktraceFrame, Input, RuntimeError,
+};
+#[cfg(feature = "std")]
+use crate::{input::Stdin, output::Stdout};
+use alloc::{boxed::Box, string::ToString, vec, vec::Vec};
+use core::{
+    fmt::{self, Debug, Formatter, Write},
+    mem::replace,
+};
+use runtime::{Array, Closure, Reference, Value, NIL};
+
+const REGISTER_COUNT: usize = 1 << 16;
+const FRAME_SIZE: usize = 1 << 8;
+
+macro_rules! arithmetic_operation {
+    ($lhs:expr, $rhs:expr, $operator:tt) => {
+        (|| Some(($lhs.to_float64()?.to_f64() $operator $rhs.to_float64()?.to_f64()).into()))()
+            .unwrap_or(NIL)
+    };
+}
+
+macro_rules! comparison_operation {
+    ($lhs:expr, $rhs:expr, $operator:tt) => {
+        ((*$lhs $operator *$rhs) as usize as f64).into()
+    };
+}
+
+/// A register machine.
+pub struct RegisterVm {
+    program_counter: usize,
+    base: usize,
+    registers: Vec<Value>,
+    frames: Stack<Frame, { 1 << 8 }>,
+    output: Box<dyn Write>,
+    input: Source,
+}
+
+impl RegisterVm {
+    /// Creates a VM writing dumped values into standard output and reading
+    /// standard input.
+    #[cfg(feature = "std")]
+    pub fn new() -> Self {
+        let mut vm = Self::with_output(Stdout);
+        vm.set_input(Stdin);
+        vm
+    }
+
+    /// Creates a VM writing dumped values into an output.
+    pub fn with_output(output: impl Write + 'static) -> Self {
+        Self {
+            program_counter: 0,
+            base: 0,
+            registers: vec![NIL; REGISTER_COUNT],
+            frames: Stack::new(),
+            output: Box::new(output),
+            input: Source::new(()),
+        }
+    }
+
+    /// Sets an input source of the VM.
+    ///
+    /// VMs created with an output read nothing by default.
+    pub fn set_input(&mut self, input: impl Input + 'static) {
+        self.input = Source::new(input);
+    }
+
+    /// Returns an input source of the VM.
+    pub fn input(&mut self) -> &mut dyn Input {
+        self.input.input()
+    }
+
+    /// Runs instructions from the current program counter to their end.
+    pub fn run(&mut self, instructions: &[RegisterInstruction]) -> Result<(), RuntimeError> {
+        while let Some(instruction) = instructions.get(self.program_counter) {
+            self.program_counter += 1;
+            self.execute(instruction, instructions)?;
+        }
+
+        Ok(())
+    }
+
+    #[inline(always)]
+    fn execute(
+        &mut self,
+        instruction: &RegisterInstruction,
+        instructions: &[RegisterInstruction],
+    ) -> Result<(), RuntimeError> {
+        match *instruction {
+            RegisterInstruction::Binary {
+                operator,
+                destination,
+                lhs,
+                rhs,
+            } => self.binary(instructions, operator, destination, lhs, rhs)?,
+            RegisterInstruction::Branch { condition, pointer } => {
+                if self.register(condition).is_nil() {
+                    self.program_counter = pointer as usize;
+                }
+            }
+            RegisterInstruction::Call { function, arity } => self.call(function, arity)?,
+            RegisterInstruction::Close {
+                destination,
+                pointer,
+                arity,
+                environment,
+                environment_size,
+            } => {
+                let mut closure = Closure::new(pointer, arity, environment_size);
+
+                for index in 0..environment_size {
+                    closure.write_environment(
+                        index as usize,
+                        self.register(environment + index).clone(),
+                    );
+                }
+
+                *self.register_mut(destination) = closure.into();
+            }
+            RegisterInstruction::Dump(source) => {
+                let value = self.register(source).clone();
+
+                if writeln!(self.output, "{value}").is_err() {
+                    return Err(RuntimeError::Output(self.capture_backtrace()));
+                }
+            }
+            RegisterInstruction::Environment { destination, index } => {
+                *self.register_mut(destination) = self
+                    .register(0)
+                    .as_closure()
+                    .expect("closure")
+                    .get_environment(index as usize)
+                    .clone();
+            }
+            RegisterInstruction::Float64 { destination, value } => {
+                *self.register_mut(destination) = value.into();
+            }
+            RegisterInstruction::Integer32 { destination, value } => {
+                *self.register_mut(destination) = value.into();
+            }
+            RegisterInstruction::Jump(pointer) => self.program_counter = pointer as usize,
+            RegisterInstruction::Move {
+                destination,
+                source,
+            } => *self.register_mut(destination) = self.register(source).clone(),
+            RegisterInstruction::Nil(destination) => *self.register_mut(destination) = NIL,
+            RegisterInstruction::Return(source) => {
+                let value = replace(self.register_mut(source), NIL);
+                self.r#return(value);
+            }
+            RegisterInstruction::Set {
+                destination,
+                array,
+                index,
+                value,
+            } => {
+                let index = self.register(index).clone();
+                let value = self.register(value).clone();
+
+                *self.register_mut(destination) = self
+                    .operand(array, destination)
+                    .into_array()
+                    .map(|array| array.set(index, value).into())
+                    .unwrap_or(NIL);
+            }
+            RegisterInstruction::Take {
+                destination,
+                source,
+            } => *self.register_mut(destination) = replace(self.register_mut(source), NIL),
+            RegisterInstruction::TailCall { function, arity } => self.tail_call(function, arity)?,
+            RegisterInstruction::Ternary {
+                operator,
+                destination,
+                first,
+                second,
+                third,
+            } => self.ternary(instructions, operator, destination, first, second, third)?,
+            RegisterInstruction::Unary {
+                operator,
+                destination,
+                source,
+            } => self.unary(operator, destination, source),
+        }
+
+        Ok(())
+    }
+
+    /// Returns registers of the current frame.
+    pub fn registers(&self) -> &[Value] {
+        &self.registers[self.base..]
+    }
+
+    #[inline(always)]
+    fn register(&self, register: Register) -> &Value {
+        &self.registers[self.base + register as usize]
+    }
+
+    #[inline(always)]
+    fn register_mut(&mut self, register: Register) -> &mut Value {
+        &mut self.registers[self.base + register as usize]
+    }
+
+    // Returns a value of an operand. An operand in a destination register is
+    // moved as it is overwritten anyway, so that arrays are updated in place.
+    fn operand(&mut self, source: Register, destination: Register) -> Value {
+        if source == destination {
+            replace(self.register_mut(source), NIL)
+        } else {
+            self.register(source).clone()
+        }
+    }
+
+    #[inline(always)]
+    fn binary(
+        &mut self,
+        instructions: &[RegisterInstruction],
+        operator: BinaryOperator,
+        destination: Register,
+        lhs: Register,
+        rhs: Register,
+    ) -> Result<(), RuntimeError> {
+        let source = lhs;
+        let lhs = self.register(lhs);
+        let rhs = self.register(rhs);
+
+        let value = match operator {
+            BinaryOperator::Add => arithmetic_operation!(lhs, rhs, +),
+            BinaryOperator::And => if lhs.is_nil() { lhs } else { rhs }.clone(),
+            BinaryOperator::Concat => {
+                let rhs = rhs.clone();
+
+                self.operand(source, destination)
+                    .into_array()
+                    .zip(rhs.as_array())
+                    .map(|(lhs, rhs)| lhs.concat(rhs).into())
+                    .unwrap_or(NIL)
+            }
+            BinaryOperator::Divide => arithmetic_operation!(lhs, rhs, /),
+            BinaryOperator::Equal => comparison_operation!(lhs, rhs, ==),
+            BinaryOperator::Filter => {
+                let (function, array) = (lhs.clone(), rhs.clone());
+                self.filter(instructions, &function, array)?
+            }
+            BinaryOperator::Get => lhs
+                .as_array()
+                .map(|array| array.get(rhs.clone()).clone())
+                .unwrap_or(NIL),
+            BinaryOperator::GreaterThan => comparison_operation!(lhs, rhs, >),
+            BinaryOperator::GreaterThanOrEqual => comparison_operation!(lhs, rhs, >=),
+            BinaryOperator::LessThan => comparison_operation!(lhs, rhs, <),
+            BinaryOperator::LessThanOrEqual => comparison_operation!(lhs, rhs, <=),
+            BinaryOperator::Map => {
+                let (function, array) = (lhs.clone(), rhs.clone());
+                self.map(instructions, &function, array)?
+            }
+            BinaryOperator::Multiply => arithmetic_operation!(lhs, rhs, *),
+            BinaryOperator::NotEqual => comparison_operation!(lhs, rhs, !=),
+            BinaryOperator::Or => if lhs.is_nil() { rhs } else { lhs }.clone(),
+            BinaryOperator::Push => {
+                let rhs = rhs.clone();
+
+                self.operand(source, destination)
+                    .into_array()
+                    .map(|array| array.push(rhs).into())
+                    .unwrap_or(NIL)
+            }
+            BinaryOperator::Range => match lhs.to_float64().zip(rhs.to_float64()) {
+                Some((start, end)) => Array::range(start.to_f64(), end.to_f64())
+                    .ok_or_else(|| RuntimeError::Range(self.capture_backtrace()))?
+                    .into(),
+                None => NIL,
+            },
+            BinaryOperator::SetRef => lhs
+                .as_reference()
+                .map(|reference| {
+                    reference.replace(rhs.clone());
+                    rhs.clone()
+                })
+                .unwrap_or(NIL),
+            BinaryOperator::Sort => {
+                let (array, function) = (lhs.clone(), rhs.clone());
+                self.sort(instructions, array, &function)?
+            }
+            BinaryOperator::Subtract => arithmetic_operation!(lhs, rhs, -),
+        };
+
+        *self.register_mut(destination) = value;
+
+        Ok(())
+    }
+
+    fn ternary(
+        &mut self,
+        instructions: &[RegisterInstruction],
+        operator: TernaryOperator,
+        destination: Register,
+        first: Register,
+        second: Register,
+        third: Register,
+    ) -> Result<(), RuntimeError> {
+        let first = self.register(first).clone();
+        let second = self.register(second).clone();
+        let third = self.register(third).clone();
+
+        let value = match operator {
+            TernaryOperator::Fold => self.fold(instructions, &first, second, third)?,
+            TernaryOperator::Slice => first
+                .as_array()
+                .map(|array| array.slice(second, third).into())
+                .unwrap_or(NIL),
+        };
+
+        *self.register_mut(destination) = value;
+
+        Ok(())
+    }
+
+    fn unary(&mut self, operator: UnaryOperator, destination: Register, source: Register) {
+        let value = self.register(source);
+
+        let value = match operator {
+            UnaryOperator::Deref => value.as_reference().map(Reference::get).unwrap_or(NIL),
+            UnaryOperator::Display => value.to_string().into(),
+            UnaryOperator::Length => value
+                .as_array()
+                .map(|array| array.len().into())
+                .unwrap_or(NIL),
+            UnaryOperator::Not => {
+                if value.is_nil() {
+                    1.0.into()
+                } else {
+                    NIL
+                }
+            }
+            UnaryOperator::Pop => self
+                .operand(source, destination)
+                .into_array()
+                .map(|array| array.pop().into())
+                .unwrap_or(NIL),
+            UnaryOperator::Ref => Reference::new(value.clone()).into(),
+            UnaryOperator::Repr => value.repr().to_string().into(),
+            UnaryOperator::Reverse => self
+                .operand(source, destination)
+                .into_array()
+                .map(|array| array.reverse().into())
+                .unwrap_or(NIL),
+        };
+
+        *self.register_mut(destination) = value;
+    }
+
+    #[inline(never)]
+    fn map(
+        &mut self,
+        instructions: &[RegisterInstruction],
+        function: &Value,
+        array: Value,
+    ) -> Result<Value, RuntimeError> {
+        let Some(mut array) = array.into_array() else {
+            return Ok(NIL);
+        };
+
+        for element in array.as_mut_slice() {
+            let value = replace(element, NIL);
+            *element = self.apply(instructions, function, [value])?;
+        }
+
+        Ok(array.into())
+    }
+
+    #[inline(never)]
+    fn filter(
+        &mut self,
+        instructions: &[RegisterInstruction],
+        function: &Value,
+        array: Value,
+    ) -> Result<Value, RuntimeError> {
+        let Some(array) = array.into_array() else {
+            return Ok(NIL);
+        };
+        let values = array.into_vec();
+        let mut kept = Vec::with_capacity(values.len());
+
+        for value in values {
+            if !self
+                .apply(instructions, function, [value.clone()])?
+                .is_nil()
+            {
+                kept.push(value);
+            }
+        }
+
+        Ok(Array::from(kept).into())
+    }
+
+    #[inline(never)]
+    fn fold(
+        &mut self,
+        instructions: &[RegisterInstruction],
+        function: &Value,
+        mut accumulator: Value,
+        array: Value,
+    ) -> Result<Value, RuntimeError> {
+        for value in array.into_array().map(Array::into_vec).unwrap_or_default() {
+            accumulator = self.apply(instructions, function, [accumulator, value])?;
+        }
+
+        Ok(accumulator)
+    }
+
+    #[inline(never)]
+    fn sort(
+        &mut self,
+        instructions: &[RegisterInstruction],
+        array: Value,
+        function: &Value,
+    ) -> Result<Value, RuntimeError> {
+        let Some(array) = array.into_array() else {
+            return Ok(NIL);
+        };
+
+        Ok(if function.is_nil() {
+            array.sort_by(|one, other| Ok::<_, RuntimeError>(one < other))?
+        } else {
+            array.sort_by(|one, other| {
+                Ok(!self
+                    .apply(instructions, function, [one.clone(), other.clone()])?
+                    .is_nil())
+            })?
+        }
+        .into())
+    }
+
+    // Calls a function from a primitive until it returns.
+    //
+    // The function and its arguments are placed right after the registers of
+    // the current frame.
+    fn apply<const N: usize>(
+        &mut self,
+        instructions: &[RegisterInstruction],
+        function: &Value,
+        arguments: [Value; N],
+    ) -> Result<Value, RuntimeError> {
+        let base = self.base + FRAME_SIZE;
+
+        if base + FRAME_SIZE > self.registers.len() {
+            return Err(RuntimeError::StackOverflow(self.capture_backtrace()));
+        }
+
+        self.registers[base] = function.clone();
+
+        for (index, argument) in arguments.into_iter().enumerate() {
+            self.registers[base + index + 1] = argument;
+        }
+
+        let program_counter = self.program_counter;
+        let depth = self.frames.len();
+
+        self.call_at(base, N as u8)?;
+
+        while self.frames.len() > depth {
+            let instruction = &instructions[self.program_counter];
+            self.program_counter += 1;
+            self.execute(instruction, instructions)?;
+        }
+
+        self.program_counter = program_counter;
+
+        Ok(replace(&mut self.registers[base], NIL))
+    }
+
+    fn call(&mut self, function: Register, arity: u8) -> Result<(), RuntimeError> {
+        self.call_at(self.base + function as usize, arity)
+    }
+
+    fn call_at(&mut self, base: usize, arity: u8) -> Result<(), RuntimeError> {
+        if self.callee(base).is_none() {
+            self.registers[base] = NIL;
+            return Ok(());
+        } else if self.frames.len() >= self.frames.capacity() {
+            return Err(RuntimeError::CallStackOverflow(self.capture_backtrace()));
+        }
+
+        self.frames
+            .push(Frame::new(self.base as u32, self.program_counter as u32));
+        self.base = base;
+
+        self.call_function(arity)
+    }
+
+    fn tail_call(&mut self, function: Register, arity: u8) -> Result<(), RuntimeError> {
+        let base = self.base + function as usize;
+
+        if self.callee(base).is_none() {
+            self.r#return(NIL);
+            return Ok(());
+        }
+
+        for index in 0..=arity as usize {
+            self.registers[self.base + index] = replace(&mut self.registers[base + index], NIL);
+        }
+
+        self.frames.top_mut().elide();
+
+        self.call_function(arity)
+    }
+
+    // Jumps into a closure at the current frame base.
+    fn call_function(&mut self, arity: u8) -> Result<(), RuntimeError> {
+        if self.base + FRAME_SIZE > self.registers.len() {
+            return Err(RuntimeError::StackOverflow(self.capture_backtrace()));
+        }
+
+        let closure = self.callee(self.base).expect("closure");
+        let id = closure.id();
+
+        for index in arity..closure.arity() {
+            *self.register_mut(index + 1) = NIL;
+        }
+
+        self.program_counter = id as usize;
+
+        Ok(())
+    }
+
+    fn r#return(&mut self, value: Value) {
+        let frame = self.frames.pop();
+
+        self.registers[self.base] = value;
+        self.base = frame.pointer() as usize;
+        self.program_counter = frame.return_address() as usize;
+    }
+
+    fn callee(&self, base: usize) -> Option<&Closure> {
+        self.registers[base]
+            .as_closure()
+            .filter(|closure| !closure.is_nil())
+    }
+
+    fn capture_backtrace(&self) -> Backtrace {
+        let mut frames = vec![];
+        let mut base = self.base;
+        let mut address = self.program_counter;
+
+        for frame in self.frames.as_slice().iter().rev() {
+            frames.push(BacktraceFrame::new(
+                self.callee(base).map(Closure::id),
+                address,
+                frame.tail_call_count(),
+            ));
+
+            base = frame.pointer() as usize;
+            address = frame.return_address() as usize - 1;
+        }
+
+        frames.push(BacktraceFrame::new(None, address, 0));
+
+        Backtrace::new(frames)
+    }
+}
+
+impl Debug for RegisterVm {
+    fn fmt(&self, formatter: &mut Formatter) -> fmt::Result {
+        formatter
+            .debug_struct("RegisterVm")
+            .field("program_counter", &self.program_counter)
+            .field("base", &self.base)
+            .field("frames", &self.frames)
+            .finish_non_exhaustive()
+    }
+}
+
+#[cfg(feature = "std")]
+impl Default for RegisterVm {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::string::String;
+    use pretty_assertions::assert_eq;
+
+    fn run(instructions: &[RegisterInstruction]) -> RegisterVm {
+        let mut vm = RegisterVm::with_output(String::new());
+
+        vm.run(instructions).unwrap();
+
+        vm
+    }
+
+    #[test]
+    fn add_numbers() {
+        let vm = run(&[
+            RegisterInstruction::Float64 {
+                destination: 1,
+                value: 1.0,
+            },
+            RegisterInstruction::Float64 {
+                destination: 2,
+                value: 2.0,
+            },
+            RegisterInstruction::Binary {
+                operator: BinaryOperator::Add,
+                destination: 3,
+                lhs: 1,
+                rhs: 2,
+            },
+        ]);
+
+        assert_eq!(vm.registers()[3], 3.0.into());
+    }
+
+    #[test]
+    fn take_value() {
+        let vm = run(&[
+            RegisterInstruction::Float64 {
+                destination: 1,
+                value: 42.0,
+            },
+            RegisterInstruction::Take {
+                destination: 2,
+                source: 1,
+            },
+        ]);
+
+        assert_eq!(vm.registers()[1], NIL);
+        assert_eq!(vm.registers()[2], 42.0.into());
+    }
+
+    #[test]
+    fn set_moved_array_in_place() {
+        let mut instructions = vec![
+            RegisterInstruction::Float64 {
+                destination: 1,
+                value: 42.0,
+            },
+            RegisterInstruction::Binary {
+                operator: BinaryOperator::Push,
+                destination: 2,
+                lhs: 2,
+                rhs: 1,
+            },
+            RegisterInstruction::Take {
+                destination: 3,
+                source: 2,
+            },
+        ];
+        let mut vm = run(&instructions);
+        let id = vm.registers()[3].as_array().unwrap().id();
+
+        instructions.push(RegisterInstruction::Set {
+            destination: 3,
+            array: 3,
+            index: 4,
+            value: 1,
+        });
+        vm.run(&instructions).unwrap();
+
+        assert_eq!(vm.registers()[3], [42.0.into()].into());
+        assert_eq!(vm.registers()[3].as_array().unwrap().id(), id);
+    }
+
+    #[test]
+    fn call_function() {
+        let vm = run(&[
+            RegisterInstruction::Jump(3),
+            RegisterInstruction::Binary {
+                operator: BinaryOperator::Multiply,
+                destination: 2,
+                lhs: 1,
+                rhs: 1,
+            },
+            RegisterInstruction::Return(2),
+            RegisterInstruction::Close {
+                destination: 1,
+                pointer: 1,
+                arity: 1,
+                environment: 2,
+                environment_size: 0,
+            },
+            RegisterInstruction::Float64 {
+                destination: 2,
+                value: 3.0,
+            },
+            RegisterInstruction::Call {
+                function: 1,
+                arity: 1,
+            },
+        ]);
+
+        assert_eq!(vm.registers()[1], 9.0.into());
+    }
+
+    #[test]
+    fn call_non_function() {
+        let vm = run(&[
+            RegisterInstruction::Float64 {
+                destination: 1,
+                value: 42.0,
+            },
+            RegisterInstruction::Call {
+                function: 1,
+                arity: 0,
+            },
+        ]);
+
+        assert_eq!(vm.registers()[1], NIL);
+    }
+
+    #[test]
+    fn create_too_long_range() {
+        let instructions = [
+            RegisterInstruction::Float64 {
+                destination: 1,
+                value: 0.0,
+            },
+            RegisterInstruction::Float64 {
+                destination: 2,
+                value: f64::INFINITY,
+            },
+            RegisterInstruction::Binary {
+                operator: BinaryOperator::Range,
+                destination: 3,
+                lhs: 1,
+                rhs: 2,
+            },
+        ];
+
+        assert!(matches!(
+            RegisterVm::with_output(String::new()).run(&instructions),
+            Err(RuntimeError::Range(_))
+        ));
+    }
+
+    #[test]
+    fn overflow_call_stack() {
+        let instructions = [
+            RegisterInstruction::Jump(3),
+            RegisterInstruction::Move {
+                destination: 1,
+                source: 0,
+            },
+            RegisterInstruction::Call {
+                function: 1,
+                arity: 0,
+            },
+            RegisterInstruction::Close {
+                destination: 1,
+                pointer: 1,
+                arity: 0,
+                environment: 2,
+                environment_size: 0,
+            },
+            RegisterInstruction::Call {
+                function: 1,
+                arity: 0,
+            },
+        ];
+
+        let Err(RuntimeError::CallStackOverflow(backtrace)) =
+            RegisterVm::with_output(String::new()).run(&instructions)
+        else {
+            panic!("call stack overflow expected");
+        };
+
+        assert_eq!(backtrace.frames().len(), 257);
+        assert_eq!(backtrace.frames()[0], BacktraceFrame::new(Some(1), 3, 0));
+        assert_eq!(backtrace.frames()[256], BacktraceFrame::new(None, 4, 0));
+    }
+}
@@ -1,6 +1,7 @@
-use std::{
-    alloc::{alloc, dealloc, Layout},
-    ptr::{copy, read, write},
+use alloc::alloc::{alloc, dealloc, Layout};
+use core::{
+    ptr::{copy, drop_in_place, read, write},
+    slice,
 };
 
 #[derive(Debug)]
@@ -17,11 +18,13 @@ impl<T, const N: usize> Stack<T, N> {
         Self { base: ptr, ptr }
     }
 
+    /// Pushes a value.
+    ///
+    /// Callers reserve capacity in advance as it is checked only in debug
+    /// builds.
     #[inline(always)]
     pub fn push(&mut self, value: T) {
-        if self.len() >= N {
-            panic!("stack overflow");
-        }
+        debug_assert!(self.len() < N, "stack overflow");
 
         unsafe {
             write(self.ptr, value);
@@ -50,11 +53,29 @@ impl<T, const N: usize> Stack<T, N> {
         unsafe { &*self.ptr.sub(index + 1) }
     }
 
+    #[inline(always)]
+    pub fn peek_mut(&mut self, index: usize) -> &mut T {
+        if self.len() as isize - index as isize <= 0 {
+            panic!("stack underflow");
+        }
+
+        unsafe { &mut *self.ptr.sub(index + 1) }
+    }
+
     #[inline(always)]
     pub fn top(&self) -> &T {
         self.peek(0)
     }
 
+    #[inline(always)]
+    pub fn top_mut(&mut self) -> &mut T {
+        if self.len() == 0 {
+            panic!("stack underflow");
+        }
+
+        unsafe { &mut *self.ptr.sub(1) }
+    }
+
     #[inline(always)]
     pub fn truncate(&mut self, start: usize, end: usize) {
         for index in start..end {
@@ -74,17 +95,35 @@ impl<T, const N: usize> Stack<T, N> {
     pub fn len(&self) -> usize {
         (unsafe { self.ptr.offset_from(self.base) }) as usize
     }
+
+    #[inline(always)]
+    pub const fn capacity(&self) -> usize {
+        N
+    }
+
+    pub fn as_slice(&self) -> &[T] {
+        unsafe { slice::from_raw_parts(self.base, self.len()) }
+    }
 }
 
 impl<T, const N: usize> Drop for Stack<T, N> {
     fn drop(&mut self) {
-        unsafe { dealloc(self.base as _, Layout::array::<T>(0).unwrap()) }
+        while self.ptr > self.base {
+            unsafe {
+                self.ptr = self.ptr.sub(1);
+                drop_in_place(self.ptr);
+            }
+        }
+
+        unsafe { dealloc(self.base as _, Layout::array::<T>(N).unwrap()) }
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use alloc::boxed::Box;
+    use std::rc::Rc;
 
     fn create_stack<T>() -> Stack<T, 256> {
         Stack::new()
@@ -153,6 +192,26 @@ mod tests {
         assert_eq!(stack.peek(1), &1);
     }
 
+    #[test]
+    fn peek_mut() {
+        let mut stack = create_stack();
+        stack.push(1);
+        stack.push(2);
+        *stack.peek_mut(1) = 3;
+
+        assert_eq!(stack.pop(), 2);
+        assert_eq!(stack.pop(), 3);
+    }
+
+    #[test]
+    fn top_mut() {
+        let mut stack = create_stack();
+        stack.push(1);
+        *stack.top_mut() = 2;
+
+        assert_eq!(stack.pop(), 2);
+    }
+
     #[test]
     fn truncate() {
         let mut stack = create_stack();
@@ -177,6 +236,28 @@ mod tests {
         assert_eq!(stack.pop(), 2);
     }
 
+    #[test]
+    fn as_slice() {
+        let mut stack = create_stack();
+        stack.push(1);
+        stack.push(2);
+
+        assert_eq!(stack.as_slice(), [1, 2]);
+    }
+
+    #[test]
+    fn drop_elements() {
+        let value = Rc::new(());
+        let mut stack = create_stack();
+
+        stack.push(value.clone());
+        stack.push(value.clone());
+
+        drop(stack);
+
+        assert_eq!(Rc::strong_count(&value), 1);
+    }
+
     #[test]
     #[should_panic]
     fn overflow() {
@@ -0,0 +1,45 @@
+use runtime::{Closure, Value};
+
+/// A view of a call frame in a VM.
+pub struct StackFrame<'a> {
+    closure: Option<&'a Closure>,
+    address: usize,
+    values: &'a [Value],
+    tail_call_count: u32,
+}
+
+impl<'a> StackFrame<'a> {
+    pub(crate) const fn new(
+        closure: Option<&'a Closure>,
+        address: usize,
+        values: &'a [Value],
+        tail_call_count: u32,
+    ) -> Self {
+        Self {
+            closure,
+            address,
+            values,
+            tail_call_count,
+        }
+    }
+
+    /// Returns a closure called in a frame or `None` for a top level.
+    pub const fn closure(&self) -> Option<&'a Closure> {
+        self.closure
+    }
+
+    /// Returns an address of an instruction executed in a frame.
+    pub const fn address(&self) -> usize {
+        self.address
+    }
+
+    /// Returns values on a stack in a frame starting from arguments.
+    pub const fn values(&self) -> &'a [Value] {
+        self.values
+    }
+
+    /// Returns a number of frames elided by tail calls below this frame.
+    pub const fn tail_call_count(&self) -> u32 {
+        self.tail_call_count
+    }
+}
@@ -0,0 +1,204 @@
+use crate::{decode_instruction, Hook, Vm};
+use alloc::{string::ToString, vec, vec::Vec};
+use core::ops::ControlFlow;
+use runtime::Value;
+use std::io::{self, Sink, Write};
+
+/// A format of execution traces.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum TraceFormat {
+    #[default]
+    Text,
+    /// JSON lines
+    Json,
+}
+
+/// A hook which counts hits of instructions and optionally logs them.
+pub struct Tracer<W: Write> {
+    output: Option<(W, TraceFormat)>,
+    counts: Vec<u64>,
+    error: Option<io::Error>,
+}
+
+impl Tracer<Sink> {
+    /// Creates a tracer only counting hits of instructions.
+    pub fn new() -> Self {
+        Self {
+            output: None,
+            counts: vec![],
+            error: None,
+        }
+    }
+}
+
+impl Default for Tracer<Sink> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<W: Write> Tracer<W> {
+    /// Creates a tracer logging each instruction with a stack top before its
+    /// execution.
+    pub fn with_output(output: W, format: TraceFormat) -> Self {
+        Self {
+            output: Some((output, format)),
+            counts: vec![],
+            error: None,
+        }
+    }
+
+    /// Returns hit counts of instructions indexed by their addresses.
+    pub fn instruction_counts(&self) -> &[u64] {
+        &self.counts
+    }
+
+    /// Flushes an output and returns hit counts of instructions.
+    pub fn finish(mut self) -> Result<Vec<u64>, io::Error> {
+        if let Some(error) = self.error {
+            return Err(error);
+        } else if let Some((output, _)) = &mut self.output {
+            output.flush()?;
+        }
+
+        Ok(self.counts)
+    }
+
+    fn trace(&mut self, vm: &Vm, codes: &[u8]) -> Result<(), io::Error> {
+        let Some((output, format)) = &mut self.output else {
+            return Ok(());
+        };
+        let address = vm.program_counter();
+        let instruction = match decode_instruction(codes, &mut address.clone()) {
+            Ok(instruction) => instruction.to_string(),
+            Err(error) => error.to_string(),
+        };
+        let top = vm.stack().last().map(Value::repr);
+        let depth = vm.frames().len();
+
+        match format {
+            TraceFormat::Text => {
+                write!(output, "{address:06x} {:depth$}{instruction:<24}", "")?;
+
+                match top {
+                    Some(top) => writeln!(output, "{top}"),
+                    None => writeln!(output, "-"),
+                }
+            }
+            TraceFormat::Json => {
+                write!(
+                    output,
+                    "{{\"address\":{address},\"depth\":{depth},\"instruction\":"
+                )?;
+                write_json_string(output, &instruction)?;
+                write!(output, ",\"top\":")?;
+
+                match top {
+                    Some(top) => write_json_string(output, &top.to_string())?,
+                    None => write!(output, "null")?,
+                }
+
+                writeln!(output, "}}")
+            }
+        }
+    }
+}
+
+impl<W: Write> Hook for Tracer<W> {
+    fn before_instruction(&mut self, vm: &Vm, codes: &[u8]) -> ControlFlow<()> {
+        let address = vm.program_counter();
+
+        if self.counts.len() < codes.len() {
+            self.counts.resize(codes.len(), 0);
+        }
+
+        self.counts[address] += 1;
+
+        if let Err(error) = self.trace(vm, codes) {
+            self.error = Some(error);
+            return ControlFlow::Break(());
+        }
+
+        ControlFlow::Continue(())
+    }
+}
+
+fn write_json_string(output: &mut impl Write, string: &str) -> Result<(), io::Error> {
+    write!(output, "\"")?;
+
+    for character in string.chars() {
+        match character {
+            '"' => write!(output, "\\\"")?,
+            '\\' => write!(output, "\\\\")?,
+            '\n' => write!(output, "\\n")?,
+            '\t' => write!(output, "\\t")?,
+            '\r' => write!(output, "\\r")?,
+            character if character.is_control() => write!(output, "\\u{:04x}", character as u32)?,
+            character => write!(output, "{character}")?,
+        }
+    }
+
+    write!(output, "\"")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Instruction;
+    use alloc::string::String;
+    use pretty_assertions::assert_eq;
+
+    fn codes() -> Vec<u8> {
+        let mut codes = vec![Instruction::Symbol as u8, 3];
+        codes.extend(b"a\"b");
+        codes.extend([
+            Instruction::Nil as u8,
+            Instruction::Drop as u8,
+            Instruction::Drop as u8,
+        ]);
+        codes
+    }
+
+    fn trace(format: TraceFormat) -> String {
+        let mut output = vec![];
+        let mut tracer = Tracer::with_output(&mut output, format);
+
+        Vm::new().run_with_hook(&codes(), &mut tracer).unwrap();
+        tracer.finish().unwrap();
+
+        String::from_utf8(output).unwrap()
+    }
+
+    #[test]
+    fn count_instructions() {
+        let mut tracer = Tracer::new();
+
+        Vm::new().run_with_hook(&codes(), &mut tracer).unwrap();
+
+        assert_eq!(tracer.finish().unwrap(), [1, 0, 0, 0, 0, 1, 1, 1]);
+    }
+
+    #[test]
+    fn trace_text() {
+        assert_eq!(
+            trace(TraceFormat::Text),
+            r#"000000 symbol 3 "a\"b"         -
+000005 nil                     a"b
+000006 drop                    ()
+000007 drop                    a"b
+"#
+        );
+    }
+
+    #[test]
+    fn trace_json() {
+        assert_eq!(
+            trace(TraceFormat::Json),
+            r#"{"address":0,"depth":0,"instruction":"symbol 3 \"a\\\"b\"","top":null}
+{"address":5,"depth":0,"instruction":"nil","top":"a\"b"}
+{"address":6,"depth":0,"instruction":"drop","top":"()"}
+{"address":7,"depth":0,"instruction":"drop","top":"a\"b"}
+"#
+        );
+    }
+}
@@ -1,11 +1,21 @@
+#[cfg(feature = "jit")]
+use crate::jit::Jit;
 use crate::{
-    decode::{decode_bytes, decode_f64, decode_u16, decode_u32, decode_u8, decode_u8_option},
     frame::Frame,
+    input::Source,
+    program::{DecodedInstruction, Operation, Program},
     stack::Stack,
-    Instruction,
+    Backtrace, BacktraceFrame, Hook, Input, RuntimeError, StackFrame,
 };
-use runtime::{Closure, Value, NIL};
-use std::str;
+#[cfg(feature = "std")]
+use crate::{input::Stdin, output::Stdout};
+use alloc::{boxed::Box, string::ToString, vec::Vec};
+use core::{
+    fmt::{self, Debug, Formatter, Write},
+    mem::replace,
+    ops::ControlFlow,
+};
+use runtime::{Array, Closure, ClosureId, Reference, Value, NIL};
 
 macro_rules! arithmetic_operation {
     ($self:expr, $operator:tt) => {
@@ -21,6 +31,20 @@ macro_rules! arithmetic_operation {
     };
 }
 
+macro_rules! constant_arithmetic_operation {
+    ($self:expr, $rhs:expr, $operator:tt) => {
+        let rhs = $rhs;
+        let value = (|| {
+            let lhs = $self.stack.pop().into_float64()?.to_f64();
+
+            Some((lhs $operator rhs).into())
+        })()
+        .unwrap_or(NIL);
+
+        $self.stack.push(value);
+    };
+}
+
 macro_rules! comparison_operation {
     ($self:expr, $operator:tt) => {
         let rhs = $self.stack.pop();
@@ -30,81 +54,288 @@ macro_rules! comparison_operation {
     };
 }
 
-#[derive(Debug)]
 pub struct Vm {
     program_counter: usize,
     stack: Stack<Value, { 1 << 11 }>,
     frames: Stack<Frame, { 1 << 8 }>,
+    program: Program,
+    output: Box<dyn Write>,
+    input: Source,
+    #[cfg(feature = "jit")]
+    jit: Jit,
 }
 
 impl Vm {
+    /// Creates a VM writing dumped values into standard output and reading
+    /// standard input.
+    #[cfg(feature = "std")]
     pub fn new() -> Self {
+        let mut vm = Self::with_output(Stdout);
+        vm.set_input(Stdin);
+        vm
+    }
+
+    /// Creates a VM writing dumped values into an output.
+    pub fn with_output(output: impl Write + 'static) -> Self {
         Self {
             program_counter: 0,
             stack: Stack::new(),
             frames: Stack::new(),
+            program: Program::default(),
+            output: Box::new(output),
+            input: Source::new(()),
+            #[cfg(feature = "jit")]
+            jit: Jit::new(),
         }
     }
 
-    pub fn run(&mut self, codes: &[u8]) {
-        while let Some(instruction) = decode_u8_option(codes, &mut self.program_counter) {
-            match instruction {
-                Instruction::ADD => self.add(),
-                Instruction::AND => self.and(),
-                Instruction::BRANCH => self.branch(codes),
-                Instruction::CALL => self.call(codes),
-                Instruction::CLOSE => self.close(codes),
-                Instruction::DIVIDE => self.divide(),
-                Instruction::DROP => self.drop(),
-                Instruction::DUMP => self.dump(),
-                Instruction::ENVIRONMENT => self.environment(codes),
-                Instruction::EQUAL => self.equal(),
-                Instruction::FLOAT64 => self.float64(codes),
-                Instruction::GET => self.get(),
-                Instruction::GREATER_THAN => self.greater_than(),
-                Instruction::GREATER_THAN_OR_EQUAL => self.greater_than_or_equal(),
-                Instruction::INTEGER32 => self.integer32(codes),
-                Instruction::JUMP => self.jump(codes),
-                Instruction::LENGTH => self.length(),
-                Instruction::LESS_THAN => self.less_than(),
-                Instruction::LESS_THAN_OR_EQUAL => self.less_than_or_equal(),
-                Instruction::MULTIPLY => self.multiply(),
-                Instruction::NIL => self.nil(),
-                Instruction::NOT => self.not(),
-                Instruction::NOT_EQUAL => self.not_equal(),
-                Instruction::OR => self.or(),
-                Instruction::PEEK => self.peek(codes),
-                Instruction::RETURN => self.r#return(),
-                Instruction::SET => self.set(),
-                Instruction::SUBTRACT => self.subtract(),
-                Instruction::SYMBOL => self.symbol(codes),
-                Instruction::TAIL_CALL => self.tail_call(codes),
-                _ => panic!("invalid instruction"),
+    /// Sets an input source of the VM.
+    ///
+    /// VMs created with an output read nothing by default.
+    pub fn set_input(&mut self, input: impl Input + 'static) {
+        self.input = Source::new(input);
+    }
+
+    /// Returns an input source of the VM.
+    pub fn input(&mut self) -> &mut dyn Input {
+        self.input.input()
+    }
+
+    /// Runs bytecodes compiling hot closures into native code if the `jit`
+    /// feature is enabled.
+    pub fn run(&mut self, codes: &[u8]) -> Result<(), RuntimeError> {
+        self.execute::<true>(codes, &mut ())
+    }
+
+    /// Runs bytecodes calling a hook before each instruction.
+    ///
+    /// Every instruction is interpreted so that the hook observes it.
+    pub fn run_with_hook(
+        &mut self,
+        codes: &[u8],
+        hook: &mut impl Hook,
+    ) -> Result<(), RuntimeError> {
+        self.execute::<false>(codes, hook)
+    }
+
+    fn execute<const JIT: bool>(
+        &mut self,
+        codes: &[u8],
+        hook: &mut impl Hook,
+    ) -> Result<(), RuntimeError> {
+        self.program.extend(codes);
+        self.reserve(self.program.frame_size(self.program_counter))?;
+
+        // Decoded instructions are as many as bytecodes.
+        while self.program_counter < self.program.len() {
+            if self.step::<JIT, _>(codes, hook)?.is_break() {
+                return Ok(());
             }
         }
+
+        Ok(())
     }
 
-    fn nil(&mut self) {
-        self.stack.push(NIL)
+    // Executes an instruction at the current program counter.
+    #[inline(always)]
+    fn step<const JIT: bool, H: Hook>(
+        &mut self,
+        codes: &[u8],
+        hook: &mut H,
+    ) -> Result<ControlFlow<()>, RuntimeError> {
+        if hook.before_instruction(self, codes).is_break() {
+            return Ok(ControlFlow::Break(()));
+        }
+
+        let address = self.program_counter;
+        let DecodedInstruction { operation, next } = self.program.get(address);
+
+        // Instructions other than symbols have constant sizes. Their next
+        // addresses are computed in their branches so that they do not wait for
+        // loads of decoded instructions.
+        self.program_counter = address + 1;
+
+        match operation {
+            Operation::Add => self.add(),
+            Operation::AddFloat64(number) => {
+                self.program_counter = address + 9;
+                self.add_float64(number)
+            }
+            Operation::And => self.and(),
+            Operation::Branch(target) => {
+                self.program_counter = address + 3;
+                self.branch(target)
+            }
+            Operation::BranchNotEqual(target) => {
+                self.program_counter = address + 3;
+                self.branch_not_equal(target)
+            }
+            Operation::Call { arity, cache } => {
+                self.program_counter = address + 2;
+                self.call::<JIT>(address, arity, cache)?
+            }
+            Operation::Concat => self.concat(),
+            Operation::Close {
+                id,
+                arity,
+                environment_size,
+            } => {
+                self.program_counter = address + 7;
+                self.close(id, arity, environment_size)
+            }
+            Operation::Deref => self.deref(),
+            Operation::Display => self.display(),
+            Operation::Divide => self.divide(),
+            Operation::Drop => self.drop(),
+            Operation::Dump => self.dump(hook)?,
+            Operation::DumpDrop => self.dump_drop(hook)?,
+            Operation::Environment(index) => {
+                self.program_counter = address + 2;
+                self.environment(index)
+            }
+            Operation::Equal => self.equal(),
+            Operation::Filter => return self.filter::<JIT>(address, codes, hook),
+            Operation::Float64(number) => {
+                self.program_counter = address + 9;
+                self.stack.push(number.into());
+            }
+            Operation::Fold => return self.fold::<JIT>(address, codes, hook),
+            Operation::Get => self.get(),
+            Operation::GreaterThan => self.greater_than(),
+            Operation::GreaterThanOrEqual => self.greater_than_or_equal(),
+            Operation::Integer32(number) => {
+                self.program_counter = address + 5;
+                self.stack.push(number.into());
+            }
+            Operation::Jump(address) => self.program_counter = address as usize,
+            Operation::Length => self.length(),
+            Operation::LessThan => self.less_than(),
+            Operation::LessThanOrEqual => self.less_than_or_equal(),
+            Operation::Map => return self.map::<JIT>(address, codes, hook),
+            Operation::Multiply => self.multiply(),
+            Operation::Nil => self.stack.push(NIL),
+            Operation::Nip(count) => {
+                self.program_counter = address + 2;
+                self.nip(count)
+            }
+            Operation::Not => self.not(),
+            Operation::NotEqual => self.not_equal(),
+            Operation::Or => self.or(),
+            Operation::Peek(index) => {
+                self.program_counter = address + 2;
+                self.peek(index)
+            }
+            Operation::Pop => self.pop(),
+            Operation::Push => self.push(),
+            Operation::Range => self.range()?,
+            Operation::Ref => self.r#ref(),
+            Operation::Repr => self.repr(),
+            Operation::Return => self.r#return(),
+            Operation::Reverse => self.reverse(),
+            Operation::Set => self.set(),
+            Operation::SetRef => self.set_ref(),
+            Operation::Slice => self.slice(),
+            Operation::Sort => return self.sort::<JIT>(address, codes, hook),
+            Operation::Store(index) => {
+                self.program_counter = address + 2;
+                self.store(index)
+            }
+            Operation::Subtract => self.subtract(),
+            Operation::SubtractFloat64(number) => {
+                self.program_counter = address + 9;
+                self.subtract_float64(number)
+            }
+            Operation::Symbol(symbol) => {
+                self.program_counter = next as usize;
+                self.stack.push(symbol.into())
+            }
+            Operation::TailCall { arity, cache } => {
+                self.program_counter = address + 2;
+                self.tail_call::<JIT>(address, arity, cache)?
+            }
+            Operation::Take(index) => {
+                self.program_counter = address + 2;
+                self.take(index)
+            }
+            Operation::Invalid => panic!("invalid instruction"),
+        }
+
+        Ok(ControlFlow::Continue(()))
     }
 
-    fn float64(&mut self, codes: &[u8]) {
-        let value = self.read_f64(codes);
-        self.stack.push(value.into());
+    pub const fn program_counter(&self) -> usize {
+        self.program_counter
     }
 
-    fn integer32(&mut self, codes: &[u8]) {
-        let value = self.read_u32(codes);
-        self.stack.push(value.into());
+    pub fn stack(&self) -> &[Value] {
+        self.stack.as_slice()
     }
 
-    fn symbol(&mut self, codes: &[u8]) {
-        let len = self.read_u8(codes);
-        let value = str::from_utf8(self.read_bytes(codes, len as usize))
-            .unwrap()
-            .into();
+    pub fn frames(&self) -> &[Frame] {
+        self.frames.as_slice()
+    }
 
-        self.stack.push(value);
+    #[cfg(all(test, feature = "jit"))]
+    pub(crate) fn jit(&self) -> &Jit {
+        &self.jit
+    }
+
+    /// Returns call frames from the innermost one to a top level.
+    pub fn backtrace(&self) -> Vec<StackFrame<'_>> {
+        let stack = self.stack();
+        let frames = self.frames();
+        let mut address = self.program_counter;
+        let mut end = stack.len();
+        let mut backtrace = Vec::with_capacity(frames.len() + 1);
+
+        for frame in frames.iter().rev() {
+            let pointer = frame.pointer() as usize;
+
+            backtrace.push(StackFrame::new(
+                stack.get(pointer).and_then(Value::as_closure),
+                address,
+                stack.get(pointer + 1..end).unwrap_or_default(),
+                frame.tail_call_count(),
+            ));
+
+            // Point to the inside of a call instruction.
+            address = frame.return_address() as usize - 1;
+            end = pointer;
+        }
+
+        backtrace.push(StackFrame::new(
+            None,
+            address,
+            &stack[..end.min(stack.len())],
+            0,
+        ));
+
+        backtrace
+    }
+
+    fn capture_backtrace(&self) -> Backtrace {
+        Backtrace::new(self.backtrace().iter().map(BacktraceFrame::from).collect())
+    }
+
+    // Reserves a stack for a frame, so that instructions in it push values
+    // without checking its capacity.
+    #[inline(always)]
+    fn reserve(&self, size: usize) -> Result<(), RuntimeError> {
+        if self.stack.len() + size > self.stack.capacity() {
+            return Err(self.overflow_stack());
+        }
+
+        Ok(())
+    }
+
+    #[cold]
+    fn overflow_stack(&self) -> RuntimeError {
+        RuntimeError::StackOverflow(self.capture_backtrace())
+    }
+
+    #[cold]
+    fn overflow_call_stack(&self) -> RuntimeError {
+        RuntimeError::CallStackOverflow(self.capture_backtrace())
     }
 
     fn get(&mut self) {
@@ -132,24 +363,290 @@ impl Vm {
         self.stack.push(value);
     }
 
+    fn r#ref(&mut self) {
+        let value = self.stack.pop();
+
+        self.stack.push(Reference::new(value).into());
+    }
+
+    fn deref(&mut self) {
+        let value = (|| Some(self.stack.pop().as_reference()?.get()))().unwrap_or(NIL);
+
+        self.stack.push(value);
+    }
+
+    fn set_ref(&mut self) {
+        let value = (|| {
+            let value = self.stack.pop();
+            let reference = self.stack.pop().into_reference()?;
+
+            reference.replace(value.clone());
+
+            Some(value)
+        })()
+        .unwrap_or(NIL);
+
+        self.stack.push(value);
+    }
+
     fn length(&mut self) {
         let value = (|| Some(self.stack.pop().into_array()?.len().into()))().unwrap_or(NIL);
 
         self.stack.push(value);
     }
 
+    fn slice(&mut self) {
+        let value = (|| {
+            let end = self.stack.pop();
+            let start = self.stack.pop();
+
+            Some(self.stack.pop().as_array()?.slice(start, end).into())
+        })()
+        .unwrap_or(NIL);
+
+        self.stack.push(value);
+    }
+
+    fn concat(&mut self) {
+        let value = (|| {
+            let other = self.stack.pop().into_array()?;
+
+            Some(self.stack.pop().into_array()?.concat(&other).into())
+        })()
+        .unwrap_or(NIL);
+
+        self.stack.push(value);
+    }
+
+    fn push(&mut self) {
+        let value = (|| {
+            let value = self.stack.pop();
+
+            Some(self.stack.pop().into_array()?.push(value).into())
+        })()
+        .unwrap_or(NIL);
+
+        self.stack.push(value);
+    }
+
+    fn pop(&mut self) {
+        let value = (|| Some(self.stack.pop().into_array()?.pop().into()))().unwrap_or(NIL);
+
+        self.stack.push(value);
+    }
+
+    fn reverse(&mut self) {
+        let value = (|| Some(self.stack.pop().into_array()?.reverse().into()))().unwrap_or(NIL);
+
+        self.stack.push(value);
+    }
+
+    fn range(&mut self) -> Result<(), RuntimeError> {
+        let end = self.stack.pop().into_float64();
+        let start = self.stack.pop().into_float64();
+        let value = match start.zip(end) {
+            Some((start, end)) => Array::range(start.to_f64(), end.to_f64())
+                .ok_or_else(|| RuntimeError::Range(self.capture_backtrace()))?
+                .into(),
+            None => NIL,
+        };
+
+        self.stack.push(value);
+
+        Ok(())
+    }
+
+    #[inline(never)]
+    fn map<const JIT: bool>(
+        &mut self,
+        address: usize,
+        codes: &[u8],
+        hook: &mut impl Hook,
+    ) -> Result<ControlFlow<()>, RuntimeError> {
+        let array = self.stack.pop();
+        let function = self.stack.pop();
+        let Some(mut array) = array.into_array() else {
+            self.stack.push(NIL);
+            return Ok(ControlFlow::Continue(()));
+        };
+
+        for element in array.as_mut_slice() {
+            let value = replace(element, NIL);
+            let ControlFlow::Continue(value) =
+                self.apply::<JIT, 1>(address, codes, hook, &function, [value])?
+            else {
+                return Ok(ControlFlow::Break(()));
+            };
+
+            *element = value;
+        }
+
+        self.stack.push(array.into());
+
+        Ok(ControlFlow::Continue(()))
+    }
+
+    #[inline(never)]
+    fn filter<const JIT: bool>(
+        &mut self,
+        address: usize,
+        codes: &[u8],
+        hook: &mut impl Hook,
+    ) -> Result<ControlFlow<()>, RuntimeError> {
+        let array = self.stack.pop();
+        let function = self.stack.pop();
+        let Some(array) = array.into_array() else {
+            self.stack.push(NIL);
+            return Ok(ControlFlow::Continue(()));
+        };
+        let values = array.into_vec();
+        let mut kept = Vec::with_capacity(values.len());
+
+        for value in values {
+            let ControlFlow::Continue(condition) =
+                self.apply::<JIT, 1>(address, codes, hook, &function, [value.clone()])?
+            else {
+                return Ok(ControlFlow::Break(()));
+            };
+
+            if !condition.is_nil() {
+                kept.push(value);
+            }
+        }
+
+        self.stack.push(Array::from(kept).into());
+
+        Ok(ControlFlow::Continue(()))
+    }
+
+    #[inline(never)]
+    fn fold<const JIT: bool>(
+        &mut self,
+        address: usize,
+        codes: &[u8],
+        hook: &mut impl Hook,
+    ) -> Result<ControlFlow<()>, RuntimeError> {
+        let array = self.stack.pop();
+        let mut accumulator = self.stack.pop();
+        let function = self.stack.pop();
+
+        for value in array.into_array().map(Array::into_vec).unwrap_or_default() {
+            let ControlFlow::Continue(value) =
+                self.apply::<JIT, 2>(address, codes, hook, &function, [accumulator, value])?
+            else {
+                return Ok(ControlFlow::Break(()));
+            };
+
+            accumulator = value;
+        }
+
+        self.stack.push(accumulator);
+
+        Ok(ControlFlow::Continue(()))
+    }
+
+    #[inline(never)]
+    fn sort<const JIT: bool>(
+        &mut self,
+        address: usize,
+        codes: &[u8],
+        hook: &mut impl Hook,
+    ) -> Result<ControlFlow<()>, RuntimeError> {
+        let function = self.stack.pop();
+        let Some(array) = self.stack.pop().into_array() else {
+            self.stack.push(NIL);
+            return Ok(ControlFlow::Continue(()));
+        };
+
+        // An error is `None` if a hook stops execution.
+        let array = if function.is_nil() {
+            array.sort_by(|one, other| Ok(one < other))
+        } else {
+            array.sort_by(|one, other| {
+                match self.apply::<JIT, 2>(
+                    address,
+                    codes,
+                    hook,
+                    &function,
+                    [one.clone(), other.clone()],
+                ) {
+                    Ok(ControlFlow::Continue(value)) => Ok(!value.is_nil()),
+                    Ok(ControlFlow::Break(())) => Err(None),
+                    Err(error) => Err(Some(error)),
+                }
+            })
+        };
+
+        match array {
+            Ok(array) => self.stack.push(array.into()),
+            Err(None) => return Ok(ControlFlow::Break(())),
+            Err(Some(error)) => return Err(error),
+        }
+
+        Ok(ControlFlow::Continue(()))
+    }
+
+    // Calls a function from a primitive until it returns.
+    fn apply<const JIT: bool, const N: usize>(
+        &mut self,
+        address: usize,
+        codes: &[u8],
+        hook: &mut impl Hook,
+        function: &Value,
+        arguments: [Value; N],
+    ) -> Result<ControlFlow<(), Value>, RuntimeError> {
+        if self.stack.len() + N + 1 > self.stack.capacity() {
+            return Err(self.overflow_stack());
+        }
+
+        let program_counter = self.program_counter;
+        let depth = self.frames.len();
+
+        self.stack.push(function.clone());
+
+        for argument in arguments {
+            self.stack.push(argument);
+        }
+
+        self.call::<JIT>(address, N as u8, None)?;
+
+        while self.frames.len() > depth {
+            if self.step::<JIT, _>(codes, hook)?.is_break() {
+                return Ok(ControlFlow::Break(()));
+            }
+        }
+
+        self.program_counter = program_counter;
+
+        Ok(ControlFlow::Continue(self.stack.pop()))
+    }
+
+    #[inline(always)]
     fn add(&mut self) {
         arithmetic_operation!(self, +);
     }
 
+    #[inline(always)]
     fn subtract(&mut self) {
         arithmetic_operation!(self, -);
     }
 
+    #[inline(always)]
+    fn add_float64(&mut self, number: f64) {
+        constant_arithmetic_operation!(self, number, +);
+    }
+
+    #[inline(always)]
+    fn subtract_float64(&mut self, number: f64) {
+        constant_arithmetic_operation!(self, number, -);
+    }
+
+    #[inline(always)]
     fn multiply(&mut self) {
         arithmetic_operation!(self, *);
     }
 
+    #[inline(always)]
     fn divide(&mut self) {
         arithmetic_operation!(self, /);
     }
@@ -158,39 +655,123 @@ impl Vm {
         self.stack.pop();
     }
 
-    fn dump(&mut self) {
+    fn dump(&mut self, hook: &mut impl Hook) -> Result<(), RuntimeError> {
         let value = self.stack.pop();
-
-        println!("{value}");
+        let result = hook.dump(&value, &mut *self.output);
 
         self.stack.push(value);
+
+        result.map_err(|_| RuntimeError::Output(self.capture_backtrace()))
+    }
+
+    fn dump_drop(&mut self, hook: &mut impl Hook) -> Result<(), RuntimeError> {
+        let value = self.stack.pop();
+
+        hook.dump(&value, &mut *self.output)
+            .map_err(|_| RuntimeError::Output(self.capture_backtrace()))
+    }
+
+    fn display(&mut self) {
+        let value = self.stack.pop();
+
+        self.stack.push(value.to_string().into());
     }
 
-    fn call(&mut self, codes: &[u8]) {
-        let arity = self.read_u8(codes) as usize;
+    fn repr(&mut self) {
+        let value = self.stack.pop();
+
+        self.stack.push(value.repr().to_string().into());
+    }
+
+    fn call<const JIT: bool>(
+        &mut self,
+        address: usize,
+        arity: u8,
+        cache: Option<ClosureId>,
+    ) -> Result<(), RuntimeError> {
+        let arity = arity as usize;
+
+        let Some(closure) = self.callee(arity) else {
+            self.call_value(arity);
+            return Ok(());
+        };
+        let id = closure.id();
+
+        if self.frames.len() >= self.frames.capacity() {
+            return Err(self.overflow_call_stack());
+        }
+
+        #[cfg(feature = "jit")]
+        if JIT {
+            if let Some(value) =
+                self.call_compiled(arity, self.frames.capacity() - self.frames.len())
+            {
+                self.stack
+                    .truncate(self.stack.len() - arity - 1, self.stack.len());
+                self.stack.push(value);
+                return Ok(());
+            }
+        }
 
-        self.frames.push(Frame::new(
+        let frame = Frame::new(
             (self.stack.len() - arity - 1) as u32,
             self.program_counter as u32,
-        ));
+        );
+
+        self.frames.push(frame);
 
-        self.call_function(arity)
+        // A monomorphic call site needs no adjustment of arguments.
+        if cache == Some(id) {
+            self.program_counter = id as usize;
+            self.reserve(self.program.frame_size(id as usize))
+        } else {
+            self.call_function(address, arity)
+        }
     }
 
-    fn tail_call(&mut self, codes: &[u8]) {
-        let arity = self.read_u8(codes) as usize;
+    fn tail_call<const JIT: bool>(
+        &mut self,
+        address: usize,
+        arity: u8,
+        cache: Option<ClosureId>,
+    ) -> Result<(), RuntimeError> {
+        let arity = arity as usize;
+
+        let Some(closure) = self.callee(arity) else {
+            self.call_value(arity);
+            self.r#return();
+            return Ok(());
+        };
+        let id = closure.id();
+
+        let pointer = self.frames.top().pointer() as usize;
+        self.stack.truncate(pointer, self.stack.len() - arity - 1);
+
+        #[cfg(feature = "jit")]
+        if JIT {
+            if let Some(value) =
+                self.call_compiled(arity, self.frames.capacity() - self.frames.len() + 1)
+            {
+                self.stack.truncate(pointer, self.stack.len());
+                self.stack.push(value);
+                self.r#return();
+                return Ok(());
+            }
+        }
 
-        let frame = self.frames.top();
-        self.stack
-            .truncate(frame.pointer() as usize, self.stack.len() - arity - 1);
+        // A tail call reuses the current frame. Only interpreted calls count
+        // it for backtraces as compiled ones return before they fail.
+        self.frames.top_mut().elide();
 
-        self.call_function(arity)
+        if cache == Some(id) {
+            self.program_counter = id as usize;
+            self.reserve(self.program.frame_size(id as usize))
+        } else {
+            self.call_function(address, arity)
+        }
     }
 
-    fn close(&mut self, codes: &[u8]) {
-        let id = self.read_u32(codes);
-        let arity = self.read_u8(codes);
-        let environment_size = self.read_u8(codes);
+    fn close(&mut self, id: ClosureId, arity: u8, environment_size: u8) {
         let mut closure = Closure::new(id, arity, environment_size);
 
         for index in (0..environment_size).rev() {
@@ -202,47 +783,73 @@ impl Vm {
         self.stack.push(closure.into());
     }
 
-    fn environment(&mut self, codes: &[u8]) {
+    fn environment(&mut self, index: u8) {
         let pointer = self.frames.top().pointer();
-        let index = self.read_u8(codes) as usize;
 
         self.stack.push(
             self.stack
-                .peek(self.stack.len() - pointer as usize)
+                .peek(self.stack.len() - pointer as usize - 1)
                 .as_closure()
                 .expect("closure")
-                .get_environment(index)
+                .get_environment(index as usize)
                 .clone(),
         );
     }
 
-    fn peek(&mut self, codes: &[u8]) {
-        // TODO Move local variables when possible.
-        let index = self.read_u8(codes);
-
+    fn peek(&mut self, index: u8) {
         self.stack.push(self.stack.peek(index as usize).clone());
     }
 
+    // Moves a local variable on its last use so that its value can be updated
+    // in place.
+    fn take(&mut self, index: u8) {
+        let value = replace(self.stack.peek_mut(index as usize), NIL);
+
+        self.stack.push(value);
+    }
+
+    // Drops values under a stack top, such as local variables in a block
+    // whose value is on the top.
+    fn nip(&mut self, count: u8) {
+        let end = self.stack.len() - 1;
+
+        self.stack.truncate(end - count as usize, end);
+    }
+
+    // Pops a value and stores it into a local variable, such as a loop
+    // variable updated on a next iteration.
+    fn store(&mut self, index: u8) {
+        let value = self.stack.pop();
+
+        *self.stack.peek_mut(index as usize) = value;
+    }
+
+    #[inline(always)]
     fn equal(&mut self) {
         comparison_operation!(self, ==);
     }
 
+    #[inline(always)]
     fn not_equal(&mut self) {
         comparison_operation!(self, !=);
     }
 
+    #[inline(always)]
     fn greater_than(&mut self) {
         comparison_operation!(self, >);
     }
 
+    #[inline(always)]
     fn greater_than_or_equal(&mut self) {
         comparison_operation!(self, >=);
     }
 
+    #[inline(always)]
     fn less_than(&mut self) {
         comparison_operation!(self, <);
     }
 
+    #[inline(always)]
     fn less_than_or_equal(&mut self) {
         comparison_operation!(self, <=);
     }
@@ -268,25 +875,24 @@ impl Vm {
         self.stack.push(if lhs.is_nil() { rhs } else { lhs });
     }
 
-    fn jump(&mut self, codes: &[u8]) {
-        let address = self.read_u16(codes);
+    fn branch(&mut self, address: u32) {
+        let value = self.stack.pop();
 
-        self.program_counter = self
-            .program_counter
-            .wrapping_add(address as i16 as isize as usize);
+        if value.is_nil() {
+            self.program_counter = address as usize;
+        }
     }
 
-    fn branch(&mut self, codes: &[u8]) {
-        let address = self.read_u16(codes);
-        let value = self.stack.pop();
+    fn branch_not_equal(&mut self, address: u32) {
+        let rhs = self.stack.pop();
+        let lhs = self.stack.pop();
 
-        if value.is_nil() {
-            self.program_counter = self
-                .program_counter
-                .wrapping_add(address as i16 as isize as usize);
+        if lhs != rhs {
+            self.program_counter = address as usize;
         }
     }
 
+    #[inline(always)]
     fn r#return(&mut self) {
         let value = self.stack.pop();
         let frame = self.frames.pop();
@@ -300,58 +906,502 @@ impl Vm {
         self.stack.push(value);
     }
 
+    // Calls a closure adjusting its arguments and caches it at a call site if
+    // they match its arity.
     #[inline(always)]
-    fn call_function(&mut self, arity: usize) {
-        if let Some(closure) = self.stack.peek(arity).as_closure() {
-            let id = closure.id();
-            let closure_arity = closure.arity() as usize;
+    fn call_function(&mut self, address: usize, arity: usize) -> Result<(), RuntimeError> {
+        let closure = self.callee(arity).expect("closure");
+        let id = closure.id();
+        let closure_arity = closure.arity() as usize;
 
-            self.program_counter = id as usize;
+        if closure_arity == arity {
+            self.program.cache_callee(address, id);
+        }
 
-            for _ in 0..arity.saturating_sub(closure_arity) {
-                self.stack.pop();
-            }
+        self.program_counter = id as usize;
+        self.reserve(closure_arity.saturating_sub(arity) + self.program.frame_size(id as usize))?;
 
-            for _ in 0..closure_arity.saturating_sub(arity) {
-                self.stack.push(NIL);
-            }
-        } else {
-            for _ in 0..arity + 1 {
-                self.stack.pop();
-            }
+        for _ in 0..arity.saturating_sub(closure_arity) {
+            self.stack.pop();
+        }
 
+        for _ in 0..closure_arity.saturating_sub(arity) {
             self.stack.push(NIL);
         }
-    }
 
-    #[inline(always)]
-    fn read_f64(&mut self, codes: &[u8]) -> f64 {
-        decode_f64(codes, &mut self.program_counter)
+        Ok(())
     }
 
-    #[inline(always)]
-    fn read_u32(&mut self, codes: &[u8]) -> u32 {
-        decode_u32(codes, &mut self.program_counter)
+    // Calls a closure with native code if it is compiled.
+    #[cfg(feature = "jit")]
+    fn call_compiled(&mut self, arity: usize, depth: usize) -> Option<Value> {
+        let base = self.stack.len() - arity - 1;
+        let values = &self.stack.as_slice()[base..];
+
+        self.jit.call(
+            &self.program,
+            values[0].as_closure()?,
+            &values[1..],
+            depth,
+            base,
+            self.stack.capacity(),
+        )
     }
 
-    #[inline(always)]
-    fn read_u16(&mut self, codes: &[u8]) -> u16 {
-        decode_u16(codes, &mut self.program_counter)
+    fn callee(&self, arity: usize) -> Option<&Closure> {
+        self.stack
+            .peek(arity)
+            .as_closure()
+            .filter(|closure| !closure.is_nil())
     }
 
-    #[inline(always)]
-    fn read_u8(&mut self, codes: &[u8]) -> u8 {
-        decode_u8(codes, &mut self.program_counter)
+    // Calls a value which is not a function resulting in `nil`.
+    fn call_value(&mut self, arity: usize) {
+        for _ in 0..arity + 1 {
+            self.stack.pop();
+        }
+
+        self.stack.push(NIL);
     }
+}
 
-    #[inline(always)]
-    fn read_bytes<'a>(&mut self, codes: &'a [u8], len: usize) -> &'a [u8] {
-        decode_bytes(codes, len, &mut self.program_counter)
+impl Debug for Vm {
+    fn fmt(&self, formatter: &mut Formatter) -> fmt::Result {
+        formatter
+            .debug_struct("Vm")
+            .field("program_counter", &self.program_counter)
+            .field("stack", &self.stack)
+            .field("frames", &self.frames)
+            .field("program", &self.program)
+            .finish_non_exhaustive()
     }
 }
 
+#[cfg(feature = "std")]
 impl Default for Vm {
     fn default() -> Self {
         Self::new()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Instruction;
+    use alloc::{rc::Rc, string::String};
+    use core::{cell::RefCell, fmt};
+    use pretty_assertions::assert_eq;
+
+    #[derive(Clone, Default)]
+    struct SharedOutput(Rc<RefCell<String>>);
+
+    impl Write for SharedOutput {
+        fn write_str(&mut self, string: &str) -> fmt::Result {
+            self.0.borrow_mut().write_str(string)
+        }
+    }
+
+    struct FailingOutput;
+
+    impl Write for FailingOutput {
+        fn write_str(&mut self, _: &str) -> fmt::Result {
+            Err(fmt::Error)
+        }
+    }
+
+    // Defines a function of no argument with a body and calls it.
+    fn call_function(body: &[u8]) -> Vec<u8> {
+        let mut codes = vec![Instruction::Jump as u8];
+        codes.extend((body.len() as u16).to_le_bytes());
+        codes.extend(body);
+        codes.push(Instruction::Close as u8);
+        codes.extend(3u32.to_le_bytes());
+        codes.extend([0, 0, Instruction::Call as u8, 0]);
+        codes
+    }
+
+    #[test]
+    fn overflow_call_stack() {
+        let codes = call_function(&[
+            Instruction::Peek as u8,
+            0,
+            Instruction::Call as u8,
+            0,
+            Instruction::Return as u8,
+        ]);
+
+        let Err(RuntimeError::CallStackOverflow(backtrace)) =
+            Vm::with_output(String::new()).run(&codes)
+        else {
+            panic!("call stack overflow expected");
+        };
+
+        assert_eq!(backtrace.frames().len(), 257);
+        assert_eq!(backtrace.frames()[0], BacktraceFrame::new(Some(3), 7, 0));
+        assert_eq!(backtrace.frames()[256], BacktraceFrame::new(None, 16, 0));
+    }
+
+    #[test]
+    fn dump_values_into_output() {
+        let mut codes = vec![Instruction::Integer32 as u8];
+        codes.extend(42u32.to_le_bytes());
+        codes.push(Instruction::Dump as u8);
+        codes.push(Instruction::DumpDrop as u8);
+        let output = SharedOutput::default();
+
+        Vm::with_output(output.clone()).run(&codes).unwrap();
+
+        assert_eq!(*output.0.borrow(), "42\n42\n");
+    }
+
+    #[test]
+    fn fail_to_dump_value() {
+        let mut codes = vec![Instruction::Integer32 as u8];
+        codes.extend(42u32.to_le_bytes());
+        codes.push(Instruction::DumpDrop as u8);
+
+        assert!(matches!(
+            Vm::with_output(FailingOutput).run(&codes),
+            Err(RuntimeError::Output(_))
+        ));
+    }
+
+    #[test]
+    fn overflow_stack() {
+        let mut body = vec![];
+
+        for _ in 0..1 << 11 {
+            body.push(Instruction::Nil as u8);
+        }
+
+        body.push(Instruction::Return as u8);
+
+        let Err(RuntimeError::StackOverflow(backtrace)) =
+            Vm::with_output(String::new()).run(&call_function(&body))
+        else {
+            panic!("stack overflow expected");
+        };
+
+        assert_eq!(backtrace.frames().len(), 2);
+        assert_eq!(backtrace.frames()[0].function(), Some(3));
+    }
+
+    #[test]
+    fn overflow_stack_at_top_level() {
+        let codes = vec![Instruction::Nil as u8; (1 << 11) + 1];
+
+        assert!(matches!(
+            Vm::with_output(String::new()).run(&codes),
+            Err(RuntimeError::StackOverflow(_))
+        ));
+    }
+
+    #[test]
+    fn create_too_long_range() {
+        let mut codes = vec![];
+
+        for number in [0.0f64, f64::INFINITY] {
+            codes.push(Instruction::Float64 as u8);
+            codes.extend(number.to_le_bytes());
+        }
+
+        codes.push(Instruction::Range as u8);
+
+        assert!(matches!(
+            Vm::with_output(String::new()).run(&codes),
+            Err(RuntimeError::Range(_))
+        ));
+    }
+
+    #[test]
+    fn count_tail_calls() {
+        #[derive(Default)]
+        struct Breakpoint {
+            hit_count: usize,
+            tail_call_count: u32,
+        }
+
+        impl Hook for Breakpoint {
+            fn before_instruction(&mut self, vm: &Vm, _: &[u8]) -> ControlFlow<()> {
+                if vm.program_counter() != 3 {
+                    return ControlFlow::Continue(());
+                }
+
+                self.hit_count += 1;
+                self.tail_call_count = vm.backtrace()[0].tail_call_count();
+
+                if self.hit_count < 4 {
+                    ControlFlow::Continue(())
+                } else {
+                    ControlFlow::Break(())
+                }
+            }
+        }
+
+        // Call itself in a tail position forever.
+        let codes = call_function(&[Instruction::Peek as u8, 0, Instruction::TailCall as u8, 0]);
+        let mut hook = Breakpoint::default();
+        let mut vm = Vm::with_output(String::new());
+
+        vm.run_with_hook(&codes, &mut hook).unwrap();
+
+        assert_eq!(vm.frames().len(), 1);
+        assert_eq!(hook.tail_call_count, 3);
+    }
+
+    #[test]
+    fn read_environment_after_tail_call() {
+        let mut codes = vec![Instruction::Jump as u8, 3, 0];
+        // A function returning its first captured value
+        codes.extend([Instruction::Environment as u8, 0, Instruction::Return as u8]);
+        // A function calling its captured function in a tail position
+        codes.extend([
+            Instruction::Jump as u8,
+            4,
+            0,
+            Instruction::Environment as u8,
+            0,
+            Instruction::TailCall as u8,
+            0,
+        ]);
+        codes.extend([Instruction::Integer32 as u8]);
+        codes.extend(42u32.to_le_bytes());
+        codes.push(Instruction::Close as u8);
+        codes.extend(3u32.to_le_bytes());
+        codes.extend([0, 1, Instruction::Close as u8]);
+        codes.extend(9u32.to_le_bytes());
+        codes.extend([0, 1, Instruction::Call as u8, 0]);
+
+        let mut vm = Vm::with_output(String::new());
+
+        vm.run(&codes).unwrap();
+
+        assert_eq!(vm.stack(), [42u32.into()]);
+    }
+
+    #[test]
+    fn call_non_function_in_tail_position() {
+        let mut vm = Vm::with_output(String::new());
+
+        vm.run(&call_function(&[
+            Instruction::Nil as u8,
+            Instruction::TailCall as u8,
+            0,
+        ]))
+        .unwrap();
+
+        assert!(vm.frames().is_empty());
+        assert_eq!(vm.stack(), [NIL]);
+    }
+
+    #[test]
+    fn take_local_variable() {
+        let mut codes = vec![Instruction::Integer32 as u8];
+        codes.extend(42u32.to_le_bytes());
+        codes.extend([Instruction::Take as u8, 0]);
+
+        let mut vm = Vm::with_output(String::new());
+
+        vm.run(&codes).unwrap();
+
+        assert_eq!(vm.stack(), [NIL, 42u32.into()]);
+    }
+
+    #[test]
+    fn nip_values() {
+        let mut codes = vec![];
+
+        for number in 0..3u32 {
+            codes.push(Instruction::Integer32 as u8);
+            codes.extend(number.to_le_bytes());
+        }
+
+        codes.extend([Instruction::Nip as u8, 1]);
+
+        let mut vm = Vm::with_output(String::new());
+
+        vm.run(&codes).unwrap();
+
+        assert_eq!(vm.stack(), [0u32.into(), 2u32.into()]);
+    }
+
+    #[test]
+    fn store_value() {
+        let mut codes = vec![];
+
+        for number in 0..3u32 {
+            codes.push(Instruction::Integer32 as u8);
+            codes.extend(number.to_le_bytes());
+        }
+
+        codes.extend([Instruction::Store as u8, 1]);
+
+        let mut vm = Vm::with_output(String::new());
+
+        vm.run(&codes).unwrap();
+
+        assert_eq!(vm.stack(), [2u32.into(), 1u32.into()]);
+    }
+
+    #[test]
+    fn update_reference() {
+        let mut codes = vec![Instruction::Nil as u8, Instruction::Ref as u8];
+
+        codes.extend([Instruction::Peek as u8, 0, Instruction::Integer32 as u8]);
+        codes.extend(42u32.to_le_bytes());
+        codes.extend([
+            Instruction::SetRef as u8,
+            Instruction::Drop as u8,
+            Instruction::Deref as u8,
+        ]);
+
+        let mut vm = Vm::with_output(String::new());
+
+        vm.run(&codes).unwrap();
+
+        assert_eq!(vm.stack(), [42u32.into()]);
+    }
+
+    // Builds an array of numbers.
+    fn array(numbers: &[f64]) -> Vec<u8> {
+        let mut codes = vec![Instruction::Nil as u8];
+
+        for number in numbers {
+            codes.push(Instruction::Float64 as u8);
+            codes.extend(number.to_le_bytes());
+            codes.push(Instruction::Push as u8);
+        }
+
+        codes
+    }
+
+    #[test]
+    fn map_array() {
+        let body = [
+            Instruction::Peek as u8,
+            0,
+            Instruction::Peek as u8,
+            1,
+            Instruction::Add as u8,
+            Instruction::Return as u8,
+        ];
+        let mut codes = vec![Instruction::Jump as u8];
+        codes.extend((body.len() as u16).to_le_bytes());
+        codes.extend(body);
+        codes.push(Instruction::Close as u8);
+        codes.extend(3u32.to_le_bytes());
+        codes.extend([1, 0]);
+        codes.extend(array(&[1.0, 2.0, 3.0]));
+        codes.push(Instruction::Map as u8);
+
+        let mut vm = Vm::with_output(String::new());
+
+        vm.run(&codes).unwrap();
+
+        assert_eq!(
+            vm.stack(),
+            [Array::from([2.0.into(), 4.0.into(), 6.0.into()]).into()]
+        );
+        assert!(vm.frames().is_empty());
+    }
+
+    #[test]
+    fn fold_array() {
+        let body = [
+            Instruction::Peek as u8,
+            1,
+            Instruction::Peek as u8,
+            1,
+            Instruction::Subtract as u8,
+            Instruction::Return as u8,
+        ];
+        let mut codes = vec![Instruction::Jump as u8];
+        codes.extend((body.len() as u16).to_le_bytes());
+        codes.extend(body);
+        codes.push(Instruction::Close as u8);
+        codes.extend(3u32.to_le_bytes());
+        codes.extend([2, 0, Instruction::Float64 as u8]);
+        codes.extend(10f64.to_le_bytes());
+        codes.extend(array(&[1.0, 2.0, 3.0]));
+        codes.push(Instruction::Fold as u8);
+
+        let mut vm = Vm::with_output(String::new());
+
+        vm.run(&codes).unwrap();
+
+        assert_eq!(vm.stack(), [4.0.into()]);
+    }
+
+    #[test]
+    fn overflow_call_stack_in_map() {
+        // A function mapping itself over an array of its argument.
+        let body = [
+            Instruction::Peek as u8,
+            1,
+            Instruction::Nil as u8,
+            Instruction::Peek as u8,
+            2,
+            Instruction::Push as u8,
+            Instruction::Map as u8,
+            Instruction::Return as u8,
+        ];
+        let mut codes = vec![Instruction::Jump as u8];
+        codes.extend((body.len() as u16).to_le_bytes());
+        codes.extend(body);
+        codes.push(Instruction::Close as u8);
+        codes.extend(3u32.to_le_bytes());
+        codes.extend([1, 0, Instruction::Nil as u8, Instruction::Call as u8, 1]);
+
+        let mut vm = Vm::with_output(String::new());
+
+        assert!(matches!(
+            vm.run(&codes),
+            Err(RuntimeError::CallStackOverflow(_))
+        ));
+    }
+
+    #[test]
+    fn call_different_closures_at_same_site() {
+        let mut codes = vec![];
+
+        // Defines a function body skipped at a top level and returns its ID.
+        let mut define = |body: &[u8]| {
+            codes.push(Instruction::Jump as u8);
+            codes.extend((body.len() as u16).to_le_bytes());
+            let id = codes.len() as u32;
+            codes.extend(body);
+            id
+        };
+
+        let constant = define(&[
+            Instruction::Integer32 as u8,
+            42,
+            0,
+            0,
+            0,
+            Instruction::Return as u8,
+        ]);
+        let identity = define(&[Instruction::Peek as u8, 0, Instruction::Return as u8]);
+        let apply = define(&[
+            Instruction::Peek as u8,
+            0,
+            Instruction::Call as u8,
+            0,
+            Instruction::Return as u8,
+        ]);
+
+        for (id, arity) in [(constant, 0), (identity, 1), (constant, 0)] {
+            codes.push(Instruction::Close as u8);
+            codes.extend(apply.to_le_bytes());
+            codes.extend([1, 0, Instruction::Close as u8]);
+            codes.extend(id.to_le_bytes());
+            codes.extend([arity, 0, Instruction::Call as u8, 1]);
+        }
+
+        let mut vm = Vm::with_output(String::new());
+
+        vm.run(&codes).unwrap();
+
+        assert_eq!(vm.stack(), [42u32.into(), NIL, 42u32.into()]);
+    }
+}